@@ -1,17 +1,52 @@
 use crate::prelude::*;
 
+mod alert;
 mod cors;
+mod exchange;
 mod market;
+mod portfolio;
+mod program;
+mod risk;
+mod store;
 use cors::CORS;
 
-pub async fn spawn(reactor: Reactor) -> Result<()> {
-    rocket::build()
+pub async fn spawn(
+    reactor: Reactor,
+    address: std::net::IpAddr,
+    port: u16,
+    backup_dir: Option<PathBuf>,
+) -> Result<()> {
+    let figment = rocket::Config::figment()
+        .merge(("address", address))
+        .merge(("port", port));
+    rocket::custom(figment)
         .manage(reactor)
+        .manage(store::BackupDir(backup_dir))
         .attach(CORS)
         .mount(
             "/market",
             routes![market::get, market::get_all, market::get_ohlc,],
         )
+        .mount(
+            "/alert",
+            routes![alert::get_all, alert::get, alert::post, alert::delete,],
+        )
+        .mount(
+            "/program",
+            routes![program::get_all, program::get_history, program::get_run,],
+        )
+        .mount(
+            "/store",
+            routes![
+                store::backup,
+                store::get_trees,
+                store::get_stat,
+                store::get_entries,
+            ],
+        )
+        .mount("/exchange", routes![exchange::get_stats, exchange::get_assets,])
+        .mount("/portfolio", routes![portfolio::get, portfolio::get_equity,])
+        .mount("/risk", routes![risk::get, risk::halt, risk::resume,])
         .launch()
         .await?;
     Ok(())
@@ -0,0 +1,41 @@
+use crate::prelude::*;
+use crate::store::Alert;
+
+#[get("/")]
+pub async fn get_all(reactor: &State<Reactor>) -> Result<Json<Vec<Alert>>> {
+    Ok(Json(reactor.store.alerts.list()?))
+}
+
+#[get("/<name>")]
+pub async fn get(reactor: &State<Reactor>, name: String) -> Result<Json<Alert>> {
+    Ok(Json(
+        reactor
+            .store
+            .alerts
+            .get(&name)?
+            .ok_or(Error::AlertNotFound(name))?,
+    ))
+}
+
+/// File and command sinks run on the daemon host, they can only be set from the shell
+#[post("/", format = "json", data = "<alert>")]
+pub async fn post(reactor: &State<Reactor>, alert: Json<Alert>) -> Result<Json<Alert>> {
+    let alert = alert.into_inner();
+    alert.condition.check()?;
+    if let Some(sink) = alert.sinks.iter().find(|e| e.is_local()) {
+        return Err(Error::NotSupported(format!(
+            "alert sink `{}` over the api, use the `alert` builtin",
+            sink
+        )));
+    }
+    reactor.store.alerts.set(&alert)?;
+    Ok(Json(alert))
+}
+
+#[delete("/<name>")]
+pub async fn delete(reactor: &State<Reactor>, name: String) -> Result<()> {
+    if !reactor.store.alerts.remove(&name)? {
+        return Err(Error::AlertNotFound(name));
+    }
+    Ok(())
+}
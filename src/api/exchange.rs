@@ -0,0 +1,24 @@
+use crate::exchange::ExchangeStats;
+use crate::prelude::*;
+
+/// Call counters and circuit state of every exchange
+#[get("/stats")]
+pub async fn get_stats(reactor: &State<Reactor>) -> Result<Json<Vec<ExchangeStats>>> {
+    let mut stats: Vec<ExchangeStats> = reactor
+        .exchanges
+        .read()
+        .await
+        .iter()
+        .filter_map(|(name, exchange)| exchange.counters().map(|e| e.stats(name)))
+        .collect();
+    stats.sort_by(|a, b| a.exchange.cmp(&b.exchange));
+    Ok(Json(stats))
+}
+
+/// Canonical asset symbols with their aliases, exchange codes are prefixed by the exchange
+#[get("/assets")]
+pub async fn get_assets(
+    reactor: &State<Reactor>,
+) -> Result<Json<std::collections::BTreeMap<String, Vec<String>>>> {
+    Ok(Json(reactor.assets.symbols()))
+}
@@ -8,17 +8,27 @@ pub struct GetAllMarketResult {
     loaded: Option<Vec<MarketIdentifier>>,
 }
 
-#[get("/?<available>&<loaded>")]
+/// `base` and `quote` filter the markets by asset, any alias of the asset can be used
+#[get("/?<available>&<loaded>&<base>&<quote>")]
 pub async fn get_all(
     reactor: &State<Reactor>,
     available: Option<bool>,
     loaded: Option<bool>,
+    base: Option<String>,
+    quote: Option<String>,
 ) -> Result<Json<GetAllMarketResult>> {
+    let assets = reactor.assets.clone();
+    let matches = |market: &MarketIdentifier| {
+        let market = assets.canonical_market(market);
+        let canonical = |code: &str| assets.canonical(&market.exchange_name, code);
+        base.as_deref().map(|e| canonical(e) == market.base).unwrap_or(true)
+            && quote.as_deref().map(|e| canonical(e) == market.quote).unwrap_or(true)
+    };
     let available = if available.unwrap_or(false) {
         let mut available = Vec::new();
         for (name, exchange) in reactor.exchanges.read().await.iter() {
-            match exchange.lock().await.get_markets().await {
-                Ok(mut markets) => available.append(&mut markets),
+            match exchange.get_markets().await {
+                Ok(markets) => available.extend(markets.into_iter().filter(|e| matches(e))),
                 Err(e) => error!("Failed to fetch markets: EXCHANGE={}, ERROR={}", name, e),
             }
         }
@@ -29,7 +39,9 @@ pub async fn get_all(
     let loaded = if loaded.unwrap_or(false) {
         let mut loaded = Vec::new();
         for (_, exchange) in reactor.store.trees.lock().unwrap().iter() {
-            loaded.push(exchange.id.clone())
+            if matches(&exchange.id) {
+                loaded.push(exchange.id.clone())
+            }
         }
         Some(loaded)
     } else {
@@ -45,11 +57,12 @@ pub struct GetMarketResult {
     last_ohlc: Option<i64>,
 }
 
-#[get("/<exchange>/<base>/<quote>")]
+#[get("/<exchange>/<base>/<quote>?<interval>")]
 pub async fn get(
     exchange: String,
     base: String,
     quote: String,
+    interval: Option<i64>,
     reactor: &State<Reactor>,
 ) -> Result<Json<GetMarketResult>> {
     let id = MarketIdentifier {
@@ -58,10 +71,13 @@ pub async fn get(
         quote,
     };
     let market = reactor.get_or_register_market(&id).await?;
+    let data = market
+        .interval(Interval::from_minuts(interval.unwrap_or(1))?)
+        .await?;
     Ok(Json(GetMarketResult {
         settings: market.store.settings()?,
-        first_ohlc: market.store.first_ohlc()?.map(|e| e.time),
-        last_ohlc: market.store.last_ohlc()?.map(|e| e.time),
+        first_ohlc: data.first_ohlc()?.map(|e| e.time),
+        last_ohlc: data.last_ohlc()?.map(|e| e.time),
     }))
 }
 
@@ -70,7 +86,7 @@ pub struct GetMarketOhlcResult {
     data: Vec<OHLC>,
 }
 
-#[get("/<exchange>/<base>/<quote>/ohlc?<from>&<to>&<exact>")]
+#[get("/<exchange>/<base>/<quote>/ohlc?<from>&<to>&<exact>&<interval>")]
 pub async fn get_ohlc(
     exchange: String,
     base: String,
@@ -78,6 +94,7 @@ pub async fn get_ohlc(
     from: i64,
     to: Option<i64>,
     exact: Option<bool>,
+    interval: Option<i64>,
     reactor: &State<Reactor>,
 ) -> Result<Json<GetMarketOhlcResult>> {
     let id = MarketIdentifier {
@@ -86,15 +103,18 @@ pub async fn get_ohlc(
         quote,
     };
     let market = reactor.get_or_register_market(&id).await?;
+    let data = market
+        .interval(Interval::from_minuts(interval.unwrap_or(1))?)
+        .await?;
     let to = if let Some(end) = to {
         end
     } else {
-        market.store.last_ohlc()?.map(|e| e.time).unwrap_or(0)
+        data.last_ohlc()?.map(|e| e.time).unwrap_or(0)
     };
     let ohlc = if exact.unwrap_or(false) {
-        market.store.exact_range(from, to)?
+        data.exact_range(from, to)?
     } else {
-        market.store.close_range(from, to)?
+        data.close_range(from, to)?
     };
     Ok(Json(GetMarketOhlcResult { data: ohlc }))
 }
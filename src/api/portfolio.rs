@@ -0,0 +1,35 @@
+use crate::prelude::*;
+use crate::reactor::{CostMethod, EquityCurve, PortfolioReport, DEFAULT_PORTFOLIO_QUOTE};
+
+/// `method` is `fifo` (default) or `average`
+#[get("/?<quote>&<method>")]
+pub async fn get(
+    reactor: &State<Reactor>,
+    quote: Option<String>,
+    method: Option<String>,
+) -> Result<Json<PortfolioReport>> {
+    let method = match method {
+        Some(method) => method.parse()?,
+        None => CostMethod::Fifo,
+    };
+    let quote = quote.unwrap_or_else(|| DEFAULT_PORTFOLIO_QUOTE.to_string());
+    Ok(Json(reactor.portfolio(&quote, method).await?))
+}
+
+/// `interval` is in minutes, the curve covers the last 30 days by default
+#[get("/equity?<quote>&<from>&<to>&<interval>")]
+pub async fn get_equity(
+    reactor: &State<Reactor>,
+    quote: Option<String>,
+    from: Option<i64>,
+    to: Option<i64>,
+    interval: Option<i64>,
+) -> Result<Json<EquityCurve>> {
+    let interval = Interval::from_minuts(interval.unwrap_or(24 * 60))?;
+    let to = to.unwrap_or_else(crate::store::now);
+    let from = from.unwrap_or(to - 30 * 24 * 60 * 60);
+    let quote = quote.unwrap_or_else(|| DEFAULT_PORTFOLIO_QUOTE.to_string());
+    Ok(Json(
+        reactor.equity_curve(&quote, from, to, interval).await?,
+    ))
+}
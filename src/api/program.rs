@@ -0,0 +1,53 @@
+use crate::prelude::*;
+use crate::store::{ProgramRun, RecordedOutput};
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RunningProgram {
+    id: u64,
+    run_id: Option<u64>,
+    source: String,
+    status: crate::interpretor::ProgramStatus,
+}
+
+#[get("/")]
+pub async fn get_all(reactor: &State<Reactor>) -> Result<Json<Vec<RunningProgram>>> {
+    let programs = reactor
+        .programs
+        .read()
+        .await
+        .values()
+        .sorted_by_key(|e| e.id)
+        .map(|e| RunningProgram {
+            id: e.id,
+            run_id: e.run_id,
+            source: e.source.clone(),
+            status: *e.status.borrow(),
+        })
+        .collect();
+    Ok(Json(programs))
+}
+
+#[get("/history?<limit>")]
+pub async fn get_history(
+    reactor: &State<Reactor>,
+    limit: Option<usize>,
+) -> Result<Json<Vec<ProgramRun>>> {
+    Ok(Json(reactor.store.history.runs(limit.unwrap_or(100))?))
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct GetRunResult {
+    run: ProgramRun,
+    logs: Vec<RecordedOutput>,
+}
+
+#[get("/history/<run_id>")]
+pub async fn get_run(reactor: &State<Reactor>, run_id: u64) -> Result<Json<GetRunResult>> {
+    let run = reactor
+        .store
+        .history
+        .run(run_id)?
+        .ok_or(Error::RunNotFound(run_id))?;
+    let logs = reactor.store.history.logs(run_id)?;
+    Ok(Json(GetRunResult { run, logs }))
+}
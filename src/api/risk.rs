@@ -0,0 +1,44 @@
+use crate::prelude::*;
+use crate::reactor::RiskStatus;
+use crate::store::KillSwitch;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct HaltRequest {
+    pub reason: String,
+}
+
+/// Releasing the kill switch needs a json body, browsers can't send one cross origin without a
+/// preflight request
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ResumeRequest {
+    pub confirm: bool,
+}
+
+#[get("/")]
+pub async fn get(reactor: &State<Reactor>) -> Result<Json<RiskStatus>> {
+    Ok(Json(reactor.risk_status().await?))
+}
+
+/// Engage the kill switch, every order is rejected until `/risk/resume`
+#[post("/halt", format = "json", data = "<request>")]
+pub async fn halt(
+    reactor: &State<Reactor>,
+    request: Json<HaltRequest>,
+) -> Result<Json<KillSwitch>> {
+    Ok(Json(
+        reactor
+            .engage_kill_switch(&request.into_inner().reason)
+            .await?,
+    ))
+}
+
+/// Whether the kill switch was engaged
+#[post("/resume", format = "json", data = "<request>")]
+pub async fn resume(reactor: &State<Reactor>, request: Json<ResumeRequest>) -> Result<Json<bool>> {
+    if !request.confirm {
+        return Err(Error::NotSupported(
+            "releasing the kill switch without `confirm`".to_string(),
+        ));
+    }
+    Ok(Json(reactor.release_kill_switch().await?))
+}
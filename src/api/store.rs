@@ -0,0 +1,76 @@
+use crate::exchange::MarketIdentifier;
+use crate::prelude::*;
+use crate::store::{BackupReport, MarketDataStats, TreeEntry, TreeInfo};
+
+/// `backup.dir` of the daemon, api backups are only written there
+pub struct BackupDir(pub Option<PathBuf>);
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BackupRequest {
+    /// File name of the backup inside `backup.dir`
+    pub name: String,
+}
+
+/// Snapshot the store of the running daemon into its backup directory
+#[post("/backup", format = "json", data = "<request>")]
+pub async fn backup(
+    reactor: &State<Reactor>,
+    dir: &State<BackupDir>,
+    request: Json<BackupRequest>,
+) -> Result<Json<BackupReport>> {
+    let dir = dir
+        .0
+        .clone()
+        .ok_or_else(|| Error::Backup("backup.dir is not set on the daemon".to_string()))?;
+    let name = request.into_inner().name;
+    let mut components = Path::new(&name).components();
+    match (components.next(), components.next()) {
+        (Some(std::path::Component::Normal(_)), None) => {}
+        _ => return Err(Error::Backup(format!("`{}` is not a file name", name))),
+    }
+    let store = reactor.store.clone();
+    let report = tokio::task::spawn_blocking(move || {
+        std::fs::create_dir_all(&dir)?;
+        store.backup(&dir.join(name))
+    })
+    .await
+    .map_err(|e| Error::Backup(e.to_string()))??;
+    Ok(Json(report))
+}
+
+/// Trees of the store of the running daemon, see `pkbot store ls`
+#[get("/trees")]
+pub async fn get_trees(reactor: &State<Reactor>) -> Result<Json<Vec<TreeInfo>>> {
+    Ok(Json(reactor.store.inspector().trees()?))
+}
+
+/// `market` is `exchange/BASE/QUOTE`, `interval` is in minutes, see `pkbot store stat`
+#[get("/stat?<market>&<interval>")]
+pub async fn get_stat(
+    reactor: &State<Reactor>,
+    market: Option<String>,
+    interval: Option<i64>,
+) -> Result<Json<Vec<MarketDataStats>>> {
+    let market = market.map(|e| reactor.assets.canonical_market(&MarketIdentifier::from(e)));
+    let interval = interval.map(Interval::from_minuts).transpose()?;
+    Ok(Json(
+        reactor.store.inspector().stats(market.as_ref(), interval)?,
+    ))
+}
+
+/// Decoded entries of a tree, see `pkbot store get`
+#[get("/get?<tree>&<key>&<limit>&<last>")]
+pub async fn get_entries(
+    reactor: &State<Reactor>,
+    tree: String,
+    key: Option<String>,
+    limit: Option<usize>,
+    last: Option<bool>,
+) -> Result<Json<Vec<TreeEntry>>> {
+    Ok(Json(reactor.store.inspector().get(
+        &tree,
+        key.as_deref(),
+        limit.unwrap_or(20),
+        last.unwrap_or(false),
+    )?))
+}
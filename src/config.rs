@@ -1,3 +1,701 @@
+use crate::exchange::{
+    AssetRegistry, BreakerPolicy, CallPolicy, MarketIdentifier, MockMarket, RateLimit, RetryPolicy, StreamChannel,
+    StreamOptions, Subscription, MAX_CALL_COST,
+};
+use crate::prelude::*;
+use crate::reactor::runtime::{human_duration, ArgumentInterval};
+use crate::reactor::{ArbitragePolicy, CostMethod, RiskPolicy};
+use crate::store::{AlertSink, HistoryRetention};
+use std::net::IpAddr;
+use std::str::FromStr;
+
+pub const DEFAULT_CONFIG_PATH: &str = "pkbot.toml";
+const SUPPORTED_EXCHANGES: [&str; 1] = ["kraken"];
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
-    markets: Vec<MarketConfig>,
-}
\ No newline at end of file
+    pub log_level: String,
+    pub store: StoreConfig,
+    pub api: ApiConfig,
+    pub backup: BackupConfig,
+    pub sync: SyncConfig,
+    pub programs: ProgramsConfig,
+    pub assets: AssetsConfig,
+    pub arbitrage: ArbitrageConfig,
+    pub risk: RiskConfig,
+    pub exchanges: Vec<ExchangeConfig>,
+    pub markets: Vec<MarketConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StoreConfig {
+    pub path: PathBuf,
+    pub history_max_runs: usize,
+    pub history_max_age: Option<String>,
+    /// How often the retention rules of the markets are applied
+    pub compact_every: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiConfig {
+    pub enabled: bool,
+    pub address: String,
+    pub port: u16,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BackupConfig {
+    /// Scheduled backups are disabled when no directory is set
+    pub dir: Option<PathBuf>,
+    pub every: String,
+    pub keep: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SyncConfig {
+    /// Market downloads running at once, across every exchange
+    pub concurrency: usize,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProgramsConfig {
+    /// Wall-clock limit of the programs started without their own (tui, strategy hooks,
+    /// schedules), none by default
+    pub timeout: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AssetsConfig {
+    /// Aliases of canonical symbols on top of the built-in ones, e.g. `BTC = ["XBT"]`
+    pub aliases: HashMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ArbitrageConfig {
+    /// Pairs compared across the exchanges by the daemon (`BTC/EUR`), none disables the scan
+    pub pairs: Vec<String>,
+    pub every: String,
+    /// Net spread, in percent, from which an alert is sent
+    pub threshold: Decimal,
+    /// Alert sinks: `bus`, `file:<path>`, `cmd:<command>` or a webhook url
+    pub sinks: Vec<String>,
+}
+
+/// Limits checked before any order is sent, see `RiskPolicy`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RiskConfig {
+    /// Asset in which notionals and losses are measured
+    pub quote: String,
+    pub max_order_notional: Option<Decimal>,
+    /// Largest volume held of each asset after a buy, e.g. `BTC = 0.5`
+    pub max_position: HashMap<String, Decimal>,
+    /// Realized loss since 00:00 UTC from which only sells are accepted
+    pub daily_loss_limit: Option<Decimal>,
+    pub max_orders_per_minute: Option<usize>,
+    /// Cost method of the realized P&L: `fifo` or `average`
+    pub method: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExchangeConfig {
+    pub name: String,
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+    /// Required unless the exchange is a mock
+    pub credentials: Option<CredentialsConfig>,
+    /// Markets of an offline exchange quoting fixed prices, `name` is then free
+    #[serde(default)]
+    pub mock: Vec<MockMarket>,
+    /// Websocket server streaming the market data, the exchange one when not set
+    pub stream_url: Option<String>,
+    /// Append every received stream frame to this file, it can be replayed with `pkbot replay`
+    pub stream_record: Option<PathBuf>,
+    #[serde(default)]
+    pub limits: LimitsConfig,
+}
+
+/// Rate limit, retries and circuit breaker of the calls to an exchange, see `CallPolicy`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    pub max_counter: f64,
+    /// Decrease of the call counter per second
+    pub decay: f64,
+    pub retries: u32,
+    pub backoff_min: String,
+    pub backoff_max: String,
+    /// Consecutive failures suspending the calls
+    pub breaker_threshold: u32,
+    pub breaker_cooldown: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "source", rename_all = "lowercase", deny_unknown_fields)]
+pub enum CredentialsConfig {
+    Env { key: String, secret: String },
+    File { path: PathBuf },
+    Inline { key: String, secret: String },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CredentialsFile {
+    key: String,
+    secret: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MarketConfig {
+    pub market: String,
+    pub intervals: Vec<String>,
+    #[serde(default = "default_refresh")]
+    pub refresh: String,
+    pub backfill: Option<String>,
+    /// Live channels: `ticker`, `trades` or `candles:<interval>`
+    #[serde(default)]
+    pub stream: Vec<String>,
+}
+
+/// Scheduled backups with their settings parsed, see `BackupConfig`
+#[derive(Debug, Clone)]
+pub struct BackupPolicy {
+    pub dir: PathBuf,
+    pub every: Duration,
+    pub keep: usize,
+}
+
+/// A watched market with its settings parsed, see `MarketConfig`
+#[derive(Debug, Clone)]
+pub struct WatchedMarket {
+    pub market: MarketIdentifier,
+    pub interval: Interval,
+    pub refresh: Duration,
+    pub backfill: Duration,
+}
+
+/// The streamed channels of an exchange with its settings parsed, see `MarketConfig::stream`
+#[derive(Debug, Clone)]
+pub struct StreamedExchange {
+    pub exchange: String,
+    pub options: StreamOptions,
+    pub subscriptions: Vec<Subscription>,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+fn default_refresh() -> String {
+    "1m".to_string()
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            log_level: "info".to_string(),
+            store: StoreConfig::default(),
+            api: ApiConfig::default(),
+            backup: BackupConfig::default(),
+            sync: SyncConfig::default(),
+            programs: ProgramsConfig::default(),
+            assets: AssetsConfig::default(),
+            arbitrage: ArbitrageConfig::default(),
+            risk: RiskConfig::default(),
+            exchanges: vec![ExchangeConfig {
+                name: "kraken".to_string(),
+                enabled: true,
+                credentials: Some(CredentialsConfig::Env {
+                    key: "KRAKEN_API_KEY".to_string(),
+                    secret: "KRAKEN_API_PRIVATE_KEY".to_string(),
+                }),
+                mock: vec![],
+                stream_url: None,
+                stream_record: None,
+                limits: LimitsConfig::default(),
+            }],
+            markets: vec![],
+        }
+    }
+}
+
+impl Default for StoreConfig {
+    fn default() -> Self {
+        let retention = HistoryRetention::default();
+        Self {
+            path: std::env::var("STORE_PATH")
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| PathBuf::from("pkbot.db")),
+            history_max_runs: retention.max_runs,
+            history_max_age: Some("30d".to_string()),
+            compact_every: "1h".to_string(),
+        }
+    }
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            address: "127.0.0.1".to_string(),
+            port: 8080,
+        }
+    }
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: crate::reactor::DEFAULT_SYNC_CONCURRENCY,
+        }
+    }
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        let policy = CallPolicy::default();
+        Self {
+            max_counter: policy.rate.max_counter,
+            decay: policy.rate.decay,
+            retries: policy.retry.max_retries,
+            backoff_min: format!("{}s", policy.retry.backoff_min.as_secs()),
+            backoff_max: format!("{}s", policy.retry.backoff_max.as_secs()),
+            breaker_threshold: policy.breaker.threshold,
+            breaker_cooldown: format!("{}s", policy.breaker.cooldown.as_secs()),
+        }
+    }
+}
+
+impl Default for ArbitrageConfig {
+    fn default() -> Self {
+        Self {
+            pairs: vec![],
+            every: "1m".to_string(),
+            threshold: "0.5".parse().unwrap(),
+            sinks: vec!["bus".to_string()],
+        }
+    }
+}
+
+impl Default for RiskConfig {
+    fn default() -> Self {
+        let policy = RiskPolicy::default();
+        Self {
+            quote: policy.quote,
+            max_order_notional: None,
+            max_position: HashMap::new(),
+            daily_loss_limit: None,
+            max_orders_per_minute: None,
+            method: policy.method.to_string(),
+        }
+    }
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            every: "1d".to_string(),
+            keep: 7,
+        }
+    }
+}
+
+/// Periods of the background loops, a zero period would make them spin
+fn check_period(errors: &mut Vec<String>, key: &str, raw: &str) {
+    match human_duration(raw) {
+        Ok(every) if every.is_zero() => errors.push(format!("{}: must not be zero", key)),
+        Ok(_) => {}
+        Err(_) => errors.push(format!("{}: invalid duration `{}`", key, raw)),
+    }
+}
+
+impl Config {
+    /// Load the configuration file at `path`, falling back to the defaults when no path is
+    /// given and `pkbot.toml` doesn't exist in the working directory
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None if Path::new(DEFAULT_CONFIG_PATH).exists() => PathBuf::from(DEFAULT_CONFIG_PATH),
+            None => return Ok(Self::default()),
+        };
+        let raw = std::fs::read_to_string(&path)
+            .map_err(|e| Error::Config(format!("{}: {}", path.display(), e)))?;
+        toml::from_str(&raw).map_err(|e| Error::Config(format!("{}: {}", path.display(), e)))
+    }
+
+    /// Check every setting and report all the problems at once
+    pub fn validate(&self) -> Result<()> {
+        let mut errors = Vec::new();
+        if log::LevelFilter::from_str(&self.log_level).is_err() {
+            errors.push(format!("log_level: unknown level `{}`", self.log_level));
+        }
+        if self.store.path.as_os_str().is_empty() {
+            errors.push("store.path: must not be empty".to_string());
+        }
+        if let Some(max_age) = self.store.history_max_age.as_deref() {
+            if human_duration(max_age).is_err() {
+                errors.push(format!("store.history_max_age: invalid duration `{}`", max_age));
+            }
+        }
+        check_period(&mut errors, "store.compact_every", &self.store.compact_every);
+        if IpAddr::from_str(&self.api.address).is_err() {
+            errors.push(format!("api.address: invalid ip address `{}`", self.api.address));
+        }
+        check_period(&mut errors, "backup.every", &self.backup.every);
+        if self.backup.keep == 0 {
+            errors.push("backup.keep: must be at least 1".to_string());
+        }
+        if self.sync.concurrency == 0 {
+            errors.push("sync.concurrency: must be at least 1".to_string());
+        }
+        if let Some(timeout) = self.programs.timeout.as_deref() {
+            if human_duration(timeout).is_err() {
+                errors.push(format!("programs.timeout: invalid duration `{}`", timeout));
+            }
+        }
+        let mut aliased: HashMap<String, &str> = HashMap::new();
+        for (symbol, aliases) in self.assets.aliases.iter().sorted_by_key(|(k, _)| *k) {
+            if symbol.is_empty() || !symbol.chars().all(|c| c.is_ascii_alphanumeric()) {
+                errors.push(format!("assets.aliases: invalid symbol `{}`", symbol));
+            }
+            for alias in aliases.iter() {
+                match aliased.insert(alias.to_uppercase(), symbol.as_str()) {
+                    Some(other) if !other.eq_ignore_ascii_case(symbol) => errors.push(format!(
+                        "assets.aliases: `{}` is an alias of both `{}` and `{}`",
+                        alias, other, symbol
+                    )),
+                    _ => {}
+                }
+            }
+        }
+        for pair in self.arbitrage.pairs.iter() {
+            match pair.split_once('/') {
+                Some((base, quote)) if !base.is_empty() && !quote.is_empty() && !quote.contains('/') => {}
+                _ => errors.push(format!("arbitrage.pairs: expected `BASE/QUOTE`, found `{}`", pair)),
+            }
+        }
+        check_period(&mut errors, "arbitrage.every", &self.arbitrage.every);
+        for sink in self.arbitrage.sinks.iter() {
+            if let Err(e) = AlertSink::from_str(sink) {
+                errors.push(format!("arbitrage.sinks: {}", e));
+            }
+        }
+        if self.risk.quote.is_empty() {
+            errors.push("risk.quote: must not be empty".to_string());
+        }
+        for (key, limit) in [
+            ("max_order_notional", self.risk.max_order_notional),
+            ("daily_loss_limit", self.risk.daily_loss_limit),
+        ] {
+            if limit.map(|e| e <= Decimal::ZERO).unwrap_or(false) {
+                errors.push(format!("risk.{}: must be positive", key));
+            }
+        }
+        for (asset, limit) in self.risk.max_position.iter().sorted_by_key(|(k, _)| *k) {
+            if *limit < Decimal::ZERO {
+                errors.push(format!("risk.max_position.{}: must not be negative", asset));
+            }
+        }
+        if self.risk.max_orders_per_minute == Some(0) {
+            errors.push("risk.max_orders_per_minute: must be at least 1".to_string());
+        }
+        if let Err(e) = CostMethod::from_str(&self.risk.method) {
+            errors.push(format!("risk.method: {}", e));
+        }
+        for (i, exchange) in self.exchanges.iter().enumerate() {
+            if exchange.mock.is_empty() && !SUPPORTED_EXCHANGES.contains(&exchange.name.as_str()) {
+                errors.push(format!(
+                    "exchanges[{}].name: unsupported exchange `{}`, expected one of: {}",
+                    i,
+                    exchange.name,
+                    SUPPORTED_EXCHANGES.join(", ")
+                ));
+            }
+            if self.exchanges[..i].iter().any(|e| e.name == exchange.name) {
+                errors.push(format!("exchanges[{}].name: `{}` is declared twice", i, exchange.name));
+            }
+            if exchange.mock.is_empty() && exchange.credentials.is_none() {
+                errors.push(format!("exchanges[{}].credentials: required by `{}`", i, exchange.name));
+            }
+            for (j, market) in exchange.mock.iter().enumerate() {
+                if market.price <= Decimal::ZERO {
+                    errors.push(format!("exchanges[{}].mock[{}].price: must be positive", i, j));
+                }
+                if market.spread < Decimal::ZERO {
+                    errors.push(format!("exchanges[{}].mock[{}].spread: must not be negative", i, j));
+                }
+            }
+            if exchange.limits.max_counter < MAX_CALL_COST {
+                errors.push(format!(
+                    "exchanges[{}].limits.max_counter: must be at least {} (cost of the most expensive call)",
+                    i, MAX_CALL_COST
+                ));
+            }
+            if exchange.limits.decay <= 0.0 {
+                errors.push(format!("exchanges[{}].limits.decay: must be positive", i));
+            }
+            if exchange.limits.breaker_threshold == 0 {
+                errors.push(format!("exchanges[{}].limits.breaker_threshold: must be at least 1", i));
+            }
+            for (key, value) in [
+                ("backoff_min", &exchange.limits.backoff_min),
+                ("backoff_max", &exchange.limits.backoff_max),
+                ("breaker_cooldown", &exchange.limits.breaker_cooldown),
+            ] {
+                if human_duration(value).is_err() {
+                    errors.push(format!("exchanges[{}].limits.{}: invalid duration `{}`", i, key, value));
+                }
+            }
+            if let Some(url) = exchange.stream_url.as_deref() {
+                if !url.starts_with("ws://") && !url.starts_with("wss://") {
+                    errors.push(format!(
+                        "exchanges[{}].stream_url: expected a ws:// or wss:// url, found `{}`",
+                        i, url
+                    ));
+                }
+            }
+        }
+        for (i, market) in self.markets.iter().enumerate() {
+            let id = MarketIdentifier::from(&market.market);
+            if id.exchange_name.is_empty() || id.base.is_empty() || id.quote.is_empty() {
+                errors.push(format!(
+                    "markets[{}].market: expected `exchange/base/quote`, found `{}`",
+                    i, market.market
+                ));
+            } else if !self
+                .exchanges
+                .iter()
+                .any(|e| e.enabled && e.name == id.exchange_name)
+            {
+                errors.push(format!(
+                    "markets[{}].market: exchange `{}` is not enabled",
+                    i, id.exchange_name
+                ));
+            }
+            if market.intervals.is_empty() {
+                errors.push(format!("markets[{}].intervals: must not be empty", i));
+            }
+            for interval in market.intervals.iter() {
+                if ArgumentInterval::new(interval).is_err() {
+                    errors.push(format!("markets[{}].intervals: invalid interval `{}`", i, interval));
+                }
+            }
+            if human_duration(&market.refresh).is_err() {
+                errors.push(format!("markets[{}].refresh: invalid duration `{}`", i, market.refresh));
+            }
+            if let Some(backfill) = market.backfill.as_deref() {
+                if human_duration(backfill).is_err() {
+                    errors.push(format!("markets[{}].backfill: invalid duration `{}`", i, backfill));
+                }
+            }
+            for channel in market.stream.iter() {
+                if let Err(e) = StreamChannel::from_str(channel) {
+                    errors.push(format!("markets[{}].stream: {}", i, e));
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Config(errors.join("\n    ")))
+        }
+    }
+
+    pub fn history_retention(&self) -> HistoryRetention {
+        HistoryRetention {
+            max_runs: self.store.history_max_runs,
+            max_age: self
+                .store
+                .history_max_age
+                .as_deref()
+                .and_then(|e| human_duration(e).ok()),
+        }
+    }
+
+    pub fn program_timeout(&self) -> Result<Option<Duration>> {
+        self.programs.timeout.as_deref().map(human_duration).transpose()
+    }
+
+    pub fn compaction_interval(&self) -> Result<Duration> {
+        human_duration(&self.store.compact_every)
+    }
+
+    pub fn api_address(&self) -> Result<IpAddr> {
+        IpAddr::from_str(&self.api.address)
+            .map_err(|_| Error::Config(format!("api.address: invalid ip address `{}`", self.api.address)))
+    }
+
+    /// `None` when scheduled backups are disabled, the config must have been validated
+    pub fn backup_policy(&self) -> Result<Option<BackupPolicy>> {
+        Ok(match self.backup.dir.as_ref() {
+            Some(dir) => Some(BackupPolicy {
+                dir: dir.clone(),
+                every: human_duration(&self.backup.every)?,
+                keep: self.backup.keep,
+            }),
+            None => None,
+        })
+    }
+
+    /// `None` when no pair is scanned, the config must have been validated
+    pub fn arbitrage_policy(&self) -> Result<Option<ArbitragePolicy>> {
+        if self.arbitrage.pairs.is_empty() {
+            return Ok(None);
+        }
+        let registry = self.asset_registry();
+        Ok(Some(ArbitragePolicy {
+            pairs: self
+                .arbitrage
+                .pairs
+                .iter()
+                .filter_map(|e| e.split_once('/'))
+                .map(|(base, quote)| (registry.canonical("", base), registry.canonical("", quote)))
+                .collect(),
+            every: human_duration(&self.arbitrage.every)?,
+            threshold: self.arbitrage.threshold,
+            sinks: self
+                .arbitrage
+                .sinks
+                .iter()
+                .map(|e| AlertSink::from_str(e))
+                .collect::<Result<_>>()?,
+        }))
+    }
+
+    /// Position limits are keyed by canonical symbols, the config must have been validated
+    pub fn risk_policy(&self) -> Result<RiskPolicy> {
+        let registry = self.asset_registry();
+        Ok(RiskPolicy {
+            quote: registry.canonical("", &self.risk.quote),
+            max_order_notional: self.risk.max_order_notional,
+            max_position: self
+                .risk
+                .max_position
+                .iter()
+                .map(|(asset, limit)| (registry.canonical("", asset), *limit))
+                .collect(),
+            daily_loss_limit: self.risk.daily_loss_limit,
+            max_orders_per_minute: self.risk.max_orders_per_minute,
+            method: CostMethod::from_str(&self.risk.method)?,
+        })
+    }
+
+    /// Built-in aliases with the configured ones
+    pub fn asset_registry(&self) -> AssetRegistry {
+        let registry = AssetRegistry::new();
+        for (symbol, aliases) in self.assets.aliases.iter() {
+            for alias in aliases.iter() {
+                registry.alias(symbol, alias);
+            }
+        }
+        registry
+    }
+
+    /// One entry per market and interval, the config must have been validated
+    pub fn watched_markets(&self) -> Result<Vec<WatchedMarket>> {
+        let mut ret = Vec::new();
+        for market in self.markets.iter() {
+            let refresh = human_duration(&market.refresh)?;
+            let backfill = match market.backfill.as_deref() {
+                Some(backfill) => human_duration(backfill)?,
+                None => Duration::from_secs(0),
+            };
+            for interval in market.intervals.iter() {
+                ret.push(WatchedMarket {
+                    market: MarketIdentifier::from(&market.market),
+                    interval: ArgumentInterval::new(interval)?.normalized,
+                    refresh,
+                    backfill,
+                });
+            }
+        }
+        Ok(ret)
+    }
+
+    /// One entry per exchange with streamed markets, the config must have been validated
+    pub fn streamed_exchanges(&self) -> Result<Vec<StreamedExchange>> {
+        let mut ret: Vec<StreamedExchange> = Vec::new();
+        for market in self.markets.iter().filter(|e| !e.stream.is_empty()) {
+            let id = MarketIdentifier::from(&market.market);
+            let index = match ret.iter().position(|e| e.exchange == id.exchange_name) {
+                Some(index) => index,
+                None => {
+                    let record = self
+                        .exchanges
+                        .iter()
+                        .find(|e| e.name == id.exchange_name)
+                        .and_then(|e| e.stream_record.clone());
+                    ret.push(StreamedExchange {
+                        exchange: id.exchange_name.clone(),
+                        options: StreamOptions {
+                            record,
+                            ..StreamOptions::default()
+                        },
+                        subscriptions: vec![],
+                    });
+                    ret.len() - 1
+                }
+            };
+            for channel in market.stream.iter() {
+                ret[index].subscriptions.push(Subscription {
+                    market: id.clone(),
+                    channel: StreamChannel::from_str(channel)?,
+                });
+            }
+        }
+        Ok(ret)
+    }
+}
+
+impl ExchangeConfig {
+    /// The config must have been validated
+    pub fn call_policy(&self) -> Result<CallPolicy> {
+        Ok(CallPolicy {
+            rate: RateLimit {
+                max_counter: self.limits.max_counter,
+                decay: self.limits.decay,
+            },
+            retry: RetryPolicy {
+                max_retries: self.limits.retries,
+                backoff_min: human_duration(&self.limits.backoff_min)?,
+                backoff_max: human_duration(&self.limits.backoff_max)?,
+            },
+            breaker: BreakerPolicy {
+                threshold: self.limits.breaker_threshold,
+                cooldown: human_duration(&self.limits.breaker_cooldown)?,
+            },
+        })
+    }
+}
+
+impl CredentialsConfig {
+    /// Resolve the `(key, secret)` pair of an exchange
+    pub fn resolve(&self) -> Result<(String, String)> {
+        match self {
+            CredentialsConfig::Env { key, secret } => Ok((
+                std::env::var(key).map_err(|_| Error::Config(format!("missing environ `{}`", key)))?,
+                std::env::var(secret)
+                    .map_err(|_| Error::Config(format!("missing environ `{}`", secret)))?,
+            )),
+            CredentialsConfig::File { path } => {
+                let raw = std::fs::read_to_string(path)
+                    .map_err(|e| Error::Config(format!("{}: {}", path.display(), e)))?;
+                let file: CredentialsFile = toml::from_str(&raw)
+                    .map_err(|e| Error::Config(format!("{}: {}", path.display(), e)))?;
+                Ok((file.key, file.secret))
+            }
+            CredentialsConfig::Inline { key, secret } => Ok((key.clone(), secret.clone())),
+        }
+    }
+}
@@ -0,0 +1,508 @@
+use crate::prelude::*;
+use std::cmp::Ordering as CmpOrdering;
+use std::str::FromStr;
+
+/// Highest number of fractional digits kept by a `Decimal`, products are rounded to it
+pub const MAX_SCALE: u32 = 18;
+/// Minimum number of fractional digits of a quotient
+pub const DIV_SCALE: u32 = 12;
+
+/// Fixed-point decimal number, `mantissa * 10^-scale`
+#[derive(Clone, Copy, Default, Encode, Decode)]
+pub struct Decimal {
+    mantissa: i128,
+    scale: u32,
+}
+
+fn pow10(exp: u32) -> i128 {
+    10i128.pow(exp)
+}
+
+/// Product of two magnitudes on 256 bits, least significant limb first
+fn mul_wide(lhs: u128, rhs: u128) -> [u64; 4] {
+    let a = [lhs as u64, (lhs >> 64) as u64];
+    let b = [rhs as u64, (rhs >> 64) as u64];
+    let mut ret = [0u64; 4];
+    for i in 0..2 {
+        let mut carry = 0u128;
+        for j in 0..2 {
+            let cur = ret[i + j] as u128 + a[i] as u128 * b[j] as u128 + carry;
+            ret[i + j] = cur as u64;
+            carry = cur >> 64;
+        }
+        ret[i + 2] = carry as u64;
+    }
+    ret
+}
+
+/// Divide a 256 bits magnitude by 10 in place, returns the remainder
+fn div10_wide(value: &mut [u64; 4]) -> u64 {
+    let mut rem = 0u128;
+    for limb in value.iter_mut().rev() {
+        let cur = (rem << 64) | *limb as u128;
+        *limb = (cur / 10) as u64;
+        rem = cur % 10;
+    }
+    rem as u64
+}
+
+/// The magnitude when it fits a positive `i128`
+fn narrow(value: &[u64; 4]) -> Option<i128> {
+    if value[2] != 0 || value[3] != 0 || value[1] >> 63 != 0 {
+        return None;
+    }
+    Some(((value[1] as u128) << 64 | value[0] as u128) as i128)
+}
+
+/// Divide rounding half away from zero
+fn div_round(num: i128, den: i128) -> i128 {
+    let q = num / den;
+    let r = num % den;
+    if r.abs() * 2 >= den.abs() {
+        q + num.signum() * den.signum()
+    } else {
+        q
+    }
+}
+
+impl Decimal {
+    pub const ZERO: Decimal = Decimal {
+        mantissa: 0,
+        scale: 0,
+    };
+    pub const ONE: Decimal = Decimal {
+        mantissa: 1,
+        scale: 0,
+    };
+    pub const ONE_HUNDRED: Decimal = Decimal {
+        mantissa: 100,
+        scale: 0,
+    };
+
+    pub fn new(mantissa: i128, scale: u32) -> Self {
+        assert!(scale <= MAX_SCALE, "Decimal scale out of range: {}", scale);
+        Self { mantissa, scale }
+    }
+
+    pub fn mantissa(&self) -> i128 {
+        self.mantissa
+    }
+
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mantissa == 0
+    }
+
+    pub fn is_sign_negative(&self) -> bool {
+        self.mantissa < 0
+    }
+
+    pub fn abs(self) -> Self {
+        Self::new(self.mantissa.abs(), self.scale)
+    }
+
+    fn rescale(self, scale: u32) -> Option<i128> {
+        if scale >= self.scale {
+            self.mantissa.checked_mul(pow10(scale - self.scale))
+        } else {
+            Some(div_round(self.mantissa, pow10(self.scale - scale)))
+        }
+    }
+
+    /// Mantissas at a common scale, the finest one both values fit at
+    fn aligned(self, other: Self) -> Option<(i128, i128, u32)> {
+        let finest = self.scale.max(other.scale);
+        let coarsest = self.scale.min(other.scale);
+        (coarsest..=finest).rev().find_map(|scale| {
+            Some((self.rescale(scale)?, other.rescale(scale)?, scale))
+        })
+    }
+
+    /// Round half away from zero to `scale` fractional digits
+    pub fn round_dp(self, scale: u32) -> Self {
+        if scale >= self.scale {
+            return self;
+        }
+        Self::new(div_round(self.mantissa, pow10(self.scale - scale)), scale)
+    }
+
+    /// Drop the fractional digits beyond `scale`, rounding toward zero
+    pub fn trunc_dp(self, scale: u32) -> Self {
+        if scale >= self.scale {
+            return self;
+        }
+        Self::new(self.mantissa / pow10(self.scale - scale), scale)
+    }
+
+    /// Remove trailing zeros of the fractional part
+    pub fn normalize(self) -> Self {
+        let mut ret = self;
+        while ret.scale > 0 && ret.mantissa % 10 == 0 {
+            ret.mantissa /= 10;
+            ret.scale -= 1;
+        }
+        ret
+    }
+
+    /// `None` when the sum doesn't fit, digits below the scale of the coarsest operand may be
+    /// rounded away for very large values
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        let (lhs, rhs, scale) = self.aligned(rhs)?;
+        Some(Self::new(lhs.checked_add(rhs)?, scale))
+    }
+
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        let (lhs, rhs, scale) = self.aligned(rhs)?;
+        Some(Self::new(lhs.checked_sub(rhs)?, scale))
+    }
+
+    /// Product rounded to `MAX_SCALE` fractional digits, or fewer when its integer part needs
+    /// the room, `None` when it doesn't fit at all
+    pub fn checked_mul(self, rhs: Self) -> Option<Self> {
+        let mut product = mul_wide(self.mantissa.unsigned_abs(), rhs.mantissa.unsigned_abs());
+        let mut scale = self.scale + rhs.scale;
+        let mut dropped = 0;
+        while scale > MAX_SCALE || (scale > 0 && narrow(&product).is_none()) {
+            dropped = div10_wide(&mut product);
+            scale -= 1;
+        }
+        let mut mantissa = narrow(&product)?;
+        // Only the most significant dropped digit decides the rounding, half away from zero
+        if dropped >= 5 {
+            mantissa = mantissa.checked_add(1)?;
+        }
+        if self.is_sign_negative() != rhs.is_sign_negative() {
+            mantissa = -mantissa;
+        }
+        Some(Self::new(mantissa, scale))
+    }
+
+    /// Quotient rounded to `scale` fractional digits, `None` when dividing by zero
+    pub fn checked_div(self, rhs: Self, scale: u32) -> Option<Self> {
+        if rhs.is_zero() {
+            return None;
+        }
+        let scale = scale.min(MAX_SCALE);
+        // mantissa of the quotient at `scale` = lhs * 10^(scale + rhs.scale - lhs.scale) / rhs
+        let exp = scale as i64 + rhs.scale as i64 - self.scale as i64;
+        let (num, den) = if exp >= 0 {
+            (self.mantissa.checked_mul(pow10(exp as u32))?, rhs.mantissa)
+        } else {
+            (self.mantissa, rhs.mantissa.checked_mul(pow10((-exp) as u32))?)
+        };
+        Some(Self::new(div_round(num, den), scale))
+    }
+
+    /// Lossy conversion for display and charting purposes
+    pub fn to_f64(&self) -> f64 {
+        self.to_string().parse().unwrap_or(0.0)
+    }
+}
+
+impl FromStr for Decimal {
+    type Err = Error;
+
+    fn from_str(raw: &str) -> Result<Self> {
+        let invalid = || Error::InvalidDecimal(raw.to_string());
+        let trimmed = raw.trim();
+        let (negative, digits) = match trimmed.as_bytes().first() {
+            Some(b'-') => (true, &trimmed[1..]),
+            Some(b'+') => (false, &trimmed[1..]),
+            _ => (false, trimmed),
+        };
+        let (int, frac) = match digits.split_once('.') {
+            Some((int, frac)) => (int, frac),
+            None => (digits, ""),
+        };
+        if (int.is_empty() && frac.is_empty())
+            || !int.bytes().chain(frac.bytes()).all(|e| e.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+        // Digits beyond MAX_SCALE are rounded away
+        let kept = frac.len().min(MAX_SCALE as usize);
+        let mut mantissa: i128 = format!("{}{}", int, &frac[..kept])
+            .parse()
+            .map_err(|_| invalid())?;
+        if frac.len() > kept && frac.as_bytes()[kept] >= b'5' {
+            mantissa += 1;
+        }
+        if negative {
+            mantissa = -mantissa;
+        }
+        Ok(Self::new(mantissa, kept as u32))
+    }
+}
+
+impl std::convert::TryFrom<f64> for Decimal {
+    type Error = Error;
+
+    fn try_from(value: f64) -> Result<Self> {
+        if !value.is_finite() {
+            return Err(Error::InvalidDecimal(value.to_string()));
+        }
+        value.to_string().parse()
+    }
+}
+
+impl From<i64> for Decimal {
+    fn from(value: i64) -> Self {
+        Self::new(value as i128, 0)
+    }
+}
+
+impl From<u64> for Decimal {
+    fn from(value: u64) -> Self {
+        Self::new(value as i128, 0)
+    }
+}
+
+impl From<usize> for Decimal {
+    fn from(value: usize) -> Self {
+        Self::new(value as i128, 0)
+    }
+}
+
+impl std::fmt::Display for Decimal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = match f.precision() {
+            Some(precision) => self.round_dp(precision as u32),
+            None => *self,
+        };
+        let padding = f
+            .precision()
+            .map(|e| (e as u32).saturating_sub(value.scale))
+            .unwrap_or(0);
+        let mantissa = value.mantissa;
+        let digits = format!(
+            "{:0>width$}",
+            mantissa.unsigned_abs(),
+            width = value.scale as usize + 1
+        );
+        let (int, frac) = digits.split_at(digits.len() - value.scale as usize);
+        if frac.is_empty() && padding == 0 {
+            f.pad_integral(mantissa >= 0, "", int)
+        } else {
+            let frac = format!("{}{}", frac, "0".repeat(padding as usize));
+            f.pad_integral(mantissa >= 0, "", &format!("{}.{}", int, frac))
+        }
+    }
+}
+
+impl std::fmt::Debug for Decimal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == CmpOrdering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Decimal {
+    /// Exact, the magnitudes are compared on 256 bits
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        let sign = self.mantissa.signum().cmp(&other.mantissa.signum());
+        if sign != CmpOrdering::Equal {
+            return sign;
+        }
+        let scale = self.scale.max(other.scale);
+        let lhs = mul_wide(
+            self.mantissa.unsigned_abs(),
+            pow10(scale - self.scale) as u128,
+        );
+        let rhs = mul_wide(
+            other.mantissa.unsigned_abs(),
+            pow10(scale - other.scale) as u128,
+        );
+        let magnitude = lhs.iter().rev().cmp(rhs.iter().rev());
+        if self.is_sign_negative() {
+            magnitude.reverse()
+        } else {
+            magnitude
+        }
+    }
+}
+
+impl std::hash::Hash for Decimal {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        let normalized = self.normalize();
+        normalized.mantissa.hash(state);
+        normalized.scale.hash(state);
+    }
+}
+
+impl std::ops::Add for Decimal {
+    type Output = Decimal;
+
+    /// Panics beyond the range of `checked_add`
+    fn add(self, rhs: Self) -> Self {
+        self.checked_add(rhs).expect("Decimal overflow")
+    }
+}
+
+impl std::ops::Sub for Decimal {
+    type Output = Decimal;
+
+    fn sub(self, rhs: Self) -> Self {
+        self.checked_sub(rhs).expect("Decimal overflow")
+    }
+}
+
+impl std::ops::Mul for Decimal {
+    type Output = Decimal;
+
+    /// Panics beyond the range of `checked_mul`
+    fn mul(self, rhs: Self) -> Self {
+        self.checked_mul(rhs).expect("Decimal overflow")
+    }
+}
+
+impl std::ops::Div for Decimal {
+    type Output = Decimal;
+
+    /// Panics when dividing by zero, see `checked_div` to pick the scale of the quotient
+    fn div(self, rhs: Self) -> Self {
+        let scale = DIV_SCALE.max(self.scale).max(rhs.scale);
+        self.checked_div(rhs, scale)
+            .expect("Decimal division by zero or overflow")
+    }
+}
+
+impl std::ops::Neg for Decimal {
+    type Output = Decimal;
+
+    fn neg(self) -> Self {
+        Self::new(-self.mantissa, self.scale)
+    }
+}
+
+impl std::ops::AddAssign for Decimal {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl std::ops::SubAssign for Decimal {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl std::iter::Sum for Decimal {
+    fn sum<I: Iterator<Item = Decimal>>(iter: I) -> Self {
+        iter.fold(Decimal::ZERO, |acc, e| acc + e)
+    }
+}
+
+impl<'a> std::iter::Sum<&'a Decimal> for Decimal {
+    fn sum<I: Iterator<Item = &'a Decimal>>(iter: I) -> Self {
+        iter.fold(Decimal::ZERO, |acc, e| acc + *e)
+    }
+}
+
+impl Serialize for Decimal {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Decimal {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct DecimalVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for DecimalVisitor {
+            type Value = Decimal;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "a decimal number or string")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> std::result::Result<Decimal, E> {
+                v.parse().map_err(E::custom)
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, v: i64) -> std::result::Result<Decimal, E> {
+                Ok(Decimal::from(v))
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, v: u64) -> std::result::Result<Decimal, E> {
+                Ok(Decimal::from(v))
+            }
+
+            fn visit_f64<E: serde::de::Error>(self, v: f64) -> std::result::Result<Decimal, E> {
+                std::convert::TryFrom::try_from(v).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(DecimalVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(raw: &str) -> Decimal {
+        raw.parse().unwrap()
+    }
+
+    #[test]
+    fn mul_rounds_before_overflowing() {
+        let product = dec("100.000000000000000000") * dec("2.000000000000000000");
+        assert_eq!(product, dec("200"));
+        assert_eq!(product.scale(), MAX_SCALE);
+        assert_eq!(dec("0.000000000000000005") * dec("0.1"), dec("0.000000000000000001"));
+        assert_eq!(dec("-1.5") * dec("0.000000000000000001"), dec("-0.000000000000000002"));
+        assert_eq!(dec("-3") * dec("-0.5"), dec("1.5"));
+    }
+
+    #[test]
+    fn mul_gives_up_fractional_digits_for_large_values() {
+        let mantissa = i128::MAX / 1_000;
+        let big = Decimal::new(mantissa, 0);
+        // The product needs every digit of an i128, its fractional .5 is rounded away
+        let product = big.checked_mul(dec("100.5")).unwrap();
+        assert_eq!(product, Decimal::new(mantissa * 100 + (mantissa + 1) / 2, 0));
+        assert!(big.checked_mul(dec("10000")).is_none());
+    }
+
+    #[test]
+    fn add_aligns_large_values() {
+        let big = Decimal::new(10i128.pow(30), 0);
+        assert_eq!(big - dec("0.000000000000000001"), big);
+        let huge = Decimal::new(10i128.pow(38), 0);
+        assert_eq!(huge + dec("0.4"), huge);
+        assert_eq!(huge + dec("0.5"), Decimal::new(10i128.pow(38) + 1, 0));
+        assert_eq!(dec("1.25") + dec("0.005"), dec("1.255"));
+        assert!(Decimal::new(i128::MAX, 0).checked_add(Decimal::ONE).is_none());
+    }
+
+    #[test]
+    fn cmp_is_exact() {
+        assert!(Decimal::new(10i128.pow(37) + 1, 9) > Decimal::new(10i128.pow(28), 0));
+        assert!(dec("-1.01") < dec("-1"));
+        assert!(dec("0") > dec("-0.000000000000000001"));
+        assert_eq!(dec("1.10"), dec("1.1"));
+    }
+
+    #[test]
+    fn display_pads_precision() {
+        assert_eq!(format!("{:.4}", dec("1.5")), "1.5000");
+        assert_eq!(format!("{:.1}", dec("-1.25")), "-1.3");
+        assert_eq!(format!("{}", dec("0.05")), "0.05");
+    }
+}
@@ -18,6 +18,10 @@ pub enum Error {
     Encoding(#[from] bincode::error::EncodeError),
     #[error("decode: {0}")]
     Decoding(#[from] bincode::error::DecodeError),
+    #[error("http: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Unexpected exchange response: {0}")]
+    ExchangeResponse(String),
     #[error("api error: {0}")]
     ApiServer(#[from] rocket::Error),
     #[error("Missing environ: {0}")]
@@ -38,10 +42,86 @@ pub enum Error {
     ParseInt(#[from] std::num::ParseIntError),
     #[error("Wrong interval: {0}")]
     InvalidInterval(i64),
+    #[error("Program not found: {0}")]
+    ProgramNotFound(u64),
+    #[error("Program run not found: {0}")]
+    RunNotFound(u64),
+    #[error("Invalid schedule: {0}")]
+    InvalidSchedule(String),
+    #[error("Schedule not found: {0}")]
+    ScheduleNotFound(String),
+    #[error("Alert not found: {0}")]
+    AlertNotFound(String),
+    #[error("Alert delivery: {0}")]
+    AlertDelivery(String),
+    #[error("Invalid watchlist: {0}")]
+    InvalidWatchlist(String),
+    #[error("Watchlist not found: {0}")]
+    WatchlistNotFound(String),
+    #[error("Invalid decimal: `{0}`")]
+    InvalidDecimal(String),
+    #[error("Store schema version mismatch: expected {expected}, found {found} (run `pkbot store migrate`)")]
+    SchemaMismatch { expected: u32, found: u32 },
+    #[error("parquet: {0}")]
+    Parquet(#[from] parquet::errors::ParquetError),
+    #[error("Import/export: {0}")]
+    Transfer(String),
+    #[error("Tree not found: {0}")]
+    TreeNotFound(String),
+    #[error("Invalid retention rule: {0}")]
+    InvalidRetention(String),
+    #[error("Backup: {0}")]
+    Backup(String),
+    #[error("Stream: {0}")]
+    Stream(String),
+    #[error("Not supported: {0}")]
+    NotSupported(String),
+    #[error("Order rejected: {0}")]
+    RiskViolation(crate::reactor::RiskViolation),
+    #[error("Invalid strategy: {0}")]
+    InvalidStrategy(String),
+    #[error("Strategy not found: {0}")]
+    StrategyNotFound(String),
+    #[error("Circuit open, calls to {0} are suspended")]
+    CircuitOpen(String),
+    #[error("Invalid configuration: {0}")]
+    Config(String),
     #[error("Arguments parsing: {0}")]
     Clap(#[from] clap::Error),
 }
 
+/// Exchange errors worth retrying, Kraken reports them with these prefixes
+const TRANSIENT_EXCHANGE_ERRORS: [&str; 5] = [
+    "EAPI:Rate limit exceeded",
+    "EService:Unavailable",
+    "EService:Busy",
+    "EService:Deadline elapsed",
+    "EGeneral:Temporary lockout",
+];
+
+impl Error {
+    /// Whether the failure may go away by itself (network issue, exchange overloaded...)
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::Http(e) => {
+                e.is_timeout()
+                    || e.is_connect()
+                    || e.status()
+                        .map(|e| e.is_server_error() || e.as_u16() == 429)
+                        .unwrap_or(false)
+            }
+            Error::IO(_) => true,
+            Error::KrakenAPIError(_) | Error::ExchangeResponse(_) => {
+                let message = self.to_string();
+                TRANSIENT_EXCHANGE_ERRORS.iter().any(|e| message.contains(e))
+                    || message.contains("timed out")
+                    || message.contains("connection")
+            }
+            _ => false,
+        }
+    }
+}
+
 impl<'r> rocket::response::Responder<'r, 'static> for crate::error::Error {
     fn respond_to(self, r: &'r rocket::Request<'_>) -> rocket::response::Result<'static> {
         log::error!(
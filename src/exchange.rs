@@ -1,6 +1,20 @@
 use crate::prelude::*;
+mod asset;
+mod book;
 mod kraken;
+mod kraken_stream;
+mod middleware;
+mod mock;
+mod order;
+mod stream;
+pub use asset::*;
+pub use book::*;
 pub use kraken::*;
+pub use kraken_stream::*;
+pub use middleware::*;
+pub use mock::*;
+pub use order::*;
+pub use stream::*;
 
 pub struct OHLCChunk {
     pub data: Vec<OHLC>,
@@ -31,16 +45,12 @@ impl OHLCChunk {
 pub struct OHLC {
     pub first_available: bool,
     pub time: Timestamp,
-    pub open: String,
-    pub high: String,
-    pub low: String,
-    pub close: String,
-    pub open_normalized: f64,
-    pub high_normalized: f64,
-    pub low_normalized: f64,
-    pub close_normalized: f64,
-    pub vwap: String,
-    pub volume: String,
+    pub open: Decimal,
+    pub high: Decimal,
+    pub low: Decimal,
+    pub close: Decimal,
+    pub vwap: Decimal,
+    pub volume: Decimal,
     pub count: u64,
 }
 
@@ -48,30 +58,43 @@ impl OHLC {
     pub fn new(
         first_available: bool,
         time: Timestamp,
-        open: String,
-        high: String,
-        low: String,
-        close: String,
-        vwap: String,
-        volume: String,
+        open: &str,
+        high: &str,
+        low: &str,
+        close: &str,
+        vwap: &str,
+        volume: &str,
         count: u64,
-    ) -> Self {
-        Self {
+    ) -> Result<Self> {
+        Ok(Self {
             first_available,
             time,
-            open_normalized: open.parse().unwrap(),
-            high_normalized: high.parse().unwrap(),
-            low_normalized: low.parse().unwrap(),
-            close_normalized: close.parse().unwrap(),
-            open,
-            high,
-            low,
-            close,
-            vwap,
-            volume,
+            open: open.parse()?,
+            high: high.parse()?,
+            low: low.parse()?,
+            close: close.parse()?,
+            vwap: vwap.parse()?,
+            volume: volume.parse()?,
             count,
+        })
+    }
+
+    /// Round prices and volume to the precision of the market
+    pub fn with_precision(self, definition: &MarketDefinition) -> Self {
+        Self {
+            open: definition.round_price(self.open),
+            high: definition.round_price(self.high),
+            low: definition.round_price(self.low),
+            close: definition.round_price(self.close),
+            vwap: definition.round_price(self.vwap),
+            volume: self.volume.round_dp(definition.lot_decimals.max(0) as u32),
+            ..self
         }
     }
+
+    pub fn is_bullish(&self) -> bool {
+        self.close >= self.open
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Eq, PartialEq, Hash, Serialize)]
@@ -108,6 +131,27 @@ pub struct MarketDefinition {
     pub ordermin: Option<String>,
 }
 
+impl MarketDefinition {
+    /// Round a price to the precision accepted by the exchange
+    pub fn round_price(&self, price: Decimal) -> Decimal {
+        price.round_dp(self.pair_decimals.max(0) as u32)
+    }
+
+    /// Truncate a volume to the lot precision so an order never exceeds the requested size
+    pub fn round_volume(&self, volume: Decimal) -> Decimal {
+        volume.trunc_dp(self.lot_decimals.max(0) as u32)
+    }
+
+    /// Fee of the lowest volume tier, in percent
+    pub fn taker_fee(&self) -> Result<Decimal> {
+        std::convert::TryFrom::try_from(self.fees.first().map(|e| e.1).unwrap_or(0.0))
+    }
+
+    pub fn min_volume(&self) -> Result<Option<Decimal>> {
+        self.ordermin.as_deref().map(str::parse).transpose()
+    }
+}
+
 impl std::fmt::Display for MarketIdentifier {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}_{}/{}", &self.exchange_name, &self.base, &self.quote)
@@ -123,9 +167,41 @@ impl MarketIdentifier {
         format!("{}_{}", self.tree_uid(), interval.as_secs())
     }
 
+    pub fn trades_tree_uid(&self) -> String {
+        format!("{}_trades", self.tree_uid())
+    }
+
     pub fn pair_name(&self) -> String {
         format!("{}/{}", self.base, self.quote)
     }
+
+    /// Inverse of `trades_tree_uid`
+    pub fn from_trades_tree_uid(name: &str) -> Option<Self> {
+        let uid = name.strip_suffix("_trades")?;
+        let (exchange_name, pair) = uid.split_once('_')?;
+        let (base, quote) = pair.split_once('/')?;
+        Some(MarketIdentifier {
+            exchange_name: exchange_name.to_string(),
+            base: base.to_string(),
+            quote: quote.to_string(),
+        })
+    }
+
+    /// Inverse of `data_tree_uid`, `None` when the name isn't the one of a market data tree
+    pub fn from_data_tree_uid(name: &str) -> Option<(Self, Interval)> {
+        let (uid, secs) = name.rsplit_once('_')?;
+        let (exchange_name, pair) = uid.split_once('_')?;
+        let (base, quote) = pair.split_once('/')?;
+        let interval = Interval::from_minuts(secs.parse::<i64>().ok()? / 60).ok()?;
+        Some((
+            MarketIdentifier {
+                exchange_name: exchange_name.to_string(),
+                base: base.to_string(),
+                quote: quote.to_string(),
+            },
+            interval,
+        ))
+    }
 }
 
 #[async_trait]
@@ -134,12 +210,41 @@ pub trait Exchange {
 
     async fn get_severt_time(&self) -> Result<NaiveDateTime>;
 
-    async fn get_ohlc(
+    /// One page of candles starting at `since`, empty or a single candle once up to date
+    async fn get_ohlc_page(
         &self,
         id: &MarketIdentifier,
         since: Timestamp,
         interval: Interval,
-    ) -> Result<OHLCChunk>;
+    ) -> Result<Vec<OHLC>>;
+
+    /// Every candle since `since`, page after page
+    async fn get_ohlc(
+        &self,
+        id: &MarketIdentifier,
+        mut since: Timestamp,
+        interval: Interval,
+    ) -> Result<OHLCChunk> {
+        let original_since = since;
+        let mut chunk = Vec::new();
+        loop {
+            log::trace!("Request OHLC chunk to external API: EXCHANGE={}, BASE={}, QUOTE={}, SINCE={}, INTERVAL={}", &id.exchange_name, &id.base, &id.quote, since, interval);
+            let page = self.get_ohlc_page(id, since, interval).await?;
+            log::trace!("OHLC chunk received from API: EXCHANGE={}, BASE={}, QUOTE={}, SINCE={}, INTERVAL={}, NBR_CANDLES={}", &id.exchange_name, &id.base, &id.quote, since, interval, page.len());
+            let page_len = page.len();
+            if let Some(last) = page.last() {
+                since = last.time;
+            }
+            chunk.extend(page);
+            if original_since == 0 && page_len > 0 {
+                chunk[0].first_available = true;
+            }
+            if page_len <= 1 {
+                break;
+            }
+        }
+        Ok(OHLCChunk::new(chunk))
+    }
 
     async fn refresh_market_cache(&self) -> Result<()>;
 
@@ -150,4 +255,69 @@ pub trait Exchange {
         id: &MarketIdentifier,
         max_age: Option<Duration>,
     ) -> Result<MarketDefinition>;
+
+    /// Markets known without calling the exchange, `None` when the cache must be filled first
+    async fn cached_markets(&self) -> Option<Vec<MarketIdentifier>> {
+        None
+    }
+
+    /// Definition known without calling the exchange, `None` when it is missing or too old
+    async fn cached_market_definition(
+        &self,
+        _id: &MarketIdentifier,
+        _max_age: Option<Duration>,
+    ) -> Option<MarketDefinition> {
+        None
+    }
+
+    /// The `depth` best levels of each side of the order book
+    async fn get_order_book(&self, id: &MarketIdentifier, depth: usize) -> Result<OrderBook>;
+
+    /// Recent public trades, only the ones after `since_ms` when given, oldest first
+    async fn get_trades(&self, id: &MarketIdentifier, since_ms: Option<i64>) -> Result<Vec<Trade>>;
+
+    /// Balances of the account by canonical asset symbol
+    async fn get_balances(&self) -> Result<HashMap<String, Decimal>> {
+        Err(Error::NotSupported(format!("{} has no account balances", self.name())))
+    }
+
+    /// Trades of the account after `since` starting at `offset`, with the number of trades
+    /// after `since`
+    async fn get_fills_page(
+        &self,
+        _since: Option<Timestamp>,
+        _offset: usize,
+    ) -> Result<(Vec<Fill>, usize)> {
+        Err(Error::NotSupported(format!("{} has no account trades", self.name())))
+    }
+
+    /// Trades of the account after `since`, oldest first, page after page
+    async fn get_fills(&self, since: Option<Timestamp>) -> Result<Vec<Fill>> {
+        let mut ret = Vec::new();
+        loop {
+            let (page, count) = self.get_fills_page(since, ret.len()).await?;
+            let page_len = page.len();
+            ret.extend(page);
+            if page_len == 0 || ret.len() >= count {
+                break;
+            }
+        }
+        ret.sort_by(|a, b| (a.time, &a.id).cmp(&(b.time, &b.id)));
+        Ok(ret)
+    }
+
+    /// Send an order, only `Reactor::submit_order` calls it so every order passes the risk checks
+    async fn place_order(&self, _order: &OrderRequest) -> Result<PlacedOrder> {
+        Err(Error::NotSupported(format!("{} has no trading api", self.name())))
+    }
+
+    /// Live market data, `None` when the exchange has no streaming api
+    fn stream(&self) -> Option<Arc<dyn ExchangeStream>> {
+        None
+    }
+
+    /// Call counters, only kept by a `GuardedExchange`
+    fn counters(&self) -> Option<Arc<ExchangeCounters>> {
+        None
+    }
 }
@@ -0,0 +1,121 @@
+use super::*;
+use std::collections::BTreeMap;
+
+/// Aliases known without configuration, Kraken prefixes its legacy codes with `X` (crypto)
+/// and `Z` (fiat)
+const DEFAULT_ALIASES: [(&str, &[&str]); 16] = [
+    ("BTC", &["XBT", "XXBT"]),
+    ("ETH", &["XETH"]),
+    ("LTC", &["XLTC"]),
+    ("XRP", &["XXRP"]),
+    ("XLM", &["XXLM"]),
+    ("XMR", &["XXMR"]),
+    ("ETC", &["XETC"]),
+    ("ZEC", &["XZEC"]),
+    ("REP", &["XREP"]),
+    ("MLN", &["XMLN"]),
+    ("DOGE", &["XDG", "XXDG"]),
+    ("EUR", &["ZEUR"]),
+    ("USD", &["ZUSD"]),
+    ("GBP", &["ZGBP"]),
+    ("CAD", &["ZCAD"]),
+    ("JPY", &["ZJPY"]),
+];
+
+/// Canonical symbols of the assets (`BTC`, `EUR`...) and the codes exchanges use for them, so
+/// a pair is named the same way on every exchange
+#[derive(Debug, Default)]
+pub struct AssetRegistry {
+    /// Alias, on any exchange, to canonical symbol
+    aliases: std::sync::RwLock<HashMap<String, String>>,
+    /// `(exchange, code)` to canonical symbol, learned from the exchanges
+    codes: std::sync::RwLock<HashMap<(String, String), String>>,
+}
+
+impl AssetRegistry {
+    /// Registry with the default aliases
+    pub fn new() -> Self {
+        let registry = Self::default();
+        for (canonical, aliases) in DEFAULT_ALIASES.iter() {
+            for alias in aliases.iter() {
+                registry.alias(canonical, alias);
+            }
+        }
+        registry
+    }
+
+    pub fn shared(self) -> Arc<Self> {
+        Arc::new(self)
+    }
+
+    pub fn alias(&self, canonical: &str, alias: &str) {
+        self.aliases
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(alias.to_uppercase(), canonical.to_uppercase());
+    }
+
+    /// Record the code used by an exchange for an asset, `symbol` may itself be an alias
+    pub fn register(&self, exchange: &str, code: &str, symbol: &str) {
+        let canonical = self.canonical("", symbol);
+        if canonical != code.to_uppercase() {
+            self.codes
+                .write()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert((exchange.to_string(), code.to_uppercase()), canonical);
+        }
+    }
+
+    /// Canonical symbol of `code`, codes unknown to the registry are their own symbol
+    pub fn canonical(&self, exchange: &str, code: &str) -> String {
+        let code = code.to_uppercase();
+        if let Some(canonical) = self
+            .codes
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&(exchange.to_string(), code.clone()))
+        {
+            return canonical.clone();
+        }
+        self.aliases
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&code)
+            .cloned()
+            .unwrap_or(code)
+    }
+
+    pub fn canonical_market(&self, id: &MarketIdentifier) -> MarketIdentifier {
+        MarketIdentifier {
+            exchange_name: id.exchange_name.clone(),
+            base: self.canonical(&id.exchange_name, &id.base),
+            quote: self.canonical(&id.exchange_name, &id.quote),
+        }
+    }
+
+    /// Whether two markets, possibly of different exchanges, trade the same pair
+    pub fn same_pair(&self, a: &MarketIdentifier, b: &MarketIdentifier) -> bool {
+        let (a, b) = (self.canonical_market(a), self.canonical_market(b));
+        a.base == b.base && a.quote == b.quote
+    }
+
+    /// Every alias and exchange code of each canonical symbol
+    pub fn symbols(&self) -> BTreeMap<String, Vec<String>> {
+        let mut ret: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (alias, canonical) in self.aliases.read().unwrap_or_else(|e| e.into_inner()).iter() {
+            ret.entry(canonical.clone()).or_default().push(alias.clone());
+        }
+        for ((exchange, code), canonical) in
+            self.codes.read().unwrap_or_else(|e| e.into_inner()).iter()
+        {
+            ret.entry(canonical.clone())
+                .or_default()
+                .push(format!("{}:{}", exchange, code));
+        }
+        for aliases in ret.values_mut() {
+            aliases.sort();
+            aliases.dedup();
+        }
+        ret
+    }
+}
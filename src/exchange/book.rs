@@ -0,0 +1,121 @@
+use super::*;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BookLevel {
+    pub price: Decimal,
+    pub volume: Decimal,
+}
+
+/// Snapshot of the best levels of an order book, bids highest first and asks lowest first
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OrderBook {
+    pub time: Timestamp,
+    pub bids: Vec<BookLevel>,
+    pub asks: Vec<BookLevel>,
+}
+
+impl OrderBook {
+    pub fn best_bid(&self) -> Option<&BookLevel> {
+        self.bids.first()
+    }
+
+    pub fn best_ask(&self) -> Option<&BookLevel> {
+        self.asks.first()
+    }
+
+    pub fn spread(&self) -> Option<Decimal> {
+        Some(self.best_ask()?.price - self.best_bid()?.price)
+    }
+
+    pub fn mid_price(&self) -> Option<Decimal> {
+        Some((self.best_ask()?.price + self.best_bid()?.price) / Decimal::from(2i64))
+    }
+
+    /// Spread relative to the mid price, in basis points
+    pub fn spread_bps(&self) -> Option<Decimal> {
+        let mid = self.mid_price()?;
+        Some((self.spread()? * Decimal::from(10_000i64)).checked_div(mid, 2)?)
+    }
+
+    /// Volume of the `levels` best bids and asks
+    pub fn depth(&self, levels: usize) -> (Decimal, Decimal) {
+        (
+            self.bids.iter().take(levels).map(|e| e.volume).sum(),
+            self.asks.iter().take(levels).map(|e| e.volume).sum(),
+        )
+    }
+
+    /// Volume of the bids and asks priced within `percent` of the mid price
+    pub fn depth_within(&self, percent: Decimal) -> Option<(Decimal, Decimal)> {
+        let mid = self.mid_price()?;
+        let offset = mid * percent / Decimal::ONE_HUNDRED;
+        Some((
+            self.bids
+                .iter()
+                .take_while(|e| e.price >= mid - offset)
+                .map(|e| e.volume)
+                .sum(),
+            self.asks
+                .iter()
+                .take_while(|e| e.price <= mid + offset)
+                .map(|e| e.volume)
+                .sum(),
+        ))
+    }
+
+    /// `(bids - asks) / (bids + asks)` over the `levels` best levels, from -1 (only asks) to 1
+    /// (only bids)
+    pub fn imbalance(&self, levels: usize) -> Option<Decimal> {
+        let (bids, asks) = self.depth(levels);
+        (bids - asks).checked_div(bids + asks, 4)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Encode, Decode, Serialize, Deserialize)]
+pub enum TradeSide {
+    Buy,
+    Sell,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Encode, Decode, Serialize, Deserialize)]
+pub enum TradeOrderType {
+    Market,
+    Limit,
+}
+
+/// A public trade of a market
+#[derive(Clone, Debug, Encode, Decode, Serialize, Deserialize)]
+pub struct Trade {
+    /// Identifier given by the exchange, unique within a market
+    pub id: u64,
+    /// Milliseconds since the epoch
+    pub time_ms: i64,
+    pub price: Decimal,
+    pub volume: Decimal,
+    pub side: TradeSide,
+    pub order_type: TradeOrderType,
+}
+
+/// A trade of the account, imported from an exchange or entered by hand
+#[derive(Clone, Debug, Encode, Decode, Serialize, Deserialize)]
+pub struct Fill {
+    /// Identifier given by the exchange, unique within the exchange
+    pub id: String,
+    /// `exchange/BASE/QUOTE` with canonical symbols
+    pub market: String,
+    pub time: Timestamp,
+    pub side: TradeSide,
+    pub price: Decimal,
+    pub volume: Decimal,
+    /// Fee paid, in the quote asset
+    pub fee: Decimal,
+    /// Transaction id of the order filled, when the exchange reports it
+    #[serde(default)]
+    pub order: Option<String>,
+}
+
+impl Fill {
+    pub fn market_id(&self) -> MarketIdentifier {
+        MarketIdentifier::from(&self.market)
+    }
+}
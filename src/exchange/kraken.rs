@@ -1,15 +1,30 @@
 use super::*;
 use crate::reactor::SyncExchange;
+use hmac::{Hmac, Mac, NewMac};
 use kraken_sdk_rest::{Client, Interval};
+use sha2::{Digest, Sha256, Sha512};
+use std::time::UNIX_EPOCH;
 use tokio::sync::OwnedMutexGuard;
 
 pub static EXCHANGE_NAME: &str = "kraken";
+/// Public endpoints missing from the sdk are queried directly
+pub(crate) const PUBLIC_API_URL: &str = "https://api.kraken.com/0/public";
+const API_URL: &str = "https://api.kraken.com";
+const PRIVATE_API_PATH: &str = "/0/private";
 
 pub struct KrakenExchange {
     pub api_key: String,
     pub api_private_key: String,
     pub client: Client,
+    http: reqwest::Client,
+    stream_url: String,
+    assets: Arc<AssetRegistry>,
+    /// Keyed by canonical pairs, see `AssetRegistry`
     pub markets_cache: Arc<Mutex<MarketCacheCell>>,
+    /// Pair names of the private api (`XXBTZEUR`, `XBTEUR`) to canonical pairs
+    pair_names: Arc<std::sync::RwLock<HashMap<String, MarketIdentifier>>>,
+    /// Nonces of the private api must always increase
+    last_nonce: AtomicU64,
 }
 
 type MarketCacheCell = Option<HashMap<MarketIdentifier, MarketDefinition>>;
@@ -18,9 +33,14 @@ impl KrakenExchange {
     pub fn new(api_key: String, api_private_key: String) -> Self {
         Self {
             client: Client::new(&api_key, &api_private_key),
+            http: reqwest::Client::new(),
+            stream_url: KRAKEN_STREAM_URL.to_string(),
+            assets: AssetRegistry::new().shared(),
             api_key,
             api_private_key,
             markets_cache: Arc::new(Mutex::new(None)),
+            pair_names: Arc::new(std::sync::RwLock::new(HashMap::new())),
+            last_nonce: AtomicU64::new(0),
         }
     }
 
@@ -32,23 +52,157 @@ impl KrakenExchange {
         Ok(Self::new(api_key, api_private_key))
     }
 
-    pub fn boxed(self) -> SyncExchange {
-        Arc::new(Mutex::new(Box::new(self)))
+    /// Stream from another server, such as `pkbot replay`
+    pub fn with_stream_url(self, stream_url: String) -> Self {
+        Self { stream_url, ..self }
     }
 
-    async fn refresh_market_cache(
-        mut lock: OwnedMutexGuard<MarketCacheCell>,
-        client: &Client,
-    ) -> Result<()> {
-        // let mut lock = self.markets_cache.lock().await;
-        let pairs = client.get_asset_pairs().send().await?;
+    /// Share the asset symbols with the reactor
+    pub fn with_assets(self, assets: Arc<AssetRegistry>) -> Self {
+        Self { assets, ..self }
+    }
+
+    pub fn shared(self) -> SyncExchange {
+        Arc::new(self)
+    }
+
+    /// Send a request to a public endpoint and return the `result` object of the response
+    async fn public_request(&self, method: &str, query: &[(&str, String)]) -> Result<Value> {
+        let response: Value = self
+            .http
+            .get(format!("{}/{}", PUBLIC_API_URL, method))
+            .query(query)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Self::response_result(method, response)
+    }
+
+    /// Microseconds since the epoch, bumped when two requests are sent within the same one
+    fn nonce(&self) -> u64 {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+        let previous = self
+            .last_nonce
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |last| Some(now.max(last + 1)))
+            .unwrap_or(now);
+        now.max(previous + 1)
+    }
+
+    /// `API-Sign` header: HMAC-SHA512 of the path and the SHA256 of the nonce and the body,
+    /// keyed by the decoded private key
+    fn sign(&self, path: &str, nonce: u64, body: &str) -> Result<String> {
+        let key = base64::decode(&self.api_private_key)
+            .map_err(|e| Error::Config(format!("kraken private key: {}", e)))?;
+        let mut mac = Hmac::<Sha512>::new_varkey(&key)
+            .map_err(|e| Error::Config(format!("kraken private key: {}", e)))?;
+        mac.update(path.as_bytes());
+        mac.update(&Sha256::digest(format!("{}{}", nonce, body).as_bytes()));
+        Ok(base64::encode(mac.finalize().into_bytes()))
+    }
+
+    /// Send a signed request to a private endpoint and return the `result` object of the
+    /// response, parameters are plain numbers and identifiers which need no encoding
+    async fn private_request(&self, method: &str, params: &[(&str, String)]) -> Result<Value> {
+        let path = format!("{}/{}", PRIVATE_API_PATH, method);
+        let nonce = self.nonce();
+        let body = std::iter::once(format!("nonce={}", nonce))
+            .chain(params.iter().map(|(k, v)| format!("{}={}", k, v)))
+            .join("&");
+        let response: Value = self
+            .http
+            .post(format!("{}{}", API_URL, path))
+            .header("API-Key", &self.api_key)
+            .header("API-Sign", self.sign(&path, nonce, &body)?)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Self::response_result(method, response)
+    }
+
+    fn response_result(method: &str, response: Value) -> Result<Value> {
+        if let Some(errors) = response["error"].as_array().filter(|e| !e.is_empty()) {
+            return Err(Error::ExchangeResponse(format!(
+                "{}: {}",
+                method,
+                errors.iter().map(|e| e.to_string()).join(", ")
+            )));
+        }
+        Ok(response["result"].clone())
+    }
+
+    /// Results are keyed by the canonical pair name which differs from the requested one
+    fn pair_result<'a>(method: &str, result: &'a Value) -> Result<&'a Value> {
+        result
+            .as_object()
+            .and_then(|e| e.iter().find(|(k, _)| *k != "last").map(|(_, v)| v))
+            .ok_or_else(|| Error::ExchangeResponse(format!("{}: missing pair in response", method)))
+    }
+
+    fn parse_decimal(method: &str, value: &Value) -> Result<Decimal> {
+        value
+            .as_str()
+            .ok_or_else(|| {
+                Error::ExchangeResponse(format!(
+                    "{}: expected a decimal string, found {}",
+                    method, value
+                ))
+            })?
+            .parse()
+    }
+
+    fn parse_levels(levels: &Value) -> Result<Vec<BookLevel>> {
+        let mut ret = Vec::new();
+        for level in levels.as_array().into_iter().flatten() {
+            ret.push(BookLevel {
+                price: Self::parse_decimal("Depth", &level[0])?,
+                volume: Self::parse_decimal("Depth", &level[1])?,
+            });
+        }
+        Ok(ret)
+    }
+
+    /// Pairs only carry the legacy codes of their assets (`XXBT`, `ZEUR`) and an altname made
+    /// of the short ones (`XBTEUR`), find which short codes the altname is made of
+    fn learn_asset_codes(assets: &AssetRegistry, base: &str, quote: &str, altname: &str) {
+        let short = |code: &str| {
+            let mut ret = vec![code.to_string()];
+            if code.len() == 4 && (code.starts_with('X') || code.starts_with('Z')) {
+                ret.push(code[1..].to_string());
+            }
+            ret
+        };
+        for (short_base, short_quote) in short(base).into_iter().cartesian_product(short(quote)) {
+            if format!("{}{}", short_base, short_quote) == altname {
+                assets.register(EXCHANGE_NAME, base, &short_base);
+                assets.register(EXCHANGE_NAME, quote, &short_quote);
+                return;
+            }
+        }
+    }
+
+    async fn fill_market_cache(&self, mut lock: OwnedMutexGuard<MarketCacheCell>) -> Result<()> {
+        let assets = &self.assets;
+        let pairs = self.client.get_asset_pairs().send().await?;
         let mut map = HashMap::new();
-        for (_, pair) in pairs {
+        let mut pair_names = HashMap::new();
+        for (name, pair) in pairs {
+            Self::learn_asset_codes(assets, &pair.base, &pair.quote, &pair.altname);
             let id = MarketIdentifier {
                 exchange_name: EXCHANGE_NAME.to_string(),
-                base: pair.base,
-                quote: pair.quote,
+                base: assets.canonical(EXCHANGE_NAME, &pair.base),
+                quote: assets.canonical(EXCHANGE_NAME, &pair.quote),
             };
+            pair_names.insert(name, id.clone());
+            pair_names.insert(pair.altname.clone(), id.clone());
             let age = std::time::SystemTime::now();
             let def = MarketDefinition {
                 pairname: pair.altname,
@@ -74,8 +228,29 @@ impl KrakenExchange {
             map.len()
         );
         lock.replace(map.clone());
+        *self.pair_names.write().unwrap_or_else(|e| e.into_inner()) = pair_names;
         Ok(())
     }
+
+    /// Canonical pair of a pair name of the private api
+    async fn pair_of(&self, name: &str) -> Result<MarketIdentifier> {
+        let known = self
+            .pair_names
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(name)
+            .cloned();
+        if let Some(id) = known {
+            return Ok(id);
+        }
+        self.refresh_market_cache().await?;
+        self.pair_names
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(name)
+            .cloned()
+            .ok_or_else(|| Error::ExchangeResponse(format!("unknown kraken pair `{}`", name)))
+    }
 }
 
 #[async_trait]
@@ -89,67 +264,65 @@ impl Exchange for KrakenExchange {
         Ok(NaiveDateTime::from_timestamp(server_time.unixtime, 0))
     }
 
-    async fn get_ohlc(
+    async fn get_ohlc_page(
         &self,
         id: &MarketIdentifier,
-        mut since: Timestamp,
+        since: Timestamp,
         interval: crate::prelude::Interval,
-    ) -> Result<OHLCChunk> {
-        let original_since = since;
+    ) -> Result<Vec<super::OHLC>> {
         let market = self.get_market_definition(id, None).await?;
-        let mut chunk = Vec::new();
-        loop {
-            log::trace!("Request OHLC chunk to external API: EXCHANGE={}, BASE={}, QUOTE={}, SINCE={}, INTERVAL={}", &id.exchange_name, &id.base, &id.quote, since, interval);
-            let mut sub_chunk_len = 0;
-            let sub_chunk = self
-                .client
-                .get_ohlc_data(market.pairname.clone())
-                .interval(interval.into())
-                .since(since as u64)
-                .send()
-                .await?;
-            log::trace!("OHLC chunk received from API: EXCHANGE={}, BASE={}, QUOTE={}, SINCE={}, INTERVAL={}, NBR_CANDLES={}", &id.exchange_name, &id.base, &id.quote, since, interval, sub_chunk.len());
-            let sub_chunk = sub_chunk.into_iter().map(|e| {
-                since = e.0;
-                sub_chunk_len += 1;
-                super::OHLC::new(false, e.0, e.1, e.2, e.3, e.4, e.5, e.6, e.7)
-            });
-            chunk.extend(sub_chunk);
-            if original_since == 0 && sub_chunk_len > 0 {
-                chunk[0].first_available = true;
-            }
-            if sub_chunk_len <= 1 {
-                break;
-            }
-        }
-        Ok(OHLCChunk::new(chunk))
+        let page = self
+            .client
+            .get_ohlc_data(market.pairname.clone())
+            .interval(interval.into())
+            .since(since as u64)
+            .send()
+            .await?;
+        page.into_iter()
+            .map(|e| {
+                Ok(super::OHLC::new(false, e.0, &e.1, &e.2, &e.3, &e.4, &e.5, &e.6, e.7)?
+                    .with_precision(&market))
+            })
+            .collect()
     }
 
     async fn refresh_market_cache(&self) -> Result<()> {
         log::trace!("Refresh market cache: EXCHANGE={}", EXCHANGE_NAME);
         let lock = self.markets_cache.clone().lock_owned().await;
-        Self::refresh_market_cache(lock, &self.client).await?;
+        self.fill_market_cache(lock).await?;
         Ok(())
     }
 
+    async fn cached_markets(&self) -> Option<Vec<MarketIdentifier>> {
+        let lock = self.markets_cache.lock().await;
+        lock.as_ref().map(|e| e.keys().cloned().collect())
+    }
+
+    async fn cached_market_definition(
+        &self,
+        id: &MarketIdentifier,
+        max_age: Option<Duration>,
+    ) -> Option<MarketDefinition> {
+        let id = &self.assets.canonical_market(id);
+        let lock = self.markets_cache.lock().await;
+        let market = lock.as_ref()?.get(id)?;
+        let fresh = max_age
+            .map(|e| market.age.elapsed().map(|age| age < e).unwrap_or(false))
+            .unwrap_or(true);
+        fresh.then(|| market.clone())
+    }
+
     async fn get_market_definition(
         &self,
         id: &MarketIdentifier,
         max_age: Option<Duration>,
     ) -> Result<MarketDefinition> {
-        let lock = self.markets_cache.clone().lock_owned().await;
-        if let Some(market) = lock
-            .as_ref()
-            .and_then(|markets| markets.get(id).map(|e| e.clone()))
-        {
-            if max_age
-                .map(|e| market.age.elapsed().unwrap() < e)
-                .unwrap_or(true)
-            {
-                return Ok(market.clone());
-            }
+        if let Some(market) = self.cached_market_definition(id, max_age).await {
+            return Ok(market);
         }
-        Self::refresh_market_cache(lock, &self.client).await?;
+        let id = &self.assets.canonical_market(id);
+        let lock = self.markets_cache.clone().lock_owned().await;
+        self.fill_market_cache(lock).await?;
         let lock = self.markets_cache.clone().lock_owned().await;
         if let Some(markets) = lock.as_ref() {
             return markets.get(id).cloned().ok_or(Error::NoData);
@@ -158,12 +331,160 @@ impl Exchange for KrakenExchange {
         }
     }
 
+    async fn get_order_book(&self, id: &MarketIdentifier, depth: usize) -> Result<OrderBook> {
+        let market = self.get_market_definition(id, None).await?;
+        let result = self
+            .public_request(
+                "Depth",
+                &[("pair", market.pairname.clone()), ("count", depth.to_string())],
+            )
+            .await?;
+        let book = Self::pair_result("Depth", &result)?;
+        Ok(OrderBook {
+            time: crate::store::now(),
+            bids: Self::parse_levels(&book["bids"])?,
+            asks: Self::parse_levels(&book["asks"])?,
+        })
+    }
+
+    async fn get_trades(&self, id: &MarketIdentifier, since_ms: Option<i64>) -> Result<Vec<Trade>> {
+        let market = self.get_market_definition(id, None).await?;
+        let mut query = vec![("pair", market.pairname.clone())];
+        if let Some(since_ms) = since_ms {
+            // The cursor of the api is in nanoseconds
+            query.push(("since", (since_ms as i128 * 1_000_000).to_string()));
+        }
+        let result = self.public_request("Trades", &query).await?;
+        let mut ret = Vec::new();
+        // Entries are `[price, volume, time, side, order type, misc, trade id]`
+        for (i, trade) in Self::pair_result("Trades", &result)?
+            .as_array()
+            .into_iter()
+            .flatten()
+            .enumerate()
+        {
+            let time_ms = (trade[2].as_f64().unwrap_or(0.0) * 1000.0).round() as i64;
+            // Other trades of the millisecond of `since_ms` may not have been stored yet, the
+            // ones already stored are replaced
+            if since_ms.map(|e| time_ms < e).unwrap_or(false) {
+                continue;
+            }
+            ret.push(Trade {
+                id: trade[6].as_u64().unwrap_or(i as u64),
+                time_ms,
+                price: Self::parse_decimal("Trades", &trade[0])?,
+                volume: Self::parse_decimal("Trades", &trade[1])?,
+                side: match trade[3].as_str() {
+                    Some("s") => TradeSide::Sell,
+                    _ => TradeSide::Buy,
+                },
+                order_type: match trade[4].as_str() {
+                    Some("l") => TradeOrderType::Limit,
+                    _ => TradeOrderType::Market,
+                },
+            });
+        }
+        Ok(ret)
+    }
+
+    /// Staked and other special balances keep their suffix (`DOT.S`)
+    async fn get_balances(&self) -> Result<HashMap<String, Decimal>> {
+        let result = self.private_request("Balance", &[]).await?;
+        let mut ret: HashMap<String, Decimal> = HashMap::new();
+        for (code, amount) in result.as_object().into_iter().flatten() {
+            let symbol = match code.split_once('.') {
+                Some((code, suffix)) => {
+                    format!("{}.{}", self.assets.canonical(EXCHANGE_NAME, code), suffix)
+                }
+                None => self.assets.canonical(EXCHANGE_NAME, code),
+            };
+            *ret.entry(symbol).or_insert(Decimal::ZERO) += Self::parse_decimal("Balance", amount)?;
+        }
+        Ok(ret)
+    }
+
+    /// `TradesHistory` returns 50 trades a page
+    async fn get_fills_page(
+        &self,
+        since: Option<Timestamp>,
+        offset: usize,
+    ) -> Result<(Vec<Fill>, usize)> {
+        let mut params = vec![("ofs", offset.to_string())];
+        if let Some(since) = since {
+            params.push(("start", since.to_string()));
+        }
+        let result = self.private_request("TradesHistory", &params).await?;
+        let mut ret = Vec::new();
+        for (txid, trade) in result["trades"].as_object().into_iter().flatten() {
+            let market = self.pair_of(trade["pair"].as_str().unwrap_or("")).await?;
+            ret.push(Fill {
+                id: txid.clone(),
+                market: format!("{}/{}/{}", market.exchange_name, market.base, market.quote),
+                time: trade["time"].as_f64().unwrap_or(0.0) as Timestamp,
+                side: match trade["type"].as_str() {
+                    Some("sell") => TradeSide::Sell,
+                    _ => TradeSide::Buy,
+                },
+                price: Self::parse_decimal("TradesHistory", &trade["price"])?,
+                volume: Self::parse_decimal("TradesHistory", &trade["vol"])?,
+                fee: Self::parse_decimal("TradesHistory", &trade["fee"])?,
+                order: trade["ordertxid"].as_str().map(String::from),
+            });
+        }
+        Ok((ret, result["count"].as_u64().unwrap_or(0) as usize))
+    }
+
+    async fn place_order(&self, order: &OrderRequest) -> Result<PlacedOrder> {
+        let market = self.get_market_definition(&order.market, None).await?;
+        let mut params = vec![
+            ("pair", market.pairname.clone()),
+            (
+                "type",
+                match order.side {
+                    TradeSide::Buy => "buy",
+                    TradeSide::Sell => "sell",
+                }
+                .to_string(),
+            ),
+            (
+                "ordertype",
+                match order.order_type {
+                    TradeOrderType::Market => "market",
+                    TradeOrderType::Limit => "limit",
+                }
+                .to_string(),
+            ),
+            ("volume", order.volume.to_string()),
+        ];
+        if let Some(price) = order.price {
+            params.push(("price", price.to_string()));
+        }
+        let result = self.private_request("AddOrder", &params).await?;
+        Ok(PlacedOrder {
+            ids: result["txid"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|e| e.as_str().map(String::from))
+                .collect(),
+            order: order.clone(),
+        })
+    }
+
+    fn stream(&self) -> Option<Arc<dyn ExchangeStream>> {
+        Some(Arc::new(KrakenStream::new(
+            self.stream_url.clone(),
+            self.http.clone(),
+            self.assets.clone(),
+        )))
+    }
+
     async fn get_markets(&self) -> Result<Vec<MarketIdentifier>> {
         let lock = self.markets_cache.clone().lock_owned().await;
         if let Some(markets) = lock.as_ref().map(|e| e.keys().map(|e| e.clone()).collect()) {
             return Ok(markets);
         }
-        Self::refresh_market_cache(lock, &self.client).await?;
+        self.fill_market_cache(lock).await?;
         let lock = self.markets_cache.clone().lock_owned().await;
         if let Some(markets) = lock.as_ref().map(|e| e.keys().map(|e| e.clone()).collect()) {
             return Ok(markets);
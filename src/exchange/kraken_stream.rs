@@ -0,0 +1,365 @@
+use super::*;
+
+pub const KRAKEN_STREAM_URL: &str = "wss://ws.kraken.com";
+
+/// Codec of the public websocket api of Kraken, pairs are named after their `wsname` there
+pub struct KrakenStream {
+    url: String,
+    http: reqwest::Client,
+    assets: Arc<AssetRegistry>,
+    /// `wsname` to market, resolved on the first subscription and kept across reconnections
+    pairs: std::sync::RwLock<HashMap<String, MarketIdentifier>>,
+}
+
+impl KrakenStream {
+    pub fn new(url: String, http: reqwest::Client, assets: Arc<AssetRegistry>) -> Self {
+        Self {
+            url,
+            http,
+            assets,
+            pairs: std::sync::RwLock::new(HashMap::new()),
+        }
+    }
+
+    fn invalid(frame: &str) -> Error {
+        Error::Stream(format!("unexpected kraken frame: {}", frame))
+    }
+
+    fn decimal(value: &Value, frame: &str) -> Result<Decimal> {
+        value.as_str().ok_or_else(|| Self::invalid(frame))?.parse()
+    }
+
+    /// Times are sent as strings of fractional seconds
+    fn seconds(value: &Value, frame: &str) -> Result<f64> {
+        value
+            .as_str()
+            .and_then(|e| e.parse::<f64>().ok())
+            .ok_or_else(|| Self::invalid(frame))
+    }
+
+    /// Map the markets to their `wsname`, markets unknown to the exchange fall back to
+    /// `BASE/QUOTE` so a replay server can be used offline
+    async fn resolve(&self, markets: &[&MarketIdentifier]) -> HashMap<String, MarketIdentifier> {
+        let mut ret = HashMap::new();
+        let pairs: Value = match async {
+            self.http
+                .get(format!("{}/AssetPairs", PUBLIC_API_URL))
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await
+        }
+        .await
+        {
+            Ok(pairs) => pairs,
+            Err(e) => {
+                log::warn!("Failed to resolve kraken stream pairs: ERROR={}", e);
+                Value::Null
+            }
+        };
+        for market in markets {
+            let market_id = self.assets.canonical_market(market);
+            let wsname = pairs["result"]
+                .as_object()
+                .into_iter()
+                .flatten()
+                .map(|(_, pair)| pair)
+                .find(|pair| {
+                    let canonical = |code: &Value| {
+                        self.assets
+                            .canonical(&market.exchange_name, code.as_str().unwrap_or(""))
+                    };
+                    canonical(&pair["base"]) == market_id.base
+                        && canonical(&pair["quote"]) == market_id.quote
+                })
+                .and_then(|pair| pair["wsname"].as_str())
+                .map(str::to_string)
+                .unwrap_or_else(|| market.pair_name());
+            ret.insert(wsname, (*market).clone());
+        }
+        ret
+    }
+
+    fn market(&self, wsname: &Value, frame: &str) -> Result<MarketIdentifier> {
+        let wsname = wsname.as_str().ok_or_else(|| Self::invalid(frame))?;
+        self.pairs
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(wsname)
+            .cloned()
+            .ok_or_else(|| Error::Stream(format!("unknown kraken pair `{}`", wsname)))
+    }
+
+    /// `{"a": [price, whole lot volume, lot volume], "b": [...], "c": [price, lot volume], "v": [today, last 24 hours], ...}`
+    fn ticker(content: &Value, frame: &str) -> Result<Ticker> {
+        Ok(Ticker {
+            time: crate::store::now(),
+            bid: Self::decimal(&content["b"][0], frame)?,
+            ask: Self::decimal(&content["a"][0], frame)?,
+            last: Self::decimal(&content["c"][0], frame)?,
+            volume: Self::decimal(&content["v"][1], frame)?,
+        })
+    }
+
+    /// `[[price, volume, time, side, order type, misc], ...]`, the feed carries no trade id so
+    /// one is derived from the time, with the position in the frame to tell apart trades of
+    /// the same microsecond. It differs from the id of the REST api, the store matches both on
+    /// their content
+    fn trades(content: &Value, frame: &str) -> Result<Vec<Trade>> {
+        let mut ret = Vec::new();
+        for (i, trade) in content.as_array().into_iter().flatten().enumerate() {
+            let time = Self::seconds(&trade[2], frame)?;
+            ret.push(Trade {
+                id: (time * 1_000_000.0).round() as u64 * 1000 + i as u64,
+                time_ms: (time * 1000.0).round() as i64,
+                price: Self::decimal(&trade[0], frame)?,
+                volume: Self::decimal(&trade[1], frame)?,
+                side: match trade[3].as_str() {
+                    Some("s") => TradeSide::Sell,
+                    _ => TradeSide::Buy,
+                },
+                order_type: match trade[4].as_str() {
+                    Some("l") => TradeOrderType::Limit,
+                    _ => TradeOrderType::Market,
+                },
+            });
+        }
+        Ok(ret)
+    }
+
+    /// `[time, end time, open, high, low, close, vwap, volume, count]`, the candle starts one
+    /// interval before its end time
+    fn candle(content: &Value, interval: Interval, frame: &str) -> Result<OHLC> {
+        Ok(OHLC {
+            first_available: false,
+            time: Self::seconds(&content[1], frame)? as Timestamp - interval.as_secs(),
+            open: Self::decimal(&content[2], frame)?,
+            high: Self::decimal(&content[3], frame)?,
+            low: Self::decimal(&content[4], frame)?,
+            close: Self::decimal(&content[5], frame)?,
+            vwap: Self::decimal(&content[6], frame)?,
+            volume: Self::decimal(&content[7], frame)?,
+            count: content[8].as_u64().ok_or_else(|| Self::invalid(frame))?,
+        })
+    }
+}
+
+#[async_trait]
+impl ExchangeStream for KrakenStream {
+    fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    async fn subscribe_frames(&self, subscriptions: &[Subscription]) -> Result<Vec<String>> {
+        let unresolved: Vec<&MarketIdentifier> = {
+            let pairs = self.pairs.read().unwrap_or_else(|e| e.into_inner());
+            subscriptions
+                .iter()
+                .map(|e| &e.market)
+                .filter(|e| !pairs.values().any(|known| known == *e))
+                .unique()
+                .collect()
+        };
+        if !unresolved.is_empty() {
+            let resolved = self.resolve(&unresolved).await;
+            self.pairs
+                .write()
+                .unwrap_or_else(|e| e.into_inner())
+                .extend(resolved);
+        }
+        let pairs = self.pairs.read().unwrap_or_else(|e| e.into_inner());
+        let mut ret = Vec::new();
+        for (channel, subscriptions) in subscriptions.iter().into_group_map_by(|e| e.channel) {
+            let subscription = match channel {
+                StreamChannel::Ticker => serde_json::json!({ "name": "ticker" }),
+                StreamChannel::Trades => serde_json::json!({ "name": "trade" }),
+                StreamChannel::Candles(interval) => {
+                    serde_json::json!({ "name": "ohlc", "interval": interval.as_secs() / 60 })
+                }
+            };
+            let wsnames: Vec<&String> = subscriptions
+                .iter()
+                .filter_map(|sub| pairs.iter().find(|(_, e)| **e == sub.market).map(|(k, _)| k))
+                .collect();
+            ret.push(
+                serde_json::json!({
+                    "event": "subscribe",
+                    "pair": wsnames,
+                    "subscription": subscription,
+                })
+                .to_string(),
+            );
+        }
+        Ok(ret)
+    }
+
+    /// Market data is sent as `[channel id, content, channel name, pair]`, everything else
+    /// (heartbeats, status) as objects with an `event` field
+    fn decode(&self, frame: &str) -> Result<Vec<StreamEvent>> {
+        let value: Value = serde_json::from_str(frame)?;
+        if let Some(event) = value.get("event") {
+            if value["status"] == "error" {
+                return Err(Error::Stream(format!(
+                    "kraken {}: {}",
+                    event,
+                    value["errorMessage"].as_str().unwrap_or("unknown error")
+                )));
+            }
+            return Ok(vec![]);
+        }
+        let frame_items = value.as_array().ok_or_else(|| Self::invalid(frame))?;
+        if frame_items.len() < 4 {
+            return Err(Self::invalid(frame));
+        }
+        let content = &frame_items[1];
+        let channel = frame_items[2].as_str().ok_or_else(|| Self::invalid(frame))?;
+        let market = self.market(&frame_items[3], frame)?;
+        Ok(match channel {
+            "ticker" => vec![StreamEvent::Ticker {
+                market,
+                ticker: Self::ticker(content, frame)?,
+            }],
+            "trade" => vec![StreamEvent::Trades {
+                market,
+                trades: Self::trades(content, frame)?,
+            }],
+            _ => match channel.strip_prefix("ohlc-") {
+                Some(minuts) => {
+                    let interval = Interval::from_minuts(minuts.parse()?)?;
+                    vec![StreamEvent::Candle {
+                        market,
+                        interval,
+                        ohlc: Self::candle(content, interval, frame)?,
+                    }]
+                }
+                None => vec![],
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimal(raw: &str) -> Decimal {
+        raw.parse().unwrap()
+    }
+
+    async fn next(receiver: &mut Receiver<StreamEvent>) -> StreamEvent {
+        tokio::time::timeout(Duration::from_secs(10), receiver.recv())
+            .await
+            .expect("no stream event received")
+            .expect("stream closed")
+    }
+
+    /// Replays the recorded frames, the replay server closes the connection after the last
+    /// one and the stream must connect again
+    #[tokio::test]
+    async fn replayed_frames() {
+        let address = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/kraken-xbt-eur.frames");
+        tokio::spawn(serve_replay(address, fixture, Duration::from_millis(1), false));
+
+        let market = MarketIdentifier::from("kraken/XXBT/ZEUR");
+        let stream = KrakenStream::new(
+            format!("ws://{}", address),
+            reqwest::Client::new(),
+            AssetRegistry::new().shared(),
+        );
+        // Known pairs are not resolved through the REST api
+        stream
+            .pairs
+            .write()
+            .unwrap()
+            .insert(String::from("XBT/EUR"), market.clone());
+        let subscriptions = vec![
+            Subscription {
+                market: market.clone(),
+                channel: StreamChannel::Ticker,
+            },
+            Subscription {
+                market: market.clone(),
+                channel: StreamChannel::Trades,
+            },
+            Subscription {
+                market: market.clone(),
+                channel: StreamChannel::Candles(Interval::Min1),
+            },
+        ];
+        let options = StreamOptions {
+            reconnect_min: Duration::from_millis(10),
+            reconnect_max: Duration::from_millis(100),
+            ..StreamOptions::default()
+        };
+        let (sender, mut receiver) = channel(64);
+        let cancel = CancellationToken::new();
+        // The replay server may not be listening yet, failed connections are retried
+        tokio::spawn(run_stream(Arc::new(stream), subscriptions, options, sender, cancel.clone()));
+        let mut event = next(&mut receiver).await;
+        while let StreamEvent::Disconnected { .. } = event {
+            event = next(&mut receiver).await;
+        }
+        assert!(matches!(event, StreamEvent::Connected));
+
+        match next(&mut receiver).await {
+            StreamEvent::Ticker { market: received, ticker } => {
+                assert_eq!(received, market);
+                assert_eq!(ticker.bid, decimal("50230.1"));
+                assert_eq!(ticker.ask, decimal("50234.5"));
+                assert_eq!(ticker.last, decimal("50232"));
+                assert_eq!(ticker.volume, decimal("1904.55321"));
+            }
+            other => panic!("expected a ticker, got {:?}", other),
+        }
+        match next(&mut receiver).await {
+            StreamEvent::Trades { market: received, trades } => {
+                assert_eq!(received, market);
+                assert_eq!(trades.len(), 2);
+                assert_eq!(trades[0].time_ms, 1_637_505_120_123);
+                assert_eq!(trades[0].price, decimal("50232"));
+                assert_eq!(trades[0].volume, decimal("0.0015"));
+                assert_eq!(trades[0].side, TradeSide::Buy);
+                assert_eq!(trades[1].time_ms, 1_637_505_120_457);
+                assert_eq!(trades[1].side, TradeSide::Sell);
+                assert_ne!(trades[0].id, trades[1].id);
+            }
+            other => panic!("expected trades, got {:?}", other),
+        }
+        match next(&mut receiver).await {
+            StreamEvent::Candle {
+                market: received,
+                interval,
+                ohlc,
+            } => {
+                assert_eq!(received, market);
+                assert_eq!(interval, Interval::Min1);
+                assert_eq!(ohlc.time, 1_637_505_120);
+                assert_eq!(ohlc.open, decimal("50210"));
+                assert_eq!(ohlc.close, decimal("50231.9"));
+                assert_eq!(ohlc.count, 14);
+            }
+            other => panic!("expected a candle, got {:?}", other),
+        }
+        assert!(matches!(next(&mut receiver).await, StreamEvent::Trades { .. }));
+        match next(&mut receiver).await {
+            StreamEvent::Candle { ohlc, .. } => {
+                assert_eq!(ohlc.time, 1_637_505_120);
+                assert_eq!(ohlc.close, decimal("50240.1"));
+                assert_eq!(ohlc.count, 15);
+            }
+            other => panic!("expected a candle, got {:?}", other),
+        }
+        assert!(matches!(next(&mut receiver).await, StreamEvent::Ticker { .. }));
+
+        // End of the replay
+        assert!(matches!(next(&mut receiver).await, StreamEvent::Disconnected { .. }));
+        assert!(matches!(next(&mut receiver).await, StreamEvent::Connected));
+        assert!(matches!(next(&mut receiver).await, StreamEvent::Ticker { .. }));
+        cancel.cancel();
+    }
+}
@@ -0,0 +1,445 @@
+use super::*;
+use crate::reactor::SyncExchange;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Instant;
+
+/// Cost of the most expensive call, the counter maximum can't be lower
+pub const MAX_CALL_COST: f64 = 2.0;
+/// A stalled call fails as a transient error, so it is retried and counts toward the breaker
+const CALL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Kraken call counter model: every call raises a counter which decays over time, calls
+/// wait while the counter would exceed its maximum
+#[derive(Debug, Clone)]
+pub struct RateLimit {
+    pub max_counter: f64,
+    /// Decrease of the counter per second
+    pub decay: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub backoff_min: Duration,
+    pub backoff_max: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct BreakerPolicy {
+    /// Consecutive failures opening the circuit
+    pub threshold: u32,
+    /// How long calls are rejected once the circuit is open
+    pub cooldown: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct CallPolicy {
+    pub rate: RateLimit,
+    pub retry: RetryPolicy,
+    pub breaker: BreakerPolicy,
+}
+
+impl Default for CallPolicy {
+    /// Limits of a Kraken starter account
+    fn default() -> Self {
+        Self {
+            rate: RateLimit {
+                max_counter: 15.0,
+                decay: 0.33,
+            },
+            retry: RetryPolicy {
+                max_retries: 5,
+                backoff_min: Duration::from_secs(1),
+                backoff_max: Duration::from_secs(30),
+            },
+            breaker: BreakerPolicy {
+                threshold: 5,
+                cooldown: Duration::from_secs(60),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    Closed,
+    Open,
+    /// The cooldown is over and a single trial call is running, another one is let through
+    /// when it didn't end within a cooldown (cancelled caller)
+    HalfOpen,
+}
+
+impl Default for CircuitState {
+    fn default() -> Self {
+        CircuitState::Closed
+    }
+}
+
+#[derive(Debug)]
+struct CallCounter {
+    level: f64,
+    updated: Instant,
+}
+
+#[derive(Debug)]
+struct Breaker {
+    state: CircuitState,
+    failures: u32,
+    /// When the circuit opened, or when the last trial call was let through
+    since: Instant,
+}
+
+/// Counters of a guarded exchange, shared with the api
+#[derive(Debug, Default)]
+pub struct ExchangeCounters {
+    pub calls: AtomicU64,
+    pub succeeded: AtomicU64,
+    pub failed: AtomicU64,
+    pub retried: AtomicU64,
+    /// Calls which waited for the call counter to decay
+    pub throttled: AtomicU64,
+    pub throttled_ms: AtomicU64,
+    /// Calls refused while the circuit was open
+    pub rejected: AtomicU64,
+    /// Bits of the `f64` level of the call counter
+    level: AtomicU64,
+    circuit: std::sync::Mutex<CircuitState>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExchangeStats {
+    pub exchange: String,
+    pub calls: u64,
+    pub succeeded: u64,
+    pub failed: u64,
+    pub retried: u64,
+    pub throttled: u64,
+    pub throttled_ms: u64,
+    pub rejected: u64,
+    /// Level of the call counter after the last call
+    pub counter: f64,
+    pub circuit: CircuitState,
+}
+
+impl ExchangeCounters {
+    pub fn stats(&self, exchange: &str) -> ExchangeStats {
+        let counter = f64::from_bits(self.level.load(Ordering::Relaxed));
+        ExchangeStats {
+            exchange: exchange.to_string(),
+            calls: self.calls.load(Ordering::Relaxed),
+            succeeded: self.succeeded.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            retried: self.retried.load(Ordering::Relaxed),
+            throttled: self.throttled.load(Ordering::Relaxed),
+            throttled_ms: self.throttled_ms.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+            counter: (counter * 100.0).round() / 100.0,
+            circuit: *self.circuit.lock().unwrap_or_else(|e| e.into_inner()),
+        }
+    }
+}
+
+/// Wrap an exchange with rate limiting, retries of transient errors and circuit breaking
+pub struct GuardedExchange {
+    name: String,
+    inner: Box<dyn Exchange + Sync + Send>,
+    policy: CallPolicy,
+    counter: std::sync::Mutex<CallCounter>,
+    breaker: std::sync::Mutex<Breaker>,
+    counters: Arc<ExchangeCounters>,
+}
+
+/// A random duration between half and all of `max`
+fn jitter(max: Duration) -> Duration {
+    let random = RandomState::new().build_hasher().finish();
+    max / 2 + Duration::from_nanos(random % (max.as_nanos() as u64 / 2).max(1))
+}
+
+impl GuardedExchange {
+    pub fn new(inner: Box<dyn Exchange + Sync + Send>, policy: CallPolicy) -> Self {
+        Self {
+            name: inner.name(),
+            inner,
+            policy,
+            counter: std::sync::Mutex::new(CallCounter {
+                level: 0.0,
+                updated: Instant::now(),
+            }),
+            breaker: std::sync::Mutex::new(Breaker {
+                state: CircuitState::Closed,
+                failures: 0,
+                since: Instant::now(),
+            }),
+            counters: Arc::new(ExchangeCounters::default()),
+        }
+    }
+
+    pub fn shared(self) -> SyncExchange {
+        Arc::new(self)
+    }
+
+    /// Wait until the call counter can take `cost`
+    async fn throttle(&self, cost: f64) {
+        let mut waited = Duration::from_secs(0);
+        loop {
+            let wait = {
+                let mut counter = self.counter.lock().unwrap_or_else(|e| e.into_inner());
+                let now = Instant::now();
+                let decayed =
+                    now.duration_since(counter.updated).as_secs_f64() * self.policy.rate.decay;
+                counter.level = (counter.level - decayed).max(0.0);
+                counter.updated = now;
+                if counter.level + cost <= self.policy.rate.max_counter {
+                    counter.level += cost;
+                    self.counters
+                        .level
+                        .store(counter.level.to_bits(), Ordering::Relaxed);
+                    None
+                } else {
+                    let excess = counter.level + cost - self.policy.rate.max_counter;
+                    Some(Duration::from_secs_f64(excess / self.policy.rate.decay))
+                }
+            };
+            match wait {
+                Some(wait) => {
+                    waited += wait;
+                    tokio::time::sleep(wait).await;
+                }
+                None => break,
+            }
+        }
+        if waited.as_millis() > 0 {
+            self.counters.throttled.fetch_add(1, Ordering::Relaxed);
+            self.counters
+                .throttled_ms
+                .fetch_add(waited.as_millis() as u64, Ordering::Relaxed);
+            log::debug!("Exchange call throttled: EXCHANGE={}, WAITED={:?}", &self.name, waited);
+        }
+    }
+
+    /// Refuse the call while the circuit is open, let a single trial through once the cooldown
+    /// is over. A trial whose caller was dropped never records its outcome, so a new trial is
+    /// let through after another cooldown
+    fn admit(&self) -> Result<()> {
+        let mut breaker = self.breaker.lock().unwrap_or_else(|e| e.into_inner());
+        match breaker.state {
+            CircuitState::Closed => return Ok(()),
+            CircuitState::Open | CircuitState::HalfOpen
+                if breaker.since.elapsed() >= self.policy.breaker.cooldown =>
+            {
+                log::info!("Circuit half open: EXCHANGE={}", &self.name);
+                breaker.state = CircuitState::HalfOpen;
+                breaker.since = Instant::now();
+                *self.counters.circuit.lock().unwrap_or_else(|e| e.into_inner()) = breaker.state;
+                return Ok(());
+            }
+            CircuitState::Open | CircuitState::HalfOpen => {}
+        }
+        self.counters.rejected.fetch_add(1, Ordering::Relaxed);
+        Err(Error::CircuitOpen(self.name.clone()))
+    }
+
+    /// Only transient failures count toward opening the circuit, any other outcome means the
+    /// exchange answered
+    fn record(&self, transient_failure: bool) {
+        let mut breaker = self.breaker.lock().unwrap_or_else(|e| e.into_inner());
+        if !transient_failure {
+            if breaker.state != CircuitState::Closed {
+                log::info!("Circuit closed: EXCHANGE={}", &self.name);
+            }
+            breaker.state = CircuitState::Closed;
+            breaker.failures = 0;
+        } else {
+            breaker.failures += 1;
+            if breaker.state == CircuitState::HalfOpen
+                || breaker.failures >= self.policy.breaker.threshold
+            {
+                log::warn!(
+                    "Circuit opened: EXCHANGE={}, FAILURES={}, COOLDOWN={:?}",
+                    &self.name,
+                    breaker.failures,
+                    self.policy.breaker.cooldown
+                );
+                breaker.state = CircuitState::Open;
+                breaker.since = Instant::now();
+            }
+        }
+        *self.counters.circuit.lock().unwrap_or_else(|e| e.into_inner()) = breaker.state;
+    }
+
+    async fn guarded<'f, T, F>(&self, method: &str, cost: f64, call: F) -> Result<T>
+    where
+        F: Fn() -> Pin<Box<dyn Future<Output = Result<T>> + Send + 'f>>,
+    {
+        self.guarded_with_retries(method, cost, self.policy.retry.max_retries, call)
+            .await
+    }
+
+    async fn guarded_with_retries<'f, T, F>(
+        &self,
+        method: &str,
+        cost: f64,
+        max_retries: u32,
+        call: F,
+    ) -> Result<T>
+    where
+        F: Fn() -> Pin<Box<dyn Future<Output = Result<T>> + Send + 'f>>,
+    {
+        self.admit()?;
+        let mut attempt = 0;
+        loop {
+            self.throttle(cost).await;
+            self.counters.calls.fetch_add(1, Ordering::Relaxed);
+            let outcome = tokio::time::timeout(CALL_TIMEOUT, call())
+                .await
+                .unwrap_or_else(|_| {
+                    Err(Error::ExchangeResponse(format!(
+                        "{} timed out after {:?}",
+                        method, CALL_TIMEOUT
+                    )))
+                });
+            match outcome {
+                Ok(ret) => {
+                    self.counters.succeeded.fetch_add(1, Ordering::Relaxed);
+                    self.record(false);
+                    return Ok(ret);
+                }
+                Err(e) if e.is_transient() && attempt < max_retries => {
+                    let backoff = self
+                        .policy
+                        .retry
+                        .backoff_min
+                        .saturating_mul(2u32.saturating_pow(attempt))
+                        .min(self.policy.retry.backoff_max);
+                    let delay = jitter(backoff);
+                    log::warn!(
+                        "Exchange call failed, retrying: EXCHANGE={}, METHOD={}, ATTEMPT={}, RETRY_IN={:?}, ERROR={}",
+                        &self.name,
+                        method,
+                        attempt + 1,
+                        delay,
+                        e
+                    );
+                    self.counters.retried.fetch_add(1, Ordering::Relaxed);
+                    attempt += 1;
+                    tokio::time::sleep(delay).await;
+                }
+                Err(e) => {
+                    self.counters.failed.fetch_add(1, Ordering::Relaxed);
+                    self.record(e.is_transient());
+                    return Err(e);
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Exchange for GuardedExchange {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    async fn get_severt_time(&self) -> Result<NaiveDateTime> {
+        self.guarded("get_server_time", 1.0, || self.inner.get_severt_time())
+            .await
+    }
+
+    async fn get_ohlc_page(
+        &self,
+        id: &MarketIdentifier,
+        since: Timestamp,
+        interval: Interval,
+    ) -> Result<Vec<OHLC>> {
+        self.guarded("get_ohlc_page", 1.0, || {
+            self.inner.get_ohlc_page(id, since, interval)
+        })
+        .await
+    }
+
+    async fn refresh_market_cache(&self) -> Result<()> {
+        self.guarded("refresh_market_cache", 1.0, || {
+            self.inner.refresh_market_cache()
+        })
+        .await
+    }
+
+    /// Cache hits neither wait on the counter nor count towards the breaker, only filling the
+    /// cache calls the exchange
+    async fn get_markets(&self) -> Result<Vec<MarketIdentifier>> {
+        if let Some(markets) = self.inner.cached_markets().await {
+            return Ok(markets);
+        }
+        self.guarded("get_markets", 1.0, || self.inner.get_markets())
+            .await
+    }
+
+    async fn get_market_definition(
+        &self,
+        id: &MarketIdentifier,
+        max_age: Option<Duration>,
+    ) -> Result<MarketDefinition> {
+        if let Some(definition) = self.inner.cached_market_definition(id, max_age).await {
+            return Ok(definition);
+        }
+        self.guarded("get_market_definition", 1.0, || {
+            self.inner.get_market_definition(id, max_age)
+        })
+        .await
+    }
+
+    async fn cached_markets(&self) -> Option<Vec<MarketIdentifier>> {
+        self.inner.cached_markets().await
+    }
+
+    async fn cached_market_definition(
+        &self,
+        id: &MarketIdentifier,
+        max_age: Option<Duration>,
+    ) -> Option<MarketDefinition> {
+        self.inner.cached_market_definition(id, max_age).await
+    }
+
+    async fn get_order_book(&self, id: &MarketIdentifier, depth: usize) -> Result<OrderBook> {
+        self.guarded("get_order_book", 1.0, || self.inner.get_order_book(id, depth))
+            .await
+    }
+
+    async fn get_trades(&self, id: &MarketIdentifier, since_ms: Option<i64>) -> Result<Vec<Trade>> {
+        self.guarded("get_trades", 1.0, || self.inner.get_trades(id, since_ms))
+            .await
+    }
+
+    async fn get_balances(&self) -> Result<HashMap<String, Decimal>> {
+        self.guarded("get_balances", 1.0, || self.inner.get_balances())
+            .await
+    }
+
+    /// Kraken counts two calls for each page of the trade history, a retry only replays its page
+    async fn get_fills_page(
+        &self,
+        since: Option<Timestamp>,
+        offset: usize,
+    ) -> Result<(Vec<Fill>, usize)> {
+        self.guarded("get_fills_page", MAX_CALL_COST, || {
+            self.inner.get_fills_page(since, offset)
+        })
+        .await
+    }
+
+    /// Never retried, a timed out order may have reached the exchange
+    async fn place_order(&self, order: &OrderRequest) -> Result<PlacedOrder> {
+        self.guarded_with_retries("place_order", 0.0, 0, || self.inner.place_order(order))
+            .await
+    }
+
+    fn stream(&self) -> Option<Arc<dyn ExchangeStream>> {
+        self.inner.stream()
+    }
+
+    fn counters(&self) -> Option<Arc<ExchangeCounters>> {
+        Some(self.counters.clone())
+    }
+}
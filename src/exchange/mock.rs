@@ -0,0 +1,235 @@
+use super::*;
+use crate::reactor::SyncExchange;
+
+/// Candles returned by a single `get_ohlc_page`, as Kraken does
+const MOCK_PAGE_SIZE: i64 = 720;
+const MOCK_BOOK_LEVELS: usize = 10;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MockMarket {
+    pub base: String,
+    pub quote: String,
+    pub price: Decimal,
+    /// Distance between the best bid and the best ask, in percent of the price
+    #[serde(default = "MockMarket::default_spread")]
+    pub spread: Decimal,
+    /// Volume of each level of the order book and of each candle
+    #[serde(default = "MockMarket::default_volume")]
+    pub volume: Decimal,
+    /// Taker fee, in percent
+    #[serde(default = "MockMarket::default_fee")]
+    pub fee: f64,
+}
+
+impl MockMarket {
+    fn default_spread() -> Decimal {
+        "0.1".parse().unwrap()
+    }
+
+    fn default_volume() -> Decimal {
+        Decimal::from(1i64)
+    }
+
+    fn default_fee() -> f64 {
+        0.26
+    }
+}
+
+/// Offline exchange quoting fixed prices, to try pkbot and compare exchanges without api keys.
+/// Orders marketable against its book are filled at once and reported as fills.
+pub struct MockExchange {
+    name: String,
+    markets: Vec<MockMarket>,
+    assets: Arc<AssetRegistry>,
+    fills: std::sync::Mutex<Vec<Fill>>,
+}
+
+impl MockExchange {
+    pub fn new(name: String, markets: Vec<MockMarket>) -> Self {
+        Self {
+            name,
+            markets,
+            assets: AssetRegistry::new().shared(),
+            fills: std::sync::Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn with_assets(self, assets: Arc<AssetRegistry>) -> Self {
+        Self { assets, ..self }
+    }
+
+    pub fn shared(self) -> SyncExchange {
+        Arc::new(self)
+    }
+
+    fn id(&self, market: &MockMarket) -> MarketIdentifier {
+        MarketIdentifier {
+            exchange_name: self.name.clone(),
+            base: self.assets.canonical(&self.name, &market.base),
+            quote: self.assets.canonical(&self.name, &market.quote),
+        }
+    }
+
+    fn market(&self, id: &MarketIdentifier) -> Result<&MockMarket> {
+        self.markets
+            .iter()
+            .find(|e| self.assets.same_pair(&self.id(e), id))
+            .ok_or(Error::NoData)
+    }
+
+    fn definition(&self, market: &MockMarket) -> MarketDefinition {
+        MarketDefinition {
+            age: SystemTime::now(),
+            pairname: format!("{}{}", market.base, market.quote),
+            pair_decimals: market.price.scale().max(2) as i32,
+            lot_decimals: 8,
+            lot_multiplier: 1,
+            leverage_buy: vec![],
+            leverage_sell: vec![],
+            fees: vec![(0.0, market.fee)],
+            fees_maker: None,
+            margin_call: 0.0,
+            margin_stop: 0.0,
+            ordermin: None,
+        }
+    }
+}
+
+#[async_trait]
+impl Exchange for MockExchange {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    async fn get_severt_time(&self) -> Result<NaiveDateTime> {
+        Ok(NaiveDateTime::from_timestamp(crate::store::now(), 0))
+    }
+
+    /// Flat candles at the price of the market up to now
+    async fn get_ohlc_page(
+        &self,
+        id: &MarketIdentifier,
+        since: Timestamp,
+        interval: Interval,
+    ) -> Result<Vec<OHLC>> {
+        let market = self.market(id)?;
+        let secs = interval.as_secs();
+        let now = crate::store::now() / secs * secs;
+        let mut time = if since == 0 {
+            now - (MOCK_PAGE_SIZE - 1) * secs
+        } else {
+            since / secs * secs
+        };
+        let mut ret = Vec::new();
+        while time <= now && (ret.len() as i64) < MOCK_PAGE_SIZE {
+            ret.push(OHLC {
+                first_available: false,
+                time,
+                open: market.price,
+                high: market.price,
+                low: market.price,
+                close: market.price,
+                vwap: market.price,
+                volume: market.volume,
+                count: 1,
+            });
+            time += secs;
+        }
+        Ok(ret)
+    }
+
+    async fn refresh_market_cache(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn get_markets(&self) -> Result<Vec<MarketIdentifier>> {
+        Ok(self.markets.iter().map(|e| self.id(e)).collect())
+    }
+
+    async fn get_market_definition(
+        &self,
+        id: &MarketIdentifier,
+        _max_age: Option<Duration>,
+    ) -> Result<MarketDefinition> {
+        Ok(self.definition(self.market(id)?))
+    }
+
+    /// Levels spaced by a basis point on each side of the spread
+    async fn get_order_book(&self, id: &MarketIdentifier, depth: usize) -> Result<OrderBook> {
+        let market = self.market(id)?;
+        let definition = self.definition(market);
+        let half_spread = market.price * market.spread / Decimal::from(200i64);
+        let tick = market.price / Decimal::from(10_000i64);
+        let level = |i: usize, side: i64| BookLevel {
+            price: definition.round_price(
+                market.price + (half_spread + tick * Decimal::from(i)) * Decimal::from(side),
+            ),
+            volume: market.volume,
+        };
+        let depth = depth.min(MOCK_BOOK_LEVELS);
+        Ok(OrderBook {
+            time: crate::store::now(),
+            bids: (0..depth).map(|i| level(i, -1)).collect(),
+            asks: (0..depth).map(|i| level(i, 1)).collect(),
+        })
+    }
+
+    async fn get_trades(&self, id: &MarketIdentifier, _since_ms: Option<i64>) -> Result<Vec<Trade>> {
+        self.market(id)?;
+        Ok(vec![])
+    }
+
+    async fn get_fills(&self, since: Option<Timestamp>) -> Result<Vec<Fill>> {
+        Ok(self
+            .fills
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .filter(|e| since.map(|since| e.time >= since).unwrap_or(true))
+            .cloned()
+            .collect())
+    }
+
+    /// Market orders fill at the best level, limit orders only when they cross the spread
+    async fn place_order(&self, order: &OrderRequest) -> Result<PlacedOrder> {
+        let market = self.market(&order.market)?;
+        let book = self.get_order_book(&order.market, 1).await?;
+        let best = match order.side {
+            TradeSide::Buy => book.best_ask(),
+            TradeSide::Sell => book.best_bid(),
+        }
+        .ok_or(Error::NoData)?
+        .price;
+        let marketable = match (order.side, order.price) {
+            (_, None) => true,
+            (TradeSide::Buy, Some(limit)) => limit >= best,
+            (TradeSide::Sell, Some(limit)) => limit <= best,
+        };
+        if !marketable {
+            return Err(Error::NotSupported(format!(
+                "{} can't rest orders, {} doesn't cross the spread",
+                self.name, order
+            )));
+        }
+        let id = self.id(market);
+        let mut fills = self.fills.lock().unwrap_or_else(|e| e.into_inner());
+        let fee = std::convert::TryFrom::try_from(market.fee / 100.0).unwrap_or(Decimal::ZERO);
+        let order_id = format!("{}-order-{}", self.name, fills.len() + 1);
+        let fill = Fill {
+            id: format!("{}-{}", self.name, fills.len() + 1),
+            market: format!("{}/{}/{}", id.exchange_name, id.base, id.quote),
+            time: crate::store::now(),
+            side: order.side,
+            price: best,
+            volume: order.volume,
+            fee: (best * order.volume * fee).round_dp(8),
+            order: Some(order_id.clone()),
+        };
+        fills.push(fill);
+        Ok(PlacedOrder {
+            ids: vec![order_id],
+            order: order.clone(),
+        })
+    }
+}
@@ -0,0 +1,66 @@
+use super::*;
+
+/// An order to send to an exchange, see `Reactor::submit_order`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderRequest {
+    pub market: MarketIdentifier,
+    pub side: TradeSide,
+    pub order_type: TradeOrderType,
+    pub volume: Decimal,
+    /// Limit price, required by limit orders
+    pub price: Option<Decimal>,
+}
+
+impl OrderRequest {
+    pub fn market(market: MarketIdentifier, side: TradeSide, volume: Decimal) -> Self {
+        Self {
+            market,
+            side,
+            order_type: TradeOrderType::Market,
+            volume,
+            price: None,
+        }
+    }
+
+    pub fn limit(
+        market: MarketIdentifier,
+        side: TradeSide,
+        volume: Decimal,
+        price: Decimal,
+    ) -> Self {
+        Self {
+            market,
+            side,
+            order_type: TradeOrderType::Limit,
+            volume,
+            price: Some(price),
+        }
+    }
+}
+
+impl std::fmt::Display for OrderRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:?} {:?} {} {}/{}/{}",
+            self.order_type,
+            self.side,
+            self.volume,
+            self.market.exchange_name,
+            self.market.base,
+            self.market.quote
+        )?;
+        match self.price {
+            Some(price) => write!(f, " at {}", price),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Order accepted by an exchange
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlacedOrder {
+    /// Transaction ids given by the exchange
+    pub ids: Vec<String>,
+    pub order: OrderRequest,
+}
@@ -0,0 +1,268 @@
+use super::*;
+use futures::{SinkExt, StreamExt};
+use std::io::Write;
+use tokio_tungstenite::tungstenite::Message;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum StreamChannel {
+    Ticker,
+    Trades,
+    /// The candle being formed, updated on every trade
+    Candles(Interval),
+}
+
+impl std::str::FromStr for StreamChannel {
+    type Err = Error;
+
+    /// `ticker`, `trades` or `candles:<interval>` (e.g. `candles:1m`)
+    fn from_str(raw: &str) -> Result<Self> {
+        match raw.split_once(':') {
+            None if raw == "ticker" => Ok(StreamChannel::Ticker),
+            None if raw == "trades" => Ok(StreamChannel::Trades),
+            Some(("candles", interval)) => Ok(StreamChannel::Candles(
+                crate::reactor::runtime::ArgumentInterval::new(interval)?.normalized,
+            )),
+            _ => Err(Error::Stream(format!(
+                "unknown channel `{}`, expected one of: ticker, trades, candles:<interval>",
+                raw
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Subscription {
+    pub market: MarketIdentifier,
+    pub channel: StreamChannel,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ticker {
+    pub time: Timestamp,
+    pub bid: Decimal,
+    pub ask: Decimal,
+    pub last: Decimal,
+    /// Volume of the last 24 hours
+    pub volume: Decimal,
+}
+
+#[derive(Debug, Clone)]
+pub enum StreamEvent {
+    Connected,
+    Disconnected { reason: String },
+    Ticker {
+        market: MarketIdentifier,
+        ticker: Ticker,
+    },
+    Trades {
+        market: MarketIdentifier,
+        trades: Vec<Trade>,
+    },
+    Candle {
+        market: MarketIdentifier,
+        interval: Interval,
+        ohlc: OHLC,
+    },
+}
+
+/// Streaming extension of an `Exchange`, it only knows the protocol of the exchange, the
+/// connection itself is handled by `run_stream`
+#[async_trait]
+pub trait ExchangeStream: Send + Sync {
+    fn url(&self) -> String;
+
+    /// Frames sent after each (re)connection
+    async fn subscribe_frames(&self, subscriptions: &[Subscription]) -> Result<Vec<String>>;
+
+    /// Decode a text frame, frames without market data (heartbeats, acks...) give no event
+    fn decode(&self, frame: &str) -> Result<Vec<StreamEvent>>;
+}
+
+#[derive(Debug, Clone)]
+pub struct StreamOptions {
+    pub reconnect_min: Duration,
+    pub reconnect_max: Duration,
+    /// Reconnect when nothing is received for this long, exchanges send heartbeats
+    pub idle_timeout: Duration,
+    /// Append every received frame to this file, one per line, see `serve_replay`
+    pub record: Option<PathBuf>,
+}
+
+impl Default for StreamOptions {
+    fn default() -> Self {
+        Self {
+            reconnect_min: Duration::from_secs(1),
+            reconnect_max: Duration::from_secs(60),
+            idle_timeout: Duration::from_secs(30),
+            record: None,
+        }
+    }
+}
+
+/// Keep a connection to the stream of an exchange until `cancel` is triggered, reconnecting
+/// with an exponential backoff and subscribing again after each reconnection
+pub async fn run_stream(
+    stream: Arc<dyn ExchangeStream>,
+    subscriptions: Vec<Subscription>,
+    options: StreamOptions,
+    events: Sender<StreamEvent>,
+    cancel: CancellationToken,
+) {
+    let mut backoff = options.reconnect_min;
+    loop {
+        let reason = tokio::select! {
+            _ = cancel.cancelled() => return,
+            reason = connection(&stream, &subscriptions, &options, &events, &mut backoff) => reason,
+        };
+        log::warn!(
+            "Stream disconnected: URL={}, REASON={}, RETRY_IN={:?}",
+            stream.url(),
+            reason,
+            backoff
+        );
+        if events.send(StreamEvent::Disconnected { reason }).await.is_err() {
+            return;
+        }
+        tokio::select! {
+            _ = cancel.cancelled() => return,
+            _ = tokio::time::sleep(backoff) => {}
+        }
+        backoff = (backoff * 2).min(options.reconnect_max);
+    }
+}
+
+/// Run one connection until it fails, returns the reason of the failure
+async fn connection(
+    stream: &Arc<dyn ExchangeStream>,
+    subscriptions: &[Subscription],
+    options: &StreamOptions,
+    events: &Sender<StreamEvent>,
+    backoff: &mut Duration,
+) -> String {
+    let url = stream.url();
+    let (mut socket, _) = match tokio_tungstenite::connect_async(url.as_str()).await {
+        Ok(connected) => connected,
+        Err(e) => return e.to_string(),
+    };
+    let frames = match stream.subscribe_frames(subscriptions).await {
+        Ok(frames) => frames,
+        Err(e) => return e.to_string(),
+    };
+    for frame in frames {
+        if let Err(e) = socket.send(Message::Text(frame)).await {
+            return e.to_string();
+        }
+    }
+    log::info!("Stream connected: URL={}, SUBSCRIPTIONS={}", url, subscriptions.len());
+    if events.send(StreamEvent::Connected).await.is_err() {
+        return String::from("stream closed");
+    }
+    let mut record = match options.record.as_ref().map(|path| {
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+    }) {
+        Some(Ok(file)) => Some(std::io::BufWriter::new(file)),
+        Some(Err(e)) => return e.to_string(),
+        None => None,
+    };
+    loop {
+        let message = match tokio::time::timeout(options.idle_timeout, socket.next()).await {
+            Err(_) => return format!("nothing received for {:?}", options.idle_timeout),
+            Ok(None) => return String::from("connection closed"),
+            Ok(Some(Err(e))) => return e.to_string(),
+            Ok(Some(Ok(message))) => message,
+        };
+        let frame = match message {
+            Message::Text(frame) => frame,
+            Message::Ping(payload) => {
+                if let Err(e) = socket.send(Message::Pong(payload)).await {
+                    return e.to_string();
+                }
+                continue;
+            }
+            Message::Close(frame) => {
+                return frame
+                    .map(|e| e.reason.to_string())
+                    .unwrap_or_else(|| String::from("connection closed"))
+            }
+            _ => continue,
+        };
+        if let Some(record) = record.as_mut() {
+            if let Err(e) = writeln!(record, "{}", frame).and_then(|_| record.flush()) {
+                log::error!("Failed to record stream frame: ERROR={}", e);
+            }
+        }
+        match stream.decode(&frame) {
+            Ok(decoded) => {
+                // The connection only counts as healthy once data flows, a server accepting then
+                // dropping connections keeps backing off
+                if !decoded.is_empty() {
+                    *backoff = options.reconnect_min;
+                }
+                for event in decoded {
+                    if events.send(event).await.is_err() {
+                        return String::from("stream closed");
+                    }
+                }
+            }
+            Err(e) => log::warn!("Failed to decode stream frame: FRAME={}, ERROR={}", frame, e),
+        }
+    }
+}
+
+/// Local stand-in for an exchange stream: every client receives the frames of `path` (one per
+/// line, as written by `StreamOptions::record`) spaced by `interval`, whatever it subscribes to
+pub async fn serve_replay(
+    address: std::net::SocketAddr,
+    path: PathBuf,
+    interval: Duration,
+    repeat: bool,
+) -> Result<()> {
+    let frames: Arc<Vec<String>> = Arc::new(
+        std::fs::read_to_string(&path)?
+            .lines()
+            .filter(|e| !e.trim().is_empty())
+            .map(str::to_string)
+            .collect(),
+    );
+    let listener = tokio::net::TcpListener::bind(address).await?;
+    log::info!(
+        "Replay server listening: ADDRESS={}, PATH={}, FRAMES={}",
+        address,
+        path.display(),
+        frames.len()
+    );
+    loop {
+        let (socket, peer) = listener.accept().await?;
+        let frames = frames.clone();
+        tokio::spawn(async move {
+            let socket = match tokio_tungstenite::accept_async(socket).await {
+                Ok(socket) => socket,
+                Err(e) => {
+                    log::warn!("Replay handshake failed: PEER={}, ERROR={}", peer, e);
+                    return;
+                }
+            };
+            log::info!("Replay client connected: PEER={}", peer);
+            let (mut sink, mut source) = socket.split();
+            // Subscriptions are read and ignored so the client never blocks on them
+            tokio::spawn(async move { while let Some(Ok(_)) = source.next().await {} });
+            loop {
+                for frame in frames.iter() {
+                    if sink.send(Message::Text(frame.clone())).await.is_err() {
+                        log::info!("Replay client disconnected: PEER={}", peer);
+                        return;
+                    }
+                    tokio::time::sleep(interval).await;
+                }
+                // Without frames a repeated replay would spin without ever awaiting
+                if !repeat || frames.is_empty() {
+                    let _ = sink.send(Message::Close(None)).await;
+                    return;
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,95 @@
+use crate::prelude::*;
+
+/// Fractional digits kept by indicator values
+pub const INDICATOR_SCALE: u32 = 12;
+
+pub fn sma(values: &[Decimal], period: usize) -> Option<Decimal> {
+    if period == 0 || values.len() < period {
+        return None;
+    }
+    values[values.len() - period..]
+        .iter()
+        .sum::<Decimal>()
+        .checked_div(Decimal::from(period), INDICATOR_SCALE)
+}
+
+/// Simple moving average for each value, `None` until enough values are available
+pub fn sma_series(values: &[Decimal], period: usize) -> Vec<Option<Decimal>> {
+    (0..values.len())
+        .map(|i| sma(&values[..=i], period))
+        .collect()
+}
+
+pub fn ema_series(values: &[Decimal], period: usize) -> Vec<Option<Decimal>> {
+    let mut ret = Vec::with_capacity(values.len());
+    let mut prev: Option<Decimal> = None;
+    let k = match Decimal::from(2usize).checked_div(Decimal::from(period + 1), INDICATOR_SCALE) {
+        Some(k) => k,
+        None => return vec![None; values.len()],
+    };
+    for (i, value) in values.iter().enumerate() {
+        prev = match prev {
+            Some(prev) => Some((*value * k + prev * (Decimal::ONE - k)).round_dp(INDICATOR_SCALE)),
+            None if period > 0 && i + 1 >= period => sma(&values[..=i], period),
+            None => None,
+        };
+        ret.push(prev);
+    }
+    ret
+}
+
+/// Wilder's relative strength index of the last value, needs at least `period + 1` closes
+pub fn rsi(closes: &[Decimal], period: usize) -> Option<Decimal> {
+    rsi_series(closes, period).last().cloned().flatten()
+}
+
+pub fn rsi_series(closes: &[Decimal], period: usize) -> Vec<Option<Decimal>> {
+    let mut ret = vec![None; closes.len()];
+    if period == 0 || closes.len() <= period {
+        return ret;
+    }
+    let period_dec = Decimal::from(period);
+    let smooth = |avg: Decimal, value: Decimal| {
+        (avg * Decimal::from(period - 1) + value)
+            .checked_div(period_dec, INDICATOR_SCALE)
+            .unwrap_or(Decimal::ZERO)
+    };
+    let (mut avg_gain, mut avg_loss) = (Decimal::ZERO, Decimal::ZERO);
+    for w in closes[..=period].windows(2) {
+        let delta = w[1] - w[0];
+        avg_gain += delta.max(Decimal::ZERO);
+        avg_loss += (-delta).max(Decimal::ZERO);
+    }
+    avg_gain = avg_gain.checked_div(period_dec, INDICATOR_SCALE).unwrap_or(Decimal::ZERO);
+    avg_loss = avg_loss.checked_div(period_dec, INDICATOR_SCALE).unwrap_or(Decimal::ZERO);
+    ret[period] = Some(rsi_from_averages(avg_gain, avg_loss));
+    for i in period + 1..closes.len() {
+        let delta = closes[i] - closes[i - 1];
+        avg_gain = smooth(avg_gain, delta.max(Decimal::ZERO));
+        avg_loss = smooth(avg_loss, (-delta).max(Decimal::ZERO));
+        ret[i] = Some(rsi_from_averages(avg_gain, avg_loss));
+    }
+    ret
+}
+
+fn rsi_from_averages(avg_gain: Decimal, avg_loss: Decimal) -> Decimal {
+    match avg_gain.checked_div(avg_loss, INDICATOR_SCALE) {
+        Some(rs) => {
+            Decimal::ONE_HUNDRED
+                - Decimal::ONE_HUNDRED
+                    .checked_div(Decimal::ONE + rs, INDICATOR_SCALE)
+                    .unwrap_or(Decimal::ZERO)
+        }
+        None => Decimal::ONE_HUNDRED,
+    }
+}
+
+pub fn percent_change(from: Decimal, to: Decimal) -> Option<Decimal> {
+    (to - from)
+        .checked_div(from, INDICATOR_SCALE)
+        .map(|e| e * Decimal::ONE_HUNDRED)
+}
+
+pub fn closes(candles: &[OHLC]) -> Vec<Decimal> {
+    candles.iter().map(|e| e.close).collect()
+}
@@ -33,12 +33,23 @@ impl ProgramOutput {
 
 pub type ProgramIdentifier = u64;
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Encode, Decode, Serialize, Deserialize)]
 pub enum ProgramStatus {
     None,
     Running,
     Success,
     Error,
+    Cancelled,
+    TimedOut,
+}
+
+impl ProgramStatus {
+    pub fn is_terminal(&self) -> bool {
+        match self {
+            ProgramStatus::None | ProgramStatus::Running => false,
+            _ => true,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -46,20 +57,101 @@ pub struct Program {
     pub root: Node,
     pub context: AstContext,
     pub status: ProgramStatus,
+    pub source: String,
+    pub timeout: Option<Duration>,
 }
 
 impl Program {
     pub fn new<T: AsRef<str>>(text: T) -> Result<Program> {
         let mut root = CommandAstNode::parse(&mut Token::lexer(text.as_ref()), None)?;
         let context = AstContext::new(&mut root, |context| {
+            context
+                .scoop_set(1, "alert", RuntimeValue::binding(crate::reactor::runtime::alert::wrap()))
+                .expect("Failed to register buitlin");
+            context
+                .scoop_set(1, "cat", RuntimeValue::binding(crate::reactor::runtime::cat::wrap()))
+                .expect("Failed to register buitlin");
+            context
+                .scoop_set(1, "compare", RuntimeValue::binding(crate::reactor::runtime::compare::wrap()))
+                .expect("Failed to register buitlin");
+            context
+                .scoop_set(1, "echo", RuntimeValue::binding(crate::reactor::runtime::echo::wrap()))
+                .expect("Failed to register buitlin");
             context
                 .scoop_set(1, "ls", RuntimeValue::binding(crate::reactor::runtime::ls::wrap()))
                 .expect("Failed to register buitlin");
+            context
+                .scoop_set(1, "sleep", RuntimeValue::binding(crate::reactor::runtime::sleep::wrap()))
+                .expect("Failed to register buitlin");
+            context
+                .scoop_set(1, "order", RuntimeValue::binding(crate::reactor::runtime::order::wrap()))
+                .expect("Failed to register buitlin");
+            context
+                .scoop_set(1, "plot", RuntimeValue::binding(crate::reactor::runtime::plot::wrap()))
+                .expect("Failed to register buitlin");
+            context
+                .scoop_set(1, "portfolio", RuntimeValue::binding(crate::reactor::runtime::portfolio::wrap()))
+                .expect("Failed to register buitlin");
+            context
+                .scoop_set(1, "ps", RuntimeValue::binding(crate::reactor::runtime::ps::wrap()))
+                .expect("Failed to register buitlin");
+            context
+                .scoop_set(1, "kill", RuntimeValue::binding(crate::reactor::runtime::kill::wrap()))
+                .expect("Failed to register buitlin");
+            context
+                .scoop_set(1, "wait", RuntimeValue::binding(crate::reactor::runtime::wait::wrap()))
+                .expect("Failed to register buitlin");
+            context
+                .scoop_set(1, "export", RuntimeValue::binding(crate::reactor::runtime::export::wrap()))
+                .expect("Failed to register buitlin");
+            context
+                .scoop_set(1, "import", RuntimeValue::binding(crate::reactor::runtime::import::wrap()))
+                .expect("Failed to register buitlin");
+            context
+                .scoop_set(1, "history", RuntimeValue::binding(crate::reactor::runtime::history::wrap()))
+                .expect("Failed to register buitlin");
+            context
+                .scoop_set(1, "logs", RuntimeValue::binding(crate::reactor::runtime::logs::wrap()))
+                .expect("Failed to register buitlin");
+            context
+                .scoop_set(1, "schedule", RuntimeValue::binding(crate::reactor::runtime::schedule::wrap()))
+                .expect("Failed to register buitlin");
+            context
+                .scoop_set(1, "retention", RuntimeValue::binding(crate::reactor::runtime::retention::wrap()))
+                .expect("Failed to register buitlin");
+            context
+                .scoop_set(1, "risk", RuntimeValue::binding(crate::reactor::runtime::risk::wrap()))
+                .expect("Failed to register buitlin");
+            context
+                .scoop_set(1, "du", RuntimeValue::binding(crate::reactor::runtime::du::wrap()))
+                .expect("Failed to register buitlin");
+            context
+                .scoop_set(1, "book", RuntimeValue::binding(crate::reactor::runtime::book::wrap()))
+                .expect("Failed to register buitlin");
+            context
+                .scoop_set(1, "trades", RuntimeValue::binding(crate::reactor::runtime::trades::wrap()))
+                .expect("Failed to register buitlin");
+            context
+                .scoop_set(1, "strategy", RuntimeValue::binding(crate::reactor::runtime::strategy::wrap()))
+                .expect("Failed to register buitlin");
+            context
+                .scoop_set(1, "sync", RuntimeValue::binding(crate::reactor::runtime::sync::wrap()))
+                .expect("Failed to register buitlin");
+            context
+                .scoop_set(1, "watchlist", RuntimeValue::binding(crate::reactor::runtime::watchlist::wrap()))
+                .expect("Failed to register buitlin");
         });
         Ok(Program {
             root,
             status: ProgramStatus::None,
             context,
+            source: text.as_ref().to_string(),
+            timeout: None,
         })
     }
+
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
 }
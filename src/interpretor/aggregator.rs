@@ -283,4 +283,25 @@ impl RuntimeValue {
     pub fn binding(generator: NativeProcedureGen) -> Self {
         Self::NativeProcedure(Arc::new(Mutex::new(generator)))
     }
+
+    pub fn to_json(&self) -> Value {
+        match self {
+            RuntimeValue::Undefined => Value::Null,
+            RuntimeValue::Number(payload) => serde_json::Number::from_f64(*payload)
+                .map(Value::Number)
+                .unwrap_or(Value::Null),
+            RuntimeValue::String(payload) => Value::String(payload.clone()),
+            RuntimeValue::Object(payload) => Value::Object(
+                payload
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.to_json()))
+                    .collect(),
+            ),
+            RuntimeValue::Array(payload) => {
+                Value::Array(payload.iter().map(|e| e.to_json()).collect())
+            }
+            RuntimeValue::Procedure(_) => Value::String("[procedure]".to_string()),
+            RuntimeValue::NativeProcedure(_) => Value::String("[native]".to_string()),
+        }
+    }
 }
@@ -390,6 +390,24 @@ impl Node {
         }
     }
 
+    pub fn call_arguments(&self) -> Vec<String> {
+        let mut arguments = Vec::new();
+        let mut next = self.0.right.as_ref();
+        while let Some(node) = next {
+            match &node.0.content {
+                CommandAstBody::Literal {
+                    token: Token::LiteralString,
+                    value,
+                } => arguments.push(value[1..value.len() - 1].to_string()),
+                CommandAstBody::Literal { value, .. } => arguments.push(value.clone()),
+                CommandAstBody::Ident { span } => arguments.push(span.clone()),
+                _ => {}
+            }
+            next = node.0.right.as_ref();
+        }
+        arguments
+    }
+
     pub fn reference(&'_ self) -> (&'_ str, Reference) {
         match &self.0.content {
             CommandAstBody::Ident { span } => (&span, self.0.meta.reference_to.expect("Dangling identifier")),
@@ -1,29 +1,241 @@
 #![feature(async_closure, type_alias_impl_trait)]
 use clap::{App, Arg};
 
+pub(crate) mod config;
+pub(crate) mod decimal;
 pub(crate) mod error;
 pub(crate) mod prelude;
 
 pub(crate) mod exchange;
+pub(crate) mod indicator;
 pub(crate) mod interpretor;
 pub(crate) mod reactor;
 pub(crate) mod store;
+pub(crate) mod ui;
 
-// mod api;
+mod api;
 
 use exchange::*;
-use interpretor::{Program, ProgramOutput};
+use interpretor::{Program, ProgramOutput, ProgramStatus};
 use prelude::*;
 use reactor::*;
 
 #[rocket::main]
 async fn main() {
-    pretty_env_logger::init();
-
     let matches = App::new("pkbot")
         .author("Asya C.")
         .version("0.1")
+        .arg(
+            Arg::new("config")
+                .help("Path of the configuration file (default: pkbot.toml)")
+                .short('c')
+                .long("config")
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            Arg::new("store")
+                .help("Override the store path")
+                .long("store")
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            Arg::new("log_level")
+                .help("Override the log level")
+                .long("log-level")
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            Arg::new("api_address")
+                .help("Override the api bind address")
+                .long("api-address")
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            Arg::new("api_port")
+                .help("Override the api port")
+                .long("api-port")
+                .takes_value(true)
+                .global(true),
+        )
         .subcommand(App::new("daemon").about("Launch a reactor deamon"))
+        .subcommand(App::new("tui").about("Launch the terminal dashboard"))
+        .subcommand(
+            App::new("sync")
+                .about("Backfill candles, of the configured markets when none is given")
+                .arg(
+                    Arg::new("market")
+                        .help("Markets to backfill (exchange/base/quote)")
+                        .multiple_values(true),
+                )
+                .arg(
+                    Arg::new("interval")
+                        .help("Intervals to backfill, required with markets")
+                        .short('i')
+                        .long("interval")
+                        .takes_value(true)
+                        .multiple_occurrences(true),
+                )
+                .arg(
+                    Arg::new("from")
+                        .help("Start of the range, rfc3339 or NOW-<duration>")
+                        .short('f')
+                        .long("from")
+                        .default_value("NOW-30d"),
+                )
+                .arg(
+                    Arg::new("to")
+                        .help("End of the range (default: now)")
+                        .short('t')
+                        .long("to")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            App::new("store")
+                .about("Manage the store")
+                .subcommand(
+                    App::new("migrate")
+                        .about("Upgrade the store to the current schema version")
+                        .arg(
+                            Arg::new("dry_run")
+                                .help("Report what would be migrated without writing anything")
+                                .long("dry-run"),
+                        ),
+                )
+                .subcommand(
+                    App::new("export")
+                        .about("Export stored candles to csv, jsonl or parquet")
+                        .arg(Arg::new("path").required(true))
+                        .arg(
+                            Arg::new("market")
+                                .help("Only export this market (e.g. kraken/BTC/EUR)")
+                                .short('m')
+                                .long("market")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::new("interval")
+                                .help("Only export this interval (e.g. 1m, 1h)")
+                                .short('i')
+                                .long("interval")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::new("format")
+                                .help("csv, jsonl or parquet, guessed from the extension by default")
+                                .short('f')
+                                .long("format")
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    App::new("import")
+                        .about("Import candles from csv, jsonl or parquet")
+                        .arg(Arg::new("path").required(true))
+                        .arg(
+                            Arg::new("format")
+                                .help("csv, jsonl or parquet, guessed from the extension by default")
+                                .short('f')
+                                .long("format")
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    App::new("backup")
+                        .about("Write a compressed snapshot of the store, into backup.dir through the daemon if it is running")
+                        .arg(Arg::new("path").required(true)),
+                )
+                .subcommand(
+                    App::new("restore")
+                        .about("Restore a backup into the store")
+                        .arg(Arg::new("path").required(true))
+                        .arg(
+                            Arg::new("to")
+                                .help("Restore into this directory instead of the configured store")
+                                .long("to")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::new("force")
+                                .help("Overwrite the content of a non empty store")
+                                .long("force"),
+                        ),
+                )
+                .subcommand(
+                    App::new("verify")
+                        .about("Check the integrity of a backup")
+                        .arg(Arg::new("path").required(true)),
+                )
+                .subcommand(
+                    App::new("ls")
+                        .about("List the trees of the store")
+                        .arg(Arg::new("json").help("Print as json").long("json")),
+                )
+                .subcommand(
+                    App::new("stat")
+                        .about("Show candle count, time range, gaps, size and settings of market data")
+                        .arg(Arg::new("market").help("Only this market (e.g. kraken/BTC/EUR)"))
+                        .arg(
+                            Arg::new("interval")
+                                .help("Only this interval (e.g. 1m, 1h)")
+                                .short('i')
+                                .long("interval")
+                                .takes_value(true),
+                        )
+                        .arg(Arg::new("json").help("Print as json").long("json")),
+                )
+                .subcommand(
+                    App::new("get")
+                        .about("Print decoded entries of a tree")
+                        .arg(Arg::new("tree").required(true))
+                        .arg(Arg::new("key").help("Timestamp, run id or name depending on the tree"))
+                        .arg(
+                            Arg::new("limit")
+                                .help("Number of entries printed without key")
+                                .short('n')
+                                .long("limit")
+                                .takes_value(true)
+                                .default_value("20"),
+                        )
+                        .arg(
+                            Arg::new("last")
+                                .help("Print the last entries instead of the first ones")
+                                .long("last"),
+                        ),
+                ),
+        )
+        .subcommand(
+            App::new("replay")
+                .about("Serve recorded stream frames as a local exchange websocket")
+                .arg(
+                    Arg::new("path")
+                        .help("Frames to send, one per line (see `stream_record`)")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("address")
+                        .help("Address to listen on")
+                        .short('a')
+                        .long("address")
+                        .default_value("127.0.0.1:9001"),
+                )
+                .arg(
+                    Arg::new("interval")
+                        .help("Milliseconds between two frames")
+                        .short('i')
+                        .long("interval")
+                        .default_value("100"),
+                )
+                .arg(
+                    Arg::new("loop")
+                        .help("Start over once every frame has been sent")
+                        .long("loop"),
+                ),
+        )
         .subcommand(
             App::new("ast")
                 .about("Print ast of a command")
@@ -32,22 +244,160 @@ async fn main() {
         .subcommand(
             App::new("exec")
                 .about("Execute a command")
-                .arg(Arg::new("command").required(true)),
+                .arg(Arg::new("command").required(true))
+                .arg(
+                    Arg::new("timeout")
+                        .help("Cancel the program after the given duration (e.g. 30s, 5m)")
+                        .short('t')
+                        .long("timeout")
+                        .takes_value(true),
+                ),
         )
         .get_matches();
 
-    let store_path = std::env::var("STORE_PATH").expect("STORE_PATH");
-    let store = Store::new(PathBuf::from(store_path)).unwrap();
-    let reactor = Reactor::new(store.handle()).await;
-    let kraken = KrakenExchange::from_env()
-        .expect("Kraken credentials")
-        .boxed();
-    reactor.register_exchange(kraken).await;
+    let config = match load_config(&matches) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let filters = std::env::var("RUST_LOG").unwrap_or_else(|_| config.log_level.clone());
+    // Logs written on stderr would corrupt the dashboard, it shows them in a pane instead
+    let tui_logs = if matches.subcommand_name() == Some("tui") {
+        let level = filters.parse().unwrap_or(log::LevelFilter::Info);
+        Some(ui::TuiLogger::init(level).expect("Failed to init logger"))
+    } else {
+        pretty_env_logger::formatted_builder()
+            .parse_filters(&filters)
+            .init();
+        None
+    };
+
+    if let Some(("store", matches)) = matches.subcommand() {
+        if let Err(e) = store_command(&config, matches).await {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(("replay", matches)) = matches.subcommand() {
+        if let Err(e) = replay_command(matches).await {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let store = Store::new(config.store.path.clone())
+        .expect("Failed to open store")
+        .with_history_retention(config.history_retention());
+    let assets = config.asset_registry().shared();
+    let reactor = Reactor::new(store.handle())
+        .await
+        .with_sync_concurrency(config.sync.concurrency)
+        .with_assets(assets.clone())
+        .with_program_timeout(config.program_timeout().expect("Failed to load program timeout"))
+        .with_risk(
+            RiskEngine::new(config.risk_policy().expect("Failed to load risk policy")).shared(),
+        );
+    for exchange in config.exchanges.iter().filter(|e| e.enabled) {
+        if !exchange.mock.is_empty() {
+            let mock = MockExchange::new(exchange.name.clone(), exchange.mock.clone())
+                .with_assets(assets.clone());
+            reactor.register_exchange(mock.shared()).await;
+            continue;
+        }
+        let (key, secret) = exchange
+            .credentials
+            .as_ref()
+            .expect("Missing exchange credentials")
+            .resolve()
+            .expect("Failed to load exchange credentials");
+        match exchange.name.as_str() {
+            "kraken" => {
+                let mut kraken = KrakenExchange::new(key, secret).with_assets(assets.clone());
+                if let Some(url) = exchange.stream_url.clone() {
+                    kraken = kraken.with_stream_url(url);
+                }
+                let policy = exchange.call_policy().expect("Failed to load exchange limits");
+                reactor
+                    .register_exchange(GuardedExchange::new(Box::new(kraken), policy).shared())
+                    .await
+            }
+            _ => unreachable!(),
+        }
+    }
     match matches.subcommand_name() {
         Some("daemon") => {
-            //api::spawn(reactor)
-            //    .await
-            //    .expect("Failed to launch api server");
+            reactor
+                .store
+                .history
+                .abort_interrupted()
+                .expect("Failed to close interrupted runs");
+            reactor
+                .start_scheduler()
+                .await
+                .expect("Failed to start scheduler");
+            reactor
+                .start_strategies()
+                .await
+                .expect("Failed to start strategies");
+            reactor.start_alerts().await;
+            reactor.start_compaction(
+                config
+                    .compaction_interval()
+                    .expect("Failed to load compaction interval"),
+            );
+            if let Some(policy) = config.backup_policy().expect("Failed to load backup policy") {
+                reactor.start_backups(policy);
+            }
+            if let Some(policy) = config
+                .arbitrage_policy()
+                .expect("Failed to load arbitrage policy")
+            {
+                reactor.start_arbitrage(policy);
+            }
+            reactor.watch_markets(
+                config
+                    .watched_markets()
+                    .expect("Failed to load watched markets"),
+            );
+            reactor
+                .start_streams(
+                    config
+                        .streamed_exchanges()
+                        .expect("Failed to load streamed markets"),
+                )
+                .await;
+            if config.api.enabled {
+                api::spawn(
+                    reactor,
+                    config.api_address().expect("Invalid api address"),
+                    config.api.port,
+                    config.backup.dir.clone(),
+                )
+                .await
+                .expect("Failed to launch api server");
+            } else {
+                futures::future::pending::<()>().await;
+            }
+        }
+        Some("sync") => {
+            let matches = matches.subcommand_matches("sync").unwrap();
+            match sync_command(&config, &reactor, matches).await {
+                Ok(true) => {}
+                Ok(false) => std::process::exit(1),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some("tui") => {
+            ui::run(reactor, tui_logs.unwrap())
+                .await
+                .expect("Failed to run terminal dashboard");
         }
         Some("ast") => {
             let matches = matches.subcommand_matches("ast").unwrap();
@@ -59,8 +409,15 @@ async fn main() {
         Some("exec") => {
             let matches = matches.subcommand_matches("exec").unwrap();
             let command = matches.value_of("command").unwrap();
-            let program = Program::new(command).expect("Failed to parse command");
-            let mut listener = reactor.event_listener().await;
+            let timeout = matches
+                .value_of("timeout")
+                .map(|e| reactor::runtime::human_duration(e).expect("Invalid timeout"));
+            let program = Program::new(command)
+                .expect("Failed to parse command")
+                .with_timeout(timeout);
+            let mut listener = reactor
+                .event_listener_with(DEFAULT_LISTENER_CAPACITY, BackpressurePolicy::Block)
+                .await;
             reactor.spawn_program(program).await;
             while let Some(event) = listener.recv().await {
                 match event {
@@ -87,6 +444,28 @@ async fn main() {
                             &content
                         );
                     }
+                    ReactorEvent::ProgramStatus { id: 0, status } => {
+                        if status.is_terminal() && status != ProgramStatus::Success {
+                            eprintln!("Program terminated: {:?}", status);
+                        }
+                    }
+                    ReactorEvent::ProgramStatus { .. } => {}
+                    ReactorEvent::AlertTriggered { notification } => {
+                        println!("[alert] {}", notification.message);
+                    }
+                    ReactorEvent::RiskViolation { order, violation } => {
+                        println!("[risk] {} rejected: {}", order, violation);
+                    }
+                    ReactorEvent::StrategyStatus {
+                        name,
+                        error: Some(error),
+                        ..
+                    } => {
+                        println!("[strategy] {} stopped: {}", name, error);
+                    }
+                    ReactorEvent::Lagged { skipped } => {
+                        eprintln!("Missed {} events", skipped);
+                    }
                     ReactorEvent::RuntimeCreated { .. } => {}
                     ReactorEvent::RuntimeDestroyed { id: 0 } => break,
                     e => {
@@ -99,3 +478,381 @@ async fn main() {
         _ => println!("Some other subcommand was used"),
     }
 }
+
+fn load_config(matches: &clap::ArgMatches) -> Result<config::Config> {
+    let mut config = config::Config::load(matches.value_of("config").map(Path::new))?;
+    if let Some(store) = matches.value_of("store") {
+        config.store.path = PathBuf::from(store);
+    }
+    if let Some(level) = matches.value_of("log_level") {
+        config.log_level = level.to_string();
+    }
+    if let Some(address) = matches.value_of("api_address") {
+        config.api.address = address.to_string();
+    }
+    if let Some(port) = matches.value_of("api_port") {
+        config.api.port = port
+            .parse()
+            .map_err(|_| Error::Config(format!("--api-port: invalid port `{}`", port)))?;
+    }
+    config.validate()?;
+    Ok(config)
+}
+
+async fn store_command(config: &config::Config, matches: &clap::ArgMatches) -> Result<()> {
+    match matches.subcommand() {
+        Some(("migrate", matches)) => {
+            let store = Store::open(config.store.path.clone())?;
+            let report = store.migrate(matches.is_present("dry_run"))?;
+            if report.steps.is_empty() {
+                println!("Store is up to date (version {})", report.to);
+            }
+            for step in report.steps.iter() {
+                println!(
+                    "{} version {}: {}",
+                    if report.dry_run { "Would migrate to" } else { "Migrated to" },
+                    step.version,
+                    step.description
+                );
+                for (tree, count) in step.trees.iter() {
+                    println!("    {}: {} values", tree, count);
+                }
+            }
+        }
+        Some(("export", matches)) => {
+            let store = Store::new(config.store.path.clone())?.handle();
+            let path = PathBuf::from(matches.value_of("path").unwrap());
+            let format = store::TransferFormat::resolve(matches.value_of("format"), &path)?;
+            let market = matches
+                .value_of("market")
+                .map(|e| config.asset_registry().canonical_market(&MarketIdentifier::from(e)));
+            let interval = matches
+                .value_of("interval")
+                .map(|e| reactor::runtime::ArgumentInterval::new(e).map(|e| e.normalized))
+                .transpose()?;
+            let count = store
+                .export(market.as_ref(), interval, format, &path)
+                .await?;
+            println!("Exported {} candles to {}", count, path.display());
+        }
+        Some(("import", matches)) => {
+            let store = Store::new(config.store.path.clone())?.handle();
+            let path = PathBuf::from(matches.value_of("path").unwrap());
+            let format = store::TransferFormat::resolve(matches.value_of("format"), &path)?;
+            let count = store
+                .import(format, &path, &config.asset_registry())
+                .await?;
+            println!("Imported {} candles from {}", count, path.display());
+        }
+        Some(("backup", matches)) => {
+            let raw = matches.value_of("path").unwrap();
+            let report = match Store::open(config.store.path.clone()) {
+                Ok(store) => store.handle().backup(&std::env::current_dir()?.join(raw))?,
+                // The store is locked while the daemon runs, ask the daemon to take the snapshot
+                Err(e) if config.api.enabled => {
+                    log::info!("Store unavailable, backup through the daemon: ERROR={}", e);
+                    remote_backup(config, raw).await?
+                }
+                Err(e) => return Err(e),
+            };
+            println!(
+                "Backed up {} trees ({} entries) to {}\nsha256 {}",
+                report.trees,
+                report.entries,
+                report.path.display(),
+                report.checksum
+            );
+        }
+        Some(("restore", matches)) => {
+            let path = PathBuf::from(matches.value_of("path").unwrap());
+            let target = matches
+                .value_of("to")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| config.store.path.clone());
+            let report = store::restore_backup(&path, &target, matches.is_present("force"))?;
+            println!(
+                "Restored {} trees ({} entries) to {}",
+                report.trees,
+                report.entries,
+                report.path.display()
+            );
+        }
+        Some(("verify", matches)) => {
+            let path = PathBuf::from(matches.value_of("path").unwrap());
+            println!("{}: OK\nsha256 {}", path.display(), store::verify_backup(&path)?);
+        }
+        Some(("ls", matches)) => {
+            let trees: Vec<store::TreeInfo> = match local_inspector(config)? {
+                Some(inspector) => inspector.trees()?,
+                None => remote_get(config, "/store/trees", &[]).await?,
+            };
+            if matches.is_present("json") {
+                println!("{}", serde_json::to_string_pretty(&trees)?);
+                return Ok(());
+            }
+            println!("{:<40} {:<28} {:>10} {:>10}", "TREE", "CONTENT", "ENTRIES", "SIZE");
+            for tree in trees.iter() {
+                let content = match &tree.content {
+                    store::TreeContent::MarketData { market, interval } => format!(
+                        "{}/{}/{} {}",
+                        market.exchange_name, market.base, market.quote, interval
+                    ),
+                    store::TreeContent::Trades { market } => format!(
+                        "{}/{}/{} trades",
+                        market.exchange_name, market.base, market.quote
+                    ),
+                    content => format!("{:?}", content),
+                };
+                println!(
+                    "{:<40} {:<28} {:>10} {:>10}",
+                    tree.name,
+                    content,
+                    tree.entries,
+                    human_size(tree.bytes)
+                );
+            }
+        }
+        Some(("stat", matches)) => {
+            let market = matches
+                .value_of("market")
+                .map(|e| config.asset_registry().canonical_market(&MarketIdentifier::from(e)));
+            let interval = matches
+                .value_of("interval")
+                .map(|e| reactor::runtime::ArgumentInterval::new(e).map(|e| e.normalized))
+                .transpose()?;
+            let stats: Vec<store::MarketDataStats> = match local_inspector(config)? {
+                Some(inspector) => inspector.stats(market.as_ref(), interval)?,
+                None => {
+                    let mut query = Vec::new();
+                    if let Some(market) = market.as_ref() {
+                        query.push((
+                            "market",
+                            format!("{}/{}/{}", market.exchange_name, market.base, market.quote),
+                        ));
+                    }
+                    if let Some(interval) = interval {
+                        query.push(("interval", (interval.as_secs() / 60).to_string()));
+                    }
+                    remote_get(config, "/store/stat", &query).await?
+                }
+            };
+            if matches.is_present("json") {
+                println!("{}", serde_json::to_string_pretty(&stats)?);
+                return Ok(());
+            }
+            for stat in stats.iter() {
+                println!(
+                    "{}/{}/{} {}",
+                    stat.market.exchange_name, stat.market.base, stat.market.quote, stat.interval
+                );
+                println!("    candles:   {}", stat.candles);
+                println!("    first:     {}", stat.first.map(format_time).unwrap_or_default());
+                println!("    last:      {}", stat.last.map(format_time).unwrap_or_default());
+                println!("    gaps:      {} ({} missing candles)", stat.gaps, stat.missing);
+                println!("    size:      {}", human_size(stat.bytes));
+                println!("    settings:  {}", serde_json::to_string(&stat.settings)?);
+            }
+        }
+        Some(("get", matches)) => {
+            let limit = matches
+                .value_of("limit")
+                .unwrap()
+                .parse()
+                .map_err(|_| Error::Parsing(String::from("Wrong limit, expected a number"), 0..0))?;
+            let tree = matches.value_of("tree").unwrap();
+            let entries: Vec<store::TreeEntry> = match local_inspector(config)? {
+                Some(inspector) => inspector.get(
+                    tree,
+                    matches.value_of("key"),
+                    limit,
+                    matches.is_present("last"),
+                )?,
+                None => {
+                    let mut query = vec![
+                        ("tree", tree.to_string()),
+                        ("limit", limit.to_string()),
+                        ("last", matches.is_present("last").to_string()),
+                    ];
+                    if let Some(key) = matches.value_of("key") {
+                        query.push(("key", key.to_string()));
+                    }
+                    remote_get(config, "/store/get", &query).await?
+                }
+            };
+            for entry in entries.iter() {
+                println!("{}: {}", entry.key, serde_json::to_string_pretty(&entry.value)?);
+            }
+        }
+        _ => eprintln!("Expected a store subcommand, see `pkbot store --help`"),
+    }
+    Ok(())
+}
+
+/// Returns whether every backfill succeeded
+async fn sync_command(
+    config: &config::Config,
+    reactor: &Reactor,
+    matches: &clap::ArgMatches,
+) -> Result<bool> {
+    let now = SystemTime::now();
+    let from = runtime::ArgumentTimestamp::new(matches.value_of("from").unwrap(), now)?.timestamp();
+    let to = match matches.value_of("to") {
+        Some(to) => runtime::ArgumentTimestamp::new(to, now)?.timestamp(),
+        None => store::now(),
+    };
+    let targets: Vec<(MarketIdentifier, Interval)> = match matches.values_of("market") {
+        Some(markets) => {
+            let intervals: Vec<Interval> = matches
+                .values_of("interval")
+                .ok_or_else(|| Error::Config("--interval is required with markets".to_string()))?
+                .map(|e| runtime::ArgumentInterval::new(e).map(|e| e.normalized))
+                .collect::<Result<_>>()?;
+            reactor
+                .resolve_markets(&markets.collect::<Vec<_>>())
+                .await?
+                .into_iter()
+                .cartesian_product(intervals)
+                .collect()
+        }
+        None => config
+            .watched_markets()?
+            .into_iter()
+            .map(|e| (e.market, e.interval))
+            .collect(),
+    };
+    if targets.is_empty() {
+        return Err(Error::Config("no market to backfill".to_string()));
+    }
+    let (sender, mut receiver) = channel::<BackfillProgress>(64);
+    let renderer = tokio::spawn(async move {
+        let mut lines: Vec<BackfillProgress> = Vec::new();
+        while let Some(progress) = receiver.recv().await {
+            match lines
+                .iter_mut()
+                .find(|e| e.market == progress.market && e.interval == progress.interval)
+            {
+                Some(line) => *line = progress,
+                None => {
+                    // Make room for the new line before redrawing
+                    eprintln!();
+                    lines.push(progress);
+                }
+            }
+            eprint!("\x1b[{}A", lines.len());
+            for line in lines.iter() {
+                eprintln!("\x1b[2K{}", line.bar(30));
+            }
+        }
+    });
+    let reports = reactor.backfill(targets, from, to, Some(sender)).await;
+    let _ = renderer.await;
+    let mut succeeded = true;
+    for report in reports.iter() {
+        match report.error.as_deref() {
+            Some(e) => {
+                succeeded = false;
+                eprintln!(
+                    "{}/{}/{} {}: {}",
+                    report.market.exchange_name,
+                    report.market.base,
+                    report.market.quote,
+                    report.interval,
+                    e
+                );
+            }
+            None => println!(
+                "{}/{}/{} {}: {} candles up to {}",
+                report.market.exchange_name,
+                report.market.base,
+                report.market.quote,
+                report.interval,
+                report.fetched,
+                format_time(report.reached)
+            ),
+        }
+    }
+    Ok(succeeded)
+}
+
+async fn replay_command(matches: &clap::ArgMatches) -> Result<()> {
+    let address = matches
+        .value_of("address")
+        .unwrap()
+        .parse()
+        .map_err(|_| Error::Stream(format!("invalid address `{}`", matches.value_of("address").unwrap())))?;
+    let interval = Duration::from_millis(matches.value_of("interval").unwrap().parse()?);
+    exchange::serve_replay(
+        address,
+        PathBuf::from(matches.value_of("path").unwrap()),
+        interval,
+        matches.is_present("loop"),
+    )
+    .await
+}
+
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+fn format_time(time: Timestamp) -> String {
+    chrono::NaiveDateTime::from_timestamp(time, 0)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+/// Read only view of the store, `None` when it is locked by the daemon and its api can be asked
+fn local_inspector(config: &config::Config) -> Result<Option<store::StoreInspector>> {
+    match Store::open_existing(config.store.path.clone()) {
+        Ok(store) => Ok(Some(store.inspector())),
+        Err(e) if config.api.enabled && config.store.path.exists() => {
+            log::info!("Store unavailable, inspect through the daemon: ERROR={}", e);
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+async fn remote_get<T: serde::de::DeserializeOwned>(
+    config: &config::Config,
+    path: &str,
+    query: &[(&str, String)],
+) -> Result<T> {
+    let url = format!("http://{}:{}{}", config.api.address, config.api.port, path);
+    Ok(reqwest::Client::new()
+        .get(&url)
+        .query(query)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?)
+}
+
+/// The daemon only writes backups in its `backup.dir`, `name` must be a file name
+async fn remote_backup(config: &config::Config, name: &str) -> Result<store::BackupReport> {
+    let url = format!("http://{}:{}/store/backup", config.api.address, config.api.port);
+    let response = reqwest::Client::new()
+        .post(&url)
+        .json(&serde_json::json!({ "name": name }))
+        .send()
+        .await
+        .map_err(|e| Error::Backup(format!("{}: {}", url, e)))?;
+    if !response.status().is_success() {
+        return Err(Error::Backup(format!("{}: {}", url, response.status())));
+    }
+    response
+        .json()
+        .await
+        .map_err(|e| Error::Backup(format!("{}: {}", url, e)))
+}
@@ -18,9 +18,11 @@ pub use std::sync::Arc;
 pub use std::time::Duration;
 pub use std::time::SystemTime;
 pub use tokio::sync::mpsc::{channel, Receiver, Sender};
-pub use tokio::sync::{Mutex, RwLock};
+pub use tokio::sync::{watch, Mutex, RwLock};
 pub use tokio::task::JoinHandle;
+pub use tokio_util::sync::CancellationToken;
 pub type Timestamp = i64;
+pub use crate::decimal::Decimal;
 pub use crate::error::{Error, Result};
 pub use crate::exchange::{OHLCChunk, OHLC};
 pub use crate::interpretor::aggregator::*;
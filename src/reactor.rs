@@ -1,17 +1,38 @@
 use crate::exchange::*;
 use crate::interpretor::*;
 use crate::prelude::*;
+use crate::store::{RunIdentifier, StrategyMode};
 
+mod alert;
+mod arbitrage;
+mod backfill;
+mod backup;
+mod listener;
+mod portfolio;
+mod retention;
+mod risk;
 pub mod runtime;
+mod scheduler;
+mod strategy;
+mod stream;
 mod sync;
 pub mod utils;
+mod watcher;
+mod watchlist;
 
+pub use alert::*;
+pub use arbitrage::*;
+pub use backfill::*;
+pub use listener::*;
+pub use portfolio::*;
+pub use risk::*;
+pub use scheduler::*;
+pub use strategy::*;
 pub use sync::*;
+pub use watchlist::*;
 
 use self::runtime::ProgramRuntime;
 
-pub type ListenerIdentifier = u64;
-
 #[derive(Debug, Clone)]
 pub enum ReactorEvent {
     ProgramOutput {
@@ -28,19 +49,72 @@ pub enum ReactorEvent {
     RuntimeDestroyed {
         id: ProgramIdentifier,
     },
+    Lagged {
+        skipped: u64,
+    },
+    ScheduleTriggered {
+        name: String,
+        id: ProgramIdentifier,
+    },
+    MarketSynced {
+        market: MarketIdentifier,
+        interval: Interval,
+        begin: Timestamp,
+        end: Timestamp,
+    },
+    AlertTriggered {
+        notification: AlertNotification,
+    },
+    TickerUpdated {
+        market: MarketIdentifier,
+        ticker: Ticker,
+    },
+    TradesReceived {
+        market: MarketIdentifier,
+        trades: Vec<Trade>,
+    },
+    /// The candle being formed changed, it is written to the store once closed
+    CandleUpdated {
+        market: MarketIdentifier,
+        interval: Interval,
+        ohlc: OHLC,
+    },
+    StreamStatus {
+        exchange: String,
+        connected: bool,
+        reason: Option<String>,
+    },
+    BackfillProgress {
+        progress: BackfillProgress,
+    },
+    ArbitrageDetected {
+        opportunity: ArbitrageOpportunity,
+    },
+    RiskViolation {
+        order: OrderRequest,
+        violation: RiskViolation,
+    },
+    OrderPlaced {
+        order: PlacedOrder,
+    },
+    KillSwitch {
+        engaged: bool,
+        reason: Option<String>,
+    },
+    StrategyStatus {
+        name: String,
+        mode: StrategyMode,
+        running: bool,
+        /// Why the strategy stopped by itself
+        error: Option<String>,
+    },
 }
 
 pub type SyncMap<K, V> = Arc<RwLock<HashMap<K, V>>>;
 
-pub struct ReactorEventListener {
-    sender: Sender<ReactorEvent>,
-}
-
-pub struct ReactorEventListenerHandle {
-    id: ListenerIdentifier,
-    receiver: Receiver<ReactorEvent>,
-    pool: SyncMap<ListenerIdentifier, ReactorEventListener>,
-}
+pub const DEFAULT_SYNC_CONCURRENCY: usize = 4;
+/// Final statuses kept once the runtime of a program is removed
+pub const FINISHED_PROGRAMS_KEPT: usize = 1024;
 
 #[derive(Clone)]
 pub struct Reactor {
@@ -48,7 +122,20 @@ pub struct Reactor {
     pub exchanges: SyncMap<String, SyncExchange>,
     pub markets: SyncMap<MarketIdentifier, SyncMarket>,
     pub programs: SyncMap<ProgramIdentifier, ProgramRuntime>,
-    pub listeners: SyncMap<ListenerIdentifier, ReactorEventListener>,
+    pub listeners: ListenerMap,
+    pub schedules: SyncMap<String, ScheduleTask>,
+    pub strategies: SyncMap<String, StrategyTask>,
+    /// Bound the number of market downloads running at once
+    pub sync_limit: Arc<tokio::sync::Semaphore>,
+    /// Canonical asset symbols, shared with the exchanges
+    pub assets: Arc<AssetRegistry>,
+    /// Checks every order before it is sent
+    pub risk: Arc<RiskEngine>,
+    /// Wall-clock limit of the programs spawned without their own
+    pub program_timeout: Option<Duration>,
+    /// Final statuses of the last finished programs, so they can still be waited or killed
+    finished: Arc<std::sync::Mutex<VecDeque<(ProgramIdentifier, ProgramStatus)>>>,
+    scheduler_running: Arc<std::sync::atomic::AtomicBool>,
     listener_counter: Arc<AtomicU64>,
     process_counter: Arc<AtomicU64>,
 }
@@ -61,36 +148,84 @@ impl Reactor {
             markets: Arc::new(RwLock::new(HashMap::new())),
             listeners: Arc::new(RwLock::new(HashMap::new())),
             programs: Arc::new(RwLock::new(HashMap::new())),
+            schedules: Arc::new(RwLock::new(HashMap::new())),
+            strategies: Arc::new(RwLock::new(HashMap::new())),
+            sync_limit: Arc::new(tokio::sync::Semaphore::new(DEFAULT_SYNC_CONCURRENCY)),
+            assets: AssetRegistry::new().shared(),
+            risk: RiskEngine::new(RiskPolicy::default()).shared(),
+            program_timeout: None,
+            finished: Arc::new(std::sync::Mutex::new(VecDeque::new())),
+            scheduler_running: Arc::new(std::sync::atomic::AtomicBool::new(false)),
             process_counter: Arc::new(AtomicU64::new(0)),
             listener_counter: Arc::new(AtomicU64::new(0)),
         };
         reactor
     }
 
+    pub fn with_sync_concurrency(self, concurrency: usize) -> Self {
+        Self {
+            sync_limit: Arc::new(tokio::sync::Semaphore::new(concurrency)),
+            ..self
+        }
+    }
+
+    pub fn with_assets(self, assets: Arc<AssetRegistry>) -> Self {
+        Self { assets, ..self }
+    }
+
+    pub fn with_program_timeout(self, program_timeout: Option<Duration>) -> Self {
+        Self {
+            program_timeout,
+            ..self
+        }
+    }
+
     pub async fn event_listener(&self) -> ReactorEventListenerHandle {
-        let id = self.listener_counter.fetch_add(1, Ordering::SeqCst);
-        let (sender, receiver) = channel(1024);
-        self.listeners
-            .write()
+        self.event_listener_with(DEFAULT_LISTENER_CAPACITY, BackpressurePolicy::DropOldest)
             .await
-            .insert(id, ReactorEventListener { sender });
-        ReactorEventListenerHandle {
-            receiver,
-            pool: self.listeners.clone(),
-            id,
-        }
     }
 
-    async fn runtime_handler(reactor: Reactor, mut runtime: ProgramRuntime) {
-        let mut receiver = runtime.stdout.take().unwrap();
-        let runtime_id = runtime.id;
-        reactor.programs.write().await.insert(runtime_id, runtime);
+    pub async fn event_listener_with(
+        &self,
+        capacity: usize,
+        policy: BackpressurePolicy,
+    ) -> ReactorEventListenerHandle {
+        let id = self.listener_counter.fetch_add(1, Ordering::SeqCst);
+        let listener = Arc::new(ReactorEventListener::new(capacity, policy));
+        self.listeners.write().await.insert(id, listener.clone());
+        ReactorEventListenerHandle::new(id, listener)
+    }
+
+    async fn runtime_handler(
+        reactor: Reactor,
+        runtime_id: ProgramIdentifier,
+        run_id: Option<RunIdentifier>,
+        mut receiver: Receiver<ProgramOutput>,
+        status: watch::Sender<ProgramStatus>,
+        supervisor: JoinHandle<ProgramStatus>,
+    ) {
         reactor
             .listeners
             .broadcast(ReactorEvent::RuntimeCreated { id: runtime_id })
             .await;
+        reactor
+            .set_program_status(runtime_id, &status, ProgramStatus::Running)
+            .await;
         log::trace!("Handling runtime: ID={}", runtime_id);
+        let mut failed = false;
         while let Some(message) = receiver.recv().await {
+            if let ProgramOutput::Exit {
+                status: ProgramStatus::Error,
+                ..
+            } = &message
+            {
+                failed = true;
+            }
+            if let Some(run_id) = run_id {
+                if let Err(e) = reactor.store.history.record(run_id, &message) {
+                    error!("Failed to record program output: RUN={}, ERROR={}", run_id, e);
+                }
+            }
             reactor
                 .listeners
                 .broadcast(ReactorEvent::ProgramOutput {
@@ -99,6 +234,29 @@ impl Reactor {
                 })
                 .await;
         }
+        let final_status = match supervisor.await {
+            Ok(ProgramStatus::Success) if failed => ProgramStatus::Error,
+            Ok(final_status) => final_status,
+            Err(e) => {
+                log::error!("Program supervisor failed: ID={}, ERROR={}", runtime_id, e);
+                ProgramStatus::Error
+            }
+        };
+        reactor
+            .set_program_status(runtime_id, &status, final_status)
+            .await;
+        {
+            let mut finished = reactor.finished.lock().unwrap_or_else(|e| e.into_inner());
+            if finished.len() >= FINISHED_PROGRAMS_KEPT {
+                finished.pop_front();
+            }
+            finished.push_back((runtime_id, final_status));
+        }
+        if let Some(run_id) = run_id {
+            if let Err(e) = reactor.store.history.finish(run_id, final_status) {
+                error!("Failed to record program status: RUN={}, ERROR={}", run_id, e);
+            }
+        }
         log::trace!("Removing runtime: ID={}", runtime_id);
         reactor.programs.write().await.remove(&runtime_id);
         reactor
@@ -107,17 +265,97 @@ impl Reactor {
             .await;
     }
 
-    pub async fn spawn_program(&self, program: Program) {
-        let runtime = ProgramRuntime::spawn(program, self.clone()).await;
-        tokio::spawn(Self::runtime_handler(self.clone(), runtime));
+    async fn set_program_status(
+        &self,
+        id: ProgramIdentifier,
+        sender: &watch::Sender<ProgramStatus>,
+        status: ProgramStatus,
+    ) {
+        let _ = sender.send(status);
+        self.listeners
+            .broadcast(ReactorEvent::ProgramStatus { id, status })
+            .await;
+    }
+
+    pub async fn spawn_program(&self, mut program: Program) -> ProgramIdentifier {
+        if program.timeout.is_none() {
+            program.timeout = self.program_timeout;
+        }
+        let mut runtime = ProgramRuntime::spawn(program, self.clone()).await;
+        let id = runtime.id;
+        runtime.run_id = self
+            .store
+            .history
+            .begin(id, &runtime.source)
+            .map_err(|e| error!("Failed to record program run: ID={}, ERROR={}", id, e))
+            .ok();
+        let run_id = runtime.run_id;
+        let receiver = runtime.stdout.take().unwrap();
+        let status = runtime.status_sender.take().unwrap();
+        let supervisor = runtime.supervisor.take().unwrap();
+        self.programs.write().await.insert(id, runtime);
+        tokio::spawn(Self::runtime_handler(
+            self.clone(),
+            id,
+            run_id,
+            receiver,
+            status,
+            supervisor,
+        ));
+        id
+    }
+
+    fn finished_status(&self, id: ProgramIdentifier) -> Option<ProgramStatus> {
+        self.finished
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .rev()
+            .find(|(finished, _)| *finished == id)
+            .map(|(_, status)| *status)
+    }
+
+    /// Cancel a running program, returns the final status of a program which already finished
+    pub async fn kill_program(&self, id: ProgramIdentifier) -> Result<Option<ProgramStatus>> {
+        if let Some(runtime) = self.programs.read().await.get(&id) {
+            runtime.cancel.cancel();
+            return Ok(None);
+        }
+        self.finished_status(id)
+            .map(Some)
+            .ok_or(Error::ProgramNotFound(id))
+    }
+
+    pub async fn wait_program(&self, id: ProgramIdentifier) -> Result<ProgramStatus> {
+        // A runtime is only removed once its final status is kept
+        let status = self.programs.read().await.get(&id).map(|e| e.status.clone());
+        let mut status = match status {
+            Some(status) => status,
+            None => return self.finished_status(id).ok_or(Error::ProgramNotFound(id)),
+        };
+        loop {
+            let current = *status.borrow();
+            if current.is_terminal() {
+                return Ok(current);
+            }
+            if status.changed().await.is_err() {
+                return Ok(*status.borrow());
+            }
+        }
+    }
+
+    /// Stored data of a market, under its canonical name
+    pub fn market_store(&self, id: &MarketIdentifier) -> Result<StoreMarketHandle> {
+        self.store.market(self.assets.canonical_market(id))
     }
 
     pub async fn register_exchange(&self, exchange: SyncExchange) {
-        let name = exchange.lock().await.name();
-        self.exchanges.write().await.insert(name, exchange);
+        self.exchanges.write().await.insert(exchange.name(), exchange);
     }
 
+    /// Markets are registered under their canonical name, aliases share the same data
     pub async fn get_or_register_market(&self, id: &MarketIdentifier) -> Result<SyncMarket> {
+        let id = &self.assets.canonical_market(id);
         let (market, _fresh) = {
             let mut lock = self.markets.write().await;
             if let Some(market) = lock.get(&id) {
@@ -132,37 +370,70 @@ impl Reactor {
     }
 }
 
-impl ReactorEventListenerHandle {
-    async fn cleanup(
-        pool: SyncMap<ListenerIdentifier, ReactorEventListener>,
-        id: ListenerIdentifier,
-    ) {
-        pool.write().await.remove(&id);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn reactor(name: &str) -> Reactor {
+        let path = std::env::temp_dir().join(format!("pkbot-reactor-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&path);
+        let store = Store::new(path).unwrap();
+        Reactor::new(store.handle()).await
     }
 
-    pub async fn recv(&mut self) -> Option<ReactorEvent> {
-        self.receiver.recv().await
+    async fn wait(reactor: &Reactor, id: ProgramIdentifier) -> ProgramStatus {
+        tokio::time::timeout(Duration::from_secs(10), reactor.wait_program(id))
+            .await
+            .expect("program still running")
+            .unwrap()
     }
-}
 
-impl Drop for ReactorEventListenerHandle {
-    fn drop(&mut self) {
-        tokio::spawn(Self::cleanup(self.pool.clone(), self.id));
+    #[tokio::test]
+    async fn finished_programs_keep_their_status() {
+        let reactor = reactor("finished").await;
+        let id = reactor.spawn_program(Program::new("echo hello").unwrap()).await;
+        assert_eq!(wait(&reactor, id).await, ProgramStatus::Success);
+        while reactor.programs.read().await.contains_key(&id) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(wait(&reactor, id).await, ProgramStatus::Success);
+        assert_eq!(reactor.kill_program(id).await.unwrap(), Some(ProgramStatus::Success));
+        assert!(matches!(
+            reactor.wait_program(id + 1).await,
+            Err(Error::ProgramNotFound(_))
+        ));
     }
-}
 
-#[async_trait]
-pub trait ListenerPool {
-    async fn broadcast(&self, event: ReactorEvent);
-}
+    #[tokio::test]
+    async fn killed_program_is_cancelled() {
+        let reactor = reactor("killed").await;
+        let id = reactor.spawn_program(Program::new("sleep 60").unwrap()).await;
+        assert_eq!(reactor.kill_program(id).await.unwrap(), None);
+        assert_eq!(wait(&reactor, id).await, ProgramStatus::Cancelled);
+    }
 
-#[async_trait]
-impl ListenerPool for SyncMap<ListenerIdentifier, ReactorEventListener> {
-    async fn broadcast(&self, event: ReactorEvent) {
-        for (id, ReactorEventListener { sender }) in self.read().await.iter() {
-            let _ = sender.send(event.clone()).await.map_err(|e| {
-                log::error!("Failed to broadcast listener: ID={} ERROR={}", id, e);
-            });
-        }
+    #[tokio::test]
+    async fn program_timeout() {
+        let reactor = reactor("timeout").await;
+        let program = Program::new("sleep 60")
+            .unwrap()
+            .with_timeout(Some(Duration::from_millis(50)));
+        let id = reactor.spawn_program(program).await;
+        assert_eq!(wait(&reactor, id).await, ProgramStatus::TimedOut);
+    }
+
+    #[tokio::test]
+    async fn default_program_timeout() {
+        let reactor = reactor("default-timeout")
+            .await
+            .with_program_timeout(Some(Duration::from_millis(50)));
+        let id = reactor.spawn_program(Program::new("sleep 60").unwrap()).await;
+        assert_eq!(wait(&reactor, id).await, ProgramStatus::TimedOut);
+        // A timeout of the program wins over the default one
+        let program = Program::new("sleep 0")
+            .unwrap()
+            .with_timeout(Some(Duration::from_secs(30)));
+        let id = reactor.spawn_program(program).await;
+        assert_eq!(wait(&reactor, id).await, ProgramStatus::Success);
     }
 }
@@ -0,0 +1,329 @@
+use super::*;
+use crate::indicator;
+use crate::store::{Alert, AlertCondition, AlertSink};
+use tokio::io::AsyncWriteExt;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertNotification {
+    pub alert: String,
+    pub market: String,
+    /// Only set for alerts on candles
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval: Option<Interval>,
+    pub time: Timestamp,
+    pub value: Decimal,
+    pub message: String,
+}
+
+impl AlertCondition {
+    pub fn required_candles(&self) -> usize {
+        match self {
+            AlertCondition::PriceAbove { .. } | AlertCondition::PriceBelow { .. } => 1,
+            // Wilder smoothing converge with more history than the strict minimum
+            AlertCondition::RsiAbove { period, .. } | AlertCondition::RsiBelow { period, .. } => {
+                period * 4 + 1
+            }
+            AlertCondition::PercentMove { window, .. } => window + 1,
+        }
+    }
+
+    /// Returns whether the condition holds on the last candle and the observed value
+    pub fn evaluate(&self, candles: &[OHLC]) -> Option<(bool, Decimal)> {
+        let closes = indicator::closes(candles);
+        let last = *closes.last()?;
+        match self {
+            AlertCondition::PriceAbove { level } => Some((last >= *level, last)),
+            AlertCondition::PriceBelow { level } => Some((last <= *level, last)),
+            AlertCondition::RsiAbove { period, threshold } => {
+                let rsi = indicator::rsi(&closes, *period)?;
+                Some((rsi >= *threshold, rsi))
+            }
+            AlertCondition::RsiBelow { period, threshold } => {
+                let rsi = indicator::rsi(&closes, *period)?;
+                Some((rsi <= *threshold, rsi))
+            }
+            AlertCondition::PercentMove { window, percent } => {
+                if closes.len() <= *window {
+                    return None;
+                }
+                let change =
+                    indicator::percent_change(closes[closes.len() - 1 - window], last)?;
+                Some((change.abs() >= *percent, change))
+            }
+        }
+    }
+}
+
+/// Sinks are delivered one after the other, a hung endpoint or script must not hold the others
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+async fn post_webhook(url: &str, notification: &AlertNotification) -> Result<()> {
+    reqwest::Client::builder()
+        .timeout(DELIVERY_TIMEOUT)
+        .build()?
+        .post(url)
+        .json(notification)
+        .send()
+        .await
+        .and_then(|e| e.error_for_status())
+        .map_err(|e| Error::AlertDelivery(format!("{}: {}", url, e)))?;
+    Ok(())
+}
+
+impl AlertSink {
+    pub async fn deliver(&self, reactor: &Reactor, notification: &AlertNotification) -> Result<()> {
+        tokio::time::timeout(DELIVERY_TIMEOUT, self.send(reactor, notification))
+            .await
+            .map_err(|_| {
+                Error::AlertDelivery(format!("{}: timed out after {:?}", self, DELIVERY_TIMEOUT))
+            })?
+    }
+
+    async fn send(&self, reactor: &Reactor, notification: &AlertNotification) -> Result<()> {
+        match self {
+            AlertSink::EventBus => {
+                reactor
+                    .listeners
+                    .broadcast(ReactorEvent::AlertTriggered {
+                        notification: notification.clone(),
+                    })
+                    .await;
+            }
+            AlertSink::File { path } => {
+                let mut file = tokio::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .await?;
+                let mut line = serde_json::to_string(notification)?;
+                line.push('\n');
+                file.write_all(line.as_bytes()).await?;
+            }
+            AlertSink::Command { command } => {
+                let status = tokio::process::Command::new("sh")
+                    .arg("-c")
+                    .arg(command)
+                    .env("PKBOT_ALERT", &notification.alert)
+                    .env("PKBOT_MARKET", &notification.market)
+                    .env("PKBOT_VALUE", notification.value.to_string())
+                    .env("PKBOT_MESSAGE", &notification.message)
+                    .env("PKBOT_NOTIFICATION", serde_json::to_string(notification)?)
+                    // The script is killed when the delivery times out
+                    .kill_on_drop(true)
+                    .status()
+                    .await?;
+                if !status.success() {
+                    return Err(Error::AlertDelivery(format!(
+                        "`{}` exited with {}",
+                        command, status
+                    )));
+                }
+            }
+            AlertSink::Webhook { url } => post_webhook(url, notification).await?,
+        }
+        Ok(())
+    }
+}
+
+impl Reactor {
+    pub async fn start_alerts(&self) {
+        let mut listener = self.event_listener().await;
+        let reactor = self.clone();
+        tokio::spawn(async move {
+            while let Some(event) = listener.recv().await {
+                match event {
+                    ReactorEvent::MarketSynced {
+                        market, interval, ..
+                    } => {
+                        if let Err(e) = reactor.evaluate_alerts(&market, interval).await {
+                            error!("Failed to evaluate alerts: MARKET={}, ERROR={}", market, e);
+                        }
+                    }
+                    ReactorEvent::Lagged { skipped } => {
+                        warn!("Alert engine missed {} events", skipped);
+                    }
+                    _ => {}
+                }
+            }
+        });
+    }
+
+    pub async fn evaluate_alerts(&self, market: &MarketIdentifier, interval: Interval) -> Result<()> {
+        let alerts: Vec<Alert> = self
+            .store
+            .alerts
+            .list()?
+            .into_iter()
+            .filter(|e| {
+                e.interval == interval
+                    && self.assets.canonical_market(&MarketIdentifier::from(&e.market))
+                        == self.assets.canonical_market(market)
+            })
+            .collect();
+        if alerts.is_empty() {
+            return Ok(());
+        }
+        let data = self.market_store(market)?.interval(interval).await?;
+        for mut alert in alerts {
+            let candles = data.last_n(alert.condition.required_candles())?;
+            let (active, value) = match alert.condition.evaluate(&candles) {
+                Some(res) => res,
+                None => continue,
+            };
+            if active && !alert.active {
+                let notification = AlertNotification {
+                    alert: alert.name.clone(),
+                    market: alert.market.clone(),
+                    interval: Some(interval),
+                    time: candles.last().map(|e| e.time).unwrap_or(0),
+                    value,
+                    message: format!(
+                        "{} {} {}: {} (value {})",
+                        alert.name, alert.market, interval, alert.condition, value
+                    ),
+                };
+                info!("Alert triggered: {}", notification.message);
+                for sink in alert.sinks.iter() {
+                    if let Err(e) = sink.deliver(self, &notification).await {
+                        error!(
+                            "Failed to deliver alert: NAME={}, SINK={}, ERROR={}",
+                            alert.name, sink, e
+                        );
+                    }
+                }
+                alert.last_triggered = Some(crate::store::now());
+            }
+            // Alerts removed while being evaluated are not brought back
+            if active != alert.active {
+                self.store
+                    .alerts
+                    .set_state(&alert.name, active, alert.last_triggered)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    fn notification() -> AlertNotification {
+        AlertNotification {
+            alert: String::from("btc-high"),
+            market: String::from("kraken/BTC/EUR"),
+            interval: Some(Interval::Hour1),
+            time: 1_640_000_000,
+            value: "51000.5".parse().unwrap(),
+            message: String::from("btc-high kraken/BTC/EUR 1h: price above 50000"),
+        }
+    }
+
+    /// Accept one request, answer it with `status` and return its body
+    async fn serve_once(listener: TcpListener, status: &'static str) -> String {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut raw = Vec::new();
+        let mut buffer = [0u8; 1024];
+        let body_start = loop {
+            let read = socket.read(&mut buffer).await.unwrap();
+            assert!(read > 0, "connection closed before the end of the headers");
+            raw.extend_from_slice(&buffer[..read]);
+            if let Some(at) = raw.windows(4).position(|e| e == b"\r\n\r\n") {
+                break at + 4;
+            }
+        };
+        let headers = String::from_utf8_lossy(&raw[..body_start]).to_lowercase();
+        let length: usize = headers
+            .lines()
+            .find_map(|e| e.strip_prefix("content-length:"))
+            .map(|e| e.trim().parse().unwrap())
+            .unwrap_or(0);
+        while raw.len() < body_start + length {
+            let read = socket.read(&mut buffer).await.unwrap();
+            raw.extend_from_slice(&buffer[..read]);
+        }
+        let response = format!("HTTP/1.1 {}\r\ncontent-length: 0\r\n\r\n", status);
+        socket.write_all(response.as_bytes()).await.unwrap();
+        String::from_utf8(raw[body_start..body_start + length].to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn webhook_posts_the_notification() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = tokio::spawn(serve_once(listener, "200 OK"));
+        post_webhook(&url, &notification()).await.unwrap();
+        let received: AlertNotification = serde_json::from_str(&server.await.unwrap()).unwrap();
+        assert_eq!(received.alert, "btc-high");
+        assert_eq!(received.market, "kraken/BTC/EUR");
+        assert_eq!(received.value, notification().value);
+        assert_eq!(received.message, notification().message);
+    }
+
+    #[tokio::test]
+    async fn webhook_reports_error_status() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = tokio::spawn(serve_once(listener, "500 Internal Server Error"));
+        let result = post_webhook(&url, &notification()).await;
+        server.await.unwrap();
+        assert!(matches!(result, Err(Error::AlertDelivery(_))));
+    }
+
+    #[test]
+    fn conditions_are_checked() {
+        let zero_period = AlertCondition::RsiAbove {
+            period: 0,
+            threshold: Decimal::from(70i64),
+        };
+        assert!(zero_period.check().is_err());
+        let zero_window = AlertCondition::PercentMove {
+            window: 0,
+            percent: Decimal::from(5i64),
+        };
+        assert!(zero_window.check().is_err());
+        let rsi = AlertCondition::RsiBelow {
+            period: 14,
+            threshold: Decimal::from(30i64),
+        };
+        assert!(rsi.check().is_ok());
+    }
+
+    #[test]
+    fn removed_alert_is_not_brought_back() {
+        let temp_path = std::env::temp_dir().join(format!("pkbot-alert-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&temp_path);
+        let store = Store::new(temp_path.clone()).unwrap().handle();
+        let alert = Alert {
+            name: String::from("btc-high"),
+            market: String::from("kraken/BTC/EUR"),
+            interval: Interval::Hour1,
+            condition: AlertCondition::PriceAbove {
+                level: Decimal::from(50_000i64),
+            },
+            sinks: vec![AlertSink::EventBus],
+            active: false,
+            last_triggered: None,
+        };
+        store.alerts.set(&alert).unwrap();
+        assert!(store.alerts.set_state(&alert.name, true, Some(1)).unwrap());
+        let saved = store.alerts.get(&alert.name).unwrap().unwrap();
+        assert!(saved.active);
+        assert_eq!(saved.last_triggered, Some(1));
+
+        store.alerts.remove(&alert.name).unwrap();
+        assert!(!store.alerts.set_state(&alert.name, false, Some(1)).unwrap());
+        assert!(store.alerts.get(&alert.name).unwrap().is_none());
+        let _ = std::fs::remove_dir_all(&temp_path);
+    }
+
+    #[test]
+    fn local_sinks() {
+        assert!("file:/tmp/alerts".parse::<AlertSink>().unwrap().is_local());
+        assert!("cmd:echo".parse::<AlertSink>().unwrap().is_local());
+        assert!(!"bus".parse::<AlertSink>().unwrap().is_local());
+        assert!(!"http://localhost/hook".parse::<AlertSink>().unwrap().is_local());
+    }
+}
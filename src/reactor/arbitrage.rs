@@ -0,0 +1,309 @@
+use super::*;
+use crate::store::AlertSink;
+use std::collections::HashSet;
+
+/// Best prices of a pair on one exchange
+#[derive(Debug, Clone, Serialize)]
+pub struct PairQuote {
+    pub market: MarketIdentifier,
+    pub bid: Decimal,
+    pub ask: Decimal,
+    pub bid_volume: Decimal,
+    pub ask_volume: Decimal,
+    /// Taker fee, in percent
+    pub fee: Decimal,
+}
+
+/// Buy at the best ask of one exchange and sell at the best bid of another
+#[derive(Debug, Clone, Serialize)]
+pub struct ArbitrageOpportunity {
+    pub buy: MarketIdentifier,
+    pub sell: MarketIdentifier,
+    pub buy_price: Decimal,
+    pub sell_price: Decimal,
+    /// Difference between the sell and the buy price, in percent of the buy price
+    pub spread: Decimal,
+    /// Spread left after the taker fees of both sides, in percent
+    pub net_spread: Decimal,
+    /// Volume available at both prices
+    pub volume: Decimal,
+    /// Net profit of trading `volume`, in the quote asset
+    pub profit: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PairComparison {
+    pub base: String,
+    pub quote: String,
+    pub time: Timestamp,
+    pub quotes: Vec<PairQuote>,
+    /// Exchanges listing the pair which failed to answer, with the error
+    pub errors: Vec<(String, String)>,
+    /// Opportunity with the highest net spread, even a negative one
+    pub best: Option<ArbitrageOpportunity>,
+}
+
+impl ArbitrageOpportunity {
+    pub fn new(buy: &PairQuote, sell: &PairQuote) -> Option<Self> {
+        let spread = ((sell.bid - buy.ask) * Decimal::ONE_HUNDRED).checked_div(buy.ask, 4)?;
+        let net_spread = spread - buy.fee - sell.fee;
+        let volume = buy.ask_volume.min(sell.bid_volume);
+        Some(Self {
+            buy: buy.market.clone(),
+            sell: sell.market.clone(),
+            buy_price: buy.ask,
+            sell_price: sell.bid,
+            spread,
+            net_spread,
+            volume,
+            profit: (buy.ask * volume * net_spread).checked_div(Decimal::ONE_HUNDRED, 8)?,
+        })
+    }
+
+    pub fn pair(&self) -> String {
+        self.buy.pair_name()
+    }
+}
+
+impl PairComparison {
+    fn best_opportunity(quotes: &[PairQuote]) -> Option<ArbitrageOpportunity> {
+        quotes
+            .iter()
+            .cartesian_product(quotes.iter())
+            .filter(|(buy, sell)| buy.market.exchange_name != sell.market.exchange_name)
+            .filter_map(|(buy, sell)| ArbitrageOpportunity::new(buy, sell))
+            .max_by(|a, b| a.net_spread.cmp(&b.net_spread))
+    }
+}
+
+/// Periodic comparison of pairs across the exchanges, see `Reactor::start_arbitrage`
+#[derive(Debug, Clone)]
+pub struct ArbitragePolicy {
+    /// Canonical `(base, quote)` of the scanned pairs
+    pub pairs: Vec<(String, String)>,
+    pub every: Duration,
+    /// Net spread, in percent, from which an alert is sent
+    pub threshold: Decimal,
+    pub sinks: Vec<AlertSink>,
+}
+
+impl Reactor {
+    async fn pair_quote(exchange: &SyncExchange, market: &MarketIdentifier) -> Result<PairQuote> {
+        let book = exchange.get_order_book(market, 1).await?;
+        let definition = exchange.get_market_definition(market, None).await?;
+        let (bid, ask) = match (book.best_bid(), book.best_ask()) {
+            (Some(bid), Some(ask)) => (bid, ask),
+            _ => return Err(Error::NoData),
+        };
+        Ok(PairQuote {
+            market: market.clone(),
+            bid: bid.price,
+            ask: ask.price,
+            bid_volume: bid.volume,
+            ask_volume: ask.volume,
+            fee: definition.taker_fee()?,
+        })
+    }
+
+    /// Quote `base/quote` (any alias of the assets) on every exchange listing it
+    pub async fn compare_pair(&self, base: &str, quote: &str) -> Result<PairComparison> {
+        let target = MarketIdentifier {
+            exchange_name: String::new(),
+            base: self.assets.canonical("", base),
+            quote: self.assets.canonical("", quote),
+        };
+        let exchanges: Vec<(String, SyncExchange)> = self
+            .exchanges
+            .read()
+            .await
+            .iter()
+            .map(|(name, exchange)| (name.clone(), exchange.clone()))
+            .collect();
+        let jobs = exchanges.iter().map(|(name, exchange)| {
+            let target = &target;
+            async move {
+                let quote: Result<Option<PairQuote>> = async {
+                    let market = exchange
+                        .get_markets()
+                        .await?
+                        .into_iter()
+                        .find(|e| self.assets.same_pair(e, target));
+                    match market {
+                        Some(market) => Self::pair_quote(exchange, &market).await.map(Some),
+                        None => Ok(None),
+                    }
+                }
+                .await;
+                quote.map_err(|e| (name.clone(), e.to_string()))
+            }
+        });
+        let mut quotes = Vec::new();
+        let mut errors = Vec::new();
+        for result in futures::future::join_all(jobs).await {
+            match result {
+                Ok(Some(quote)) => quotes.push(quote),
+                Ok(None) => {}
+                Err(e) => errors.push(e),
+            }
+        }
+        quotes.sort_by(|a, b| a.market.exchange_name.cmp(&b.market.exchange_name));
+        if quotes.is_empty() && errors.is_empty() {
+            return Err(Error::Parsing(
+                format!("No exchange lists {}", target.pair_name()),
+                0..0,
+            ));
+        }
+        Ok(PairComparison {
+            base: target.base,
+            quote: target.quote,
+            time: crate::store::now(),
+            best: PairComparison::best_opportunity(&quotes),
+            quotes,
+            errors,
+        })
+    }
+
+    pub fn start_arbitrage(&self, policy: ArbitragePolicy) {
+        log::info!(
+            "Scanning arbitrage: PAIRS={}, EVERY={:?}, THRESHOLD={}%",
+            policy.pairs.iter().map(|(base, quote)| format!("{}/{}", base, quote)).join(","),
+            policy.every,
+            policy.threshold
+        );
+        tokio::spawn(Self::arbitrage_handler(self.clone(), policy));
+    }
+
+    async fn arbitrage_handler(reactor: Reactor, policy: ArbitragePolicy) {
+        // Pairs above the threshold at the last scan, like alerts they only fire when crossing it
+        let mut active: HashSet<String> = HashSet::new();
+        loop {
+            for (base, quote) in policy.pairs.iter() {
+                let pair = format!("{}/{}", base, quote);
+                let comparison = match reactor.compare_pair(base, quote).await {
+                    Ok(comparison) => comparison,
+                    Err(e) => {
+                        error!("Failed to compare pair: PAIR={}, ERROR={}", pair, e);
+                        continue;
+                    }
+                };
+                match comparison.best.filter(|e| e.net_spread >= policy.threshold) {
+                    Some(opportunity) if active.insert(pair.clone()) => {
+                        reactor.notify_arbitrage(&policy, opportunity).await;
+                    }
+                    Some(_) => {}
+                    None => {
+                        active.remove(&pair);
+                    }
+                }
+            }
+            tokio::time::sleep(policy.every).await;
+        }
+    }
+
+    async fn notify_arbitrage(&self, policy: &ArbitragePolicy, opportunity: ArbitrageOpportunity) {
+        let notification = AlertNotification {
+            alert: String::from("arbitrage"),
+            market: opportunity.pair(),
+            interval: None,
+            time: crate::store::now(),
+            value: opportunity.net_spread,
+            message: format!(
+                "arbitrage {}: buy on {} at {}, sell on {} at {}, net spread {}% on {}",
+                opportunity.pair(),
+                opportunity.buy.exchange_name,
+                opportunity.buy_price,
+                opportunity.sell.exchange_name,
+                opportunity.sell_price,
+                opportunity.net_spread,
+                opportunity.volume
+            ),
+        };
+        info!("Alert triggered: {}", notification.message);
+        self.listeners
+            .broadcast(ReactorEvent::ArbitrageDetected { opportunity })
+            .await;
+        for sink in policy.sinks.iter() {
+            if let Err(e) = sink.deliver(self, &notification).await {
+                error!(
+                    "Failed to deliver alert: NAME={}, SINK={}, ERROR={}",
+                    notification.alert, sink, e
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mock(name: &str, base: &str, price: &str, volume: &str) -> SyncExchange {
+        MockExchange::new(
+            name.to_string(),
+            vec![MockMarket {
+                base: base.to_string(),
+                quote: String::from("EUR"),
+                price: price.parse().unwrap(),
+                spread: Decimal::ZERO,
+                volume: volume.parse().unwrap(),
+                fee: 0.1,
+            }],
+        )
+        .shared()
+    }
+
+    /// Buying at 100 on `cheap` and selling at 110 on `dear`, which names bitcoin `XBT`
+    async fn reactor() -> Reactor {
+        let path = std::env::temp_dir().join(format!("pkbot-arbitrage-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        let reactor = Reactor::new(Store::new(path).unwrap().handle()).await;
+        reactor.register_exchange(mock("cheap", "BTC", "100", "2")).await;
+        reactor.register_exchange(mock("dear", "XBT", "110", "3")).await;
+        reactor
+    }
+
+    #[tokio::test]
+    async fn compare_mock_exchanges() {
+        let reactor = reactor().await;
+        let comparison = reactor.compare_pair("XBT", "EUR").await.unwrap();
+        assert_eq!(comparison.quotes.len(), 2);
+        assert!(comparison.errors.is_empty());
+        let best = comparison.best.unwrap();
+        assert_eq!(best.buy.exchange_name, "cheap");
+        assert_eq!(best.sell.exchange_name, "dear");
+        assert_eq!(best.buy_price, "100".parse().unwrap());
+        assert_eq!(best.sell_price, "110".parse().unwrap());
+        assert_eq!(best.spread, "10".parse().unwrap());
+        // Less the taker fees of both exchanges
+        assert_eq!(best.net_spread, "9.8".parse().unwrap());
+        assert_eq!(best.volume, "2".parse().unwrap());
+        assert_eq!(best.profit, "19.6".parse().unwrap());
+
+        // The alert only fires when the spread crosses the threshold, not on every scan
+        let mut listener = reactor.event_listener().await;
+        reactor.start_arbitrage(ArbitragePolicy {
+            pairs: vec![(String::from("BTC"), String::from("EUR"))],
+            every: Duration::from_millis(10),
+            threshold: "1".parse().unwrap(),
+            sinks: vec![AlertSink::EventBus],
+        });
+        let (mut detected, mut triggered) = (0, 0);
+        while let Ok(Some(event)) =
+            tokio::time::timeout(Duration::from_millis(300), listener.recv()).await
+        {
+            match event {
+                ReactorEvent::ArbitrageDetected { opportunity } => {
+                    assert_eq!(opportunity.net_spread, "9.8".parse().unwrap());
+                    detected += 1;
+                }
+                ReactorEvent::AlertTriggered { notification } => {
+                    assert_eq!(notification.alert, "arbitrage");
+                    assert_eq!(notification.market, "BTC/EUR");
+                    triggered += 1;
+                }
+                _ => {}
+            }
+        }
+        assert_eq!((detected, triggered), (1, 1));
+    }
+}
@@ -0,0 +1,222 @@
+use super::*;
+use std::time::Instant;
+
+/// State of the backfill of one market interval, sent after every page and once done
+#[derive(Debug, Clone, Serialize)]
+pub struct BackfillProgress {
+    pub market: MarketIdentifier,
+    pub interval: Interval,
+    /// Where the download started, after the last stored candle when resuming
+    pub from: Timestamp,
+    pub to: Timestamp,
+    /// Time of the last fetched candle
+    pub reached: Timestamp,
+    pub fetched: usize,
+    pub eta_secs: Option<u64>,
+    /// Range the exchange didn't serve, Kraken only returns the 720 most recent candles
+    pub gap: Option<(Timestamp, Timestamp)>,
+    pub done: bool,
+    pub error: Option<String>,
+}
+
+impl BackfillProgress {
+    pub fn new(market: MarketIdentifier, interval: Interval, from: Timestamp, to: Timestamp) -> Self {
+        Self {
+            market,
+            interval,
+            from,
+            to,
+            reached: from,
+            fetched: 0,
+            eta_secs: None,
+            gap: None,
+            done: false,
+            error: None,
+        }
+    }
+
+    pub fn ratio(&self) -> f64 {
+        if self.done || self.to <= self.from {
+            return 1.0;
+        }
+        ((self.reached - self.from) as f64 / (self.to - self.from) as f64).clamp(0.0, 1.0)
+    }
+
+    /// Estimate the remaining time from the pace since `began`
+    fn update_eta(&mut self, began: Instant) {
+        let covered = (self.reached - self.from) as f64;
+        self.eta_secs = if covered > 0.0 {
+            let remaining = (self.to - self.reached).max(0) as f64;
+            Some((began.elapsed().as_secs_f64() * remaining / covered).round() as u64)
+        } else {
+            None
+        };
+    }
+
+    /// One line progress bar, e.g. `kraken/XXBT/ZEUR 1h [#####-----]  50% 360 candles ETA 12s`
+    pub fn bar(&self, width: usize) -> String {
+        let filled = (self.ratio() * width as f64).round() as usize;
+        let status = match (&self.error, self.done, self.eta_secs) {
+            (Some(e), _, _) => format!("FAILED {}", e),
+            (None, true, _) => String::from("done"),
+            (None, false, Some(eta)) => format!("ETA {}s", eta),
+            (None, false, None) => String::new(),
+        };
+        format!(
+            "{}/{}/{} {} [{}{}] {:>3}% {} candles {}",
+            self.market.exchange_name,
+            self.market.base,
+            self.market.quote,
+            self.interval,
+            "#".repeat(filled),
+            "-".repeat(width - filled),
+            (self.ratio() * 100.0).floor() as u64,
+            self.fetched,
+            status
+        )
+    }
+}
+
+impl SyncMarket {
+    /// Download the candles of `from..to` page by page, resuming after the last stored candle
+    /// when the store already covers `from`. Failures are reported in the returned progress
+    pub async fn backfill(
+        &self,
+        from: Timestamp,
+        to: Timestamp,
+        interval: Interval,
+        progress: &Sender<BackfillProgress>,
+    ) -> BackfillProgress {
+        let _permit = self.sync_limit.acquire().await;
+        let mut state = BackfillProgress::new(self.store.id.clone(), interval, from, to);
+        if let Err(e) = self.backfill_pages(&mut state, progress).await {
+            log::error!(
+                "Backfill failed: EXCHANGE={}, BASE={}, QUOTE={}, INTERVAL={}, ERROR={}",
+                &self.store.id.exchange_name,
+                &self.store.id.base,
+                &self.store.id.quote,
+                interval,
+                e
+            );
+            state.error = Some(e.to_string());
+        }
+        state.done = true;
+        state.eta_secs = Some(0);
+        let _ = progress.send(state.clone()).await;
+        if state.fetched > 0 {
+            self.listeners
+                .broadcast(ReactorEvent::MarketSynced {
+                    market: self.store.id.clone(),
+                    interval,
+                    begin: state.from,
+                    end: state.reached,
+                })
+                .await;
+        }
+        state
+    }
+
+    async fn backfill_pages(
+        &self,
+        state: &mut BackfillProgress,
+        progress: &Sender<BackfillProgress>,
+    ) -> Result<()> {
+        let tree = self.interval(state.interval).await?;
+        if let (Some(first), Some(last)) = (tree.first_ohlc()?, tree.last_ohlc()?) {
+            if (first.time <= state.from || first.first_available) && last.time > state.from {
+                state.from = last.time;
+                state.reached = last.time;
+            }
+        }
+        log::info!(
+            "Backfill started: EXCHANGE={}, BASE={}, QUOTE={}, INTERVAL={}, FROM={}, TO={}",
+            &self.store.id.exchange_name,
+            &self.store.id.base,
+            &self.store.id.quote,
+            state.interval,
+            state.from,
+            state.to
+        );
+        let began = Instant::now();
+        let mut since = state.from;
+        while since < state.to {
+            let mut page = self
+                .exchange
+                .get_ohlc_page(&self.store.id, since, state.interval)
+                .await?;
+            if since == 0 {
+                if let Some(first) = page.first_mut() {
+                    first.first_available = true;
+                }
+            } else if since == state.from {
+                // Only the first page can start late, the next ones start at the last candle
+                state.gap = page
+                    .first()
+                    .map(|e| (since, e.time))
+                    .filter(|(_, first)| *first > since + state.interval.as_secs());
+            }
+            page.retain(|e| e.time <= state.to);
+            let last = page.last().map(|e| e.time);
+            state.fetched += page.len();
+            tree.extend(page)?;
+            match last {
+                Some(last) if last > since => since = last,
+                _ => break,
+            }
+            state.reached = since;
+            state.update_eta(began);
+            let _ = progress.send(state.clone()).await;
+        }
+        if let Some((begin, end)) = state.gap {
+            return Err(Error::ExchangeResponse(format!(
+                "no candles served between {} and {}, they can't be backfilled",
+                begin, end
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Reactor {
+    /// Backfill every target in parallel (within `sync_limit`), progress is broadcast as
+    /// `ReactorEvent::BackfillProgress` and forwarded to `progress` when given
+    pub async fn backfill(
+        &self,
+        targets: Vec<(MarketIdentifier, Interval)>,
+        from: Timestamp,
+        to: Timestamp,
+        progress: Option<Sender<BackfillProgress>>,
+    ) -> Vec<BackfillProgress> {
+        let (sender, mut receiver) = channel::<BackfillProgress>(64);
+        let listeners = self.listeners.clone();
+        let forwarder = tokio::spawn(async move {
+            while let Some(state) = receiver.recv().await {
+                if let Some(progress) = progress.as_ref() {
+                    let _ = progress.send(state.clone()).await;
+                }
+                listeners
+                    .broadcast(ReactorEvent::BackfillProgress { progress: state })
+                    .await;
+            }
+        });
+        let jobs = targets.into_iter().map(|(market, interval)| {
+            let sender = sender.clone();
+            async move {
+                match self.get_or_register_market(&market).await {
+                    Ok(synced) => synced.backfill(from, to, interval, &sender).await,
+                    Err(e) => {
+                        let mut state = BackfillProgress::new(market, interval, from, to);
+                        state.error = Some(e.to_string());
+                        state.done = true;
+                        let _ = sender.send(state.clone()).await;
+                        state
+                    }
+                }
+            }
+        });
+        let reports = futures::future::join_all(jobs).await;
+        drop(sender);
+        let _ = forwarder.await;
+        reports
+    }
+}
@@ -0,0 +1,61 @@
+use super::*;
+use crate::config::BackupPolicy;
+
+const BACKUP_PREFIX: &str = "pkbot-";
+const BACKUP_SUFFIX: &str = ".bak.gz";
+
+impl Reactor {
+    /// Periodically snapshot the store to `policy.dir`, keeping the `policy.keep` most recent backups
+    pub fn start_backups(&self, policy: BackupPolicy) {
+        log::info!(
+            "Scheduled backups: DIR={}, EVERY={:?}, KEEP={}",
+            policy.dir.display(),
+            policy.every,
+            policy.keep
+        );
+        tokio::spawn(Self::backup_handler(self.clone(), policy));
+    }
+
+    async fn backup_handler(reactor: Reactor, policy: BackupPolicy) {
+        loop {
+            tokio::time::sleep(policy.every).await;
+            let store = reactor.store.clone();
+            let dir = policy.dir.clone();
+            let keep = policy.keep;
+            let done = tokio::task::spawn_blocking(move || -> Result<()> {
+                std::fs::create_dir_all(&dir)?;
+                let name = format!(
+                    "{}{}{}",
+                    BACKUP_PREFIX,
+                    chrono::Utc::now().format("%Y%m%d-%H%M%S"),
+                    BACKUP_SUFFIX
+                );
+                store.backup(&dir.join(name))?;
+                prune_backups(&dir, keep)
+            })
+            .await;
+            match done {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => log::error!("Scheduled backup failed: DIR={}, ERROR={}", policy.dir.display(), e),
+                Err(e) => log::error!("Scheduled backup panicked: DIR={}, ERROR={}", policy.dir.display(), e),
+            }
+        }
+    }
+}
+
+/// Remove the oldest scheduled backups, names sort by date
+fn prune_backups(dir: &Path, keep: usize) -> Result<()> {
+    let mut backups = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let name = entry?.file_name().to_string_lossy().to_string();
+        if name.starts_with(BACKUP_PREFIX) && name.ends_with(BACKUP_SUFFIX) {
+            backups.push(name);
+        }
+    }
+    backups.sort();
+    for name in backups.iter().take(backups.len().saturating_sub(keep)) {
+        log::info!("Pruning backup: PATH={}", dir.join(name).display());
+        std::fs::remove_file(dir.join(name))?;
+    }
+    Ok(())
+}
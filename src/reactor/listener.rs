@@ -0,0 +1,293 @@
+use super::*;
+use tokio::sync::Notify;
+
+pub type ListenerIdentifier = u64;
+
+pub const DEFAULT_LISTENER_CAPACITY: usize = 1024;
+
+/// What a broadcast does when a listener buffer is full.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BackpressurePolicy {
+    /// Evict the oldest buffered event, the listener receive a `ReactorEvent::Lagged`.
+    DropOldest,
+    /// Close the listener, it still receive the buffered events and then `None`.
+    Disconnect,
+    /// Wait until the listener drained its buffer (only for consumers that must not lose events).
+    Block,
+}
+
+struct ListenerBuffer {
+    queue: VecDeque<ReactorEvent>,
+    lagged: u64,
+    closed: bool,
+}
+
+pub struct ReactorEventListener {
+    capacity: usize,
+    policy: BackpressurePolicy,
+    buffer: std::sync::Mutex<ListenerBuffer>,
+    readable: Notify,
+    writable: Notify,
+}
+
+pub type ListenerMap = SyncMap<ListenerIdentifier, Arc<ReactorEventListener>>;
+
+pub struct ReactorEventListenerHandle {
+    id: ListenerIdentifier,
+    listener: Arc<ReactorEventListener>,
+}
+
+impl ReactorEventListener {
+    pub fn new(capacity: usize, policy: BackpressurePolicy) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            policy,
+            buffer: std::sync::Mutex::new(ListenerBuffer {
+                queue: VecDeque::with_capacity(capacity.max(1)),
+                lagged: 0,
+                closed: false,
+            }),
+            readable: Notify::new(),
+            writable: Notify::new(),
+        }
+    }
+
+    /// Returns `false` once the listener is closed and should be removed from the pool.
+    async fn push(&self, id: ListenerIdentifier, event: ReactorEvent) -> bool {
+        let mut event = Some(event);
+        loop {
+            let notified = self.writable.notified();
+            {
+                let mut buffer = self.buffer.lock().unwrap();
+                if buffer.closed {
+                    return false;
+                }
+                if buffer.queue.len() >= self.capacity {
+                    match self.policy {
+                        BackpressurePolicy::DropOldest => {
+                            buffer.queue.pop_front();
+                            buffer.lagged += 1;
+                        }
+                        BackpressurePolicy::Disconnect => {
+                            warn!(
+                                "Disconnecting slow listener: ID={}, CAPACITY={}",
+                                id, self.capacity
+                            );
+                            buffer.closed = true;
+                            drop(buffer);
+                            self.readable.notify_one();
+                            return false;
+                        }
+                        BackpressurePolicy::Block => {}
+                    }
+                }
+                if buffer.queue.len() < self.capacity {
+                    buffer.queue.push_back(event.take().unwrap());
+                    drop(buffer);
+                    self.readable.notify_one();
+                    return true;
+                }
+            }
+            notified.await;
+        }
+    }
+
+    fn close(&self) {
+        self.buffer.lock().unwrap().closed = true;
+        self.readable.notify_one();
+        self.writable.notify_waiters();
+    }
+}
+
+impl ReactorEventListenerHandle {
+    pub fn new(id: ListenerIdentifier, listener: Arc<ReactorEventListener>) -> Self {
+        Self { id, listener }
+    }
+
+    pub fn id(&self) -> ListenerIdentifier {
+        self.id
+    }
+
+    pub async fn recv(&mut self) -> Option<ReactorEvent> {
+        loop {
+            let notified = self.listener.readable.notified();
+            {
+                let mut buffer = self.listener.buffer.lock().unwrap();
+                if buffer.lagged > 0 {
+                    let skipped = std::mem::take(&mut buffer.lagged);
+                    return Some(ReactorEvent::Lagged { skipped });
+                }
+                if let Some(event) = buffer.queue.pop_front() {
+                    drop(buffer);
+                    self.listener.writable.notify_one();
+                    return Some(event);
+                }
+                if buffer.closed {
+                    return None;
+                }
+            }
+            notified.await;
+        }
+    }
+}
+
+impl Drop for ReactorEventListenerHandle {
+    fn drop(&mut self) {
+        // The pool prunes closed listeners on the next broadcast
+        self.listener.close();
+    }
+}
+
+#[async_trait]
+pub trait ListenerPool {
+    async fn broadcast(&self, event: ReactorEvent);
+}
+
+#[async_trait]
+impl ListenerPool for ListenerMap {
+    async fn broadcast(&self, event: ReactorEvent) {
+        // Never hold the pool lock while delivering, a blocking listener must not prevent
+        // others from registering or unregistering.
+        let listeners: Vec<_> = self
+            .read()
+            .await
+            .iter()
+            .map(|(id, listener)| (*id, listener.clone()))
+            .collect();
+        let mut closed = Vec::new();
+        for (id, listener) in listeners {
+            if !listener.push(id, event.clone()).await {
+                closed.push(id);
+            }
+        }
+        if !closed.is_empty() {
+            let mut lock = self.write().await;
+            for id in closed {
+                log::trace!("Removing closed listener: ID={}", id);
+                lock.remove(&id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(id: ProgramIdentifier) -> ReactorEvent {
+        ReactorEvent::RuntimeCreated { id }
+    }
+
+    async fn pool(
+        listeners: &[(usize, BackpressurePolicy)],
+    ) -> (ListenerMap, Vec<ReactorEventListenerHandle>) {
+        let pool = ListenerMap::default();
+        let mut handles = Vec::new();
+        for (id, (capacity, policy)) in listeners.iter().enumerate() {
+            let listener = Arc::new(ReactorEventListener::new(*capacity, *policy));
+            pool.write().await.insert(id as u64, listener.clone());
+            handles.push(ReactorEventListenerHandle::new(id as u64, listener));
+        }
+        (pool, handles)
+    }
+
+    async fn recv(handle: &mut ReactorEventListenerHandle) -> Option<ReactorEvent> {
+        tokio::time::timeout(Duration::from_secs(5), handle.recv())
+            .await
+            .expect("no event received")
+    }
+
+    fn created(event: Option<ReactorEvent>) -> ProgramIdentifier {
+        match event {
+            Some(ReactorEvent::RuntimeCreated { id }) => id,
+            other => panic!("expected RuntimeCreated, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn drop_oldest_reports_lag() {
+        let (pool, mut handles) = pool(&[(2, BackpressurePolicy::DropOldest)]).await;
+        for id in 0..5 {
+            pool.broadcast(event(id)).await;
+        }
+        let handle = &mut handles[0];
+        match recv(handle).await {
+            Some(ReactorEvent::Lagged { skipped }) => assert_eq!(skipped, 3),
+            other => panic!("expected Lagged, got {:?}", other),
+        }
+        assert_eq!(created(recv(handle).await), 3);
+        assert_eq!(created(recv(handle).await), 4);
+        // Nothing was dropped since the last lag report
+        pool.broadcast(event(5)).await;
+        assert_eq!(created(recv(handle).await), 5);
+    }
+
+    #[tokio::test]
+    async fn disconnect_closes_full_listener() {
+        let (pool, mut handles) = pool(&[(2, BackpressurePolicy::Disconnect)]).await;
+        for id in 0..3 {
+            pool.broadcast(event(id)).await;
+        }
+        assert!(pool.read().await.is_empty());
+        let handle = &mut handles[0];
+        assert_eq!(created(recv(handle).await), 0);
+        assert_eq!(created(recv(handle).await), 1);
+        assert!(recv(handle).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn block_waits_for_the_consumer() {
+        let (pool, mut handles) = pool(&[(1, BackpressurePolicy::Block)]).await;
+        pool.broadcast(event(0)).await;
+        let delivered = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let blocked = tokio::spawn({
+            let pool = pool.clone();
+            let delivered = delivered.clone();
+            async move {
+                pool.broadcast(event(1)).await;
+                delivered.store(true, Ordering::SeqCst);
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!delivered.load(Ordering::SeqCst));
+        let handle = &mut handles[0];
+        assert_eq!(created(recv(handle).await), 0);
+        tokio::time::timeout(Duration::from_secs(5), blocked)
+            .await
+            .expect("broadcast still blocked")
+            .unwrap();
+        assert_eq!(created(recv(handle).await), 1);
+    }
+
+    #[tokio::test]
+    async fn slow_consumer_does_not_stall_the_others() {
+        let (pool, mut handles) = pool(&[
+            (1, BackpressurePolicy::DropOldest),
+            (16, BackpressurePolicy::DropOldest),
+            (1, BackpressurePolicy::Disconnect),
+        ])
+        .await;
+        // Nobody reads the first and last listeners
+        tokio::time::timeout(Duration::from_secs(5), async {
+            for id in 0..10 {
+                pool.broadcast(event(id)).await;
+            }
+        })
+        .await
+        .expect("broadcast stalled by a slow listener");
+        for id in 0..10 {
+            assert_eq!(created(recv(&mut handles[1]).await), id);
+        }
+        assert_eq!(pool.read().await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn dropped_handle_is_pruned() {
+        let (pool, mut handles) =
+            pool(&[(4, BackpressurePolicy::Block), (4, BackpressurePolicy::Block)]).await;
+        drop(handles.remove(0));
+        pool.broadcast(event(0)).await;
+        assert_eq!(pool.read().await.len(), 1);
+        assert_eq!(created(recv(&mut handles[0]).await), 0);
+    }
+}
@@ -0,0 +1,725 @@
+use super::*;
+use std::collections::BTreeMap;
+
+/// Scale of unit costs and converted values
+const PORTFOLIO_SCALE: u32 = 8;
+/// Quote asset of the reports when none is given
+pub const DEFAULT_PORTFOLIO_QUOTE: &str = "EUR";
+
+/// How the cost of the volume sold is computed
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CostMethod {
+    /// The oldest lots are sold first
+    Fifo,
+    /// Every lot costs the average price paid
+    Average,
+}
+
+impl std::str::FromStr for CostMethod {
+    type Err = Error;
+
+    fn from_str(raw: &str) -> Result<Self> {
+        match raw {
+            "fifo" => Ok(CostMethod::Fifo),
+            "average" | "avg" => Ok(CostMethod::Average),
+            _ => Err(Error::Parsing(
+                format!(
+                    "Wrong cost method `{}`, expected one of: fifo, average",
+                    raw
+                ),
+                0..0,
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for CostMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CostMethod::Fifo => write!(f, "fifo"),
+            CostMethod::Average => write!(f, "average"),
+        }
+    }
+}
+
+/// Open lots of a pair as `(volume, unit cost)`, in the quote asset of the pair
+#[derive(Debug, Default)]
+struct LotBook {
+    lots: VecDeque<(Decimal, Decimal)>,
+    realized: Decimal,
+}
+
+impl LotBook {
+    fn buy(&mut self, method: CostMethod, volume: Decimal, cost: Decimal) {
+        let unit_cost = match cost.checked_div(volume, PORTFOLIO_SCALE) {
+            Some(unit_cost) => unit_cost,
+            None => return,
+        };
+        match method {
+            CostMethod::Fifo => self.lots.push_back((volume, unit_cost)),
+            CostMethod::Average => {
+                let total_volume = self.volume() + volume;
+                let total_cost = self.cost() + cost;
+                self.lots.clear();
+                if let Some(unit_cost) = total_cost.checked_div(total_volume, PORTFOLIO_SCALE) {
+                    self.lots.push_back((total_volume, unit_cost));
+                }
+            }
+        }
+    }
+
+    /// Only the volume covered by the recorded lots is realized, returns the volume sold beyond
+    /// them (bought before the history) whose cost is unknown
+    fn sell(&mut self, volume: Decimal, proceeds: Decimal) -> Decimal {
+        let mut left = volume;
+        let mut cost = Decimal::ZERO;
+        while left > Decimal::ZERO {
+            let (lot_volume, unit_cost) = match self.lots.front_mut() {
+                Some(lot) => lot,
+                None => break,
+            };
+            let taken = left.min(*lot_volume);
+            cost += taken * *unit_cost;
+            left -= taken;
+            *lot_volume -= taken;
+            if lot_volume.is_zero() {
+                self.lots.pop_front();
+            }
+        }
+        let matched = volume - left;
+        if let Some(matched_proceeds) = (proceeds * matched).checked_div(volume, PORTFOLIO_SCALE) {
+            self.realized += matched_proceeds - cost;
+        }
+        left
+    }
+
+    /// Book a fill, returns the error of a sell beyond the recorded lots
+    fn book(&mut self, method: CostMethod, fill: &Fill) -> Option<String> {
+        match fill.side {
+            TradeSide::Buy => {
+                self.buy(method, fill.volume, fill.price * fill.volume + fill.fee);
+                None
+            }
+            TradeSide::Sell => {
+                let oversold = self.sell(fill.volume, fill.price * fill.volume - fill.fee);
+                (!oversold.is_zero()).then(|| {
+                    format!(
+                        "{}: fill {} sells {} more than the recorded buys, left out of the P&L",
+                        fill.market, fill.id, oversold
+                    )
+                })
+            }
+        }
+    }
+
+    fn volume(&self) -> Decimal {
+        self.lots.iter().map(|(volume, _)| *volume).sum()
+    }
+
+    fn cost(&self) -> Decimal {
+        self.lots.iter().map(|(volume, cost)| *volume * *cost).sum()
+    }
+}
+
+/// Position on a pair built from the fills, amounts in the quote asset of the pair
+#[derive(Debug, Clone, Serialize)]
+pub struct PositionReport {
+    pub pair: String,
+    pub volume: Decimal,
+    pub cost: Decimal,
+    pub average_cost: Option<Decimal>,
+    pub price: Option<Decimal>,
+    pub realized: Decimal,
+    pub unrealized: Option<Decimal>,
+}
+
+/// An asset held on an exchange, valued in the quote asset of the report
+#[derive(Debug, Clone, Serialize)]
+pub struct HoldingReport {
+    pub exchange: String,
+    pub asset: String,
+    pub volume: Decimal,
+    pub price: Option<Decimal>,
+    pub value: Option<Decimal>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PortfolioReport {
+    pub time: Timestamp,
+    pub quote: String,
+    pub method: CostMethod,
+    pub holdings: Vec<HoldingReport>,
+    /// Value of the holdings which could be priced
+    pub value: Decimal,
+    pub positions: Vec<PositionReport>,
+    /// Realized and unrealized P&L of the positions, converted at the current rates
+    pub realized: Decimal,
+    pub unrealized: Decimal,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EquityPoint {
+    pub time: Timestamp,
+    pub value: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EquityCurve {
+    pub quote: String,
+    pub interval: Interval,
+    pub points: Vec<EquityPoint>,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FillImport {
+    pub imported: usize,
+    pub errors: Vec<String>,
+}
+
+/// Markets of every exchange, to price an asset in another
+struct PriceSources {
+    markets: Vec<(SyncExchange, MarketIdentifier)>,
+    /// Current prices already fetched, keyed by `ASSET/QUOTE`
+    prices: HashMap<String, Option<Decimal>>,
+}
+
+impl PriceSources {
+    /// Market trading `asset` against `quote`, or the inverse pair (`true`), preferably on
+    /// `exchange`
+    fn find(
+        &self,
+        asset: &str,
+        quote: &str,
+        exchange: Option<&str>,
+    ) -> Option<(SyncExchange, MarketIdentifier, bool)> {
+        let candidates = self.markets.iter().filter_map(|(source, market)| {
+            if market.base == asset && market.quote == quote {
+                Some((source, market, false))
+            } else if market.base == quote && market.quote == asset {
+                Some((source, market, true))
+            } else {
+                None
+            }
+        });
+        candidates
+            .min_by_key(|(_, market, inverse)| {
+                (Some(market.exchange_name.as_str()) != exchange, *inverse)
+            })
+            .map(|(source, market, inverse)| (source.clone(), market.clone(), inverse))
+    }
+
+    /// Mid price of `asset` in `quote`
+    async fn price(
+        &mut self,
+        asset: &str,
+        quote: &str,
+        exchange: Option<&str>,
+    ) -> Result<Option<Decimal>> {
+        if asset == quote {
+            return Ok(Some(Decimal::ONE));
+        }
+        let key = format!("{}/{}", asset, quote);
+        if let Some(price) = self.prices.get(&key) {
+            return Ok(*price);
+        }
+        let price = match self.find(asset, quote, exchange) {
+            Some((source, market, inverse)) => {
+                let mid = source
+                    .get_order_book(&market, 1)
+                    .await?
+                    .mid_price()
+                    .ok_or(Error::NoData)?;
+                match inverse {
+                    true => Decimal::ONE.checked_div(mid, PORTFOLIO_SCALE),
+                    false => Some(mid),
+                }
+            }
+            None => None,
+        };
+        self.prices.insert(key, price);
+        Ok(price)
+    }
+}
+
+/// Signed volume of each asset moved by a fill, fees are paid in the quote asset
+fn fill_flows(fill: &Fill) -> [(String, Decimal); 2] {
+    let market = fill.market_id();
+    let notional = fill.price * fill.volume;
+    match fill.side {
+        TradeSide::Buy => [
+            (market.base, fill.volume),
+            (market.quote, -(notional + fill.fee)),
+        ],
+        TradeSide::Sell => [
+            (market.base, -fill.volume),
+            (market.quote, notional - fill.fee),
+        ],
+    }
+}
+
+/// Balances may carry a suffix (`DOT.S` for staked DOT), priced as the asset itself
+fn priced_asset(asset: &str) -> &str {
+    asset.split('.').next().unwrap_or(asset)
+}
+
+/// Realized P&L of the fills made from `since`, by quote asset, and the sells beyond the
+/// recorded buys
+pub fn realized_since(
+    fills: &[Fill],
+    method: CostMethod,
+    since: Timestamp,
+) -> (BTreeMap<String, Decimal>, Vec<String>) {
+    let mut books: HashMap<(String, String), LotBook> = HashMap::new();
+    let mut ret = BTreeMap::new();
+    let mut oversold = Vec::new();
+    for fill in fills.iter() {
+        let market = fill.market_id();
+        let book = books
+            .entry((market.base, market.quote.clone()))
+            .or_default();
+        let before = book.realized;
+        oversold.extend(book.book(method, fill));
+        if fill.time >= since {
+            *ret.entry(market.quote).or_insert(Decimal::ZERO) += book.realized - before;
+        }
+    }
+    (ret, oversold)
+}
+
+impl Reactor {
+    async fn price_sources(&self, errors: &mut Vec<String>) -> PriceSources {
+        let exchanges: Vec<(String, SyncExchange)> = self
+            .exchanges
+            .read()
+            .await
+            .iter()
+            .map(|(name, exchange)| (name.clone(), exchange.clone()))
+            .collect();
+        let mut markets = Vec::new();
+        for (name, exchange) in exchanges {
+            match exchange.get_markets().await {
+                Ok(list) => markets.extend(
+                    list.iter()
+                        .map(|e| (exchange.clone(), self.assets.canonical_market(e))),
+                ),
+                Err(e) => errors.push(format!("{}: {}", name, e)),
+            }
+        }
+        PriceSources {
+            markets,
+            prices: HashMap::new(),
+        }
+    }
+
+    /// Balances of the exchanges with an account api, by exchange then asset
+    async fn exchange_balances(
+        &self,
+        errors: &mut Vec<String>,
+    ) -> BTreeMap<String, BTreeMap<String, Decimal>> {
+        let exchanges: Vec<(String, SyncExchange)> = self
+            .exchanges
+            .read()
+            .await
+            .iter()
+            .map(|(name, exchange)| (name.clone(), exchange.clone()))
+            .collect();
+        let mut ret = BTreeMap::new();
+        for (name, exchange) in exchanges {
+            match exchange.get_balances().await {
+                Ok(balances) => {
+                    ret.insert(
+                        name,
+                        balances.into_iter().filter(|(_, e)| !e.is_zero()).collect(),
+                    );
+                }
+                Err(Error::NotSupported(_)) => {}
+                Err(e) => errors.push(format!("{}: {}", name, e)),
+            }
+        }
+        ret
+    }
+
+    /// Holdings by exchange then asset: the balances when the exchange reports them, the sum
+    /// of the stored fills otherwise
+    fn holdings(
+        balances: &BTreeMap<String, BTreeMap<String, Decimal>>,
+        fills: &[Fill],
+    ) -> BTreeMap<String, BTreeMap<String, Decimal>> {
+        let mut ret = balances.clone();
+        for fill in fills.iter() {
+            let exchange = fill.market_id().exchange_name;
+            if balances.contains_key(&exchange) {
+                continue;
+            }
+            let assets = ret.entry(exchange).or_default();
+            for (asset, volume) in fill_flows(fill).iter() {
+                *assets.entry(asset.clone()).or_insert(Decimal::ZERO) += *volume;
+            }
+        }
+        ret
+    }
+
+    /// Current mid price of `asset` in `quote`, `None` when no market trades them
+    pub async fn price_of(&self, asset: &str, quote: &str) -> Result<Option<Decimal>> {
+        let mut errors = Vec::new();
+        let mut sources = self.price_sources(&mut errors).await;
+        let price = sources
+            .price(
+                &self.assets.canonical("", asset),
+                &self.assets.canonical("", quote),
+                None,
+            )
+            .await?;
+        match (price, errors.is_empty()) {
+            (None, false) => Err(Error::ExchangeResponse(errors.join(", "))),
+            (price, _) => Ok(price),
+        }
+    }
+
+    /// Volume of `asset` held across the exchanges, fails when a balance can't be read
+    pub async fn holding(&self, asset: &str) -> Result<Decimal> {
+        let asset = self.assets.canonical("", asset);
+        let mut errors = Vec::new();
+        let balances = self.exchange_balances(&mut errors).await;
+        if !errors.is_empty() {
+            return Err(Error::ExchangeResponse(errors.join(", ")));
+        }
+        let fills = self.store.fills.list()?;
+        Ok(Self::holdings(&balances, &fills)
+            .values()
+            .flat_map(|assets| assets.iter())
+            .filter(|(held, _)| priced_asset(held) == asset)
+            .map(|(_, volume)| *volume)
+            .sum())
+    }
+
+    /// Import the trades made since the last stored fill of each exchange
+    pub async fn import_fills(&self) -> Result<FillImport> {
+        let exchanges: Vec<(String, SyncExchange)> = self
+            .exchanges
+            .read()
+            .await
+            .iter()
+            .map(|(name, exchange)| (name.clone(), exchange.clone()))
+            .collect();
+        let mut imported = 0;
+        let mut errors = Vec::new();
+        for (name, exchange) in exchanges {
+            let since = self.store.fills.last_time(&name)?;
+            match exchange.get_fills(since).await {
+                Ok(fills) => {
+                    log::info!("Fills imported: EXCHANGE={}, COUNT={}", name, fills.len());
+                    imported += self.store.fills.extend(&fills)?;
+                }
+                Err(Error::NotSupported(_)) => {}
+                Err(e) => errors.push(format!("{}: {}", name, e)),
+            }
+        }
+        Ok(FillImport { imported, errors })
+    }
+
+    /// Value the holdings and the positions built from the stored fills in `quote`
+    pub async fn portfolio(&self, quote: &str, method: CostMethod) -> Result<PortfolioReport> {
+        let quote = self.assets.canonical("", quote);
+        let mut errors = Vec::new();
+        let fills = self.store.fills.list()?;
+        let balances = self.exchange_balances(&mut errors).await;
+        let mut sources = self.price_sources(&mut errors).await;
+
+        let mut holdings = Vec::new();
+        let mut value = Decimal::ZERO;
+        for (exchange, assets) in Self::holdings(&balances, &fills) {
+            for (asset, volume) in assets.into_iter().filter(|(_, e)| !e.is_zero()) {
+                let price = match sources
+                    .price(priced_asset(&asset), &quote, Some(&exchange))
+                    .await
+                {
+                    Ok(Some(price)) => Some(price),
+                    Ok(None) => {
+                        errors.push(format!("No market to value {} in {}", asset, quote));
+                        None
+                    }
+                    Err(e) => {
+                        errors.push(format!("{}/{}: {}", asset, quote, e));
+                        None
+                    }
+                };
+                let asset_value = price.map(|price| (price * volume).round_dp(PORTFOLIO_SCALE));
+                value += asset_value.unwrap_or(Decimal::ZERO);
+                holdings.push(HoldingReport {
+                    exchange: exchange.clone(),
+                    asset,
+                    volume,
+                    price,
+                    value: asset_value,
+                });
+            }
+        }
+
+        // Positions merge the fills of a pair across the exchanges
+        let mut books: BTreeMap<(String, String), (LotBook, String)> = BTreeMap::new();
+        for fill in fills.iter() {
+            let market = fill.market_id();
+            let (book, exchange) = books
+                .entry((market.base, market.quote))
+                .or_insert_with(|| (LotBook::default(), String::new()));
+            *exchange = market.exchange_name;
+            errors.extend(book.book(method, fill));
+        }
+        let mut positions = Vec::new();
+        let mut realized = Decimal::ZERO;
+        let mut unrealized = Decimal::ZERO;
+        for ((base, pair_quote), (book, exchange)) in books {
+            let pair = format!("{}/{}", base, pair_quote);
+            let volume = book.volume();
+            let cost = book.cost();
+            let price = match volume.is_zero() {
+                true => None,
+                false => sources
+                    .price(&base, &pair_quote, Some(&exchange))
+                    .await
+                    .unwrap_or_else(|e| {
+                        errors.push(format!("{}: {}", pair, e));
+                        None
+                    }),
+            };
+            let position_unrealized = price.map(|price| price * volume - cost);
+            match sources.price(&pair_quote, &quote, None).await {
+                Ok(Some(rate)) => {
+                    realized += (book.realized * rate).round_dp(PORTFOLIO_SCALE);
+                    unrealized += (position_unrealized.unwrap_or(Decimal::ZERO) * rate)
+                        .round_dp(PORTFOLIO_SCALE);
+                }
+                Ok(None) => {
+                    errors.push(format!("No market to convert {} in {}", pair_quote, quote))
+                }
+                Err(e) => errors.push(format!("{}/{}: {}", pair_quote, quote, e)),
+            }
+            positions.push(PositionReport {
+                pair,
+                volume,
+                cost: cost.round_dp(PORTFOLIO_SCALE),
+                average_cost: cost.checked_div(volume, PORTFOLIO_SCALE),
+                price,
+                realized: book.realized.round_dp(PORTFOLIO_SCALE),
+                unrealized: position_unrealized.map(|e| e.round_dp(PORTFOLIO_SCALE)),
+            });
+        }
+        errors.dedup();
+        Ok(PortfolioReport {
+            time: crate::store::now(),
+            quote,
+            method,
+            holdings,
+            value,
+            positions,
+            realized,
+            unrealized,
+            errors,
+        })
+    }
+
+    /// Value of the holdings at each `interval` between `from` and `to`, priced at the close of
+    /// the stored candles which are synchronized first. Holdings are rebuilt backward from the
+    /// current balances with the fills, from zero for exchanges without balances.
+    pub async fn equity_curve(
+        &self,
+        quote: &str,
+        from: Timestamp,
+        to: Timestamp,
+        interval: Interval,
+    ) -> Result<EquityCurve> {
+        let quote = self.assets.canonical("", quote);
+        let mut errors = Vec::new();
+        let fills = self.store.fills.list()?;
+        let balances = self.exchange_balances(&mut errors).await;
+        let sources = self.price_sources(&mut errors).await;
+
+        // Holdings before the first fill, by asset
+        let mut start: BTreeMap<String, Decimal> = BTreeMap::new();
+        for (exchange, assets) in balances.iter() {
+            for (asset, volume) in assets.iter() {
+                *start
+                    .entry(priced_asset(asset).to_string())
+                    .or_insert(Decimal::ZERO) += *volume;
+            }
+            for fill in fills
+                .iter()
+                .filter(|e| &e.market_id().exchange_name == exchange)
+            {
+                for (asset, volume) in fill_flows(fill).iter() {
+                    *start.entry(asset.clone()).or_insert(Decimal::ZERO) -= *volume;
+                }
+            }
+        }
+        let mut assets: Vec<String> = start.keys().cloned().collect();
+        for fill in fills.iter() {
+            for (asset, _) in fill_flows(fill).iter() {
+                if !assets.contains(asset) {
+                    assets.push(asset.clone());
+                }
+            }
+        }
+
+        let mut candles = HashMap::new();
+        for asset in assets.iter().filter(|e| **e != quote) {
+            let (_, market, inverse) = match sources.find(asset, &quote, None) {
+                Some(found) => found,
+                None => {
+                    errors.push(format!("No market to value {} in {}", asset, quote));
+                    continue;
+                }
+            };
+            let synced = async {
+                let sync = self.get_or_register_market(&market).await?;
+                sync.sync_periode(from, to, interval).await?;
+                sync.interval(interval).await
+            }
+            .await;
+            match synced {
+                Ok(handle) => {
+                    candles.insert(asset.clone(), (handle, inverse));
+                }
+                Err(e) => errors.push(format!(
+                    "{}/{}/{}: {}",
+                    market.exchange_name, market.base, market.quote, e
+                )),
+            }
+        }
+
+        let step = interval.as_secs();
+        let mut points = Vec::new();
+        let mut held = start;
+        let mut next_fill = fills.iter().peekable();
+        let mut time = from / step * step;
+        while time <= to {
+            while let Some(fill) = next_fill.peek().filter(|e| e.time <= time) {
+                for (asset, volume) in fill_flows(fill).iter() {
+                    *held.entry(asset.clone()).or_insert(Decimal::ZERO) += *volume;
+                }
+                next_fill.next();
+            }
+            let mut value = Decimal::ZERO;
+            for (asset, volume) in held.iter().filter(|(_, e)| !e.is_zero()) {
+                let price = if *asset == quote {
+                    Some(Decimal::ONE)
+                } else {
+                    match candles.get(asset) {
+                        Some((handle, inverse)) => {
+                            handle.prev_ohlc(time + 1)?.and_then(|e| match inverse {
+                                true => Decimal::ONE.checked_div(e.close, PORTFOLIO_SCALE),
+                                false => Some(e.close),
+                            })
+                        }
+                        None => None,
+                    }
+                };
+                value += price.map(|e| e * *volume).unwrap_or(Decimal::ZERO);
+            }
+            points.push(EquityPoint {
+                time,
+                value: value.round_dp(PORTFOLIO_SCALE),
+            });
+            time += step;
+        }
+        Ok(EquityCurve {
+            quote,
+            interval,
+            points,
+            errors,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(raw: &str) -> Decimal {
+        raw.parse().unwrap()
+    }
+
+    fn fill(
+        id: &str,
+        time: Timestamp,
+        side: TradeSide,
+        price: &str,
+        volume: &str,
+        fee: &str,
+    ) -> Fill {
+        Fill {
+            id: id.to_string(),
+            market: String::from("kraken/BTC/EUR"),
+            time,
+            side,
+            price: dec(price),
+            volume: dec(volume),
+            fee: dec(fee),
+            order: None,
+        }
+    }
+
+    #[test]
+    fn fifo_consumes_the_oldest_lots() {
+        let mut book = LotBook::default();
+        book.buy(CostMethod::Fifo, dec("1"), dec("100"));
+        book.buy(CostMethod::Fifo, dec("1"), dec("200"));
+        assert_eq!(book.sell(dec("1.5"), dec("450")), Decimal::ZERO);
+        // 1 at 100 then half a lot at 200
+        assert_eq!(book.realized, dec("250"));
+        assert_eq!(book.volume(), dec("0.5"));
+        assert_eq!(book.cost(), dec("100"));
+        assert_eq!(book.sell(dec("0.5"), dec("90")), Decimal::ZERO);
+        assert_eq!(book.realized, dec("240"));
+        assert!(book.lots.is_empty());
+    }
+
+    #[test]
+    fn average_merges_the_lots() {
+        let mut book = LotBook::default();
+        book.buy(CostMethod::Average, dec("1"), dec("100"));
+        book.buy(CostMethod::Average, dec("3"), dec("500"));
+        assert_eq!(book.volume(), dec("4"));
+        assert_eq!(book.cost(), dec("600"));
+        book.sell(dec("2"), dec("400"));
+        assert_eq!(book.realized, dec("100"));
+        assert_eq!(book.cost(), dec("300"));
+    }
+
+    #[test]
+    fn fees_count_in_the_cost_and_proceeds() {
+        let fills = vec![
+            fill("1", 100, TradeSide::Buy, "100", "1", "0.26"),
+            fill("2", 200, TradeSide::Sell, "110", "1", "0.286"),
+        ];
+        let (realized, oversold) = realized_since(&fills, CostMethod::Fifo, 0);
+        assert_eq!(realized.get("EUR"), Some(&dec("9.454")));
+        assert!(oversold.is_empty());
+        // Only the sell made from `since` is realized, its cost comes from the older buy
+        let (realized, _) = realized_since(&fills, CostMethod::Fifo, 150);
+        assert_eq!(realized.get("EUR"), Some(&dec("9.454")));
+        let (realized, _) = realized_since(&fills, CostMethod::Fifo, 250);
+        assert_eq!(realized.get("EUR"), None);
+    }
+
+    #[test]
+    fn oversell_is_reported_not_booked() {
+        let mut book = LotBook::default();
+        book.buy(CostMethod::Fifo, dec("1"), dec("100"));
+        assert_eq!(book.sell(dec("2"), dec("300")), dec("1"));
+        // Only the half covered by the lot is realized
+        assert_eq!(book.realized, dec("50"));
+
+        let fills = vec![
+            fill("1", 100, TradeSide::Buy, "100", "1", "0"),
+            fill("2", 200, TradeSide::Sell, "150", "2", "0"),
+        ];
+        let (realized, oversold) = realized_since(&fills, CostMethod::Fifo, 0);
+        assert_eq!(realized.get("EUR"), Some(&dec("50")));
+        assert_eq!(oversold.len(), 1);
+        assert!(oversold[0].contains("fill 2 sells 1 more"));
+    }
+}
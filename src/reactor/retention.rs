@@ -0,0 +1,18 @@
+use super::*;
+
+impl Reactor {
+    /// Periodically apply the retention rules of every stored market
+    pub fn start_compaction(&self, every: Duration) {
+        log::info!("Scheduled compaction: EVERY={:?}", every);
+        tokio::spawn(Self::compaction_handler(self.clone(), every));
+    }
+
+    async fn compaction_handler(reactor: Reactor, every: Duration) {
+        loop {
+            tokio::time::sleep(every).await;
+            if let Err(e) = reactor.store.compact().await {
+                log::error!("Compaction failed: ERROR={}", e);
+            }
+        }
+    }
+}
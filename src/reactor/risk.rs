@@ -0,0 +1,710 @@
+use super::*;
+use crate::store::KillSwitch;
+use std::time::Instant;
+
+/// Limits applied to every order before it is sent, unset limits are not checked
+#[derive(Debug, Clone)]
+pub struct RiskPolicy {
+    /// Asset in which notionals and losses are measured
+    pub quote: String,
+    pub max_order_notional: Option<Decimal>,
+    /// Largest volume held of each asset after a buy
+    pub max_position: HashMap<String, Decimal>,
+    /// Realized loss since 00:00 UTC from which only sells are accepted
+    pub daily_loss_limit: Option<Decimal>,
+    pub max_orders_per_minute: Option<usize>,
+    /// Cost method of the realized P&L
+    pub method: CostMethod,
+}
+
+impl Default for RiskPolicy {
+    fn default() -> Self {
+        Self {
+            quote: DEFAULT_PORTFOLIO_QUOTE.to_string(),
+            max_order_notional: None,
+            max_position: HashMap::new(),
+            daily_loss_limit: None,
+            max_orders_per_minute: None,
+            method: CostMethod::Fifo,
+        }
+    }
+}
+
+/// Why an order was rejected
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "check", rename_all = "snake_case")]
+pub enum RiskViolation {
+    KillSwitch {
+        reason: String,
+    },
+    MinVolume {
+        volume: Decimal,
+        min: Decimal,
+    },
+    LotPrecision {
+        volume: Decimal,
+        decimals: i32,
+    },
+    PricePrecision {
+        price: Decimal,
+        decimals: i32,
+    },
+    MissingPrice,
+    OrderRate {
+        orders: usize,
+        limit: usize,
+    },
+    OrderNotional {
+        notional: Decimal,
+        limit: Decimal,
+        quote: String,
+    },
+    Position {
+        asset: String,
+        position: Decimal,
+        limit: Decimal,
+    },
+    DailyLoss {
+        loss: Decimal,
+        limit: Decimal,
+        quote: String,
+    },
+    /// A limit couldn't be evaluated, orders are rejected rather than sent unchecked
+    Unavailable {
+        check: String,
+        reason: String,
+    },
+}
+
+impl std::fmt::Display for RiskViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RiskViolation::KillSwitch { reason } => {
+                write!(f, "kill switch engaged ({})", reason)
+            }
+            RiskViolation::MinVolume { volume, min } => {
+                write!(f, "volume {} below the minimum order of {}", volume, min)
+            }
+            RiskViolation::LotPrecision { volume, decimals } => {
+                write!(f, "volume {} has more than {} decimals", volume, decimals)
+            }
+            RiskViolation::PricePrecision { price, decimals } => {
+                write!(f, "price {} has more than {} decimals", price, decimals)
+            }
+            RiskViolation::MissingPrice => write!(f, "limit orders need a price"),
+            RiskViolation::OrderRate { orders, limit } => write!(
+                f,
+                "{} orders sent in the last minute, the limit is {}",
+                orders, limit
+            ),
+            RiskViolation::OrderNotional {
+                notional,
+                limit,
+                quote,
+            } => write!(
+                f,
+                "notional {} {} above the limit of {} {}",
+                notional, quote, limit, quote
+            ),
+            RiskViolation::Position {
+                asset,
+                position,
+                limit,
+            } => write!(
+                f,
+                "position of {} {} above the limit of {}",
+                position, asset, limit
+            ),
+            RiskViolation::DailyLoss { loss, limit, quote } => write!(
+                f,
+                "daily loss {} {} reached the limit of {} {}, only sells are accepted",
+                loss, quote, limit, quote
+            ),
+            RiskViolation::Unavailable { check, reason } => {
+                write!(f, "{} can't be checked: {}", check, reason)
+            }
+        }
+    }
+}
+
+/// State of the risk checks, see `Reactor::risk_status`
+#[derive(Debug, Clone, Serialize)]
+pub struct RiskStatus {
+    pub kill_switch: Option<KillSwitch>,
+    pub quote: String,
+    pub max_order_notional: Option<Decimal>,
+    pub max_position: HashMap<String, Decimal>,
+    pub daily_loss_limit: Option<Decimal>,
+    pub max_orders_per_minute: Option<usize>,
+    pub orders_last_minute: usize,
+    /// Realized P&L since 00:00 UTC, in `quote`
+    pub daily_realized: Option<Decimal>,
+}
+
+pub struct RiskEngine {
+    policy: RiskPolicy,
+    /// Send time of the orders of the last minute
+    sent: std::sync::Mutex<VecDeque<Instant>>,
+    /// Held from the checks to the send of an order, so concurrent orders see each other
+    submitting: Mutex<()>,
+}
+
+impl RiskEngine {
+    pub fn new(policy: RiskPolicy) -> Self {
+        Self {
+            policy,
+            sent: std::sync::Mutex::new(VecDeque::new()),
+            submitting: Mutex::new(()),
+        }
+    }
+
+    pub fn shared(self) -> Arc<Self> {
+        Arc::new(self)
+    }
+
+    pub fn policy(&self) -> &RiskPolicy {
+        &self.policy
+    }
+
+    fn orders_last_minute(&self) -> usize {
+        let mut sent = self.sent.lock().unwrap_or_else(|e| e.into_inner());
+        while sent
+            .front()
+            .map(|e| e.elapsed() >= Duration::from_secs(60))
+            .unwrap_or(false)
+        {
+            sent.pop_front();
+        }
+        sent.len()
+    }
+
+    fn record_sent(&self) {
+        self.sent
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push_back(Instant::now());
+    }
+}
+
+/// Unix time has no leap seconds, UTC days start at multiples of 86400
+fn start_of_day(time: Timestamp) -> Timestamp {
+    time - time.rem_euclid(24 * 60 * 60)
+}
+
+impl Reactor {
+    pub fn with_risk(self, risk: Arc<RiskEngine>) -> Self {
+        Self { risk, ..self }
+    }
+
+    /// Stop every order until `release_kill_switch`
+    pub async fn engage_kill_switch(&self, reason: &str) -> Result<KillSwitch> {
+        let kill_switch = KillSwitch {
+            reason: reason.to_string(),
+            engaged_at: crate::store::now(),
+        };
+        self.store.risk.engage_kill_switch(&kill_switch)?;
+        log::warn!("Kill switch engaged: REASON={}", reason);
+        self.listeners
+            .broadcast(ReactorEvent::KillSwitch {
+                engaged: true,
+                reason: Some(reason.to_string()),
+            })
+            .await;
+        Ok(kill_switch)
+    }
+
+    pub async fn release_kill_switch(&self) -> Result<bool> {
+        let released = self.store.risk.release_kill_switch()?;
+        if released {
+            log::warn!("Kill switch released");
+            self.listeners
+                .broadcast(ReactorEvent::KillSwitch {
+                    engaged: false,
+                    reason: None,
+                })
+                .await;
+        }
+        Ok(released)
+    }
+
+    /// Realized P&L since 00:00 UTC converted to the quote of the policy
+    async fn daily_realized(&self) -> Result<Decimal> {
+        let quote = &self.risk.policy.quote;
+        let since = start_of_day(crate::store::now());
+        let fills = self.store.fills.list()?;
+        let mut total = Decimal::ZERO;
+        let (realized, oversold) = realized_since(&fills, self.risk.policy.method, since);
+        for error in oversold.iter() {
+            log::warn!("Daily loss: {}", error);
+        }
+        for (asset, realized) in realized.into_iter().filter(|(_, e)| !e.is_zero()) {
+            let rate = self.price_of(&asset, quote).await?.ok_or_else(|| {
+                Error::ExchangeResponse(format!("no market to convert {} in {}", asset, quote))
+            })?;
+            total += realized * rate;
+        }
+        Ok(total)
+    }
+
+    pub async fn risk_status(&self) -> Result<RiskStatus> {
+        let policy = &self.risk.policy;
+        Ok(RiskStatus {
+            kill_switch: self.store.risk.kill_switch()?,
+            quote: policy.quote.clone(),
+            max_order_notional: policy.max_order_notional,
+            max_position: policy.max_position.clone(),
+            daily_loss_limit: policy.daily_loss_limit,
+            max_orders_per_minute: policy.max_orders_per_minute,
+            orders_last_minute: self.risk.orders_last_minute(),
+            daily_realized: self.daily_realized().await.ok(),
+        })
+    }
+
+    /// Run every check of the risk policy against an order, `definition` is the one of its
+    /// market
+    async fn risk_violation(
+        &self,
+        order: &OrderRequest,
+        definition: &MarketDefinition,
+        exchange: &SyncExchange,
+    ) -> Result<Option<RiskViolation>> {
+        let policy = &self.risk.policy;
+        let unavailable = |check: &str, e: Error| RiskViolation::Unavailable {
+            check: check.to_string(),
+            reason: e.to_string(),
+        };
+        if let Some(kill_switch) = self.store.risk.kill_switch()? {
+            return Ok(Some(RiskViolation::KillSwitch {
+                reason: kill_switch.reason,
+            }));
+        }
+
+        if definition.round_volume(order.volume) != order.volume {
+            return Ok(Some(RiskViolation::LotPrecision {
+                volume: order.volume,
+                decimals: definition.lot_decimals,
+            }));
+        }
+        if let Some(min) = definition.min_volume()? {
+            if order.volume < min {
+                return Ok(Some(RiskViolation::MinVolume {
+                    volume: order.volume,
+                    min,
+                }));
+            }
+        }
+        match (order.order_type, order.price) {
+            (TradeOrderType::Limit, None) => return Ok(Some(RiskViolation::MissingPrice)),
+            (_, Some(price)) if definition.round_price(price) != price => {
+                return Ok(Some(RiskViolation::PricePrecision {
+                    price,
+                    decimals: definition.pair_decimals,
+                }))
+            }
+            _ => {}
+        }
+
+        if let Some(limit) = policy.max_orders_per_minute {
+            let orders = self.risk.orders_last_minute();
+            if orders >= limit {
+                return Ok(Some(RiskViolation::OrderRate { orders, limit }));
+            }
+        }
+
+        if let Some(limit) = policy.max_order_notional {
+            let notional: Result<Decimal> = async {
+                let price = match order.price {
+                    Some(price) => price,
+                    None => {
+                        let book = exchange.get_order_book(&order.market, 1).await?;
+                        match order.side {
+                            TradeSide::Buy => book.best_ask(),
+                            TradeSide::Sell => book.best_bid(),
+                        }
+                        .ok_or(Error::NoData)?
+                        .price
+                    }
+                };
+                let rate = self
+                    .price_of(&order.market.quote, &policy.quote)
+                    .await?
+                    .ok_or_else(|| {
+                        Error::ExchangeResponse(format!(
+                            "no market to convert {} in {}",
+                            order.market.quote, policy.quote
+                        ))
+                    })?;
+                price
+                    .checked_mul(order.volume)
+                    .and_then(|e| e.checked_mul(rate))
+                    .ok_or_else(|| {
+                        Error::InvalidDecimal(format!("{} * {} * {}", price, order.volume, rate))
+                    })
+            }
+            .await;
+            match notional {
+                Ok(notional) if notional > limit => {
+                    return Ok(Some(RiskViolation::OrderNotional {
+                        notional: notional.round_dp(8),
+                        limit,
+                        quote: policy.quote.clone(),
+                    }))
+                }
+                Ok(_) => {}
+                Err(e) => return Ok(Some(unavailable("order notional", e))),
+            }
+        }
+
+        if order.side == TradeSide::Buy {
+            let asset = self.assets.canonical_market(&order.market).base;
+            if let Some(limit) = policy.max_position.get(&asset) {
+                let position = self.holding(&asset).await.and_then(|held| {
+                    held.checked_add(order.volume).ok_or_else(|| {
+                        Error::InvalidDecimal(format!("{} + {}", held, order.volume))
+                    })
+                });
+                match position {
+                    Ok(position) if position > *limit => {
+                        return Ok(Some(RiskViolation::Position {
+                            asset,
+                            position,
+                            limit: *limit,
+                        }))
+                    }
+                    Ok(_) => {}
+                    Err(e) => return Ok(Some(unavailable("position", e))),
+                }
+            }
+            if let Some(limit) = policy.daily_loss_limit {
+                match self.daily_realized().await {
+                    Ok(realized) if -realized >= limit => {
+                        return Ok(Some(RiskViolation::DailyLoss {
+                            loss: (-realized).round_dp(8),
+                            limit,
+                            quote: policy.quote.clone(),
+                        }))
+                    }
+                    Ok(_) => {}
+                    Err(e) => return Ok(Some(unavailable("daily loss", e))),
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Check an order against the risk policy without sending it
+    pub async fn check_order(&self, order: &OrderRequest) -> Result<()> {
+        let exchange = self.exchange(&order.market.exchange_name).await?;
+        let definition = exchange.get_market_definition(&order.market, None).await?;
+        match self.risk_violation(order, &definition, &exchange).await? {
+            Some(violation) => {
+                log::warn!("Order rejected: ORDER={}, REASON={}", order, violation);
+                self.listeners
+                    .broadcast(ReactorEvent::RiskViolation {
+                        order: order.clone(),
+                        violation: violation.clone(),
+                    })
+                    .await;
+                Err(Error::RiskViolation(violation))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// The only way orders leave pkbot, they are sent once they pass the risk checks
+    pub async fn submit_order(&self, order: &OrderRequest) -> Result<PlacedOrder> {
+        // The limits are global (orders per minute, positions, kill switch), orders of every
+        // exchange wait for each other
+        let _submitting = self.risk.submitting.lock().await;
+        self.check_order(order).await?;
+        let exchange = self.exchange(&order.market.exchange_name).await?;
+        self.risk.record_sent();
+        let placed = exchange.place_order(order).await?;
+        log::info!(
+            "Order placed: ORDER={}, IDS={}",
+            order,
+            placed.ids.join(",")
+        );
+        self.listeners
+            .broadcast(ReactorEvent::OrderPlaced {
+                order: placed.clone(),
+            })
+            .await;
+        Ok(placed)
+    }
+
+    async fn exchange(&self, name: &str) -> Result<SyncExchange> {
+        self.exchanges
+            .read()
+            .await
+            .get(name)
+            .cloned()
+            .ok_or_else(|| Error::ExchangeNotFound(name.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(raw: &str) -> Decimal {
+        raw.parse().unwrap()
+    }
+
+    fn btc_usd() -> MarketIdentifier {
+        MarketIdentifier::from("mock/BTC/USD")
+    }
+
+    fn mock_market(base: &str, quote: &str, price: &str) -> MockMarket {
+        MockMarket {
+            base: base.to_string(),
+            quote: quote.to_string(),
+            price: dec(price),
+            spread: dec("0.1"),
+            volume: Decimal::from(1i64),
+            fee: 0.0,
+        }
+    }
+
+    /// Reactor trading BTC/USD at 40000 and EUR/USD at 1.25 on a mock exchange
+    async fn reactor(name: &str, policy: RiskPolicy) -> (Reactor, SyncExchange) {
+        let path = std::env::temp_dir().join(format!("pkbot-risk-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&path);
+        let reactor = Reactor::new(Store::new(path).unwrap().handle())
+            .await
+            .with_risk(RiskEngine::new(policy).shared());
+        let exchange = MockExchange::new(
+            String::from("mock"),
+            vec![
+                mock_market("BTC", "USD", "40000"),
+                mock_market("EUR", "USD", "1.25"),
+            ],
+        )
+        .shared();
+        reactor.register_exchange(exchange.clone()).await;
+        (reactor, exchange)
+    }
+
+    fn fill(id: &str, side: TradeSide, price: &str, volume: &str) -> Fill {
+        Fill {
+            id: id.to_string(),
+            market: String::from("mock/BTC/USD"),
+            time: crate::store::now(),
+            side,
+            price: dec(price),
+            volume: dec(volume),
+            fee: Decimal::ZERO,
+            order: None,
+        }
+    }
+
+    fn violation(result: Result<()>) -> RiskViolation {
+        match result {
+            Err(Error::RiskViolation(violation)) => violation,
+            other => panic!("expected a risk violation, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn kill_switch_stops_every_order() {
+        let (reactor, _) = reactor("kill-switch", RiskPolicy::default()).await;
+        let order = OrderRequest::market(btc_usd(), TradeSide::Sell, dec("0.1"));
+        reactor.engage_kill_switch("manual stop").await.unwrap();
+        let rejected = reactor.submit_order(&order).await.map(|_| ());
+        assert!(matches!(
+            violation(rejected),
+            RiskViolation::KillSwitch { reason } if reason == "manual stop"
+        ));
+        assert!(reactor.release_kill_switch().await.unwrap());
+        reactor.submit_order(&order).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn orders_respect_the_market_precision() {
+        let (reactor, exchange) = reactor("precision", RiskPolicy::default()).await;
+        let mut definition = exchange
+            .get_market_definition(&btc_usd(), None)
+            .await
+            .unwrap();
+        let check = |order: OrderRequest, definition: MarketDefinition| {
+            let reactor = &reactor;
+            let exchange = &exchange;
+            async move {
+                reactor
+                    .risk_violation(&order, &definition, exchange)
+                    .await
+                    .unwrap()
+            }
+        };
+
+        let order = OrderRequest::market(btc_usd(), TradeSide::Buy, dec("0.000000001"));
+        assert!(matches!(
+            check(order, definition.clone()).await,
+            Some(RiskViolation::LotPrecision { decimals: 8, .. })
+        ));
+        let order = OrderRequest::limit(btc_usd(), TradeSide::Buy, dec("0.1"), dec("40000.125"));
+        assert!(matches!(
+            check(order, definition.clone()).await,
+            Some(RiskViolation::PricePrecision { decimals: 2, .. })
+        ));
+        let order = OrderRequest {
+            price: None,
+            ..OrderRequest::limit(btc_usd(), TradeSide::Buy, dec("0.1"), dec("40000"))
+        };
+        assert!(matches!(
+            check(order, definition.clone()).await,
+            Some(RiskViolation::MissingPrice)
+        ));
+
+        definition.ordermin = Some(String::from("0.0001"));
+        let order = OrderRequest::market(btc_usd(), TradeSide::Buy, dec("0.00005"));
+        assert!(matches!(
+            check(order, definition.clone()).await,
+            Some(RiskViolation::MinVolume { min, .. }) if min == dec("0.0001")
+        ));
+        let order = OrderRequest::limit(btc_usd(), TradeSide::Buy, dec("0.0001"), dec("40000.12"));
+        assert!(check(order, definition).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn orders_per_minute() {
+        let policy = RiskPolicy {
+            max_orders_per_minute: Some(2),
+            ..RiskPolicy::default()
+        };
+        let (reactor, _) = reactor("rate", policy).await;
+        let order = OrderRequest::market(btc_usd(), TradeSide::Buy, dec("0.1"));
+        reactor.submit_order(&order).await.unwrap();
+        reactor.submit_order(&order).await.unwrap();
+        // Rejected orders are not counted
+        for _ in 0..2 {
+            let rejected = reactor.submit_order(&order).await.map(|_| ());
+            assert!(matches!(
+                violation(rejected),
+                RiskViolation::OrderRate {
+                    orders: 2,
+                    limit: 2
+                }
+            ));
+        }
+        assert_eq!(reactor.risk_status().await.unwrap().orders_last_minute, 2);
+    }
+
+    #[tokio::test]
+    async fn notional_is_converted_to_the_policy_quote() {
+        let policy = RiskPolicy {
+            quote: String::from("EUR"),
+            max_order_notional: Some(dec("1000")),
+            ..RiskPolicy::default()
+        };
+        let (reactor, _) = reactor("notional", policy).await;
+        // 1200 USD at 1.25 USD per EUR
+        let order = OrderRequest::limit(btc_usd(), TradeSide::Buy, dec("0.03"), dec("40000"));
+        reactor.check_order(&order).await.unwrap();
+        // 1600 USD
+        let order = OrderRequest::limit(btc_usd(), TradeSide::Sell, dec("0.04"), dec("40000"));
+        assert!(matches!(
+            violation(reactor.check_order(&order).await),
+            RiskViolation::OrderNotional { notional, limit, quote }
+                if notional == dec("1280") && limit == dec("1000") && quote == "EUR"
+        ));
+        // Market orders are valued at the best level of the book, the ask of 40020 for a buy
+        let order = OrderRequest::market(btc_usd(), TradeSide::Buy, dec("0.03125"));
+        assert!(matches!(
+            violation(reactor.check_order(&order).await),
+            RiskViolation::OrderNotional { notional, .. } if notional == dec("1000.5")
+        ));
+    }
+
+    #[tokio::test]
+    async fn max_position_only_limits_buys() {
+        let policy = RiskPolicy {
+            max_position: vec![(String::from("BTC"), dec("0.5"))]
+                .into_iter()
+                .collect(),
+            ..RiskPolicy::default()
+        };
+        let (reactor, _) = reactor("position", policy).await;
+        reactor
+            .store
+            .fills
+            .extend(&[fill("mock-1", TradeSide::Buy, "40000", "0.4")])
+            .unwrap();
+        let order = OrderRequest::market(btc_usd(), TradeSide::Buy, dec("0.1"));
+        reactor.check_order(&order).await.unwrap();
+        let order = OrderRequest::market(btc_usd(), TradeSide::Buy, dec("0.2"));
+        assert!(matches!(
+            violation(reactor.check_order(&order).await),
+            RiskViolation::Position { asset, position, limit }
+                if asset == "BTC" && position == dec("0.6") && limit == dec("0.5")
+        ));
+        let order = OrderRequest::market(btc_usd(), TradeSide::Sell, dec("1"));
+        reactor.check_order(&order).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn daily_loss_only_accepts_sells() {
+        let policy = RiskPolicy {
+            quote: String::from("USD"),
+            daily_loss_limit: Some(dec("150")),
+            ..RiskPolicy::default()
+        };
+        let (reactor, _) = reactor("daily-loss", policy).await;
+        let order = OrderRequest::market(btc_usd(), TradeSide::Buy, dec("0.1"));
+        reactor.check_order(&order).await.unwrap();
+        reactor
+            .store
+            .fills
+            .extend(&[
+                fill("mock-1", TradeSide::Buy, "40000", "2"),
+                fill("mock-2", TradeSide::Sell, "39800", "1"),
+            ])
+            .unwrap();
+        assert!(matches!(
+            violation(reactor.check_order(&order).await),
+            RiskViolation::DailyLoss { loss, limit, quote }
+                if loss == dec("200") && limit == dec("150") && quote == "USD"
+        ));
+        let order = OrderRequest::market(btc_usd(), TradeSide::Sell, dec("1"));
+        reactor.check_order(&order).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn unavailable_checks_reject_orders() {
+        // No market converts USD in JPY
+        let policy = RiskPolicy {
+            quote: String::from("JPY"),
+            max_order_notional: Some(dec("1000000")),
+            ..RiskPolicy::default()
+        };
+        let (reactor, _) = reactor("unavailable-notional", policy).await;
+        let order = OrderRequest::market(btc_usd(), TradeSide::Sell, dec("0.1"));
+        assert!(matches!(
+            violation(reactor.check_order(&order).await),
+            RiskViolation::Unavailable { check, .. } if check == "order notional"
+        ));
+
+        let policy = RiskPolicy {
+            quote: String::from("JPY"),
+            daily_loss_limit: Some(dec("1000000")),
+            ..RiskPolicy::default()
+        };
+        let (reactor, _) = reactor("unavailable-loss", policy).await;
+        reactor
+            .store
+            .fills
+            .extend(&[
+                fill("mock-1", TradeSide::Buy, "40000", "1"),
+                fill("mock-2", TradeSide::Sell, "39800", "1"),
+            ])
+            .unwrap();
+        let order = OrderRequest::market(btc_usd(), TradeSide::Buy, dec("0.1"));
+        assert!(matches!(
+            violation(reactor.check_order(&order).await),
+            RiskViolation::Unavailable { check, .. } if check == "daily loss"
+        ));
+        // Sells are still accepted
+        let order = OrderRequest::market(btc_usd(), TradeSide::Sell, dec("0.1"));
+        reactor.check_order(&order).await.unwrap();
+    }
+}
@@ -14,28 +14,38 @@ const CHANN_SIZE_MAIN: usize = 2048;
 
 pub struct ProgramRuntime {
     pub id: ProgramIdentifier,
+    pub run_id: Option<RunIdentifier>,
+    pub source: String,
+    pub started_at: SystemTime,
+    pub timeout: Option<Duration>,
+    pub cancel: CancellationToken,
+    pub status: watch::Receiver<ProgramStatus>,
     pub stdout: Option<Receiver<ProgramOutput>>,
+    pub(super) status_sender: Option<watch::Sender<ProgramStatus>>,
+    pub(super) supervisor: Option<JoinHandle<ProgramStatus>>,
 }
 
 macro_rules! inner_spawn {
-    ($reactor:expr => $node:expr, $stdin:expr, $stdout:expr, $context:expr) => {
+    ($reactor:expr => $node:expr, $stdin:expr, $stdout:expr, $context:expr, $cancel:expr) => {
         match $node.0.content {
             CommandAstBody::Call { .. } => {
-                ProgramRuntime::call($reactor, $node, $stdin, $stdout, $context)
+                ProgramRuntime::call($reactor, $node, $stdin, $stdout, $context, $cancel)
             }
             CommandAstBody::Assignation { .. } => {
-                ProgramRuntime::assignation($reactor, $node, $stdin, $stdout, $context)
+                ProgramRuntime::assignation($reactor, $node, $stdin, $stdout, $context, $cancel)
             }
             CommandAstBody::Literal { .. } => unimplemented!(),
-            //CommandAstBody::Pipe if $node.0.left.is_some() && $node.0.right.is_some() => {
-            //    ProgramRuntime::pipe(
-            //        $node.0.left.unwrap(),
-            //        $node.0.right.unwrap(),
-            //        $reactor,
-            //        $stdin,
-            //        $stdout,
-            //    )
-            //}
+            CommandAstBody::Pipe if $node.0.left.is_some() && $node.0.right.is_some() => {
+                ProgramRuntime::pipe(
+                    $node.0.left.unwrap(),
+                    $node.0.right.unwrap(),
+                    $reactor,
+                    $stdin,
+                    $stdout,
+                    $context,
+                    $cancel,
+                )
+            }
             CommandAstBody::Comma => ProgramRuntime::separator(
                 $node.0.left,
                 $node.0.right,
@@ -43,10 +53,13 @@ macro_rules! inner_spawn {
                 $stdin,
                 $stdout,
                 $context,
+                $cancel,
+            ),
+            CommandAstBody::Pipe => ProgramRuntime::fail(
+                Error::Parsing(String::from("pipe without both operands"), 0..0),
+                $stdout,
+                $cancel,
             ),
-            //CommandAstBody::Pipe => {
-            //    panic!("Invalide pipe");
-            //}
             //CommandAstBody::Arguments { .. } => {
             //    panic!("Expected call or pipe, found arguments");
             //}
@@ -58,12 +71,61 @@ macro_rules! inner_spawn {
 impl ProgramRuntime {
     pub async fn spawn(program: Program, reactor: Reactor) -> ProgramRuntime {
         let (main_sender, main_receiver) = channel(CHANN_SIZE_MAIN);
+        let (status_sender, status) = watch::channel(ProgramStatus::None);
         let context = Arc::new(RwLock::new(program.context));
         let id = reactor.process_counter.fetch_add(1, Ordering::SeqCst);
-        Self::inner_spawn(program.root, reactor, None, main_sender, context).await;
+        let cancel = CancellationToken::new();
+        let root = Self::inner_spawn(
+            program.root,
+            reactor,
+            None,
+            main_sender,
+            context,
+            cancel.clone(),
+        )
+        .await;
+        let supervisor = tokio::spawn(Self::supervise(root, cancel.clone(), program.timeout));
         ProgramRuntime {
             id,
+            run_id: None,
+            source: program.source,
+            started_at: SystemTime::now(),
+            timeout: program.timeout,
+            cancel,
+            status,
             stdout: Some(main_receiver),
+            status_sender: Some(status_sender),
+            supervisor: Some(supervisor),
+        }
+    }
+
+    /// Wait for the root task of a program and resolve its final status, cancelling
+    /// the whole task tree once the wall-clock timeout is exceeded.
+    async fn supervise(
+        root: JoinHandle<()>,
+        cancel: CancellationToken,
+        timeout: Option<Duration>,
+    ) -> ProgramStatus {
+        let deadline = async move {
+            match timeout {
+                Some(timeout) => tokio::time::sleep(timeout).await,
+                None => futures::future::pending::<()>().await,
+            }
+        };
+        tokio::select! {
+            biased;
+            _ = cancel.cancelled() => ProgramStatus::Cancelled,
+            _ = deadline => {
+                cancel.cancel();
+                ProgramStatus::TimedOut
+            }
+            res = root => match res {
+                Ok(()) => ProgramStatus::Success,
+                Err(e) => {
+                    log::error!("Program root task failed: ERROR={}", e);
+                    ProgramStatus::Error
+                }
+            },
         }
     }
 
@@ -73,8 +135,30 @@ impl ProgramRuntime {
         stdin: Option<Receiver<ProgramOutput>>,
         stdout: Sender<ProgramOutput>,
         context: SyncContext,
+        cancel: CancellationToken,
     ) -> JoinHandle<()> {
-        inner_spawn!(reactor => root, stdin, stdout, context)
+        inner_spawn!(reactor => root, stdin, stdout, context, cancel)
+    }
+
+    /// Every task of a program tree is spawned through here so a single `cancel` stops
+    /// all of them, including the ones whose handle was not awaited.
+    fn spawn_cancellable<F: Future<Output = ()> + Send + 'static>(
+        cancel: CancellationToken,
+        task: F,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = cancel.cancelled() => {}
+                _ = task => {}
+            }
+        })
+    }
+
+    /// Report an error through the exit of the program instead of spawning a node
+    fn fail(error: Error, stdout: Sender<ProgramOutput>, cancel: CancellationToken) -> JoinHandle<()> {
+        Self::spawn_cancellable(cancel, async move {
+            let _ = stdout.send(error.into()).await;
+        })
     }
 
     //TODO: optime
@@ -84,10 +168,9 @@ impl ProgramRuntime {
         stdin: Option<Receiver<ProgramOutput>>,
         stdout: Sender<ProgramOutput>,
         context: SyncContext,
+        cancel: CancellationToken,
     ) -> JoinHandle<()> {
-        tokio::spawn((async move || {
-
-
+        Self::spawn_cancellable(cancel.clone(), (async move || {
             let (pipline_sender, mut pipline_receiver) = channel(CHANN_SIZE_PIPLINE);
             let left = root.0.left.expect("Left operand");
             let call = root.0.right.expect("Right operand"); 
@@ -98,7 +181,7 @@ impl ProgramRuntime {
                     dbg!(e);
                 }
             } else {
-                let handle = inner_spawn!(reactor => call, stdin, pipline_sender, context.clone());
+                let handle = inner_spawn!(reactor => call, stdin, pipline_sender, context.clone(), cancel);
                 let mut values = Vec::with_capacity(1);
                 while let Some(res) = pipline_receiver.recv().await {
                     match res {
@@ -129,24 +212,36 @@ impl ProgramRuntime {
         stdin: Option<Receiver<ProgramOutput>>,
         stdout: Sender<ProgramOutput>,
         context: SyncContext,
+        cancel: CancellationToken,
     ) -> JoinHandle<()> {
-        tokio::spawn((async move || { 
+        Self::spawn_cancellable(cancel.clone(), (async move || { 
             let (name, scoop) = root.0.right.as_ref().unwrap().reference();
+            let arguments = root
+                .0
+                .left
+                .as_ref()
+                .map(|e| e.call_arguments())
+                .unwrap_or_default();
             let ctx = context.clone();
             let lock = context.read().await;
             let value = lock.scoop_get(scoop, name).unwrap();
             match value {
                 RuntimeValue::NativeProcedure(gen) => {
-                    let fut = (gen.lock().await)(reactor, vec![], stdin, stdout.clone());
+                    let fut = (gen.lock().await)(reactor, arguments, stdin, stdout.clone());
                     drop(lock);
-                    if let Ok(res) = fut.await {
-                        let _ = stdout.send(res).await;
+                    match fut.await {
+                        Ok(res) => {
+                            let _ = stdout.send(res).await;
+                        }
+                        Err(e) => {
+                            let _ = stdout.send(e.into()).await;
+                        }
                     }
                 },
                 RuntimeValue::Procedure(node) => {
                     if let Some(right) = node.0.right.as_ref().unwrap().0.left.clone() {
                         drop(lock);
-                        let fut = inner_spawn!(reactor => right, stdin, stdout, ctx);
+                        let fut = inner_spawn!(reactor => right, stdin, stdout, ctx, cancel);
                         let _ = fut.await;
                     }
                 },
@@ -161,13 +256,15 @@ impl ProgramRuntime {
         reactor: Reactor,
         stdin: Option<Receiver<ProgramOutput>>,
         stdout: Sender<ProgramOutput>,
+        context: SyncContext,
+        cancel: CancellationToken,
     ) -> JoinHandle<()> {
-        // tokio::spawn((async move || {
-        // let (pipline_sender, pipline_receiver) = channel(CHANN_SIZE_PIPLINE);
-        // let _left = inner_spawn!(reactor.clone() => left, stdin, pipline_sender);
-        // let _ = inner_spawn!(reactor.clone() => right, Some(pipline_receiver), stdout.clone());
-        // })())
-        tokio::spawn((async || {})())
+        Self::spawn_cancellable(cancel.clone(), (async move || {
+            let (pipline_sender, pipline_receiver) = channel(CHANN_SIZE_PIPLINE);
+            let left = inner_spawn!(reactor.clone() => left, stdin, pipline_sender, context.clone(), cancel.clone());
+            let right = inner_spawn!(reactor => right, Some(pipline_receiver), stdout, context, cancel);
+            let _ = tokio::join!(left, right);
+        })())
     }
 
     fn separator(
@@ -177,15 +274,16 @@ impl ProgramRuntime {
         stdin: Option<Receiver<ProgramOutput>>,
         stdout: Sender<ProgramOutput>,
         context: SyncContext,
+        cancel: CancellationToken,
     ) -> JoinHandle<()> {
-        tokio::spawn((async move || {
+        Self::spawn_cancellable(cancel.clone(), (async move || {
             if let Some(left) = left {
                 let _ = tokio::join!(
-                    inner_spawn!(reactor.clone() => left, stdin, stdout.clone(), context.clone())
+                    inner_spawn!(reactor.clone() => left, stdin, stdout.clone(), context.clone(), cancel.clone())
                 );
             }
             if let Some(right) = right {
-                let _ = tokio::join!(inner_spawn!(reactor => right, None, stdout.clone(), context));
+                let _ = tokio::join!(inner_spawn!(reactor => right, None, stdout.clone(), context, cancel));
             }
         })())
     }
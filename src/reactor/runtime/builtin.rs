@@ -75,6 +75,18 @@ impl From<u32> for RuntimeValue {
     }
 }
 
+impl From<u64> for RuntimeValue {
+    fn from(val: u64) -> Self {
+        RuntimeValue::Number(val as f64)
+    }
+}
+
+impl From<String> for RuntimeValue {
+    fn from(val: String) -> Self {
+        RuntimeValue::String(val)
+    }
+}
+
 impl From<f32> for RuntimeValue {
     fn from(val: f32) -> Self {
         RuntimeValue::Number(val as f64)
@@ -87,16 +99,91 @@ impl From<f64> for RuntimeValue {
     }
 }
 
+impl From<&OHLC> for RuntimeValue {
+    fn from(val: &OHLC) -> Self {
+        RuntimeValue::Object(runtime_value! {
+            "time": val.time as f64,
+            "open": val.open.to_f64(),
+            "high": val.high.to_f64(),
+            "low": val.low.to_f64(),
+            "close": val.close.to_f64(),
+            "volume": val.volume.to_f64(),
+            "count": val.count,
+        })
+    }
+}
+
+impl From<Decimal> for RuntimeValue {
+    fn from(val: Decimal) -> Self {
+        RuntimeValue::Number(val.to_f64())
+    }
+}
+
+impl<T: Into<RuntimeValue>> From<Option<T>> for RuntimeValue {
+    fn from(val: Option<T>) -> Self {
+        val.map(Into::into).unwrap_or(RuntimeValue::Undefined)
+    }
+}
+
+impl From<&crate::exchange::Trade> for RuntimeValue {
+    fn from(val: &crate::exchange::Trade) -> Self {
+        RuntimeValue::Object(runtime_value! {
+            "id": val.id,
+            "time": val.time_ms as f64 / 1000.0,
+            "price": val.price,
+            "volume": val.volume,
+            "side": format!("{:?}", val.side).to_lowercase(),
+            "order_type": format!("{:?}", val.order_type).to_lowercase(),
+        })
+    }
+}
+
+impl From<&crate::exchange::Fill> for RuntimeValue {
+    fn from(val: &crate::exchange::Fill) -> Self {
+        RuntimeValue::Object(runtime_value! {
+            "id": val.id.clone(),
+            "market": val.market.clone(),
+            "time": val.time as f64,
+            "side": format!("{:?}", val.side).to_lowercase(),
+            "price": val.price,
+            "volume": val.volume,
+            "fee": val.fee,
+            "order": val.order.clone(),
+        })
+    }
+}
+
 impl  From<Vec<RuntimeValue>> for RuntimeValue {
     fn from(val: Vec<RuntimeValue>) -> Self {
         RuntimeValue::Array(val)
     }
 }
 
+pub mod alert;
+pub mod book;
 pub mod cat;
+pub mod compare;
+pub mod du;
 pub mod echo;
+pub mod export;
+pub mod history;
+pub mod import;
+pub mod kill;
+pub mod logs;
 pub mod ls;
+pub mod order;
+pub mod plot;
+pub mod portfolio;
+pub mod ps;
+pub mod retention;
+pub mod risk;
+pub mod schedule;
 pub mod sleep;
+pub mod strategy;
+pub mod sync;
+pub mod trades;
+pub mod wait;
+pub mod watchlist;
 
 #[derive(Debug, Clone)]
 pub struct ArgumentInterval {
@@ -159,7 +246,7 @@ impl ArgumentInterval {
     }
 }
 
-fn human_duration(str: &str) -> Result<Duration> {
+pub fn human_duration(str: &str) -> Result<Duration> {
     let str = str.to_lowercase();
     if str.ends_with("s") {
         let parsed: u64 = str[..str.len() - 1].parse()?;
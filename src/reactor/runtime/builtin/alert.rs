@@ -0,0 +1,169 @@
+use super::*;
+use crate::store::{Alert, AlertCondition, AlertSink};
+use clap::{App, ArgGroup, ArgMatches};
+
+fn condition(app: &ArgMatches) -> Result<AlertCondition> {
+    let condition = parse_condition(app)?;
+    condition.check()?;
+    Ok(condition)
+}
+
+fn parse_condition(app: &ArgMatches) -> Result<AlertCondition> {
+    let period: usize = app.value_of("period").unwrap_or("14").parse()?;
+    let parse_decimal = |name: &str| -> Result<Decimal> {
+        let raw = app.value_of(name).unwrap();
+        raw.parse()
+            .map_err(|_| Error::Parsing(format!("Expected a number, found `{}`", raw), 0..0))
+    };
+    if app.is_present("above") {
+        Ok(AlertCondition::PriceAbove {
+            level: parse_decimal("above")?,
+        })
+    } else if app.is_present("below") {
+        Ok(AlertCondition::PriceBelow {
+            level: parse_decimal("below")?,
+        })
+    } else if app.is_present("rsi_above") {
+        Ok(AlertCondition::RsiAbove {
+            period,
+            threshold: parse_decimal("rsi_above")?,
+        })
+    } else if app.is_present("rsi_below") {
+        Ok(AlertCondition::RsiBelow {
+            period,
+            threshold: parse_decimal("rsi_below")?,
+        })
+    } else {
+        Ok(AlertCondition::PercentMove {
+            window: app.value_of("window").unwrap_or("1").parse()?,
+            percent: parse_decimal("move")?,
+        })
+    }
+}
+
+fn add(reactor: Reactor, app: &ArgMatches) -> Result<ProgramOutput> {
+    let sinks = app
+        .values_of("notify")
+        .map(|e| e.map(|e| e.parse()).collect::<Result<Vec<AlertSink>>>())
+        .transpose()?
+        .unwrap_or_else(|| vec![AlertSink::EventBus]);
+    let alert = Alert {
+        name: app.value_of("name").unwrap().to_string(),
+        market: app.value_of("market").unwrap().to_string(),
+        interval: ArgumentInterval::new(app.value_of("interval").unwrap())?.normalized,
+        condition: condition(app)?,
+        sinks,
+        active: false,
+        last_triggered: None,
+    };
+    reactor.store.alerts.set(&alert)?;
+    Ok(ProgramOutput::Exit {
+        message: None,
+        status: ProgramStatus::Success,
+    })
+}
+
+fn ls(reactor: Reactor) -> Result<ProgramOutput> {
+    let results: Vec<_> = reactor
+        .store
+        .alerts
+        .list()?
+        .into_iter()
+        .map(|alert| {
+            let sinks: Vec<_> = alert
+                .sinks
+                .iter()
+                .map(|e| RuntimeValue::from(format!("{}", e)))
+                .collect();
+            RuntimeValue::Object(runtime_value! {
+                "name": alert.name.as_str(),
+                "market": alert.market.as_str(),
+                "interval": format!("{}", alert.interval),
+                "condition": format!("{}", alert.condition),
+                "notify": sinks,
+                "active": if alert.active { 1.0 } else { 0.0 },
+                "last_triggered": alert.last_triggered.map(|e| RuntimeValue::from(e as f64)).unwrap_or(RuntimeValue::Undefined),
+            })
+        })
+        .collect();
+    Ok(ProgramOutput::json(RuntimeValue::from(results)))
+}
+
+pub async fn main(
+    reactor: Reactor,
+    mut args: Vec<String>,
+    _stdin: Option<Receiver<ProgramOutput>>,
+    stdout: Sender<ProgramOutput>,
+) -> Result<ProgramOutput> {
+    args.insert(0, "alert".to_string());
+    let app = App::new("alert")
+        .subcommand(
+            App::new("add")
+                .about("Register an alert evaluated on each synced candle")
+                .arg(Arg::new("name").required(true).index(1))
+                .arg(Arg::new("market").required(true).index(2))
+                .arg(
+                    Arg::new("interval")
+                        .validator(ArgumentInterval::validator)
+                        .takes_value(true)
+                        .required(true)
+                        .short('i')
+                        .long("interval"),
+                )
+                .arg(Arg::new("above").long("above").takes_value(true))
+                .arg(Arg::new("below").long("below").takes_value(true))
+                .arg(Arg::new("rsi_above").long("rsi-above").takes_value(true))
+                .arg(Arg::new("rsi_below").long("rsi-below").takes_value(true))
+                .arg(Arg::new("move").long("move").takes_value(true))
+                .group(
+                    ArgGroup::new("condition")
+                        .args(&["above", "below", "rsi_above", "rsi_below", "move"])
+                        .required(true),
+                )
+                .arg(Arg::new("period").long("period").takes_value(true))
+                .arg(Arg::new("window").long("window").takes_value(true))
+                .arg(
+                    Arg::new("notify")
+                        .short('n')
+                        .long("notify")
+                        .takes_value(true)
+                        .multiple_occurrences(true),
+                ),
+        )
+        .subcommand(App::new("ls").about("List alerts"))
+        .subcommand(
+            App::new("rm")
+                .about("Remove alerts")
+                .arg(Arg::new("name").required(true).multiple_values(true)),
+        );
+    let app = app.try_get_matches_from(args)?;
+    match app.subcommand() {
+        Some(("add", app)) => add(reactor, app),
+        Some(("ls", _)) => ls(reactor),
+        Some(("rm", app)) => {
+            for name in app.values_of("name").unwrap() {
+                if !reactor.store.alerts.remove(name)? {
+                    return Err(Error::AlertNotFound(name.to_string()));
+                }
+                buitlin_print!(stdout, "Removed {}", name);
+            }
+            Ok(ProgramOutput::Exit {
+                message: None,
+                status: ProgramStatus::Success,
+            })
+        }
+        _ => Err(Error::Parsing(
+            String::from("Expected one of: add, ls, rm"),
+            0..0,
+        )),
+    }
+}
+
+pub fn wrap() -> NativeProcedureGen {
+    Box::new(
+        |reactor: Reactor,
+         args: Vec<String>,
+         stdin: Option<Receiver<ProgramOutput>>,
+         stdout: Sender<ProgramOutput>| { Box::pin(main(reactor, args, stdin, stdout)) },
+    )
+}
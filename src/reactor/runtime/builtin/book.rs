@@ -0,0 +1,101 @@
+use super::*;
+use crate::exchange::BookLevel;
+
+fn levels_value(levels: &[BookLevel]) -> RuntimeValue {
+    RuntimeValue::from(
+        levels
+            .iter()
+            .map(|e| {
+                RuntimeValue::Object(runtime_value! {
+                    "price": e.price,
+                    "volume": e.volume,
+                })
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+pub async fn main(
+    reactor: Reactor,
+    mut args: Vec<String>,
+    _stdin: Option<Receiver<ProgramOutput>>,
+    _stdout: Sender<ProgramOutput>,
+) -> Result<ProgramOutput> {
+    args.insert(0, "book".to_string());
+    let app = clap::App::new("book")
+        .arg(Arg::new("market_name").required(true).index(1))
+        .arg(
+            Arg::new("depth")
+                .help("Number of levels fetched on each side")
+                .short('d')
+                .long("depth")
+                .takes_value(true)
+                .default_value("10"),
+        )
+        .arg(
+            Arg::new("levels")
+                .help("Number of levels used for the depth and the imbalance, all by default")
+                .short('l')
+                .long("levels")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("within")
+                .help("Also report the volume priced within this percentage of the mid price")
+                .short('w')
+                .long("within")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("metrics")
+                .help("Only report the metrics, not the levels")
+                .short('m')
+                .long("metrics"),
+        );
+    let app = app.try_get_matches_from(args)?;
+    let depth: usize = app.value_of("depth").unwrap().parse()?;
+    let levels: usize = app
+        .value_of("levels")
+        .map(str::parse)
+        .transpose()?
+        .unwrap_or(depth);
+    let within: Option<Decimal> = app.value_of("within").map(str::parse).transpose()?;
+    let id = reactor
+        .resolve_market(app.value_of("market_name").unwrap())
+        .await?;
+    let book = reactor
+        .get_or_register_market(&id)
+        .await?
+        .order_book(depth)
+        .await?;
+    let (bid_depth, ask_depth) = book.depth(levels);
+    let mut result = runtime_value! {
+        "time": book.time as f64,
+        "best_bid": book.best_bid().map(|e| e.price),
+        "best_ask": book.best_ask().map(|e| e.price),
+        "spread": book.spread(),
+        "spread_bps": book.spread_bps(),
+        "mid_price": book.mid_price(),
+        "bid_depth": bid_depth,
+        "ask_depth": ask_depth,
+        "imbalance": book.imbalance(levels),
+    };
+    if let Some((bids, asks)) = within.and_then(|e| book.depth_within(e)) {
+        result.insert("bid_within".to_string(), RuntimeValue::from(bids));
+        result.insert("ask_within".to_string(), RuntimeValue::from(asks));
+    }
+    if !app.is_present("metrics") {
+        result.insert("bids".to_string(), levels_value(&book.bids));
+        result.insert("asks".to_string(), levels_value(&book.asks));
+    }
+    Ok(ProgramOutput::json(RuntimeValue::Object(result)))
+}
+
+pub fn wrap() -> NativeProcedureGen {
+    Box::new(
+        |reactor: Reactor,
+         args: Vec<String>,
+         stdin: Option<Receiver<ProgramOutput>>,
+         stdout: Sender<ProgramOutput>| { Box::pin(main(reactor, args, stdin, stdout)) },
+    )
+}
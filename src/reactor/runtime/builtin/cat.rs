@@ -49,21 +49,31 @@ pub async fn main(
         .map(|e| e.timestamp())
         .unwrap_or(now.duration_since(UNIX_EPOCH).unwrap().as_secs() as Timestamp);
     let interval = ArgumentInterval::new(app.value_of("interval").unwrap()).unwrap();
-    let markets = app.values_of("market_name").unwrap();
+    let markets = reactor
+        .resolve_markets(&app.values_of("market_name").unwrap().collect::<Vec<_>>())
+        .await?;
     let mut results = Vec::new();
-    for val in markets.into_iter() {
-        let id = MarketIdentifier::from(val);
+    for id in markets.into_iter() {
         let target_market = reactor.get_or_register_market(&id).await?;
-        let range = target_market
+        target_market
             .sync_periode(from, to, interval.normalized)
             .await?;
-        let _ = dbg!(range);
         let chunk = target_market
             .interval(interval.normalized)
             .await?
             .close_range(from, to)?;
-        results.push(chunk);
+        results.push(RuntimeValue::from(
+            chunk.iter().map(RuntimeValue::from).collect::<Vec<_>>(),
+        ));
     }
-    Ok(unimplemented!())
-    // Ok(ProgramOutput::json(&results)?)
+    Ok(ProgramOutput::json(RuntimeValue::from(results)))
+}
+
+pub fn wrap() -> NativeProcedureGen {
+    Box::new(
+        |reactor: Reactor,
+         args: Vec<String>,
+         stdin: Option<Receiver<ProgramOutput>>,
+         stdout: Sender<ProgramOutput>| { Box::pin(main(reactor, args, stdin, stdout)) },
+    )
 }
@@ -0,0 +1,97 @@
+use super::*;
+use crate::reactor::{ArbitrageOpportunity, PairComparison, PairQuote};
+
+fn market_name(market: &MarketIdentifier) -> String {
+    format!("{}/{}/{}", market.exchange_name, market.base, market.quote)
+}
+
+fn quote_value(quote: &PairQuote) -> RuntimeValue {
+    RuntimeValue::Object(runtime_value! {
+        "market": market_name(&quote.market),
+        "bid": quote.bid,
+        "ask": quote.ask,
+        "bid_volume": quote.bid_volume,
+        "ask_volume": quote.ask_volume,
+        "fee": quote.fee,
+    })
+}
+
+fn opportunity_value(opportunity: &ArbitrageOpportunity) -> RuntimeValue {
+    RuntimeValue::Object(runtime_value! {
+        "buy": market_name(&opportunity.buy),
+        "sell": market_name(&opportunity.sell),
+        "buy_price": opportunity.buy_price,
+        "sell_price": opportunity.sell_price,
+        "spread": opportunity.spread,
+        "net_spread": opportunity.net_spread,
+        "volume": opportunity.volume,
+        "profit": opportunity.profit,
+    })
+}
+
+fn comparison_value(comparison: &PairComparison) -> RuntimeValue {
+    RuntimeValue::Object(runtime_value! {
+        "pair": format!("{}/{}", comparison.base, comparison.quote),
+        "time": comparison.time as f64,
+        "quotes": comparison.quotes.iter().map(quote_value).collect::<Vec<_>>(),
+        "errors": comparison
+            .errors
+            .iter()
+            .map(|(exchange, error)| {
+                RuntimeValue::Object(runtime_value! {
+                    "exchange": exchange.clone(),
+                    "error": error.clone(),
+                })
+            })
+            .collect::<Vec<_>>(),
+        "best": comparison.best.as_ref().map(opportunity_value),
+    })
+}
+
+pub async fn main(
+    reactor: Reactor,
+    mut args: Vec<String>,
+    _stdin: Option<Receiver<ProgramOutput>>,
+    _stdout: Sender<ProgramOutput>,
+) -> Result<ProgramOutput> {
+    args.insert(0, "compare".to_string());
+    let app = clap::App::new("compare")
+        .about("Compare the prices of pairs across the exchanges and find arbitrage opportunities")
+        .arg(
+            Arg::new("pair")
+                .help("Pair (BTC/EUR), market, glob or watchlist whose pairs are compared")
+                .required(true)
+                .takes_value(true)
+                .multiple_values(true),
+        );
+    let app = app.try_get_matches_from(args)?;
+    let mut pairs: Vec<(String, String)> = Vec::new();
+    for raw in app.values_of("pair").unwrap() {
+        let markets = match raw.split_once('/') {
+            Some((base, quote)) if !quote.contains('/') && !crate::reactor::is_glob(raw) => {
+                vec![MarketIdentifier::from(format!("/{}/{}", base, quote))]
+            }
+            _ => reactor.resolve_markets(&[raw]).await?,
+        };
+        for market in markets {
+            let market = reactor.assets.canonical_market(&market);
+            if !pairs.contains(&(market.base.clone(), market.quote.clone())) {
+                pairs.push((market.base, market.quote));
+            }
+        }
+    }
+    let mut results = Vec::new();
+    for (base, quote) in pairs {
+        results.push(comparison_value(&reactor.compare_pair(&base, &quote).await?));
+    }
+    Ok(ProgramOutput::json(RuntimeValue::from(results)))
+}
+
+pub fn wrap() -> NativeProcedureGen {
+    Box::new(
+        |reactor: Reactor,
+         args: Vec<String>,
+         stdin: Option<Receiver<ProgramOutput>>,
+         stdout: Sender<ProgramOutput>| { Box::pin(main(reactor, args, stdin, stdout)) },
+    )
+}
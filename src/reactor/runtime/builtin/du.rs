@@ -0,0 +1,69 @@
+use super::*;
+
+pub async fn main(
+    reactor: Reactor,
+    mut args: Vec<String>,
+    _stdin: Option<Receiver<ProgramOutput>>,
+    _stdout: Sender<ProgramOutput>,
+) -> Result<ProgramOutput> {
+    args.insert(0, "du".to_string());
+    let app = clap::App::new("du")
+        .arg(
+            Arg::new("filter")
+                .help("Only report trees whose name contains this text (e.g. BTC/EUR)")
+                .index(1),
+        )
+        .arg(
+            Arg::new("summarize")
+                .help("Only report the totals")
+                .short('s')
+                .long("summarize"),
+        );
+    let app = app.try_get_matches_from(args)?;
+    let filter = app.value_of("filter").map(str::to_uppercase);
+    let usage: Vec<_> = reactor
+        .store
+        .usage()?
+        .into_iter()
+        .filter(|e| {
+            filter
+                .as_ref()
+                .map(|filter| e.name.to_uppercase().contains(filter))
+                .unwrap_or(true)
+        })
+        .collect();
+    let entries: u64 = usage.iter().map(|e| e.entries as u64).sum();
+    let bytes: u64 = usage.iter().map(|e| e.bytes).sum();
+    let mut result = runtime_value! {
+        "entries": entries,
+        "bytes": bytes,
+        "size_on_disk": reactor.store.size_on_disk()?,
+    };
+    if !app.is_present("summarize") {
+        result.insert(
+            "trees".to_string(),
+            RuntimeValue::from(
+                usage
+                    .iter()
+                    .map(|e| {
+                        RuntimeValue::Object(runtime_value! {
+                            "name": e.name.as_str(),
+                            "entries": e.entries as u64,
+                            "bytes": e.bytes,
+                        })
+                    })
+                    .collect::<Vec<_>>(),
+            ),
+        );
+    }
+    Ok(ProgramOutput::json(RuntimeValue::Object(result)))
+}
+
+pub fn wrap() -> NativeProcedureGen {
+    Box::new(
+        |reactor: Reactor,
+         args: Vec<String>,
+         stdin: Option<Receiver<ProgramOutput>>,
+         stdout: Sender<ProgramOutput>| { Box::pin(main(reactor, args, stdin, stdout)) },
+    )
+}
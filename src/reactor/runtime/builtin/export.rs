@@ -0,0 +1,62 @@
+use super::*;
+use crate::store::TransferFormat;
+
+pub async fn main(
+    reactor: Reactor,
+    mut args: Vec<String>,
+    _stdin: Option<Receiver<ProgramOutput>>,
+    _stdout: Sender<ProgramOutput>,
+) -> Result<ProgramOutput> {
+    args.insert(0, "export".to_string());
+    let app = clap::App::new("export")
+        .arg(Arg::new("path").required(true).takes_value(true).index(1))
+        .arg(
+            Arg::new("market")
+                .help("Only export this market (e.g. kraken/BTC/EUR)")
+                .short('m')
+                .long("market")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("interval")
+                .validator(ArgumentInterval::validator)
+                .short('i')
+                .long("interval")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("format")
+                .help("csv, jsonl or parquet, guessed from the extension by default")
+                .short('f')
+                .long("format")
+                .takes_value(true),
+        );
+    let app = app.try_get_matches_from(args)?;
+    let path = PathBuf::from(app.value_of("path").unwrap());
+    let format = TransferFormat::resolve(app.value_of("format"), &path)?;
+    let market = match app.value_of("market") {
+        Some(raw) => Some(reactor.assets.canonical_market(&reactor.resolve_market(raw).await?)),
+        None => None,
+    };
+    let interval = app
+        .value_of("interval")
+        .map(|e| ArgumentInterval::new(e).map(|e| e.normalized))
+        .transpose()?;
+    let count = reactor
+        .store
+        .export(market.as_ref(), interval, format, &path)
+        .await?;
+    Ok(ProgramOutput::json(RuntimeValue::Object(runtime_value! {
+        "path": path.display().to_string(),
+        "candles": count as u64,
+    })))
+}
+
+pub fn wrap() -> NativeProcedureGen {
+    Box::new(
+        |reactor: Reactor,
+         args: Vec<String>,
+         stdin: Option<Receiver<ProgramOutput>>,
+         stdout: Sender<ProgramOutput>| { Box::pin(main(reactor, args, stdin, stdout)) },
+    )
+}
@@ -0,0 +1,44 @@
+use super::*;
+
+pub async fn main(
+    reactor: Reactor,
+    mut args: Vec<String>,
+    _stdin: Option<Receiver<ProgramOutput>>,
+    _stdout: Sender<ProgramOutput>,
+) -> Result<ProgramOutput> {
+    args.insert(0, "history".to_string());
+    let app = clap::App::new("history").arg(
+        Arg::new("limit")
+            .takes_value(true)
+            .short('n')
+            .long("limit"),
+    );
+    let app = app.try_get_matches_from(args)?;
+    let limit: usize = app.value_of("limit").unwrap_or("20").parse()?;
+    let results: Vec<_> = reactor
+        .store
+        .history
+        .runs(limit)?
+        .into_iter()
+        .map(|run| {
+            RuntimeValue::Object(runtime_value! {
+                "run": run.run_id,
+                "id": run.program_id,
+                "status": format!("{:?}", run.status),
+                "started_at": run.started_at as f64,
+                "ended_at": run.ended_at.map(|e| RuntimeValue::from(e as f64)).unwrap_or(RuntimeValue::Undefined),
+                "source": run.source,
+            })
+        })
+        .collect();
+    Ok(ProgramOutput::json(RuntimeValue::from(results)))
+}
+
+pub fn wrap() -> NativeProcedureGen {
+    Box::new(
+        |reactor: Reactor,
+         args: Vec<String>,
+         stdin: Option<Receiver<ProgramOutput>>,
+         stdout: Sender<ProgramOutput>| { Box::pin(main(reactor, args, stdin, stdout)) },
+    )
+}
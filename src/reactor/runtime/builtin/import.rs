@@ -0,0 +1,37 @@
+use super::*;
+use crate::store::TransferFormat;
+
+pub async fn main(
+    reactor: Reactor,
+    mut args: Vec<String>,
+    _stdin: Option<Receiver<ProgramOutput>>,
+    _stdout: Sender<ProgramOutput>,
+) -> Result<ProgramOutput> {
+    args.insert(0, "import".to_string());
+    let app = clap::App::new("import")
+        .arg(Arg::new("path").required(true).takes_value(true).index(1))
+        .arg(
+            Arg::new("format")
+                .help("csv, jsonl or parquet, guessed from the extension by default")
+                .short('f')
+                .long("format")
+                .takes_value(true),
+        );
+    let app = app.try_get_matches_from(args)?;
+    let path = PathBuf::from(app.value_of("path").unwrap());
+    let format = TransferFormat::resolve(app.value_of("format"), &path)?;
+    let count = reactor.store.import(format, &path, &reactor.assets).await?;
+    Ok(ProgramOutput::json(RuntimeValue::Object(runtime_value! {
+        "path": path.display().to_string(),
+        "candles": count as u64,
+    })))
+}
+
+pub fn wrap() -> NativeProcedureGen {
+    Box::new(
+        |reactor: Reactor,
+         args: Vec<String>,
+         stdin: Option<Receiver<ProgramOutput>>,
+         stdout: Sender<ProgramOutput>| { Box::pin(main(reactor, args, stdin, stdout)) },
+    )
+}
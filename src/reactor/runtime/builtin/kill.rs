@@ -0,0 +1,41 @@
+use super::*;
+
+pub async fn main(
+    reactor: Reactor,
+    mut args: Vec<String>,
+    _stdin: Option<Receiver<ProgramOutput>>,
+    stdout: Sender<ProgramOutput>,
+) -> Result<ProgramOutput> {
+    args.insert(0, "kill".to_string());
+    let app = clap::App::new("kill").arg(
+        Arg::new("id")
+            .required(true)
+            .takes_value(true)
+            .multiple_values(true),
+    );
+    let app = app.try_get_matches_from(args)?;
+    for id in app.values_of("id").unwrap() {
+        let id: ProgramIdentifier = id.parse()?;
+        match reactor.kill_program(id).await? {
+            None => {
+                buitlin_print!(stdout, "Killed {}", id);
+            }
+            Some(status) => {
+                buitlin_print!(stdout, "{} already finished: {:?}", id, status);
+            }
+        }
+    }
+    Ok(ProgramOutput::Exit {
+        message: None,
+        status: ProgramStatus::Success,
+    })
+}
+
+pub fn wrap() -> NativeProcedureGen {
+    Box::new(
+        |reactor: Reactor,
+         args: Vec<String>,
+         stdin: Option<Receiver<ProgramOutput>>,
+         stdout: Sender<ProgramOutput>| { Box::pin(main(reactor, args, stdin, stdout)) },
+    )
+}
@@ -0,0 +1,49 @@
+use super::*;
+use crate::store::RecordedOutputKind;
+
+pub async fn main(
+    reactor: Reactor,
+    mut args: Vec<String>,
+    _stdin: Option<Receiver<ProgramOutput>>,
+    stdout: Sender<ProgramOutput>,
+) -> Result<ProgramOutput> {
+    args.insert(0, "logs".to_string());
+    let app = clap::App::new("logs").arg(
+        Arg::new("run")
+            .required(true)
+            .index(1)
+            .takes_value(true),
+    );
+    let app = app.try_get_matches_from(args)?;
+    let run_id: RunIdentifier = app.value_of("run").unwrap().parse()?;
+    if reactor.store.history.run(run_id)?.is_none() {
+        return Err(Error::RunNotFound(run_id));
+    }
+    for output in reactor.store.history.logs(run_id)? {
+        let time = NaiveDateTime::from_timestamp(output.time, 0);
+        match (output.kind, output.status) {
+            (RecordedOutputKind::Exit, Some(status)) if output.content.is_empty() => {
+                buitlin_print!(stdout, "[{}] exit {:?}", time, status);
+            }
+            (RecordedOutputKind::Exit, Some(status)) => {
+                buitlin_print!(stdout, "[{}] exit {:?}: {}", time, status, output.content);
+            }
+            _ => {
+                buitlin_print!(stdout, "[{}] {}", time, output.content);
+            }
+        }
+    }
+    Ok(ProgramOutput::Exit {
+        message: None,
+        status: ProgramStatus::Success,
+    })
+}
+
+pub fn wrap() -> NativeProcedureGen {
+    Box::new(
+        |reactor: Reactor,
+         args: Vec<String>,
+         stdin: Option<Receiver<ProgramOutput>>,
+         stdout: Sender<ProgramOutput>| { Box::pin(main(reactor, args, stdin, stdout)) },
+    )
+}
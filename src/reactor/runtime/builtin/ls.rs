@@ -15,7 +15,7 @@ struct DereferencedLsEntry {
     definition: MarketDefinition,
 }
 
-async fn get_markets(
+pub async fn get_markets(
     reactor: Reactor,
     exchange_filter: Option<&str>,
     base_filter: Option<String>,
@@ -29,13 +29,18 @@ async fn get_markets(
         .iter()
         .filter(|(name, _)| exchange_filter.as_ref().map(|e| e == *name).unwrap_or(true))
     {
-        match exchange.lock().await.get_markets().await {
+        // Filters may use any alias of the assets, `XBT` finds the `BTC` markets
+        let canonical = |code: &str| reactor.assets.canonical(name, code);
+        match exchange.get_markets().await {
             Ok(markets) => available.extend(markets.into_iter().filter(|e| {
                 quote_filter
                     .as_deref()
-                    .map(|f| f == e.quote)
+                    .map(|f| canonical(f) == canonical(&e.quote))
                     .unwrap_or(true)
-                    && base_filter.as_deref().map(|f| f == e.base).unwrap_or(true)
+                    && base_filter
+                        .as_deref()
+                        .map(|f| canonical(f) == canonical(&e.base))
+                        .unwrap_or(true)
             })),
             Err(e) => error!("Failed to fetch markets: EXCHANGE={}, ERROR={}", name, e),
         }
@@ -73,9 +78,8 @@ pub async fn main(
     if app.is_present("definition") {
         let mut ret: Vec<DereferencedLsEntry> = Vec::with_capacity(results.len());
         for market in results {
-            match reactor.exchanges.read().await[&market.exchange_name]
-                .lock()
-                .await
+            let exchange = reactor.exchanges.read().await[&market.exchange_name].clone();
+            match exchange
                 .get_market_definition(&market, None)
                 .await
             {
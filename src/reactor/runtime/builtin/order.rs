@@ -0,0 +1,65 @@
+use super::*;
+use crate::exchange::{OrderRequest, TradeSide};
+
+pub async fn main(
+    reactor: Reactor,
+    mut args: Vec<String>,
+    _stdin: Option<Receiver<ProgramOutput>>,
+    _stdout: Sender<ProgramOutput>,
+) -> Result<ProgramOutput> {
+    args.insert(0, "order".to_string());
+    let app = clap::App::new("order")
+        .about("Send an order once it passes the risk checks")
+        .arg(Arg::new("market").required(true).index(1))
+        .arg(
+            Arg::new("side")
+                .required(true)
+                .index(2)
+                .possible_values(&["buy", "sell"]),
+        )
+        .arg(Arg::new("volume").required(true).index(3))
+        .arg(
+            Arg::new("price")
+                .help("Limit price, a market order is sent without it")
+                .short('p')
+                .long("price")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("check")
+                .help("Only run the risk checks")
+                .short('c')
+                .long("check"),
+        );
+    let app = app.try_get_matches_from(args)?;
+    let market = reactor.resolve_market(app.value_of("market").unwrap()).await?;
+    let side = match app.value_of("side").unwrap() {
+        "sell" => TradeSide::Sell,
+        _ => TradeSide::Buy,
+    };
+    let volume: Decimal = app.value_of("volume").unwrap().parse()?;
+    let order = match app.value_of("price") {
+        Some(price) => OrderRequest::limit(market, side, volume, price.parse()?),
+        None => OrderRequest::market(market, side, volume),
+    };
+    if app.is_present("check") {
+        reactor.check_order(&order).await?;
+        return Ok(ProgramOutput::json(RuntimeValue::from(
+            format!("{} passes the risk checks", order).as_str(),
+        )));
+    }
+    let placed = reactor.submit_order(&order).await?;
+    Ok(ProgramOutput::json(RuntimeValue::Object(runtime_value! {
+        "order": order.to_string(),
+        "ids": placed.ids.iter().map(|e| RuntimeValue::from(e.as_str())).collect::<Vec<_>>(),
+    })))
+}
+
+pub fn wrap() -> NativeProcedureGen {
+    Box::new(
+        |reactor: Reactor,
+         args: Vec<String>,
+         stdin: Option<Receiver<ProgramOutput>>,
+         stdout: Sender<ProgramOutput>| { Box::pin(main(reactor, args, stdin, stdout)) },
+    )
+}
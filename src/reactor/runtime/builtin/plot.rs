@@ -0,0 +1,429 @@
+use super::*;
+use crate::indicator;
+use std::collections::BTreeMap;
+use crate::ui::chart::{CandlePart, PriceScale, AXIS_WIDTH};
+
+const DEFAULT_WIDTH: usize = 80;
+const VOLUME_HEIGHT: usize = 4;
+const VOLUME_BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const COLOR_UP: &str = "32";
+const COLOR_DOWN: &str = "31";
+const COLOR_AXIS: &str = "90";
+const COLOR_OVERLAYS: [&str; 4] = ["33", "35", "36", "34"];
+
+type Cell = (char, Option<&'static str>);
+
+struct Canvas {
+    rows: Vec<Vec<Cell>>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            rows: vec![vec![(' ', None); width]; height],
+        }
+    }
+
+    fn set(&mut self, x: usize, y: usize, symbol: char, color: &'static str) {
+        if let Some(cell) = self.rows.get_mut(y).and_then(|e| e.get_mut(x)) {
+            *cell = (symbol, Some(color));
+        }
+    }
+
+    fn is_empty(&self, x: usize, y: usize) -> bool {
+        self.rows
+            .get(y)
+            .and_then(|e| e.get(x))
+            .map(|e| e.0 == ' ')
+            .unwrap_or(false)
+    }
+
+    fn line(&self, y: usize, color: bool) -> String {
+        let mut ret = String::new();
+        for (symbol, code) in self.rows[y].iter() {
+            match code {
+                Some(code) if color && *symbol != ' ' => paint(&mut ret, &symbol.to_string(), code),
+                _ => ret.push(*symbol),
+            }
+        }
+        ret
+    }
+}
+
+fn paint(out: &mut String, text: &str, code: &str) {
+    out.push_str(&format!("\x1b[{}m{}\x1b[0m", code, text));
+}
+
+fn format_price(price: f64) -> String {
+    let decimals = if price.abs() >= 1000.0 {
+        2
+    } else if price.abs() >= 1.0 {
+        4
+    } else {
+        6
+    };
+    format!("{:.*}", decimals, price)
+}
+
+fn format_time(time: Timestamp) -> String {
+    NaiveDateTime::from_timestamp(time, 0)
+        .format("%m-%d %H:%M")
+        .to_string()
+}
+
+fn value_number(value: &BTreeMap<String, RuntimeValue>, key: &str) -> Option<f64> {
+    match value.get(key)? {
+        RuntimeValue::Number(n) => Some(*n),
+        RuntimeValue::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+fn candle_from_value(value: &RuntimeValue) -> Option<OHLC> {
+    match value {
+        RuntimeValue::Object(map) => OHLC::new(
+            false,
+            value_number(map, "time")? as Timestamp,
+            &value_number(map, "open")?.to_string(),
+            &value_number(map, "high")?.to_string(),
+            &value_number(map, "low")?.to_string(),
+            &value_number(map, "close")?.to_string(),
+            "0",
+            &value_number(map, "volume").unwrap_or(0.0).to_string(),
+            value_number(map, "count").unwrap_or(0.0) as u64,
+        )
+        .ok(),
+        _ => None,
+    }
+}
+
+/// Find the first series of candles in a piped value, `cat` outputs one array per market
+fn candles_from_value(value: &RuntimeValue) -> Vec<OHLC> {
+    match value {
+        RuntimeValue::Array(values) if values.iter().any(|e| matches!(e, RuntimeValue::Object(_))) => {
+            values.iter().filter_map(candle_from_value).collect()
+        }
+        RuntimeValue::Array(values) => values
+            .iter()
+            .map(candles_from_value)
+            .find(|e| !e.is_empty())
+            .unwrap_or_default(),
+        _ => vec![],
+    }
+}
+
+fn to_f64_series(series: Vec<Option<Decimal>>) -> Vec<Option<f64>> {
+    series.into_iter().map(|e| e.map(|e| e.to_f64())).collect()
+}
+
+struct Overlay {
+    label: String,
+    values: Vec<Option<f64>>,
+}
+
+struct PlotOptions {
+    width: usize,
+    height: usize,
+    line: bool,
+    volume: bool,
+    color: bool,
+}
+
+fn render(title: &str, candles: &[OHLC], overlays: &[Overlay], options: &PlotOptions) -> Option<String> {
+    let plot_width = options.width.checked_sub(AXIS_WIDTH as usize)?.max(1);
+    let offset = candles.len().saturating_sub(plot_width);
+    let candles = &candles[offset..];
+    let last = candles.last()?;
+
+    let overlay_values = overlays
+        .iter()
+        .flat_map(|e| e.values[offset..].iter().filter_map(|e| *e));
+    let (mut min, mut max) = candles.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), e| {
+        (min.min(e.low.to_f64()), max.max(e.high.to_f64()))
+    });
+    for value in overlay_values {
+        min = min.min(value);
+        max = max.max(value);
+    }
+    let scale = PriceScale::new(min, max, options.height as u16)?;
+
+    let mut canvas = Canvas::new(plot_width, options.height);
+    let mut prev_row: Option<usize> = None;
+    for (x, candle) in candles.iter().enumerate() {
+        let color = if candle.is_bullish() { COLOR_UP } else { COLOR_DOWN };
+        if options.line {
+            let row = scale.row(candle.close.to_f64()) as usize;
+            if let Some(prev) = prev_row {
+                for y in prev.min(row) + 1..prev.max(row) {
+                    canvas.set(x, y, '│', color);
+                }
+            }
+            canvas.set(x, row, '•', color);
+            prev_row = Some(row);
+        } else {
+            for (row, part) in scale.candle(candle) {
+                let symbol = match part {
+                    CandlePart::Body => '┃',
+                    CandlePart::Wick => '│',
+                };
+                canvas.set(x, row as usize, symbol, color);
+            }
+        }
+    }
+    for (overlay, color) in overlays.iter().zip(COLOR_OVERLAYS.iter().cycle()) {
+        for (x, value) in overlay.values[offset..].iter().enumerate() {
+            if let Some(value) = value {
+                let row = scale.row(*value) as usize;
+                if canvas.is_empty(x, row) {
+                    canvas.set(x, row, '·', color);
+                }
+            }
+        }
+    }
+
+    let mut out = String::new();
+    let change = indicator::percent_change(candles[0].open, last.close)
+        .map(|e| format!(" ({:+.2}%)", e.to_f64()))
+        .unwrap_or_default();
+    out.push_str(&format!(
+        "{}  O {} H {} L {} C {}{}",
+        title,
+        last.open,
+        last.high,
+        last.low,
+        last.close,
+        change
+    ));
+    for (overlay, color) in overlays.iter().zip(COLOR_OVERLAYS.iter().cycle()) {
+        out.push_str("  ");
+        if options.color {
+            paint(&mut out, &overlay.label, color);
+        } else {
+            out.push_str(&overlay.label);
+        }
+    }
+    out.push('\n');
+
+    let label_every = (options.height / 5).max(1);
+    for y in 0..options.height {
+        out.push_str(&canvas.line(y, options.color));
+        if y % label_every == 0 || y == options.height - 1 {
+            let label = format!(" {}", format_price(scale.price_at(y as u16)));
+            if options.color {
+                paint(&mut out, &label, COLOR_AXIS);
+            } else {
+                out.push_str(&label);
+            }
+        }
+        out.push('\n');
+    }
+
+    if options.volume {
+        let volumes: Vec<f64> = candles
+            .iter()
+            .map(|e| e.volume.to_f64())
+            .collect();
+        let max_volume = volumes.iter().cloned().fold(0.0, f64::max);
+        let mut volume_canvas = Canvas::new(plot_width, VOLUME_HEIGHT);
+        if max_volume > 0.0 {
+            for (x, (volume, candle)) in volumes.iter().zip(candles).enumerate() {
+                let color = if candle.is_bullish() { COLOR_UP } else { COLOR_DOWN };
+                let level = (volume / max_volume * (VOLUME_HEIGHT * 8) as f64).round() as usize;
+                for y in 0..VOLUME_HEIGHT {
+                    let fill = level.saturating_sub((VOLUME_HEIGHT - 1 - y) * 8).min(8);
+                    if fill > 0 {
+                        volume_canvas.set(x, y, VOLUME_BLOCKS[fill - 1], color);
+                    }
+                }
+            }
+        }
+        for y in 0..VOLUME_HEIGHT {
+            out.push_str(&volume_canvas.line(y, options.color));
+            if y == 0 {
+                out.push_str(&format!(" vol {}", format_price(max_volume)));
+            }
+            out.push('\n');
+        }
+    }
+
+    let mut axis: Vec<char> = vec![' '; plot_width];
+    let labels = [0, plot_width / 2, plot_width.saturating_sub(1)];
+    let mut next_free = 0;
+    for x in labels.iter() {
+        let candle = match candles.get(*x) {
+            Some(candle) => candle,
+            None => continue,
+        };
+        let label: Vec<char> = format_time(candle.time).chars().collect();
+        let start = x.saturating_sub(label.len() / 2).min(plot_width.saturating_sub(label.len()));
+        if start < next_free || start + label.len() > plot_width {
+            continue;
+        }
+        axis[start..start + label.len()].copy_from_slice(&label);
+        next_free = start + label.len() + 1;
+    }
+    out.push_str(axis.into_iter().collect::<String>().trim_end());
+    Some(out)
+}
+
+async fn load_candles(
+    reactor: &Reactor,
+    market: &str,
+    interval: Interval,
+    from: Timestamp,
+    to: Timestamp,
+) -> Result<Vec<OHLC>> {
+    let target_market = reactor
+        .get_or_register_market(&reactor.resolve_market(market).await?)
+        .await?;
+    target_market.sync_periode(from, to, interval).await?;
+    target_market.interval(interval).await?.close_range(from, to)
+}
+
+pub async fn main(
+    reactor: Reactor,
+    mut args: Vec<String>,
+    stdin: Option<Receiver<ProgramOutput>>,
+    stdout: Sender<ProgramOutput>,
+) -> Result<ProgramOutput> {
+    args.insert(0, "plot".to_string());
+    let app = clap::App::new("plot")
+        .arg(Arg::new("market_name").takes_value(true).index(1))
+        .arg(
+            Arg::new("interval")
+                .validator(ArgumentInterval::validator)
+                .takes_value(true)
+                .short('i')
+                .long("interval"),
+        )
+        .arg(
+            Arg::new("from")
+                .validator(ArgumentTimestamp::validator)
+                .takes_value(true)
+                .short('f')
+                .long("from"),
+        )
+        .arg(
+            Arg::new("to")
+                .validator(ArgumentTimestamp::validator)
+                .takes_value(true)
+                .short('t')
+                .long("to"),
+        )
+        .arg(Arg::new("line").help("Plot closes as a line").short('l').long("line"))
+        .arg(Arg::new("width").takes_value(true).short('w').long("width"))
+        .arg(Arg::new("height").takes_value(true).short('H').long("height"))
+        .arg(
+            Arg::new("sma")
+                .help("Overlay a simple moving average of the given period")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .long("sma"),
+        )
+        .arg(
+            Arg::new("ema")
+                .help("Overlay an exponential moving average of the given period")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .long("ema"),
+        )
+        .arg(Arg::new("no_volume").long("no-volume"))
+        .arg(Arg::new("no_color").long("no-color"));
+    let app = app.try_get_matches_from(args)?;
+
+    let width = match app.value_of("width") {
+        Some(width) => width.parse()?,
+        None => crossterm::terminal::size()
+            .map(|(width, _)| width as usize)
+            .unwrap_or(DEFAULT_WIDTH),
+    };
+    let options = PlotOptions {
+        width,
+        height: app.value_of("height").unwrap_or("20").parse()?,
+        line: app.is_present("line"),
+        volume: !app.is_present("no_volume"),
+        color: !app.is_present("no_color"),
+    };
+    if options.height < 2 || options.width <= AXIS_WIDTH as usize {
+        return Ok(ProgramOutput::Exit {
+            message: Some(format!(
+                "Plot area is too small: WIDTH={}, HEIGHT={}",
+                options.width, options.height
+            )),
+            status: ProgramStatus::Error,
+        });
+    }
+
+    let interval = ArgumentInterval::new(app.value_of("interval").unwrap_or("1h"))?;
+    let (title, candles) = match (app.value_of("market_name"), stdin) {
+        (Some(market), _) => {
+            let now = SystemTime::now();
+            let to = app
+                .value_of("to")
+                .and_then(|e| ArgumentTimestamp::new(e, now).ok())
+                .map(|e| e.timestamp())
+                .unwrap_or(now.duration_since(UNIX_EPOCH).unwrap().as_secs() as Timestamp);
+            let from = app
+                .value_of("from")
+                .and_then(|e| ArgumentTimestamp::new(e, now).ok())
+                .map(|e| e.timestamp())
+                .unwrap_or(to - interval.normalized.as_secs() * options.width as i64);
+            let candles = load_candles(&reactor, market, interval.normalized, from, to).await?;
+            (format!("{} {}", market, interval.raw), candles)
+        }
+        (None, Some(mut stdin)) => {
+            let mut candles = Vec::new();
+            while let Some(output) = stdin.recv().await {
+                if let ProgramOutput::Json { content } = output {
+                    if candles.is_empty() {
+                        candles = candles_from_value(&content);
+                    }
+                }
+            }
+            ("stdin".to_string(), candles)
+        }
+        (None, None) => {
+            return Ok(ProgramOutput::Exit {
+                message: Some(String::from("Expected a market or piped candles")),
+                status: ProgramStatus::Error,
+            });
+        }
+    };
+
+    let closes = indicator::closes(&candles);
+    let mut overlays = Vec::new();
+    for period in app.values_of("sma").into_iter().flatten() {
+        let period: usize = period.parse()?;
+        overlays.push(Overlay {
+            label: format!("SMA({})", period),
+            values: to_f64_series(indicator::sma_series(&closes, period)),
+        });
+    }
+    for period in app.values_of("ema").into_iter().flatten() {
+        let period: usize = period.parse()?;
+        overlays.push(Overlay {
+            label: format!("EMA({})", period),
+            values: to_f64_series(indicator::ema_series(&closes, period)),
+        });
+    }
+
+    match render(&title, &candles, &overlays, &options) {
+        Some(chart) => {
+            buitlin_print!(stdout, "{}", chart);
+            Ok(ProgramOutput::Exit {
+                message: None,
+                status: ProgramStatus::Success,
+            })
+        }
+        None => Err(Error::NoData),
+    }
+}
+
+pub fn wrap() -> NativeProcedureGen {
+    Box::new(
+        |reactor: Reactor,
+         args: Vec<String>,
+         stdin: Option<Receiver<ProgramOutput>>,
+         stdout: Sender<ProgramOutput>| { Box::pin(main(reactor, args, stdin, stdout)) },
+    )
+}
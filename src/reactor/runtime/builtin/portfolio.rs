@@ -0,0 +1,282 @@
+use super::*;
+use crate::exchange::{Fill, TradeSide};
+use crate::reactor::{CostMethod, EquityCurve, PortfolioReport, DEFAULT_PORTFOLIO_QUOTE};
+use clap::{App, ArgMatches};
+
+fn strings(values: &[String]) -> RuntimeValue {
+    RuntimeValue::from(
+        values
+            .iter()
+            .map(|e| RuntimeValue::from(e.as_str()))
+            .collect::<Vec<_>>(),
+    )
+}
+
+fn report_value(report: &PortfolioReport) -> RuntimeValue {
+    RuntimeValue::Object(runtime_value! {
+        "time": report.time as f64,
+        "quote": report.quote.clone(),
+        "method": report.method.to_string(),
+        "value": report.value,
+        "realized": report.realized,
+        "unrealized": report.unrealized,
+        "holdings": report
+            .holdings
+            .iter()
+            .map(|e| {
+                RuntimeValue::Object(runtime_value! {
+                    "exchange": e.exchange.clone(),
+                    "asset": e.asset.clone(),
+                    "volume": e.volume,
+                    "price": e.price,
+                    "value": e.value,
+                })
+            })
+            .collect::<Vec<_>>(),
+        "positions": report
+            .positions
+            .iter()
+            .map(|e| {
+                RuntimeValue::Object(runtime_value! {
+                    "pair": e.pair.clone(),
+                    "volume": e.volume,
+                    "cost": e.cost,
+                    "average_cost": e.average_cost,
+                    "price": e.price,
+                    "realized": e.realized,
+                    "unrealized": e.unrealized,
+                })
+            })
+            .collect::<Vec<_>>(),
+        "errors": strings(&report.errors),
+    })
+}
+
+fn curve_value(curve: &EquityCurve) -> RuntimeValue {
+    RuntimeValue::Object(runtime_value! {
+        "quote": curve.quote.clone(),
+        "interval": curve.interval.to_string(),
+        "points": curve
+            .points
+            .iter()
+            .map(|e| {
+                RuntimeValue::Object(runtime_value! {
+                    "time": e.time as f64,
+                    "value": e.value,
+                })
+            })
+            .collect::<Vec<_>>(),
+        "errors": strings(&curve.errors),
+    })
+}
+
+async fn report(reactor: Reactor, app: &ArgMatches) -> Result<ProgramOutput> {
+    let method: CostMethod = app.value_of("method").unwrap().parse()?;
+    let report = reactor
+        .portfolio(app.value_of("quote").unwrap(), method)
+        .await?;
+    Ok(ProgramOutput::json(report_value(&report)))
+}
+
+async fn sync(reactor: Reactor) -> Result<ProgramOutput> {
+    let import = reactor.import_fills().await?;
+    Ok(ProgramOutput::json(RuntimeValue::Object(runtime_value! {
+        "imported": import.imported as u64,
+        "errors": strings(&import.errors),
+    })))
+}
+
+fn add(reactor: Reactor, app: &ArgMatches) -> Result<ProgramOutput> {
+    let market = reactor
+        .assets
+        .canonical_market(&MarketIdentifier::from(app.value_of("market").unwrap()));
+    if market.exchange_name.is_empty() || market.base.is_empty() || market.quote.is_empty() {
+        return Err(Error::Parsing(
+            format!(
+                "Wrong market `{}`, expected exchange/BASE/QUOTE",
+                app.value_of("market").unwrap()
+            ),
+            0..0,
+        ));
+    }
+    let time = match app.value_of("time") {
+        Some(raw) => ArgumentTimestamp::new(raw, SystemTime::now())?.timestamp(),
+        None => crate::store::now(),
+    };
+    let fill = Fill {
+        id: reactor.store.fills.manual_id()?,
+        market: format!("{}/{}/{}", market.exchange_name, market.base, market.quote),
+        time,
+        side: match app.value_of("side").unwrap() {
+            "sell" => TradeSide::Sell,
+            _ => TradeSide::Buy,
+        },
+        volume: app.value_of("volume").unwrap().parse()?,
+        price: app.value_of("price").unwrap().parse()?,
+        fee: app.value_of("fee").unwrap().parse()?,
+        order: None,
+    };
+    reactor.store.fills.extend(&[fill.clone()])?;
+    Ok(ProgramOutput::json(RuntimeValue::from(&fill)))
+}
+
+fn rm(reactor: Reactor, app: &ArgMatches) -> Result<ProgramOutput> {
+    let id = app.value_of("id").unwrap();
+    if !reactor.store.fills.remove(id)? {
+        return Err(Error::Parsing(format!("No fill `{}`", id), 0..0));
+    }
+    Ok(ProgramOutput::json(RuntimeValue::from(id)))
+}
+
+fn fills(reactor: Reactor, app: &ArgMatches) -> Result<ProgramOutput> {
+    let pattern = app.value_of("market");
+    let fills = reactor
+        .store
+        .fills
+        .list()?
+        .into_iter()
+        .filter(|e| {
+            pattern
+                .map(|pattern| crate::reactor::market_matches(pattern, &e.market_id()))
+                .unwrap_or(true)
+        })
+        .collect::<Vec<_>>();
+    Ok(ProgramOutput::json(RuntimeValue::from(
+        fills.iter().map(RuntimeValue::from).collect::<Vec<_>>(),
+    )))
+}
+
+async fn equity(reactor: Reactor, app: &ArgMatches) -> Result<ProgramOutput> {
+    let now = SystemTime::now();
+    let from = ArgumentTimestamp::new(app.value_of("from").unwrap(), now)?.timestamp();
+    let to = match app.value_of("to") {
+        Some(raw) => ArgumentTimestamp::new(raw, now)?.timestamp(),
+        None => crate::store::now(),
+    };
+    let interval = ArgumentInterval::new(app.value_of("interval").unwrap())?.normalized;
+    let curve = reactor
+        .equity_curve(app.value_of("quote").unwrap(), from, to, interval)
+        .await?;
+    Ok(ProgramOutput::json(curve_value(&curve)))
+}
+
+pub async fn main(
+    reactor: Reactor,
+    mut args: Vec<String>,
+    _stdin: Option<Receiver<ProgramOutput>>,
+    _stdout: Sender<ProgramOutput>,
+) -> Result<ProgramOutput> {
+    args.insert(0, "portfolio".to_string());
+    let quote = || {
+        Arg::new("quote")
+            .help("Asset in which the portfolio is valued")
+            .short('q')
+            .long("quote")
+            .takes_value(true)
+            .default_value(DEFAULT_PORTFOLIO_QUOTE)
+    };
+    let app = App::new("portfolio")
+        .about("Value the holdings and report the P&L of the trades of the account")
+        .subcommand(
+            App::new("report")
+                .about("Value the holdings and the positions, the default")
+                .arg(quote())
+                .arg(
+                    Arg::new("method")
+                        .help("Cost of the volume sold: fifo or average")
+                        .short('m')
+                        .long("method")
+                        .takes_value(true)
+                        .default_value("fifo"),
+                ),
+        )
+        .subcommand(App::new("sync").about("Import the new trades of the exchanges"))
+        .subcommand(
+            App::new("add")
+                .about("Record a trade by hand")
+                .arg(Arg::new("market").required(true).index(1))
+                .arg(
+                    Arg::new("side")
+                        .required(true)
+                        .index(2)
+                        .possible_values(&["buy", "sell"]),
+                )
+                .arg(Arg::new("volume").required(true).index(3))
+                .arg(Arg::new("price").required(true).index(4))
+                .arg(
+                    Arg::new("fee")
+                        .help("Fee paid, in the quote asset")
+                        .long("fee")
+                        .takes_value(true)
+                        .default_value("0"),
+                )
+                .arg(
+                    Arg::new("time")
+                        .validator(ArgumentTimestamp::validator)
+                        .long("time")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            App::new("rm")
+                .about("Remove a trade")
+                .arg(Arg::new("id").required(true).index(1)),
+        )
+        .subcommand(
+            App::new("fills")
+                .about("List the recorded trades")
+                .arg(Arg::new("market").help("Market or glob").index(1)),
+        )
+        .subcommand(
+            App::new("equity")
+                .about("Value of the holdings over time, priced with the stored candles")
+                .arg(quote())
+                .arg(
+                    Arg::new("interval")
+                        .validator(ArgumentInterval::validator)
+                        .short('i')
+                        .long("interval")
+                        .takes_value(true)
+                        .default_value("1d"),
+                )
+                .arg(
+                    Arg::new("from")
+                        .validator(ArgumentTimestamp::validator)
+                        .short('f')
+                        .long("from")
+                        .takes_value(true)
+                        .default_value("NOW-30d"),
+                )
+                .arg(
+                    Arg::new("to")
+                        .validator(ArgumentTimestamp::validator)
+                        .short('t')
+                        .long("to")
+                        .takes_value(true),
+                ),
+        );
+    let app = app.try_get_matches_from(args)?;
+    match app.subcommand() {
+        Some(("report", app)) => report(reactor, app).await,
+        Some(("sync", _)) => sync(reactor).await,
+        Some(("add", app)) => add(reactor, app),
+        Some(("rm", app)) => rm(reactor, app),
+        Some(("fills", app)) => fills(reactor, app),
+        Some(("equity", app)) => equity(reactor, app).await,
+        _ => {
+            let report = reactor
+                .portfolio(DEFAULT_PORTFOLIO_QUOTE, CostMethod::Fifo)
+                .await?;
+            Ok(ProgramOutput::json(report_value(&report)))
+        }
+    }
+}
+
+pub fn wrap() -> NativeProcedureGen {
+    Box::new(
+        |reactor: Reactor,
+         args: Vec<String>,
+         stdin: Option<Receiver<ProgramOutput>>,
+         stdout: Sender<ProgramOutput>| { Box::pin(main(reactor, args, stdin, stdout)) },
+    )
+}
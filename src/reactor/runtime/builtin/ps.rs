@@ -0,0 +1,39 @@
+use super::*;
+
+pub async fn main(
+    reactor: Reactor,
+    mut args: Vec<String>,
+    _stdin: Option<Receiver<ProgramOutput>>,
+    _stdout: Sender<ProgramOutput>,
+) -> Result<ProgramOutput> {
+    args.insert(0, "ps".to_string());
+    let app = clap::App::new("ps");
+    let _app = app.try_get_matches_from(args)?;
+    let results: Vec<_> = reactor
+        .programs
+        .read()
+        .await
+        .iter()
+        .sorted_by_key(|(id, _)| **id)
+        .map(|(id, runtime)| {
+            RuntimeValue::Object(runtime_value! {
+                "id": *id,
+                "run": runtime.run_id.map(RuntimeValue::from).unwrap_or(RuntimeValue::Undefined),
+                "status": format!("{:?}", *runtime.status.borrow()),
+                "elapsed": runtime.started_at.elapsed().map(|e| e.as_secs()).unwrap_or(0),
+                "timeout": runtime.timeout.map(|e| e.as_secs()).unwrap_or(0),
+                "source": runtime.source.as_str(),
+            })
+        })
+        .collect();
+    Ok(ProgramOutput::json(RuntimeValue::from(results)))
+}
+
+pub fn wrap() -> NativeProcedureGen {
+    Box::new(
+        |reactor: Reactor,
+         args: Vec<String>,
+         stdin: Option<Receiver<ProgramOutput>>,
+         stdout: Sender<ProgramOutput>| { Box::pin(main(reactor, args, stdin, stdout)) },
+    )
+}
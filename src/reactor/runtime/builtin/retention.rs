@@ -0,0 +1,206 @@
+use super::*;
+use crate::store::{CompactionReport, RetentionRule};
+use clap::{App, ArgMatches};
+
+fn checked(raw: &str, market: MarketIdentifier) -> Result<MarketIdentifier> {
+    if market.exchange_name.is_empty() || market.base.is_empty() || market.quote.is_empty() {
+        return Err(Error::Parsing(
+            format!("Wrong market `{}`, expected `exchange/base/quote`", raw),
+            0..0,
+        ));
+    }
+    Ok(market)
+}
+
+/// The market of the `market` argument, which may be a glob or a watchlist matching a single one
+async fn market_arg(reactor: &Reactor, app: &ArgMatches) -> Result<MarketIdentifier> {
+    let raw = app.value_of("market").unwrap();
+    checked(raw, reactor.resolve_market(raw).await?)
+}
+
+/// Every market matched by the `market` argument
+async fn market_args(reactor: &Reactor, app: &ArgMatches) -> Result<Vec<MarketIdentifier>> {
+    let raw = app.value_of("market").unwrap();
+    reactor
+        .resolve_markets(&[raw])
+        .await?
+        .into_iter()
+        .map(|e| checked(raw, e))
+        .collect()
+}
+
+fn market_name(market: &MarketIdentifier) -> String {
+    format!("{}/{}/{}", market.exchange_name, market.base, market.quote)
+}
+
+fn rule_value(market: &MarketIdentifier, rule: &RetentionRule) -> RuntimeValue {
+    RuntimeValue::Object(runtime_value! {
+        "market": market_name(market),
+        "interval": format!("{}", rule.interval),
+        "max_age": rule.max_age.map(RuntimeValue::from).unwrap_or(RuntimeValue::Undefined),
+        "downsample": rule.downsample.map(|e| RuntimeValue::from(format!("{}", e))).unwrap_or(RuntimeValue::Undefined),
+    })
+}
+
+fn report_value(report: &CompactionReport) -> RuntimeValue {
+    RuntimeValue::Object(runtime_value! {
+        "market": market_name(&report.market),
+        "interval": format!("{}", report.interval),
+        "downsampled": report.downsampled as u64,
+        "removed": report.removed as u64,
+    })
+}
+
+async fn set(reactor: Reactor, app: &ArgMatches) -> Result<ProgramOutput> {
+    let market = reactor.market_store(&market_arg(&reactor, app).await?)?;
+    let rule = RetentionRule {
+        interval: ArgumentInterval::new(app.value_of("interval").unwrap())?.normalized,
+        max_age: app
+            .value_of("keep")
+            .map(human_duration)
+            .transpose()?
+            .map(|e| e.as_secs()),
+        downsample: app
+            .value_of("downsample")
+            .map(|e| ArgumentInterval::new(e).map(|e| e.normalized))
+            .transpose()?,
+    };
+    rule.validate()?;
+    let mut settings = market.settings()?;
+    settings.set_retention(rule.clone());
+    market.set_settings(&settings)?;
+    Ok(ProgramOutput::json(rule_value(&market.id, &rule)))
+}
+
+async fn rm(reactor: Reactor, app: &ArgMatches) -> Result<ProgramOutput> {
+    let market = reactor.market_store(&market_arg(&reactor, app).await?)?;
+    let interval = ArgumentInterval::new(app.value_of("interval").unwrap())?.normalized;
+    let mut settings = market.settings()?;
+    if settings.retention(interval).is_none() {
+        return Err(Error::InvalidRetention(format!(
+            "no rule for {} on {}",
+            interval,
+            market_name(&market.id)
+        )));
+    }
+    settings.retention.retain(|e| e.interval != interval);
+    market.set_settings(&settings)?;
+    Ok(ProgramOutput::Exit {
+        message: None,
+        status: ProgramStatus::Success,
+    })
+}
+
+async fn ls(reactor: Reactor, app: &ArgMatches) -> Result<ProgramOutput> {
+    let markets = match app.value_of("market") {
+        Some(_) => market_args(&reactor, app).await?,
+        None => {
+            let mut markets: Vec<MarketIdentifier> = Vec::new();
+            for (market, _) in reactor.store.market_trees()? {
+                if !markets.contains(&market) {
+                    markets.push(market);
+                }
+            }
+            markets
+        }
+    };
+    let mut results = Vec::new();
+    for market in markets {
+        let market = reactor.market_store(&market)?;
+        for rule in market.settings()?.retention.iter() {
+            results.push(rule_value(&market.id, rule));
+        }
+    }
+    Ok(ProgramOutput::json(RuntimeValue::from(results)))
+}
+
+async fn compact(reactor: Reactor, app: &ArgMatches) -> Result<ProgramOutput> {
+    let reports = match app.value_of("market") {
+        Some(_) => {
+            let mut reports = Vec::new();
+            for market in market_args(&reactor, app).await? {
+                reports.extend(reactor.market_store(&market)?.compact(crate::store::now()).await?);
+            }
+            reports
+        }
+        None => reactor.store.compact().await?,
+    };
+    Ok(ProgramOutput::json(RuntimeValue::from(
+        reports.iter().map(report_value).collect::<Vec<_>>(),
+    )))
+}
+
+pub async fn main(
+    reactor: Reactor,
+    mut args: Vec<String>,
+    _stdin: Option<Receiver<ProgramOutput>>,
+    _stdout: Sender<ProgramOutput>,
+) -> Result<ProgramOutput> {
+    args.insert(0, "retention".to_string());
+    let app = App::new("retention")
+        .subcommand(
+            App::new("set")
+                .about("Set the retention rule of a market interval")
+                .arg(Arg::new("market").required(true).index(1))
+                .arg(
+                    Arg::new("interval")
+                        .validator(ArgumentInterval::validator)
+                        .required(true)
+                        .index(2),
+                )
+                .arg(
+                    Arg::new("keep")
+                        .help("Remove candles older than this duration (e.g. 90d), kept forever by default")
+                        .long("keep")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("downsample")
+                        .help("Aggregate candles into this interval before removing them (e.g. 1h)")
+                        .validator(ArgumentInterval::validator)
+                        .long("downsample")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            App::new("rm")
+                .about("Remove the retention rule of a market interval")
+                .arg(Arg::new("market").required(true).index(1))
+                .arg(
+                    Arg::new("interval")
+                        .validator(ArgumentInterval::validator)
+                        .required(true)
+                        .index(2),
+                ),
+        )
+        .subcommand(
+            App::new("ls")
+                .about("List retention rules")
+                .arg(Arg::new("market").index(1)),
+        )
+        .subcommand(
+            App::new("compact")
+                .about("Apply the retention rules now")
+                .arg(Arg::new("market").index(1)),
+        );
+    let app = app.try_get_matches_from(args)?;
+    match app.subcommand() {
+        Some(("set", app)) => set(reactor, app).await,
+        Some(("rm", app)) => rm(reactor, app).await,
+        Some(("ls", app)) => ls(reactor, app).await,
+        Some(("compact", app)) => compact(reactor, app).await,
+        _ => Err(Error::Parsing(
+            String::from("Expected one of: set, rm, ls, compact"),
+            0..0,
+        )),
+    }
+}
+
+pub fn wrap() -> NativeProcedureGen {
+    Box::new(
+        |reactor: Reactor,
+         args: Vec<String>,
+         stdin: Option<Receiver<ProgramOutput>>,
+         stdout: Sender<ProgramOutput>| { Box::pin(main(reactor, args, stdin, stdout)) },
+    )
+}
@@ -0,0 +1,74 @@
+use super::*;
+use clap::App;
+
+pub async fn main(
+    reactor: Reactor,
+    mut args: Vec<String>,
+    _stdin: Option<Receiver<ProgramOutput>>,
+    _stdout: Sender<ProgramOutput>,
+) -> Result<ProgramOutput> {
+    args.insert(0, "risk".to_string());
+    let app = App::new("risk")
+        .about("Inspect the risk limits and operate the kill switch")
+        .subcommand(App::new("status").about("Limits, kill switch and usage, the default"))
+        .subcommand(
+            App::new("halt")
+                .about("Engage the kill switch, every order is rejected until `risk resume`")
+                .arg(
+                    Arg::new("reason")
+                        .takes_value(true)
+                        .multiple_values(true)
+                        .index(1),
+                ),
+        )
+        .subcommand(App::new("resume").about("Release the kill switch"));
+    let app = app.try_get_matches_from(args)?;
+    match app.subcommand() {
+        Some(("halt", app)) => {
+            let reason = app
+                .values_of("reason")
+                .map(|e| e.collect::<Vec<_>>().join(" "))
+                .unwrap_or_else(|| String::from("manual halt"));
+            let kill_switch = reactor.engage_kill_switch(&reason).await?;
+            Ok(ProgramOutput::json(RuntimeValue::Object(runtime_value! {
+                "reason": kill_switch.reason,
+                "engaged_at": kill_switch.engaged_at as f64,
+            })))
+        }
+        Some(("resume", _)) => {
+            let released = reactor.release_kill_switch().await?;
+            Ok(ProgramOutput::json(RuntimeValue::from(match released {
+                true => "kill switch released",
+                false => "kill switch was not engaged",
+            })))
+        }
+        _ => {
+            let status = reactor.risk_status().await?;
+            Ok(ProgramOutput::json(RuntimeValue::Object(runtime_value! {
+                "kill_switch": status.kill_switch.map(|e| e.reason),
+                "quote": status.quote,
+                "max_order_notional": status.max_order_notional,
+                "max_position": RuntimeValue::Object(
+                    status
+                        .max_position
+                        .into_iter()
+                        .map(|(asset, limit)| (asset, RuntimeValue::from(limit)))
+                        .collect(),
+                ),
+                "daily_loss_limit": status.daily_loss_limit,
+                "max_orders_per_minute": status.max_orders_per_minute.map(|e| e as u64),
+                "orders_last_minute": status.orders_last_minute as u64,
+                "daily_realized": status.daily_realized,
+            })))
+        }
+    }
+}
+
+pub fn wrap() -> NativeProcedureGen {
+    Box::new(
+        |reactor: Reactor,
+         args: Vec<String>,
+         stdin: Option<Receiver<ProgramOutput>>,
+         stdout: Sender<ProgramOutput>| { Box::pin(main(reactor, args, stdin, stdout)) },
+    )
+}
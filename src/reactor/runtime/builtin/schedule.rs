@@ -0,0 +1,159 @@
+use super::*;
+use crate::store::{now, MissedRunPolicy, OverlapPolicy, Schedule, ScheduleTrigger};
+use clap::{App, ArgGroup, ArgMatches};
+
+fn parse_overlap(raw: &str) -> Result<OverlapPolicy> {
+    match raw {
+        "skip" => Ok(OverlapPolicy::Skip),
+        "queue" => Ok(OverlapPolicy::Queue),
+        "cancel" => Ok(OverlapPolicy::CancelPrevious),
+        _ => Err(Error::Parsing(
+            format!("Wrong overlap policy `{}`, expected one of: skip, queue, cancel", raw),
+            0..0,
+        )),
+    }
+}
+
+fn parse_missed(raw: &str) -> Result<MissedRunPolicy> {
+    match raw {
+        "skip" => Ok(MissedRunPolicy::Skip),
+        "run" => Ok(MissedRunPolicy::RunOnce),
+        _ => Err(Error::Parsing(
+            format!("Wrong missed run policy `{}`, expected one of: skip, run", raw),
+            0..0,
+        )),
+    }
+}
+
+async fn add(reactor: Reactor, app: &ArgMatches) -> Result<ProgramOutput> {
+    let name = app.value_of("name").unwrap().to_string();
+    let source = match (app.value_of("source"), app.value_of("file")) {
+        (_, Some(path)) => tokio::fs::read_to_string(path).await?,
+        (Some(source), None) => source.to_string(),
+        (None, None) => unreachable!(),
+    };
+    let trigger = if let Some(expression) = app.value_of("cron") {
+        ScheduleTrigger::Cron {
+            expression: expression.to_string(),
+        }
+    } else if let Some(every) = app.value_of("every") {
+        ScheduleTrigger::Every {
+            secs: human_duration(every)?.as_secs() as i64,
+        }
+    } else {
+        ScheduleTrigger::Candle {
+            interval: ArgumentInterval::new(app.value_of("candle").unwrap())?.normalized,
+        }
+    };
+    let schedule = Schedule {
+        name,
+        source,
+        trigger,
+        overlap: parse_overlap(app.value_of("overlap").unwrap_or("skip"))?,
+        missed: parse_missed(app.value_of("missed").unwrap_or("run"))?,
+        timeout: app
+            .value_of("timeout")
+            .map(human_duration)
+            .transpose()?
+            .map(|e| e.as_secs()),
+        created_at: now(),
+        last_run: None,
+    };
+    reactor.add_schedule(schedule).await?;
+    Ok(ProgramOutput::Exit {
+        message: None,
+        status: ProgramStatus::Success,
+    })
+}
+
+fn ls(reactor: Reactor) -> Result<ProgramOutput> {
+    let results: Vec<_> = reactor
+        .store
+        .schedules
+        .list()?
+        .into_iter()
+        .map(|schedule| {
+            let next_run = schedule
+                .next_run()
+                .map(|e| RuntimeValue::from(e as f64))
+                .unwrap_or(RuntimeValue::Undefined);
+            RuntimeValue::Object(runtime_value! {
+                "name": schedule.name.as_str(),
+                "trigger": format!("{}", schedule.trigger),
+                "overlap": format!("{:?}", schedule.overlap),
+                "missed": format!("{:?}", schedule.missed),
+                "last_run": schedule.last_run.map(|e| RuntimeValue::from(e as f64)).unwrap_or(RuntimeValue::Undefined),
+                "next_run": next_run,
+                "source": schedule.source.as_str(),
+            })
+        })
+        .collect();
+    Ok(ProgramOutput::json(RuntimeValue::from(results)))
+}
+
+pub async fn main(
+    reactor: Reactor,
+    mut args: Vec<String>,
+    _stdin: Option<Receiver<ProgramOutput>>,
+    stdout: Sender<ProgramOutput>,
+) -> Result<ProgramOutput> {
+    args.insert(0, "schedule".to_string());
+    let app = App::new("schedule")
+        .subcommand(
+            App::new("add")
+                .about("Run a program periodically")
+                .arg(Arg::new("name").required(true).index(1))
+                .arg(Arg::new("source").index(2).required_unless_present("file"))
+                .arg(Arg::new("file").short('f').long("file").takes_value(true))
+                .arg(Arg::new("cron").long("cron").takes_value(true))
+                .arg(Arg::new("every").long("every").takes_value(true))
+                .arg(
+                    Arg::new("candle")
+                        .validator(ArgumentInterval::validator)
+                        .long("candle")
+                        .takes_value(true),
+                )
+                .group(
+                    ArgGroup::new("trigger")
+                        .args(&["cron", "every", "candle"])
+                        .required(true),
+                )
+                .arg(Arg::new("overlap").long("overlap").takes_value(true))
+                .arg(Arg::new("missed").long("missed").takes_value(true))
+                .arg(Arg::new("timeout").long("timeout").takes_value(true)),
+        )
+        .subcommand(App::new("ls").about("List schedules"))
+        .subcommand(
+            App::new("rm")
+                .about("Remove schedules")
+                .arg(Arg::new("name").required(true).multiple_values(true)),
+        );
+    let app = app.try_get_matches_from(args)?;
+    match app.subcommand() {
+        Some(("add", app)) => add(reactor, app).await,
+        Some(("ls", _)) => ls(reactor),
+        Some(("rm", app)) => {
+            for name in app.values_of("name").unwrap() {
+                reactor.remove_schedule(name).await?;
+                buitlin_print!(stdout, "Removed {}", name);
+            }
+            Ok(ProgramOutput::Exit {
+                message: None,
+                status: ProgramStatus::Success,
+            })
+        }
+        _ => Err(Error::Parsing(
+            String::from("Expected one of: add, ls, rm"),
+            0..0,
+        )),
+    }
+}
+
+pub fn wrap() -> NativeProcedureGen {
+    Box::new(
+        |reactor: Reactor,
+         args: Vec<String>,
+         stdin: Option<Receiver<ProgramOutput>>,
+         stdout: Sender<ProgramOutput>| { Box::pin(main(reactor, args, stdin, stdout)) },
+    )
+}
@@ -0,0 +1,367 @@
+use super::*;
+use crate::exchange::TradeSide;
+use crate::reactor::{StrategyAction, StrategyReport};
+use crate::store::{
+    now, ScriptHooks, StrategyDefinition, StrategyMode, StrategyParam, StrategySource,
+    StrategyState,
+};
+use clap::{App, ArgGroup, ArgMatches};
+
+fn definition_value(
+    definition: &StrategyDefinition,
+    state: Option<StrategyState>,
+    running: bool,
+) -> RuntimeValue {
+    let state = state.unwrap_or_default();
+    RuntimeValue::Object(runtime_value! {
+        "name": definition.name.clone(),
+        "source": match &definition.source {
+            StrategySource::Native { name } => name.clone(),
+            StrategySource::Script { .. } => String::from("script"),
+        },
+        "market": definition.market.clone(),
+        "interval": definition.interval.to_string(),
+        "mode": definition.mode.to_string(),
+        "status": match running {
+            true => "running",
+            false => "stopped",
+        },
+        "timer": definition.timer.map(|e| e as f64),
+        "params": RuntimeValue::Object(
+            definition
+                .params
+                .iter()
+                .map(|e| (e.key.clone(), RuntimeValue::from(e.value.as_str())))
+                .collect(),
+        ),
+        "state": RuntimeValue::Object(
+            state
+                .values
+                .iter()
+                .map(|e| (e.key.clone(), RuntimeValue::from(e.value.as_str())))
+                .collect(),
+        ),
+        "position": state.position,
+        "cash": state.cash,
+        "orders": state.orders,
+        "fills": state.fills.len() as u64,
+        "last_candle": state.last_candle.map(|e| e as f64),
+    })
+}
+
+fn report_value(report: &StrategyReport) -> RuntimeValue {
+    RuntimeValue::Object(runtime_value! {
+        "name": report.name.clone(),
+        "mode": report.mode.to_string(),
+        "candles": report.candles as u64,
+        "orders": report.orders,
+        "fills": report.fills as u64,
+        "position": report.position,
+        "cash": report.cash,
+        "price": report.price,
+        "value": report.value,
+        "errors": report
+            .errors
+            .iter()
+            .map(|e| RuntimeValue::from(e.as_str()))
+            .collect::<Vec<_>>(),
+    })
+}
+
+async fn definition(reactor: &Reactor, app: &ArgMatches) -> Result<StrategyDefinition> {
+    let name = app.value_of("name").unwrap();
+    let market = match app.value_of("market") {
+        Some(market) => market,
+        None => {
+            return reactor
+                .store
+                .strategies
+                .get(name)?
+                .ok_or_else(|| Error::StrategyNotFound(name.to_string()))
+        }
+    };
+    let market = reactor
+        .assets
+        .canonical_market(&MarketIdentifier::from(market));
+    let source = if let Some(native) = app.value_of("native") {
+        StrategySource::Native {
+            name: native.to_string(),
+        }
+    } else if let Some(path) = app.value_of("file") {
+        let text = tokio::fs::read_to_string(path).await?;
+        StrategySource::Script {
+            hooks: toml::from_str(&text)
+                .map_err(|e| Error::InvalidStrategy(format!("{}: {}", path, e)))?,
+        }
+    } else {
+        let hook = |name: &str| app.value_of(name).map(str::to_string);
+        StrategySource::Script {
+            hooks: ScriptHooks {
+                init: hook("init"),
+                on_candle: hook("on-candle"),
+                on_fill: hook("on-fill"),
+                on_timer: hook("on-timer"),
+                shutdown: hook("shutdown"),
+            },
+        }
+    };
+    let mut params = Vec::new();
+    for raw in app.values_of("set").into_iter().flatten() {
+        let (key, value) = raw.split_once('=').ok_or_else(|| {
+            Error::InvalidStrategy(format!("wrong parameter `{}`, expected key=value", raw))
+        })?;
+        params.push(StrategyParam {
+            key: key.to_string(),
+            value: value.to_string(),
+        });
+    }
+    let now_time = SystemTime::now();
+    Ok(StrategyDefinition {
+        name: name.to_string(),
+        source,
+        market: format!("{}/{}/{}", market.exchange_name, market.base, market.quote),
+        interval: ArgumentInterval::new(app.value_of("interval").unwrap())?.normalized,
+        mode: app.value_of("mode").unwrap().parse()?,
+        timer: app
+            .value_of("timer")
+            .map(human_duration)
+            .transpose()?
+            .map(|e| e.as_secs() as i64),
+        params,
+        from: app
+            .value_of("from")
+            .map(|e| ArgumentTimestamp::new(e, now_time).map(|e| e.timestamp()))
+            .transpose()?,
+        to: app
+            .value_of("to")
+            .map(|e| ArgumentTimestamp::new(e, now_time).map(|e| e.timestamp()))
+            .transpose()?,
+        created_at: now(),
+        running: false,
+    })
+}
+
+async fn start(reactor: Reactor, app: &ArgMatches) -> Result<ProgramOutput> {
+    let definition = definition(&reactor, app).await?;
+    if definition.mode == StrategyMode::Backtest {
+        let report = reactor.backtest_strategy(definition).await?;
+        return Ok(ProgramOutput::json(report_value(&report)));
+    }
+    reactor.start_strategy(definition.clone()).await?;
+    let state = reactor.store.strategies.state(&definition.name)?;
+    Ok(ProgramOutput::json(definition_value(
+        &definition,
+        state,
+        true,
+    )))
+}
+
+async fn ls(reactor: Reactor) -> Result<ProgramOutput> {
+    let running = reactor.strategies.read().await;
+    let mut ret = Vec::new();
+    for definition in reactor.store.strategies.list()? {
+        let state = reactor.store.strategies.state(&definition.name)?;
+        let is_running = running.contains_key(&definition.name);
+        ret.push(definition_value(&definition, state, is_running));
+    }
+    Ok(ProgramOutput::json(RuntimeValue::from(ret)))
+}
+
+async fn order(reactor: Reactor, side: TradeSide, app: &ArgMatches) -> Result<ProgramOutput> {
+    let action = StrategyAction::Order {
+        side,
+        volume: app.value_of("volume").unwrap().parse()?,
+        price: app.value_of("price").map(str::parse).transpose()?,
+    };
+    reactor
+        .strategy_action(app.value_of("name").unwrap(), action)
+        .await?;
+    Ok(ProgramOutput::json(RuntimeValue::from("order queued")))
+}
+
+pub async fn main(
+    reactor: Reactor,
+    mut args: Vec<String>,
+    _stdin: Option<Receiver<ProgramOutput>>,
+    _stdout: Sender<ProgramOutput>,
+) -> Result<ProgramOutput> {
+    args.insert(0, "strategy".to_string());
+    let name = || Arg::new("name").required(true).index(1);
+    let hook = |name: &'static str, help: &'static str| {
+        Arg::new(name).help(help).long(name).takes_value(true)
+    };
+    let order_args = |app: App<'static>| {
+        app.arg(name())
+            .arg(Arg::new("volume").required(true).index(2))
+            .arg(
+                Arg::new("price")
+                    .help("Limit price, a market order is queued without it")
+                    .short('p')
+                    .long("price")
+                    .takes_value(true),
+            )
+    };
+    let app = App::new("strategy")
+        .about("Run trading strategies against the live market, paper trading or a backtest")
+        .subcommand(
+            App::new("start")
+                .about("Start a strategy, or resume a saved one when only its name is given")
+                .arg(name())
+                .arg(Arg::new("market").help("exchange/BASE/QUOTE").index(2))
+                .arg(
+                    Arg::new("native")
+                        .help("Strategy implemented in pkbot: sma_cross")
+                        .short('n')
+                        .long("native")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("file")
+                        .help("Toml file giving the script of each hook")
+                        .long("file")
+                        .takes_value(true),
+                )
+                .arg(hook("init", "Script run when the strategy starts"))
+                .arg(hook("on-candle", "Script run after each closed candle"))
+                .arg(hook("on-fill", "Script run after each fill of an order"))
+                .arg(hook("on-timer", "Script run on each tick of --timer"))
+                .arg(hook("shutdown", "Script run when the strategy stops"))
+                .group(
+                    ArgGroup::new("source")
+                        .args(&[
+                            "native",
+                            "file",
+                            "init",
+                            "on-candle",
+                            "on-fill",
+                            "on-timer",
+                            "shutdown",
+                        ])
+                        .multiple(true)
+                        .requires("market"),
+                )
+                .arg(
+                    Arg::new("interval")
+                        .validator(ArgumentInterval::validator)
+                        .short('i')
+                        .long("interval")
+                        .takes_value(true)
+                        .default_value("1h"),
+                )
+                .arg(
+                    Arg::new("mode")
+                        .short('m')
+                        .long("mode")
+                        .takes_value(true)
+                        .possible_values(&["live", "paper", "backtest"])
+                        .default_value("paper"),
+                )
+                .arg(
+                    Arg::new("timer")
+                        .help("Delay between two on_timer calls (30s, 5m...)")
+                        .long("timer")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("set")
+                        .help("Parameter given to the strategy, key=value")
+                        .short('s')
+                        .long("set")
+                        .takes_value(true)
+                        .multiple_occurrences(true),
+                )
+                .arg(
+                    Arg::new("from")
+                        .help("Start of the backtest period")
+                        .validator(ArgumentTimestamp::validator)
+                        .short('f')
+                        .long("from")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("to")
+                        .help("End of the backtest period, now by default")
+                        .validator(ArgumentTimestamp::validator)
+                        .short('t')
+                        .long("to")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            App::new("stop")
+                .about("Stop a strategy, its shutdown hook runs first")
+                .arg(name())
+                .arg(
+                    Arg::new("rm")
+                        .help("Also forget its definition and state")
+                        .long("rm"),
+                ),
+        )
+        .subcommand(App::new("ls").about("List the strategies with their state"))
+        .subcommand(order_args(
+            App::new("buy").about("Queue a buy order on a running strategy, from its hooks"),
+        ))
+        .subcommand(order_args(
+            App::new("sell").about("Queue a sell order on a running strategy, from its hooks"),
+        ))
+        .subcommand(
+            App::new("set")
+                .about("Set a value of the state of a running strategy")
+                .arg(name())
+                .arg(Arg::new("key").required(true).index(2))
+                .arg(Arg::new("value").required(true).index(3)),
+        )
+        .subcommand(
+            App::new("unset")
+                .about("Remove a value of the state of a running strategy")
+                .arg(name())
+                .arg(Arg::new("key").required(true).index(2)),
+        );
+    let app = app.try_get_matches_from(args)?;
+    match app.subcommand() {
+        Some(("start", app)) => start(reactor, app).await,
+        Some(("stop", app)) => {
+            let name = app.value_of("name").unwrap();
+            match app.is_present("rm") {
+                true => reactor.remove_strategy(name).await?,
+                false => reactor.stop_strategy(name).await?,
+            }
+            Ok(ProgramOutput::json(RuntimeValue::from(name)))
+        }
+        Some(("buy", app)) => order(reactor, TradeSide::Buy, app).await,
+        Some(("sell", app)) => order(reactor, TradeSide::Sell, app).await,
+        Some(("set", app)) => {
+            let action = StrategyAction::Set {
+                key: app.value_of("key").unwrap().to_string(),
+                value: app.value_of("value").unwrap().to_string(),
+            };
+            reactor
+                .strategy_action(app.value_of("name").unwrap(), action)
+                .await?;
+            Ok(ProgramOutput::json(RuntimeValue::from(
+                app.value_of("key").unwrap(),
+            )))
+        }
+        Some(("unset", app)) => {
+            let action = StrategyAction::Unset {
+                key: app.value_of("key").unwrap().to_string(),
+            };
+            reactor
+                .strategy_action(app.value_of("name").unwrap(), action)
+                .await?;
+            Ok(ProgramOutput::json(RuntimeValue::from(
+                app.value_of("key").unwrap(),
+            )))
+        }
+        _ => ls(reactor).await,
+    }
+}
+
+pub fn wrap() -> NativeProcedureGen {
+    Box::new(
+        |reactor: Reactor,
+         args: Vec<String>,
+         stdin: Option<Receiver<ProgramOutput>>,
+         stdout: Sender<ProgramOutput>| { Box::pin(main(reactor, args, stdin, stdout)) },
+    )
+}
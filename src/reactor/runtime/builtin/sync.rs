@@ -0,0 +1,117 @@
+use super::*;
+use crate::reactor::BackfillProgress;
+
+fn progress_value(progress: &BackfillProgress) -> RuntimeValue {
+    let market = &progress.market;
+    RuntimeValue::Object(runtime_value! {
+        "market": format!("{}/{}/{}", market.exchange_name, market.base, market.quote),
+        "interval": format!("{}", progress.interval),
+        "from": progress.from as f64,
+        "to": progress.to as f64,
+        "reached": progress.reached as f64,
+        "fetched": progress.fetched as u64,
+        "error": progress.error.clone(),
+    })
+}
+
+pub async fn main(
+    reactor: Reactor,
+    mut args: Vec<String>,
+    _stdin: Option<Receiver<ProgramOutput>>,
+    stdout: Sender<ProgramOutput>,
+) -> Result<ProgramOutput> {
+    args.insert(0, "sync".to_string());
+    let app = clap::App::new("sync")
+        .about("Backfill the candles of markets over a time range")
+        .arg(
+            Arg::new("interval")
+                .validator(ArgumentInterval::validator)
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .required(true)
+                .short('i')
+                .long("interval"),
+        )
+        .arg(
+            Arg::new("from")
+                .validator(ArgumentTimestamp::validator)
+                .takes_value(true)
+                .short('f')
+                .long("from")
+                .default_value("NOW-30d"),
+        )
+        .arg(
+            Arg::new("to")
+                .validator(ArgumentTimestamp::validator)
+                .takes_value(true)
+                .short('t')
+                .long("to"),
+        )
+        .arg(
+            Arg::new("quiet")
+                .help("Don't report the progress")
+                .short('q')
+                .long("quiet"),
+        )
+        .arg(
+            Arg::new("market_name")
+                .required(true)
+                .takes_value(true)
+                .multiple_values(true),
+        );
+    let app = app.try_get_matches_from(args)?;
+    let now = SystemTime::now();
+    let from = ArgumentTimestamp::new(app.value_of("from").unwrap(), now)?.timestamp();
+    let to = app
+        .value_of("to")
+        .map(|e| ArgumentTimestamp::new(e, now))
+        .transpose()?
+        .map(|e| e.timestamp())
+        .unwrap_or(now.duration_since(UNIX_EPOCH).unwrap().as_secs() as Timestamp);
+    let markets = reactor
+        .resolve_markets(&app.values_of("market_name").unwrap().collect::<Vec<_>>())
+        .await?;
+    let mut targets = Vec::new();
+    for market in markets {
+        for interval in app.values_of("interval").unwrap() {
+            targets.push((
+                market.clone(),
+                ArgumentInterval::new(interval)?.normalized,
+            ));
+        }
+    }
+    let (sender, mut receiver) = channel::<BackfillProgress>(64);
+    let reporter = {
+        let quiet = app.is_present("quiet");
+        tokio::spawn(async move {
+            // One line per second at most for each target, and the final one
+            let mut printed: HashMap<(MarketIdentifier, Interval), std::time::Instant> =
+                HashMap::new();
+            while let Some(progress) = receiver.recv().await {
+                let key = (progress.market.clone(), progress.interval);
+                let due = printed
+                    .get(&key)
+                    .map(|e| e.elapsed() >= Duration::from_secs(1))
+                    .unwrap_or(true);
+                if !quiet && (due || progress.done) {
+                    printed.insert(key, std::time::Instant::now());
+                    buitlin_print!(stdout, "{}", progress.bar(20));
+                }
+            }
+        })
+    };
+    let reports = reactor.backfill(targets, from, to, Some(sender)).await;
+    let _ = reporter.await;
+    Ok(ProgramOutput::json(RuntimeValue::from(
+        reports.iter().map(progress_value).collect::<Vec<_>>(),
+    )))
+}
+
+pub fn wrap() -> NativeProcedureGen {
+    Box::new(
+        |reactor: Reactor,
+         args: Vec<String>,
+         stdin: Option<Receiver<ProgramOutput>>,
+         stdout: Sender<ProgramOutput>| { Box::pin(main(reactor, args, stdin, stdout)) },
+    )
+}
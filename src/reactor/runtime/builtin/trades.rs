@@ -0,0 +1,75 @@
+use super::*;
+use crate::exchange::TradeSide;
+
+pub async fn main(
+    reactor: Reactor,
+    mut args: Vec<String>,
+    _stdin: Option<Receiver<ProgramOutput>>,
+    _stdout: Sender<ProgramOutput>,
+) -> Result<ProgramOutput> {
+    args.insert(0, "trades".to_string());
+    let app = clap::App::new("trades")
+        .arg(Arg::new("market_name").required(true).index(1))
+        .arg(
+            Arg::new("count")
+                .help("Number of trades reported, most recent last")
+                .short('n')
+                .long("count")
+                .takes_value(true)
+                .default_value("50"),
+        )
+        .arg(
+            Arg::new("cached")
+                .help("Only report stored trades without fetching new ones")
+                .short('c')
+                .long("cached"),
+        )
+        .arg(
+            Arg::new("summary")
+                .help("Report the volume and vwap of the trades instead of the trades")
+                .short('s')
+                .long("summary"),
+        );
+    let app = app.try_get_matches_from(args)?;
+    let count: usize = app.value_of("count").unwrap().parse()?;
+    let id = reactor
+        .resolve_market(app.value_of("market_name").unwrap())
+        .await?;
+    let market = reactor.get_or_register_market(&id).await?;
+    if !app.is_present("cached") {
+        market.sync_trades().await?;
+    }
+    let trades = market.store.trades()?.last_n(count)?;
+    if !app.is_present("summary") {
+        return Ok(ProgramOutput::json(RuntimeValue::from(
+            trades.iter().map(RuntimeValue::from).collect::<Vec<_>>(),
+        )));
+    }
+    let volume = |side: TradeSide| -> Decimal {
+        trades
+            .iter()
+            .filter(|e| e.side == side)
+            .map(|e| e.volume)
+            .sum()
+    };
+    let total: Decimal = trades.iter().map(|e| e.volume).sum();
+    let notional: Decimal = trades.iter().map(|e| e.price * e.volume).sum();
+    Ok(ProgramOutput::json(RuntimeValue::Object(runtime_value! {
+        "trades": trades.len() as u64,
+        "from": trades.first().map(|e| e.time_ms as f64 / 1000.0),
+        "to": trades.last().map(|e| e.time_ms as f64 / 1000.0),
+        "volume": total,
+        "buy_volume": volume(TradeSide::Buy),
+        "sell_volume": volume(TradeSide::Sell),
+        "vwap": notional.checked_div(total, crate::indicator::INDICATOR_SCALE),
+    })))
+}
+
+pub fn wrap() -> NativeProcedureGen {
+    Box::new(
+        |reactor: Reactor,
+         args: Vec<String>,
+         stdin: Option<Receiver<ProgramOutput>>,
+         stdout: Sender<ProgramOutput>| { Box::pin(main(reactor, args, stdin, stdout)) },
+    )
+}
@@ -0,0 +1,36 @@
+use super::*;
+
+pub async fn main(
+    reactor: Reactor,
+    mut args: Vec<String>,
+    _stdin: Option<Receiver<ProgramOutput>>,
+    _stdout: Sender<ProgramOutput>,
+) -> Result<ProgramOutput> {
+    args.insert(0, "wait".to_string());
+    let app = clap::App::new("wait").arg(
+        Arg::new("id")
+            .required(true)
+            .takes_value(true)
+            .multiple_values(true),
+    );
+    let app = app.try_get_matches_from(args)?;
+    let mut results = Vec::new();
+    for id in app.values_of("id").unwrap() {
+        let id: ProgramIdentifier = id.parse()?;
+        let status = reactor.wait_program(id).await?;
+        results.push(RuntimeValue::Object(runtime_value! {
+            "id": id,
+            "status": format!("{:?}", status),
+        }));
+    }
+    Ok(ProgramOutput::json(RuntimeValue::from(results)))
+}
+
+pub fn wrap() -> NativeProcedureGen {
+    Box::new(
+        |reactor: Reactor,
+         args: Vec<String>,
+         stdin: Option<Receiver<ProgramOutput>>,
+         stdout: Sender<ProgramOutput>| { Box::pin(main(reactor, args, stdin, stdout)) },
+    )
+}
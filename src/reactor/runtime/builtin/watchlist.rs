@@ -0,0 +1,155 @@
+use super::*;
+use crate::store::Watchlist;
+use clap::{App, ArgMatches};
+
+fn watchlist_value(watchlist: &Watchlist, markets: Option<&[MarketIdentifier]>) -> RuntimeValue {
+    let mut value = runtime_value! {
+        "name": watchlist.name.clone(),
+        "members": RuntimeValue::from(
+            watchlist.members.iter().map(|e| RuntimeValue::from(e.as_str())).collect::<Vec<_>>()
+        ),
+    };
+    if let Some(markets) = markets {
+        value.insert(
+            "markets".to_string(),
+            RuntimeValue::from(
+                markets
+                    .iter()
+                    .map(|e| RuntimeValue::from(format!("{}/{}/{}", e.exchange_name, e.base, e.quote).as_str()))
+                    .collect::<Vec<_>>(),
+            ),
+        );
+    }
+    RuntimeValue::Object(value)
+}
+
+fn members(app: &ArgMatches) -> Vec<String> {
+    app.values_of("member")
+        .map(|e| e.map(String::from).collect())
+        .unwrap_or_default()
+}
+
+fn existing(reactor: &Reactor, name: &str) -> Result<Watchlist> {
+    reactor
+        .store
+        .watchlist(name)?
+        .ok_or_else(|| Error::WatchlistNotFound(name.to_string()))
+}
+
+fn set(reactor: Reactor, app: &ArgMatches) -> Result<ProgramOutput> {
+    let watchlist = Watchlist {
+        name: app.value_of("name").unwrap().to_string(),
+        members: members(app).into_iter().unique().collect(),
+    };
+    reactor.store.set_watchlist(&watchlist)?;
+    Ok(ProgramOutput::json(watchlist_value(&watchlist, None)))
+}
+
+fn add(reactor: Reactor, app: &ArgMatches) -> Result<ProgramOutput> {
+    let mut watchlist = existing(&reactor, app.value_of("name").unwrap())?;
+    for member in members(app) {
+        if !watchlist.members.contains(&member) {
+            watchlist.members.push(member);
+        }
+    }
+    reactor.store.set_watchlist(&watchlist)?;
+    Ok(ProgramOutput::json(watchlist_value(&watchlist, None)))
+}
+
+fn rm(reactor: Reactor, app: &ArgMatches) -> Result<ProgramOutput> {
+    let name = app.value_of("name").unwrap();
+    let removed = members(app);
+    if removed.is_empty() {
+        if !reactor.store.remove_watchlist(name)? {
+            return Err(Error::WatchlistNotFound(name.to_string()));
+        }
+        return Ok(ProgramOutput::json(RuntimeValue::from(name)));
+    }
+    let mut watchlist = existing(&reactor, name)?;
+    watchlist.members.retain(|e| !removed.contains(e));
+    reactor.store.set_watchlist(&watchlist)?;
+    Ok(ProgramOutput::json(watchlist_value(&watchlist, None)))
+}
+
+async fn ls(reactor: Reactor, app: &ArgMatches) -> Result<ProgramOutput> {
+    let watchlists = match app.value_of("name") {
+        Some(name) => vec![existing(&reactor, name)?],
+        None => reactor.store.watchlists()?,
+    };
+    let mut results = Vec::new();
+    for watchlist in watchlists.iter() {
+        let markets = if app.is_present("expand") {
+            Some(reactor.resolve_markets(&[format!("@{}", watchlist.name)]).await?)
+        } else {
+            None
+        };
+        results.push(watchlist_value(watchlist, markets.as_deref()));
+    }
+    Ok(ProgramOutput::json(RuntimeValue::from(results)))
+}
+
+pub async fn main(
+    reactor: Reactor,
+    mut args: Vec<String>,
+    _stdin: Option<Receiver<ProgramOutput>>,
+    _stdout: Sender<ProgramOutput>,
+) -> Result<ProgramOutput> {
+    args.insert(0, "watchlist".to_string());
+    let member = || {
+        Arg::new("member")
+            .help("Market (kraken/XBT/EUR), glob (kraken/*/EUR) or watchlist (@name)")
+            .takes_value(true)
+            .multiple_values(true)
+            .index(2)
+    };
+    let app = App::new("watchlist")
+        .subcommand(
+            App::new("set")
+                .about("Create or replace a watchlist")
+                .arg(Arg::new("name").required(true).index(1))
+                .arg(member()),
+        )
+        .subcommand(
+            App::new("add")
+                .about("Add members to a watchlist")
+                .arg(Arg::new("name").required(true).index(1))
+                .arg(member().required(true)),
+        )
+        .subcommand(
+            App::new("rm")
+                .about("Remove members from a watchlist, or the watchlist itself without members")
+                .arg(Arg::new("name").required(true).index(1))
+                .arg(member()),
+        )
+        .subcommand(
+            App::new("ls")
+                .about("List watchlists")
+                .arg(Arg::new("name").index(1))
+                .arg(
+                    Arg::new("expand")
+                        .help("Resolve the markets of the watchlists")
+                        .short('e')
+                        .long("expand"),
+                ),
+        );
+    let app = app.try_get_matches_from(args)?;
+    match app.subcommand() {
+        Some(("set", app)) => set(reactor, app),
+        Some(("add", app)) => add(reactor, app),
+        Some(("rm", app)) => rm(reactor, app),
+        Some(("ls", app)) => ls(reactor, app).await,
+        _ => Err(Error::Parsing(
+            String::from("Expected one of: set, add, rm, ls"),
+            0..0,
+        )),
+    }
+}
+
+pub fn wrap() -> NativeProcedureGen {
+    Box::new(
+        |reactor: Reactor,
+         args: Vec<String>,
+         stdin: Option<Receiver<ProgramOutput>>,
+         stdout: Sender<ProgramOutput>| { Box::pin(main(reactor, args, stdin, stdout)) },
+    )
+}
@@ -0,0 +1,163 @@
+use super::*;
+use crate::store::{now, MissedRunPolicy, OverlapPolicy, Schedule};
+
+/// A fire time older than this is considered missed (e.g. the daemon was down)
+const MISSED_RUN_GRACE: Timestamp = 60;
+
+pub struct ScheduleTask {
+    cancel: CancellationToken,
+    #[allow(unused)]
+    handle: JoinHandle<()>,
+}
+
+impl Reactor {
+    pub async fn start_scheduler(&self) -> Result<()> {
+        self.scheduler_running.store(true, Ordering::SeqCst);
+        for schedule in self.store.schedules.list()? {
+            log::info!("Starting schedule: NAME={}, TRIGGER={}", schedule.name, schedule.trigger);
+            self.spawn_schedule(schedule.name).await;
+        }
+        Ok(())
+    }
+
+    pub async fn add_schedule(&self, schedule: Schedule) -> Result<()> {
+        schedule.trigger.validate()?;
+        Program::new(&schedule.source)?;
+        self.store.schedules.set(&schedule)?;
+        if self.scheduler_running.load(Ordering::SeqCst) {
+            self.spawn_schedule(schedule.name).await;
+        }
+        Ok(())
+    }
+
+    pub async fn remove_schedule(&self, name: &str) -> Result<()> {
+        if !self.store.schedules.remove(name)? {
+            return Err(Error::ScheduleNotFound(name.to_string()));
+        }
+        if let Some(task) = self.schedules.write().await.remove(name) {
+            task.cancel.cancel();
+        }
+        Ok(())
+    }
+
+    async fn spawn_schedule(&self, name: String) {
+        let cancel = CancellationToken::new();
+        let handle = tokio::spawn(Self::schedule_handler(
+            self.clone(),
+            name.clone(),
+            cancel.clone(),
+        ));
+        if let Some(previous) = self
+            .schedules
+            .write()
+            .await
+            .insert(name, ScheduleTask { cancel, handle })
+        {
+            previous.cancel.cancel();
+        }
+    }
+
+    async fn schedule_handler(reactor: Reactor, name: String, cancel: CancellationToken) {
+        let mut previous = None;
+        loop {
+            // Reload on every iteration so updates and removals are picked up
+            let schedule = match reactor.store.schedules.get(&name) {
+                Ok(Some(schedule)) => schedule,
+                Ok(None) => break,
+                Err(e) => {
+                    error!("Failed to load schedule: NAME={}, ERROR={}", name, e);
+                    break;
+                }
+            };
+            let next = match schedule.next_run() {
+                Ok(next) => next,
+                Err(e) => {
+                    error!("Failed to compute next run: NAME={}, ERROR={}", name, e);
+                    break;
+                }
+            };
+            let current = now();
+            if next + MISSED_RUN_GRACE < current {
+                match schedule.missed {
+                    MissedRunPolicy::Skip => {
+                        warn!("Skipping missed run: NAME={}, AT={}", name, next);
+                        reactor.mark_schedule_run(&name, current);
+                        continue;
+                    }
+                    MissedRunPolicy::RunOnce => {
+                        warn!("Catching up missed run: NAME={}, AT={}", name, next);
+                    }
+                }
+            } else if next > current {
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = tokio::time::sleep(Duration::from_secs((next - current) as u64)) => {}
+                }
+            }
+            previous = tokio::select! {
+                _ = cancel.cancelled() => break,
+                id = reactor.fire_schedule(&schedule, previous) => id,
+            };
+            reactor.mark_schedule_run(&name, next.max(current));
+        }
+        log::trace!("Schedule handler stopped: NAME={}", name);
+    }
+
+    async fn fire_schedule(
+        &self,
+        schedule: &Schedule,
+        previous: Option<ProgramIdentifier>,
+    ) -> Option<ProgramIdentifier> {
+        let running = match previous {
+            Some(id) if self.programs.read().await.contains_key(&id) => Some(id),
+            _ => None,
+        };
+        if let Some(id) = running {
+            match schedule.overlap {
+                OverlapPolicy::Skip => {
+                    info!(
+                        "Previous run still active, skipping: NAME={}, ID={}",
+                        schedule.name, id
+                    );
+                    return previous;
+                }
+                OverlapPolicy::Queue => {
+                    let _ = self.wait_program(id).await;
+                }
+                OverlapPolicy::CancelPrevious => {
+                    let _ = self.kill_program(id).await;
+                    let _ = self.wait_program(id).await;
+                }
+            }
+        }
+        let program = match Program::new(&schedule.source) {
+            Ok(program) => program.with_timeout(schedule.timeout.map(Duration::from_secs)),
+            Err(e) => {
+                error!("Failed to parse scheduled program: NAME={}, ERROR={}", schedule.name, e);
+                return None;
+            }
+        };
+        let id = self.spawn_program(program).await;
+        log::trace!("Schedule triggered: NAME={}, ID={}", schedule.name, id);
+        self.listeners
+            .broadcast(ReactorEvent::ScheduleTriggered {
+                name: schedule.name.clone(),
+                id,
+            })
+            .await;
+        Some(id)
+    }
+
+    fn mark_schedule_run(&self, name: &str, at: Timestamp) {
+        let res = self.store.schedules.get(name).and_then(|schedule| match schedule {
+            Some(mut schedule) => {
+                schedule.last_run = Some(at);
+                self.store.schedules.set(&schedule)
+            }
+            None => Ok(()),
+        });
+        if let Err(e) = res {
+            error!("Failed to save schedule run: NAME={}, ERROR={}", name, e);
+        }
+    }
+}
@@ -0,0 +1,1388 @@
+use super::*;
+use crate::exchange::{Fill, OrderRequest, TradeSide};
+use crate::store::{
+    now, ScriptHooks, StrategyDefinition, StrategyMode, StrategyParam, StrategySource,
+    StrategyState,
+};
+use std::str::FromStr;
+
+/// Fractional digits of the simulated fees
+const STRATEGY_SCALE: u32 = 8;
+/// Rounds of `on_fill` hooks sending new orders that are run before the feed moves on
+const MAX_FILL_ROUNDS: usize = 8;
+/// Delay after the end of a candle before asking for it, so the exchange has closed it
+const CANDLE_CLOSE_DELAY: Timestamp = 5;
+/// Live fills are polled from this long before the order is sent, or before the newest booked
+/// fill, so fills reported late or by a clock behind ours are not missed
+const FILL_TIME_MARGIN: Timestamp = 60;
+/// Simulated fills and live order ids kept in the state of a strategy
+const MAX_STATE_FILLS: usize = 1000;
+
+pub const NATIVE_STRATEGIES: [&str; 1] = ["sma_cross"];
+
+/// Trading logic run by the reactor, see `Reactor::start_strategy`.
+///
+/// Orders are only queued on the context and executed by the feed once the hook returns, so
+/// the same code runs against the live, paper and backtest feeds.
+#[async_trait]
+pub trait Strategy: Send {
+    async fn init(&mut self, _ctx: &mut StrategyContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// A candle of the market closed
+    async fn on_candle(&mut self, ctx: &mut StrategyContext, candle: &OHLC) -> Result<()>;
+
+    /// An order of the strategy was filled
+    async fn on_fill(&mut self, _ctx: &mut StrategyContext, _fill: &Fill) -> Result<()> {
+        Ok(())
+    }
+
+    async fn on_timer(&mut self, _ctx: &mut StrategyContext) -> Result<()> {
+        Ok(())
+    }
+
+    async fn shutdown(&mut self, _ctx: &mut StrategyContext) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Sent to a running strategy by the `strategy` builtin, how scripts act on their context
+#[derive(Debug, Clone)]
+pub enum StrategyAction {
+    Order {
+        side: TradeSide,
+        volume: Decimal,
+        price: Option<Decimal>,
+    },
+    Set {
+        key: String,
+        value: String,
+    },
+    Unset {
+        key: String,
+    },
+}
+
+type StrategyInbox = Arc<std::sync::Mutex<Vec<StrategyAction>>>;
+
+pub struct StrategyTask {
+    cancel: CancellationToken,
+    inbox: StrategyInbox,
+}
+
+/// What a strategy sees of the feed, and the only way it trades
+pub struct StrategyContext {
+    pub name: String,
+    pub market: MarketIdentifier,
+    pub interval: Interval,
+    pub mode: StrategyMode,
+    /// Time of the feed: close time of the last candle, or of the timer
+    pub time: Timestamp,
+    /// Close of the last candle
+    pub price: Option<Decimal>,
+    /// Saved in the store after each event, reset when a backtest starts
+    pub state: StrategyState,
+    params: Vec<StrategyParam>,
+    orders: Vec<OrderRequest>,
+    reactor: Reactor,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StrategyReport {
+    pub name: String,
+    pub mode: StrategyMode,
+    pub candles: usize,
+    pub orders: u64,
+    pub fills: usize,
+    pub position: Decimal,
+    pub cash: Decimal,
+    pub price: Option<Decimal>,
+    /// `cash` plus the position valued at `price`
+    pub value: Option<Decimal>,
+    pub errors: Vec<String>,
+}
+
+impl StrategyContext {
+    pub fn param(&self, key: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|e| e.key == key)
+            .map(|e| e.value.as_str())
+    }
+
+    pub fn param_or<T: FromStr>(&self, key: &str, default: T) -> Result<T> {
+        match self.param(key) {
+            Some(raw) => raw.parse().map_err(|_| {
+                Error::InvalidStrategy(format!("wrong value `{}` for parameter {}", raw, key))
+            }),
+            None => Ok(default),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.state
+            .values
+            .iter()
+            .find(|e| e.key == key)
+            .map(|e| e.value.as_str())
+    }
+
+    pub fn set<V: ToString>(&mut self, key: &str, value: V) {
+        let value = value.to_string();
+        match self.state.values.iter_mut().find(|e| e.key == key) {
+            Some(entry) => entry.value = value,
+            None => self.state.values.push(StrategyParam {
+                key: key.to_string(),
+                value,
+            }),
+        }
+    }
+
+    pub fn unset(&mut self, key: &str) {
+        self.state.values.retain(|e| e.key != key);
+    }
+
+    /// Base asset held through the orders of the strategy
+    pub fn position(&self) -> Decimal {
+        self.state.position
+    }
+
+    pub fn buy(&mut self, volume: Decimal) {
+        self.order(TradeSide::Buy, volume, None)
+    }
+
+    pub fn sell(&mut self, volume: Decimal) {
+        self.order(TradeSide::Sell, volume, None)
+    }
+
+    /// Queue an order, a limit order when `price` is given
+    pub fn order(&mut self, side: TradeSide, volume: Decimal, price: Option<Decimal>) {
+        let market = self.market.clone();
+        self.orders.push(match price {
+            Some(price) => OrderRequest::limit(market, side, volume, price),
+            None => OrderRequest::market(market, side, volume),
+        });
+    }
+
+    /// The `count` last candles closed at `time`, oldest first
+    pub async fn candles(&self, count: usize) -> Result<Vec<OHLC>> {
+        let handle = self
+            .reactor
+            .get_or_register_market(&self.market)
+            .await?
+            .interval(self.interval)
+            .await?;
+        let mut ret = Vec::with_capacity(count);
+        let mut before = self.time - self.interval.as_secs() + 1;
+        while ret.len() < count {
+            match handle.prev_ohlc(before)? {
+                Some(candle) => {
+                    before = candle.time;
+                    ret.push(candle);
+                }
+                None => break,
+            }
+        }
+        ret.reverse();
+        Ok(ret)
+    }
+
+    fn apply(&mut self, action: StrategyAction) {
+        match action {
+            StrategyAction::Order {
+                side,
+                volume,
+                price,
+            } => self.order(side, volume, price),
+            StrategyAction::Set { key, value } => self.set(&key, value),
+            StrategyAction::Unset { key } => self.unset(&key),
+        }
+    }
+
+    /// Values substituted to `${name}` in the hooks of a script strategy
+    fn variables(&self) -> HashMap<String, String> {
+        let mut ret = HashMap::new();
+        ret.insert("strategy".to_string(), self.name.clone());
+        ret.insert(
+            "market".to_string(),
+            format!(
+                "{}/{}/{}",
+                self.market.exchange_name, self.market.base, self.market.quote
+            ),
+        );
+        ret.insert("exchange".to_string(), self.market.exchange_name.clone());
+        ret.insert("base".to_string(), self.market.base.clone());
+        ret.insert("quote".to_string(), self.market.quote.clone());
+        ret.insert(
+            "interval".to_string(),
+            (self.interval.as_secs() / 60).to_string(),
+        );
+        ret.insert("mode".to_string(), self.mode.to_string());
+        ret.insert("time".to_string(), self.time.to_string());
+        ret.insert(
+            "price".to_string(),
+            self.price.map(|e| e.to_string()).unwrap_or_default(),
+        );
+        ret.insert("position".to_string(), self.state.position.to_string());
+        ret.insert("cash".to_string(), self.state.cash.to_string());
+        for param in self.params.iter() {
+            ret.insert(format!("param.{}", param.key), param.value.clone());
+        }
+        for value in self.state.values.iter() {
+            ret.insert(format!("state.{}", value.key), value.value.clone());
+        }
+        ret
+    }
+}
+
+/// Replace each `${name}` of `source` by its value, unknown names are left untouched
+fn expand<F: Fn(&str) -> Option<String>>(source: &str, lookup: F) -> String {
+    let mut ret = String::with_capacity(source.len());
+    let mut rest = source;
+    while let Some(start) = rest.find("${") {
+        ret.push_str(&rest[..start]);
+        match rest[start..].find('}') {
+            Some(end) => {
+                match lookup(&rest[start + 2..start + end]) {
+                    Some(value) => ret.push_str(&value),
+                    None => ret.push_str(&rest[start..=start + end]),
+                }
+                rest = &rest[start + end + 1..];
+            }
+            None => {
+                ret.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    ret.push_str(rest);
+    ret
+}
+
+/// Each hook runs a pkbot script, which trades with `strategy buy|sell` and keeps its state
+/// with `strategy set|unset`
+pub struct ScriptStrategy {
+    hooks: ScriptHooks,
+}
+
+impl ScriptStrategy {
+    pub fn new(hooks: ScriptHooks) -> Self {
+        Self { hooks }
+    }
+
+    /// Parse every hook, with its variables blanked
+    pub fn validate(hooks: &ScriptHooks) -> Result<()> {
+        for source in [
+            &hooks.init,
+            &hooks.on_candle,
+            &hooks.on_fill,
+            &hooks.on_timer,
+            &hooks.shutdown,
+        ]
+        .iter()
+        .filter_map(|e| e.as_ref())
+        {
+            Program::new(expand(source, |_| Some(String::from("0"))))?;
+        }
+        Ok(())
+    }
+
+    async fn run(
+        ctx: &mut StrategyContext,
+        hook: &str,
+        source: Option<&str>,
+        event: Vec<(String, String)>,
+    ) -> Result<()> {
+        let source = match source {
+            Some(source) => source,
+            None => return Ok(()),
+        };
+        let mut variables = ctx.variables();
+        variables.extend(event);
+        let program = Program::new(expand(source, |name| variables.get(name).cloned()))?;
+        let id = ctx.reactor.spawn_program(program).await;
+        match ctx.reactor.wait_program(id).await {
+            Ok(ProgramStatus::Success) | Err(Error::ProgramNotFound(_)) => Ok(()),
+            Ok(status) => Err(Error::InvalidStrategy(format!(
+                "{} hook ended with {:?}",
+                hook, status
+            ))),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[async_trait]
+impl Strategy for ScriptStrategy {
+    async fn init(&mut self, ctx: &mut StrategyContext) -> Result<()> {
+        Self::run(ctx, "init", self.hooks.init.as_deref(), Vec::new()).await
+    }
+
+    async fn on_candle(&mut self, ctx: &mut StrategyContext, candle: &OHLC) -> Result<()> {
+        let event = vec![
+            ("candle.time".to_string(), candle.time.to_string()),
+            ("candle.open".to_string(), candle.open.to_string()),
+            ("candle.high".to_string(), candle.high.to_string()),
+            ("candle.low".to_string(), candle.low.to_string()),
+            ("candle.close".to_string(), candle.close.to_string()),
+            ("candle.volume".to_string(), candle.volume.to_string()),
+        ];
+        Self::run(ctx, "on_candle", self.hooks.on_candle.as_deref(), event).await
+    }
+
+    async fn on_fill(&mut self, ctx: &mut StrategyContext, fill: &Fill) -> Result<()> {
+        let event = vec![
+            ("fill.id".to_string(), fill.id.clone()),
+            (
+                "fill.side".to_string(),
+                match fill.side {
+                    TradeSide::Buy => "buy".to_string(),
+                    TradeSide::Sell => "sell".to_string(),
+                },
+            ),
+            ("fill.price".to_string(), fill.price.to_string()),
+            ("fill.volume".to_string(), fill.volume.to_string()),
+            ("fill.fee".to_string(), fill.fee.to_string()),
+        ];
+        Self::run(ctx, "on_fill", self.hooks.on_fill.as_deref(), event).await
+    }
+
+    async fn on_timer(&mut self, ctx: &mut StrategyContext) -> Result<()> {
+        Self::run(ctx, "on_timer", self.hooks.on_timer.as_deref(), Vec::new()).await
+    }
+
+    async fn shutdown(&mut self, ctx: &mut StrategyContext) -> Result<()> {
+        Self::run(ctx, "shutdown", self.hooks.shutdown.as_deref(), Vec::new()).await
+    }
+}
+
+/// Buy `volume` when the `fast` moving average of the closes crosses above the `slow` one,
+/// sell the position when it crosses below
+#[derive(Default)]
+struct SmaCross {
+    fast: usize,
+    slow: usize,
+    volume: Decimal,
+    closes: Vec<Decimal>,
+}
+
+#[async_trait]
+impl Strategy for SmaCross {
+    async fn init(&mut self, ctx: &mut StrategyContext) -> Result<()> {
+        self.fast = ctx.param_or("fast", 10)?;
+        self.slow = ctx.param_or("slow", 30)?;
+        self.volume = ctx
+            .param("volume")
+            .ok_or_else(|| Error::InvalidStrategy("sma_cross needs a volume parameter".into()))?
+            .parse()?;
+        if self.fast == 0 || self.fast >= self.slow {
+            return Err(Error::InvalidStrategy(format!(
+                "sma_cross needs 0 < fast < slow, found fast={}, slow={}",
+                self.fast, self.slow
+            )));
+        }
+        self.closes = crate::indicator::closes(&ctx.candles(self.slow).await?);
+        Ok(())
+    }
+
+    async fn on_candle(&mut self, ctx: &mut StrategyContext, candle: &OHLC) -> Result<()> {
+        self.closes.push(candle.close);
+        if self.closes.len() > self.slow {
+            self.closes.remove(0);
+        }
+        let (fast, slow) = match (
+            crate::indicator::sma(&self.closes, self.fast),
+            crate::indicator::sma(&self.closes, self.slow),
+        ) {
+            (Some(fast), Some(slow)) => (fast, slow),
+            _ => return Ok(()),
+        };
+        let trend = if fast > slow { "up" } else { "down" };
+        // The trend is kept in the state so a restart does not trade the same cross twice
+        match ctx.get("trend").map(str::to_string).as_deref() {
+            Some(previous) if previous == trend => {}
+            Some(_) if trend == "up" && ctx.position() <= Decimal::ZERO => ctx.buy(self.volume),
+            Some(_) if trend == "down" && ctx.position() > Decimal::ZERO => {
+                let position = ctx.position();
+                ctx.sell(position)
+            }
+            _ => {}
+        }
+        ctx.set("trend", trend);
+        Ok(())
+    }
+}
+
+pub fn native_strategy(name: &str) -> Result<Box<dyn Strategy>> {
+    match name {
+        "sma_cross" => Ok(Box::new(SmaCross::default())),
+        _ => Err(Error::InvalidStrategy(format!(
+            "no native strategy `{}`, expected one of: {}",
+            name,
+            NATIVE_STRATEGIES.join(", ")
+        ))),
+    }
+}
+
+enum StrategyEvent {
+    Init,
+    Candle(OHLC),
+    Fill(Fill),
+    Timer,
+    Shutdown,
+}
+
+struct CancelOnDrop(CancellationToken);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+/// A strategy plugged to its feed
+struct StrategyRun {
+    strategy: Box<dyn Strategy>,
+    ctx: StrategyContext,
+    inbox: StrategyInbox,
+    /// Delay between two `on_timer` calls
+    every: Option<Timestamp>,
+    from: Option<Timestamp>,
+    to: Option<Timestamp>,
+    /// Taker fee of the simulated fills, in percent
+    fee: Decimal,
+    /// Live orders were sent since then, their fills are polled from the exchange
+    fills_since: Option<Timestamp>,
+    candles: usize,
+    fills: usize,
+    errors: Vec<String>,
+}
+
+impl StrategyRun {
+    async fn new(
+        reactor: &Reactor,
+        definition: &StrategyDefinition,
+        strategy: Box<dyn Strategy>,
+        inbox: StrategyInbox,
+    ) -> Result<Self> {
+        let market = reactor
+            .assets
+            .canonical_market(&MarketIdentifier::from(&definition.market));
+        let state = match definition.mode {
+            StrategyMode::Backtest => StrategyState::default(),
+            _ => reactor
+                .store
+                .strategies
+                .state(&definition.name)?
+                .unwrap_or_default(),
+        };
+        let fee = match definition.mode {
+            StrategyMode::Live => Decimal::ZERO,
+            _ => reactor.strategy_fee(&market).await,
+        };
+        // The fills booked before the state was saved are still in it, see `trim`
+        let fills_since = match definition.mode {
+            StrategyMode::Live if state.orders > 0 => Some(state.updated_at - FILL_TIME_MARGIN),
+            _ => None,
+        };
+        Ok(Self {
+            strategy,
+            ctx: StrategyContext {
+                name: definition.name.clone(),
+                market,
+                interval: definition.interval,
+                mode: definition.mode,
+                time: definition.from.unwrap_or_else(now),
+                price: None,
+                state,
+                params: definition.params.clone(),
+                orders: Vec::new(),
+                reactor: reactor.clone(),
+            },
+            inbox,
+            every: definition.timer,
+            from: definition.from,
+            to: definition.to,
+            fee,
+            fills_since,
+            candles: 0,
+            fills: 0,
+            errors: Vec::new(),
+        })
+    }
+
+    fn error(&mut self, message: String) {
+        error!("Strategy failed: NAME={}, ERROR={}", self.ctx.name, message);
+        self.errors.push(message);
+    }
+
+    fn save(&mut self) {
+        self.ctx.state.updated_at = now();
+        if let Err(e) = self
+            .ctx
+            .reactor
+            .store
+            .strategies
+            .set_state(&self.ctx.name, &self.ctx.state)
+        {
+            error!(
+                "Failed to save strategy state: NAME={}, ERROR={}",
+                self.ctx.name, e
+            );
+        }
+    }
+
+    async fn dispatch(&mut self, event: &StrategyEvent) -> Result<()> {
+        let ctx = &mut self.ctx;
+        let res = match event {
+            StrategyEvent::Init => self.strategy.init(ctx).await,
+            StrategyEvent::Candle(candle) => self.strategy.on_candle(ctx, candle).await,
+            StrategyEvent::Fill(fill) => self.strategy.on_fill(ctx, fill).await,
+            StrategyEvent::Timer => self.strategy.on_timer(ctx).await,
+            StrategyEvent::Shutdown => self.strategy.shutdown(ctx).await,
+        };
+        let actions: Vec<StrategyAction> = self.inbox.lock().unwrap().drain(..).collect();
+        for action in actions {
+            self.ctx.apply(action);
+        }
+        res
+    }
+
+    /// Run the hook of `event` then execute its orders, feeding the fills back
+    async fn process(&mut self, event: StrategyEvent) -> Result<()> {
+        let res = self.dispatch(&event).await;
+        self.settle().await;
+        res
+    }
+
+    /// Execute the queued orders and give their fills to `on_fill`, the live fills of the
+    /// earlier orders included. The only place fills are booked.
+    async fn settle(&mut self) {
+        let mut fills = self.execute().await;
+        let mut rounds = 0;
+        while !fills.is_empty() && rounds < MAX_FILL_ROUNDS {
+            for fill in fills {
+                if let Err(e) = self.dispatch(&StrategyEvent::Fill(fill)).await {
+                    self.error(format!("on_fill: {}", e));
+                }
+            }
+            fills = self.execute().await;
+            rounds += 1;
+        }
+    }
+
+    async fn execute(&mut self) -> Vec<Fill> {
+        let mut fills = Vec::new();
+        for order in std::mem::take(&mut self.ctx.orders) {
+            if order.volume <= Decimal::ZERO {
+                self.error(format!("{}: volume must be positive", order));
+                continue;
+            }
+            self.ctx.state.orders += 1;
+            match self.ctx.mode {
+                StrategyMode::Live => {
+                    let sent_at = now() - FILL_TIME_MARGIN;
+                    match self.ctx.reactor.submit_order(&order).await {
+                        Ok(placed) => {
+                            self.ctx.state.order_ids.extend(placed.ids);
+                            self.fills_since =
+                                Some(self.fills_since.map_or(sent_at, |e| e.min(sent_at)));
+                        }
+                        Err(e) => self.error(format!("{}: {}", order, e)),
+                    }
+                }
+                StrategyMode::Paper | StrategyMode::Backtest => match self.simulate(&order) {
+                    Ok(fill) => fills.push(fill),
+                    Err(e) => self.error(format!("{}: {}", order, e)),
+                },
+            }
+        }
+        if self.ctx.mode == StrategyMode::Live {
+            fills.extend(self.live_fills().await);
+        }
+        for fill in fills.iter() {
+            self.record(fill.clone());
+        }
+        self.trim();
+        fills
+    }
+
+    /// Move the polling of live fills to the newest booked one and bound the saved state. Live
+    /// fills are kept as long as they are polled, to tell the ones already booked.
+    fn trim(&mut self) {
+        let state = &mut self.ctx.state;
+        let newest = state.fills.iter().map(|e| e.time).max();
+        match (self.fills_since, newest) {
+            (Some(since), Some(newest)) => {
+                let since = since.max(newest - FILL_TIME_MARGIN);
+                self.fills_since = Some(since);
+                state.fills.retain(|e| e.time >= since);
+            }
+            _ => {
+                let excess = state.fills.len().saturating_sub(MAX_STATE_FILLS);
+                state.fills.drain(..excess);
+            }
+        }
+        let excess = state.order_ids.len().saturating_sub(MAX_STATE_FILLS);
+        state.order_ids.drain(..excess);
+    }
+
+    /// Fill at the close of the last candle, limit orders which would rest in the book fail
+    fn simulate(&self, order: &OrderRequest) -> Result<Fill> {
+        let price = self.ctx.price.ok_or(Error::NoData)?;
+        let marketable = match (order.price, order.side) {
+            (Some(limit), TradeSide::Buy) => limit >= price,
+            (Some(limit), TradeSide::Sell) => limit <= price,
+            (None, _) => true,
+        };
+        if !marketable {
+            return Err(Error::InvalidStrategy(format!(
+                "limit not reached, last price is {}",
+                price
+            )));
+        }
+        Ok(Fill {
+            id: format!("{}-{}", self.ctx.name, self.ctx.state.orders),
+            market: format!(
+                "{}/{}/{}",
+                order.market.exchange_name, order.market.base, order.market.quote
+            ),
+            time: self.ctx.time,
+            side: order.side,
+            price,
+            volume: order.volume,
+            fee: (price * order.volume * self.fee)
+                .checked_div(Decimal::ONE_HUNDRED, STRATEGY_SCALE)
+                .unwrap_or(Decimal::ZERO),
+            order: None,
+        })
+    }
+
+    /// Fills of the live orders of the strategy not booked yet, from `fills_since`
+    async fn live_fills(&mut self) -> Vec<Fill> {
+        let since = match self.fills_since {
+            Some(since) => since,
+            None => return Vec::new(),
+        };
+        let exchange = self
+            .ctx
+            .reactor
+            .exchanges
+            .read()
+            .await
+            .get(&self.ctx.market.exchange_name)
+            .cloned();
+        let fills = match exchange {
+            Some(exchange) => exchange.get_fills(Some(since)).await,
+            None => Err(Error::ExchangeNotFound(
+                self.ctx.market.exchange_name.clone(),
+            )),
+        };
+        match fills {
+            Ok(fills) => fills
+                .into_iter()
+                .filter(|e| {
+                    e.order
+                        .as_ref()
+                        .map(|id| self.ctx.state.order_ids.contains(id))
+                        .unwrap_or(false)
+                })
+                .filter(|e| !self.ctx.state.fills.iter().any(|seen| seen.id == e.id))
+                .collect(),
+            Err(e) => {
+                self.error(format!("fills: {}", e));
+                Vec::new()
+            }
+        }
+    }
+
+    fn record(&mut self, fill: Fill) {
+        let amount = fill.price * fill.volume;
+        match fill.side {
+            TradeSide::Buy => {
+                self.ctx.state.position += fill.volume;
+                self.ctx.state.cash -= amount + fill.fee;
+            }
+            TradeSide::Sell => {
+                self.ctx.state.position -= fill.volume;
+                self.ctx.state.cash += amount - fill.fee;
+            }
+        }
+        info!(
+            "Strategy order filled: NAME={}, SIDE={:?}, VOLUME={}, PRICE={}",
+            self.ctx.name, fill.side, fill.volume, fill.price
+        );
+        self.ctx.state.fills.push(fill);
+        self.fills += 1;
+    }
+
+    async fn candle(&mut self, candle: OHLC) {
+        self.ctx.time = candle.time + self.ctx.interval.as_secs();
+        self.ctx.price = Some(candle.close);
+        self.ctx.state.last_candle = Some(candle.time);
+        self.candles += 1;
+        if let Err(e) = self.process(StrategyEvent::Candle(candle)).await {
+            self.error(format!("on_candle: {}", e));
+        }
+    }
+
+    async fn timer(&mut self, time: Timestamp) {
+        self.ctx.time = time;
+        if let Err(e) = self.process(StrategyEvent::Timer).await {
+            self.error(format!("on_timer: {}", e));
+        }
+    }
+
+    /// Download the candles of the backtest period missing from the store
+    async fn backfill(&mut self) {
+        let reactor = self.ctx.reactor.clone();
+        for progress in reactor
+            .backfill(
+                vec![(self.ctx.market.clone(), self.ctx.interval)],
+                self.from.unwrap_or(0),
+                self.to.unwrap_or_else(now),
+                None,
+            )
+            .await
+        {
+            if let Some(e) = progress.error {
+                self.error(format!("backfill: {}", e));
+            }
+        }
+    }
+
+    /// Replay the stored candles of the period, timers fire on the time of the candles
+    async fn backtest(&mut self, cancel: &CancellationToken) -> Result<()> {
+        let secs = self.ctx.interval.as_secs();
+        let from = self.from.unwrap_or(0);
+        let to = self.to.unwrap_or_else(now);
+        let handle = self
+            .ctx
+            .reactor
+            .get_or_register_market(&self.ctx.market)
+            .await?
+            .interval(self.ctx.interval)
+            .await?;
+        let mut next_timer = self.every.map(|e| from + e);
+        let mut at = from - 1;
+        while let Some(candle) = handle.next_ohlc(at)? {
+            if candle.time + secs > to || cancel.is_cancelled() {
+                break;
+            }
+            at = candle.time;
+            while let Some(time) = next_timer.filter(|e| *e <= candle.time + secs) {
+                self.timer(time).await;
+                next_timer = self.every.map(|e| time + e);
+            }
+            self.candle(candle).await;
+        }
+        Ok(())
+    }
+
+    /// Wait for each candle to close, and for the timer, until cancelled
+    async fn live(&mut self, cancel: &CancellationToken) -> Result<()> {
+        let secs = self.ctx.interval.as_secs();
+        let market = self
+            .ctx
+            .reactor
+            .get_or_register_market(&self.ctx.market)
+            .await?;
+        let mut next_timer = self.every.map(|e| now() + e);
+        loop {
+            let current = now();
+            let next_candle = (current / secs + 1) * secs + CANDLE_CLOSE_DELAY;
+            let wake = next_timer
+                .map(|e| e.min(next_candle))
+                .unwrap_or(next_candle);
+            tokio::select! {
+                _ = cancel.cancelled() => break,
+                _ = tokio::time::sleep(Duration::from_secs((wake - current).max(0) as u64)) => {}
+            }
+            if let Some(time) = next_timer.filter(|e| *e <= now()) {
+                self.timer(now()).await;
+                next_timer = self.every.map(|e| time.max(now()) + e);
+            }
+            if now() >= next_candle {
+                if let Err(e) = self.closed_candles(&market).await {
+                    self.error(format!("candles: {}", e));
+                }
+            } else if self.fills_since.is_some() {
+                self.settle().await;
+            }
+            self.save();
+        }
+        Ok(())
+    }
+
+    /// Give the strategy the candles closed since the last one it saw, only the last closed
+    /// candle when it never saw any
+    async fn closed_candles(&mut self, market: &SyncMarket) -> Result<()> {
+        let secs = self.ctx.interval.as_secs();
+        let current = now();
+        let last = self.ctx.state.last_candle;
+        let page = market
+            .exchange
+            .get_ohlc_page(
+                &market.store.id,
+                last.unwrap_or(current - 2 * secs),
+                self.ctx.interval,
+            )
+            .await?;
+        market
+            .interval(self.ctx.interval)
+            .await?
+            .extend(page.clone())?;
+        let mut closed: Vec<OHLC> = page
+            .into_iter()
+            .filter(|e| e.time + secs <= current)
+            .filter(|e| last.map(|last| e.time > last).unwrap_or(true))
+            .collect();
+        if last.is_none() && closed.len() > 1 {
+            closed.drain(..closed.len() - 1);
+        }
+        for candle in closed {
+            self.candle(candle).await;
+        }
+        // Orders of the previous hooks may have been filled since
+        self.settle().await;
+        Ok(())
+    }
+
+    /// Init the strategy, feed it until its period ends or it is cancelled, then shut it down
+    async fn run(&mut self, cancel: &CancellationToken) -> Result<StrategyReport> {
+        // Lets `init` warm up on the candles of the period
+        if self.ctx.mode == StrategyMode::Backtest {
+            self.backfill().await;
+        }
+        self.process(StrategyEvent::Init).await?;
+        let fed = match self.ctx.mode {
+            StrategyMode::Backtest => self.backtest(cancel).await,
+            StrategyMode::Live | StrategyMode::Paper => self.live(cancel).await,
+        };
+        if let Err(e) = fed {
+            self.error(format!("feed: {}", e));
+        }
+        if let Err(e) = self.process(StrategyEvent::Shutdown).await {
+            self.error(format!("shutdown: {}", e));
+        }
+        self.save();
+        Ok(self.report())
+    }
+
+    fn report(&self) -> StrategyReport {
+        StrategyReport {
+            name: self.ctx.name.clone(),
+            mode: self.ctx.mode,
+            candles: self.candles,
+            orders: self.ctx.state.orders,
+            fills: self.fills,
+            position: self.ctx.state.position,
+            cash: self.ctx.state.cash.round_dp(STRATEGY_SCALE),
+            price: self.ctx.price,
+            value: self.ctx.price.map(|e| {
+                (self.ctx.state.cash + self.ctx.state.position * e).round_dp(STRATEGY_SCALE)
+            }),
+            errors: self.errors.clone(),
+        }
+    }
+}
+
+impl Reactor {
+    /// Check a definition without running it
+    pub fn validate_strategy(&self, definition: &StrategyDefinition) -> Result<()> {
+        let market = MarketIdentifier::from(&definition.market);
+        if market.exchange_name.is_empty() || market.base.is_empty() || market.quote.is_empty() {
+            return Err(Error::InvalidStrategy(format!(
+                "wrong market `{}`, expected exchange/BASE/QUOTE",
+                definition.market
+            )));
+        }
+        match &definition.source {
+            StrategySource::Native { name } => {
+                native_strategy(name)?;
+            }
+            StrategySource::Script { hooks } => ScriptStrategy::validate(hooks)?,
+        }
+        if let Some(timer) = definition.timer.filter(|e| *e <= 0) {
+            return Err(Error::InvalidStrategy(format!(
+                "timer must be positive, found {}s",
+                timer
+            )));
+        }
+        match (definition.mode, definition.from, definition.to) {
+            (StrategyMode::Backtest, None, _) => Err(Error::InvalidStrategy(
+                "a backtest needs the start of the period".into(),
+            )),
+            (StrategyMode::Backtest, Some(from), Some(to)) if from >= to => Err(
+                Error::InvalidStrategy(format!("empty period {}..{}", from, to)),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// Resume the live and paper strategies which were running when the daemon stopped
+    pub async fn start_strategies(&self) -> Result<()> {
+        for definition in self
+            .store
+            .strategies
+            .list()?
+            .into_iter()
+            .filter(|e| e.running)
+        {
+            log::info!(
+                "Resuming strategy: NAME={}, MODE={}, MARKET={}",
+                definition.name,
+                definition.mode,
+                definition.market
+            );
+            if let Err(e) = self.start_strategy(definition).await {
+                error!("Failed to resume strategy: ERROR={}", e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Save a live or paper strategy and run it until `Reactor::stop_strategy`
+    pub async fn start_strategy(&self, mut definition: StrategyDefinition) -> Result<()> {
+        if definition.mode == StrategyMode::Backtest {
+            return Err(Error::InvalidStrategy(
+                "a backtest runs until the end of its period, see `Reactor::backtest_strategy`"
+                    .into(),
+            ));
+        }
+        self.validate_strategy(&definition)?;
+        let (cancel, inbox) = self.register_strategy(&definition).await?;
+        definition.running = true;
+        self.store.strategies.set(&definition)?;
+        self.listeners
+            .broadcast(ReactorEvent::StrategyStatus {
+                name: definition.name.clone(),
+                mode: definition.mode,
+                running: true,
+                error: None,
+            })
+            .await;
+        tokio::spawn(Self::strategy_handler(
+            self.clone(),
+            definition,
+            cancel,
+            inbox,
+        ));
+        Ok(())
+    }
+
+    /// Replay the period of the definition, the state of the strategy starts empty. A live or
+    /// paper strategy of the same name is never replaced by a backtest.
+    pub async fn backtest_strategy(
+        &self,
+        mut definition: StrategyDefinition,
+    ) -> Result<StrategyReport> {
+        definition.mode = StrategyMode::Backtest;
+        definition.running = false;
+        self.validate_strategy(&definition)?;
+        if let Some(stored) = self.store.strategies.get(&definition.name)? {
+            if stored.mode != StrategyMode::Backtest {
+                return Err(Error::InvalidStrategy(format!(
+                    "{} is a {} strategy, backtest it under another name",
+                    definition.name, stored.mode
+                )));
+            }
+        }
+        let (cancel, inbox) = self.register_strategy(&definition).await?;
+        self.store.strategies.set(&definition)?;
+        // Runs in its own task so the strategy is unregistered even when the caller is dropped
+        // (program killed or timed out), which cancels the backtest
+        let _cancel_on_drop = CancelOnDrop(cancel.clone());
+        let reactor = self.clone();
+        tokio::spawn(async move {
+            let name = definition.name.clone();
+            let report = reactor.run_strategy(definition, cancel, inbox).await;
+            reactor.strategies.write().await.remove(&name);
+            report
+        })
+        .await
+        .map_err(|e| Error::InvalidStrategy(e.to_string()))?
+    }
+
+    pub async fn stop_strategy(&self, name: &str) -> Result<()> {
+        let mut definition = self
+            .store
+            .strategies
+            .get(name)?
+            .ok_or_else(|| Error::StrategyNotFound(name.to_string()))?;
+        definition.running = false;
+        self.store.strategies.set(&definition)?;
+        // The handler forgets the task once the shutdown hook ran
+        if let Some(task) = self.strategies.read().await.get(name) {
+            task.cancel.cancel();
+        }
+        Ok(())
+    }
+
+    /// Stop a strategy and forget its definition and state
+    pub async fn remove_strategy(&self, name: &str) -> Result<()> {
+        self.stop_strategy(name).await?;
+        self.store.strategies.remove(name)?;
+        Ok(())
+    }
+
+    /// Hand an action to a running strategy, applied once its current hook returns
+    pub async fn strategy_action(&self, name: &str, action: StrategyAction) -> Result<()> {
+        self.strategies
+            .read()
+            .await
+            .get(name)
+            .ok_or_else(|| Error::StrategyNotFound(name.to_string()))?
+            .inbox
+            .lock()
+            .unwrap()
+            .push(action);
+        Ok(())
+    }
+
+    async fn register_strategy(
+        &self,
+        definition: &StrategyDefinition,
+    ) -> Result<(CancellationToken, StrategyInbox)> {
+        let mut lock = self.strategies.write().await;
+        if lock.contains_key(&definition.name) {
+            return Err(Error::InvalidStrategy(format!(
+                "{} is already running",
+                definition.name
+            )));
+        }
+        let cancel = CancellationToken::new();
+        let inbox: StrategyInbox = Arc::new(std::sync::Mutex::new(Vec::new()));
+        lock.insert(
+            definition.name.clone(),
+            StrategyTask {
+                cancel: cancel.clone(),
+                inbox: inbox.clone(),
+            },
+        );
+        Ok((cancel, inbox))
+    }
+
+    async fn strategy_handler(
+        reactor: Reactor,
+        definition: StrategyDefinition,
+        cancel: CancellationToken,
+        inbox: StrategyInbox,
+    ) {
+        let name = definition.name.clone();
+        let mode = definition.mode;
+        let result = reactor.run_strategy(definition, cancel, inbox).await;
+        reactor.strategies.write().await.remove(&name);
+        let error = match result {
+            Ok(report) => {
+                info!(
+                    "Strategy stopped: NAME={}, POSITION={}, CASH={}",
+                    name, report.position, report.cash
+                );
+                None
+            }
+            Err(e) => {
+                error!("Strategy stopped: NAME={}, ERROR={}", name, e);
+                // Not resumed by the daemon until started again
+                if let Err(e) = reactor.stop_strategy(&name).await {
+                    error!("Failed to save strategy: NAME={}, ERROR={}", name, e);
+                }
+                Some(e.to_string())
+            }
+        };
+        reactor
+            .listeners
+            .broadcast(ReactorEvent::StrategyStatus {
+                name,
+                mode,
+                running: false,
+                error,
+            })
+            .await;
+    }
+
+    async fn run_strategy(
+        &self,
+        definition: StrategyDefinition,
+        cancel: CancellationToken,
+        inbox: StrategyInbox,
+    ) -> Result<StrategyReport> {
+        let strategy: Box<dyn Strategy> = match &definition.source {
+            StrategySource::Native { name } => native_strategy(name)?,
+            StrategySource::Script { hooks } => Box::new(ScriptStrategy::new(hooks.clone())),
+        };
+        let mut run = StrategyRun::new(self, &definition, strategy, inbox).await?;
+        log::info!(
+            "Strategy started: NAME={}, MODE={}, MARKET={}, INTERVAL={}",
+            definition.name,
+            definition.mode,
+            definition.market,
+            definition.interval
+        );
+        run.run(&cancel).await
+    }
+
+    /// Taker fee applied to the simulated fills, none when the exchange does not tell it
+    async fn strategy_fee(&self, market: &MarketIdentifier) -> Decimal {
+        let exchange = self
+            .exchanges
+            .read()
+            .await
+            .get(&market.exchange_name)
+            .cloned();
+        let fee = match exchange {
+            Some(exchange) => exchange
+                .get_market_definition(market, None)
+                .await
+                .and_then(|e| e.taker_fee()),
+            None => Err(Error::ExchangeNotFound(market.exchange_name.clone())),
+        };
+        fee.unwrap_or_else(|e| {
+            warn!(
+                "Simulating fills without fee: MARKET={}, ERROR={}",
+                market, e
+            );
+            Decimal::ZERO
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(raw: &str) -> Decimal {
+        raw.parse().unwrap()
+    }
+
+    type Events = Arc<std::sync::Mutex<Vec<String>>>;
+
+    /// Logs the hooks it is given and trades as told
+    #[derive(Default)]
+    struct Recorder {
+        events: Events,
+        init_orders: Vec<(TradeSide, Decimal)>,
+        /// Sent at the close of the candle given first
+        candle_orders: Vec<(Decimal, TradeSide, Decimal)>,
+        /// Sent by the first `on_fill`
+        fill_orders: Vec<(TradeSide, Decimal)>,
+    }
+
+    impl Recorder {
+        fn log(&self, event: String) {
+            self.events.lock().unwrap().push(event);
+        }
+    }
+
+    #[async_trait]
+    impl Strategy for Recorder {
+        async fn init(&mut self, ctx: &mut StrategyContext) -> Result<()> {
+            self.log(String::from("init"));
+            for (side, volume) in self.init_orders.iter() {
+                ctx.order(*side, *volume, None);
+            }
+            Ok(())
+        }
+
+        async fn on_candle(&mut self, ctx: &mut StrategyContext, candle: &OHLC) -> Result<()> {
+            self.log(format!("candle {}", candle.close));
+            for (_, side, volume) in self.candle_orders.iter().filter(|e| e.0 == candle.close) {
+                ctx.order(*side, *volume, None);
+            }
+            Ok(())
+        }
+
+        async fn on_fill(&mut self, ctx: &mut StrategyContext, fill: &Fill) -> Result<()> {
+            self.log(format!("fill {:?} {}", fill.side, fill.volume));
+            for (side, volume) in std::mem::take(&mut self.fill_orders) {
+                ctx.order(side, volume, None);
+            }
+            Ok(())
+        }
+
+        async fn shutdown(&mut self, _ctx: &mut StrategyContext) -> Result<()> {
+            self.log(String::from("shutdown"));
+            Ok(())
+        }
+    }
+
+    /// Reactor trading BTC/USD at 40000 with a taker fee of 0.25% on a mock exchange
+    async fn reactor(name: &str) -> Reactor {
+        let path =
+            std::env::temp_dir().join(format!("pkbot-strategy-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&path);
+        let reactor = Reactor::new(Store::new(path).unwrap().handle()).await;
+        let market = MockMarket {
+            base: String::from("BTC"),
+            quote: String::from("USD"),
+            price: dec("40000"),
+            spread: dec("0.1"),
+            volume: Decimal::from(1i64),
+            fee: 0.25,
+        };
+        reactor
+            .register_exchange(MockExchange::new(String::from("mock"), vec![market]).shared())
+            .await;
+        reactor
+    }
+
+    fn definition(name: &str, mode: StrategyMode) -> StrategyDefinition {
+        StrategyDefinition {
+            name: name.to_string(),
+            source: StrategySource::Native {
+                name: String::from("sma_cross"),
+            },
+            market: String::from("mock/BTC/USD"),
+            interval: Interval::Hour1,
+            mode,
+            timer: None,
+            params: vec![],
+            from: None,
+            to: None,
+            created_at: now(),
+            running: false,
+        }
+    }
+
+    fn candle(time: Timestamp, close: &str) -> OHLC {
+        let close = dec(close);
+        OHLC {
+            first_available: false,
+            time,
+            open: close,
+            high: close,
+            low: close,
+            close,
+            vwap: close,
+            volume: Decimal::from(1i64),
+            count: 1,
+        }
+    }
+
+    async fn run(
+        reactor: &Reactor,
+        definition: &StrategyDefinition,
+        strategy: Recorder,
+    ) -> StrategyRun {
+        let inbox: StrategyInbox = Arc::new(std::sync::Mutex::new(Vec::new()));
+        StrategyRun::new(reactor, definition, Box::new(strategy), inbox)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn backtest_replays_stored_candles() {
+        let reactor = reactor("backtest").await;
+        let from = 1_600_000_000 / 3600 * 3600;
+        let candles = reactor
+            .store
+            .market(MarketIdentifier::from("mock/BTC/USD"))
+            .unwrap()
+            .interval(Interval::Hour1)
+            .await
+            .unwrap();
+        // The last candle closes after the period, it is stored so nothing is backfilled
+        for (i, close) in ["100", "110", "120", "130", "140"].iter().enumerate() {
+            candles
+                .insert(candle(from + i as i64 * 3600, close))
+                .unwrap();
+        }
+        let definition = StrategyDefinition {
+            from: Some(from),
+            to: Some(from + 4 * 3600),
+            ..definition("backtest", StrategyMode::Backtest)
+        };
+        let recorder = Recorder {
+            candle_orders: vec![
+                (dec("100"), TradeSide::Buy, dec("1")),
+                (dec("120"), TradeSide::Sell, dec("0.5")),
+            ],
+            ..Recorder::default()
+        };
+        let events = recorder.events.clone();
+        let mut run = run(&reactor, &definition, recorder).await;
+        let report = run.run(&CancellationToken::new()).await.unwrap();
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                "init",
+                "candle 100",
+                "fill Buy 1",
+                "candle 110",
+                "candle 120",
+                "fill Sell 0.5",
+                "candle 130",
+                "shutdown"
+            ]
+        );
+        let fills = &run.ctx.state.fills;
+        assert_eq!(fills.len(), 2);
+        assert_eq!((fills[0].price, fills[0].fee), (dec("100"), dec("0.25")));
+        assert_eq!(fills[0].time, from + 3600);
+        assert_eq!((fills[1].price, fills[1].fee), (dec("120"), dec("0.15")));
+        assert!(report.errors.is_empty(), "{:?}", report.errors);
+        assert_eq!(report.candles, 4);
+        assert_eq!(report.orders, 2);
+        assert_eq!(report.fills, 2);
+        assert_eq!(report.position, dec("0.5"));
+        // -100 - 0.25 + 60 - 0.15
+        assert_eq!(report.cash, dec("-40.4"));
+        assert_eq!(report.price, Some(dec("130")));
+        assert_eq!(report.value, Some(dec("24.6")));
+    }
+
+    #[tokio::test]
+    async fn live_fills_are_booked_once() {
+        let reactor = reactor("live").await;
+        let definition = definition("live", StrategyMode::Live);
+        // Two orders filled by the same poll, then an order sent from `on_fill`
+        let recorder = Recorder {
+            init_orders: vec![(TradeSide::Buy, dec("0.1")), (TradeSide::Buy, dec("0.2"))],
+            fill_orders: vec![(TradeSide::Sell, dec("0.05"))],
+            ..Recorder::default()
+        };
+        let events = recorder.events.clone();
+        let mut run = run(&reactor, &definition, recorder).await;
+        run.process(StrategyEvent::Init).await.unwrap();
+        // Polled again between candles
+        run.settle().await;
+        run.settle().await;
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec!["init", "fill Buy 0.1", "fill Buy 0.2", "fill Sell 0.05"]
+        );
+        assert!(run.errors.is_empty(), "{:?}", run.errors);
+        assert_eq!(run.fills, 3);
+        assert_eq!(run.ctx.state.fills.len(), 3);
+        assert_eq!(run.ctx.state.order_ids.len(), 3);
+        assert_eq!(run.ctx.state.position, dec("0.25"));
+        // Bought at the ask of 40020 and sold at the bid of 39980, with 0.25% of fees
+        assert_eq!(run.ctx.state.cash, dec("-10042.0125"));
+        let newest = run.ctx.state.fills.iter().map(|e| e.time).max().unwrap();
+        assert_eq!(run.fills_since, Some(newest - FILL_TIME_MARGIN));
+    }
+
+    #[tokio::test]
+    async fn backtest_keeps_live_definitions() {
+        let reactor = reactor("taken").await;
+        reactor
+            .store
+            .strategies
+            .set(&definition("taken", StrategyMode::Paper))
+            .unwrap();
+        let backtest = StrategyDefinition {
+            from: Some(1_600_000_000),
+            ..definition("taken", StrategyMode::Backtest)
+        };
+        assert!(matches!(
+            reactor.backtest_strategy(backtest).await,
+            Err(Error::InvalidStrategy(_))
+        ));
+        let stored = reactor.store.strategies.get("taken").unwrap().unwrap();
+        assert_eq!(stored.mode, StrategyMode::Paper);
+        assert!(reactor.strategies.read().await.is_empty());
+    }
+}
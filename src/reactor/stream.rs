@@ -0,0 +1,104 @@
+use super::*;
+use crate::config::StreamedExchange;
+
+impl Reactor {
+    /// Stream the configured channels of each exchange into the store, returns a token
+    /// stopping every stream
+    pub async fn start_streams(&self, streamed: Vec<StreamedExchange>) -> CancellationToken {
+        let cancel = CancellationToken::new();
+        for streamed in streamed {
+            let stream = match self.exchanges.read().await.get(&streamed.exchange) {
+                Some(exchange) => exchange.stream(),
+                None => {
+                    log::error!("Stream not started: EXCHANGE={}, ERROR=exchange not registered", &streamed.exchange);
+                    continue;
+                }
+            };
+            let stream = match stream {
+                Some(stream) => stream,
+                None => {
+                    log::warn!("Stream not started: EXCHANGE={}, ERROR=no streaming api", &streamed.exchange);
+                    continue;
+                }
+            };
+            log::info!(
+                "Streaming exchange: EXCHANGE={}, URL={}, SUBSCRIPTIONS={}",
+                &streamed.exchange,
+                stream.url(),
+                streamed.subscriptions.len()
+            );
+            let (sender, receiver) = channel(1024);
+            tokio::spawn(run_stream(
+                stream,
+                streamed.subscriptions,
+                streamed.options,
+                sender,
+                cancel.clone(),
+            ));
+            tokio::spawn(Self::stream_handler(self.clone(), streamed.exchange, receiver));
+        }
+        cancel
+    }
+
+    /// Candles are kept out of the store while they are formed, a candle is written once an
+    /// update of the next one shows it closed
+    async fn stream_handler(reactor: Reactor, exchange: String, mut receiver: Receiver<StreamEvent>) {
+        let mut forming: HashMap<(MarketIdentifier, Interval), OHLC> = HashMap::new();
+        while let Some(event) = receiver.recv().await {
+            let event = match event {
+                StreamEvent::Connected => ReactorEvent::StreamStatus {
+                    exchange: exchange.clone(),
+                    connected: true,
+                    reason: None,
+                },
+                StreamEvent::Disconnected { reason } => ReactorEvent::StreamStatus {
+                    exchange: exchange.clone(),
+                    connected: false,
+                    reason: Some(reason),
+                },
+                StreamEvent::Ticker { market, ticker } => {
+                    ReactorEvent::TickerUpdated { market, ticker }
+                }
+                StreamEvent::Trades { market, trades } => {
+                    if let Err(e) = reactor
+                        .market_store(&market)
+                        .and_then(|e| e.trades())
+                        .and_then(|e| e.extend(trades.iter().cloned()))
+                    {
+                        log::error!("Failed to store streamed trades: EXCHANGE={}, BASE={}, QUOTE={}, ERROR={}", &market.exchange_name, &market.base, &market.quote, e);
+                    }
+                    ReactorEvent::TradesReceived { market, trades }
+                }
+                StreamEvent::Candle {
+                    market,
+                    interval,
+                    ohlc,
+                } => {
+                    let closed = match forming.insert((market.clone(), interval), ohlc.clone()) {
+                        Some(previous) if previous.time < ohlc.time => Some(previous),
+                        _ => None,
+                    };
+                    if let Some(closed) = closed {
+                        let stored = async {
+                            reactor
+                                .market_store(&market)?
+                                .interval(interval)
+                                .await?
+                                .insert(closed)
+                        }
+                        .await;
+                        if let Err(e) = stored {
+                            log::error!("Failed to store streamed candle: EXCHANGE={}, BASE={}, QUOTE={}, INTERVAL={}, ERROR={}", &market.exchange_name, &market.base, &market.quote, interval, e);
+                        }
+                    }
+                    ReactorEvent::CandleUpdated {
+                        market,
+                        interval,
+                        ohlc,
+                    }
+                }
+            };
+            reactor.listeners.broadcast(event).await;
+        }
+    }
+}
@@ -1,11 +1,14 @@
 use crate::store::StoreMarketDataHandle;
+use tokio::sync::Semaphore;
 
 use super::*;
 
 #[derive(Clone)]
 pub struct SyncMarket {
-    exchange: SyncExchange,
-    store: StoreMarketHandle,
+    pub(super) exchange: SyncExchange,
+    pub(super) listeners: ListenerMap,
+    pub(super) sync_limit: Arc<Semaphore>,
+    pub store: StoreMarketHandle,
 }
 
 impl SyncMarket {
@@ -24,7 +27,12 @@ impl SyncMarket {
             .ok_or_else(|| Error::ExchangeNotFound(identifier.exchange_name.clone()))?
             .clone();
         let store = reactor.store.market(identifier)?;
-        Ok(SyncMarket { store, exchange })
+        Ok(SyncMarket {
+            store,
+            exchange,
+            listeners: reactor.listeners.clone(),
+            sync_limit: reactor.sync_limit.clone(),
+        })
     }
 
     pub async fn interval(&self, interval: Interval) -> Result<StoreMarketDataHandle> {
@@ -49,11 +57,11 @@ impl SyncMarket {
             return Ok(from..to);
         }
 
-        let exchange_lock = self.exchange.lock().await;
-        let chunk = exchange_lock
-            .get_ohlc(&self.store.id, from, interval)
-            .await?;
-        let exchange_name = exchange_lock.name();
+        let chunk = {
+            let _permit = self.sync_limit.acquire().await;
+            self.exchange.get_ohlc(&self.store.id, from, interval).await?
+        };
+        let exchange_name = self.exchange.name();
         log::trace!("Appending {} OHLC metric into store: EXCHANGE={}, REQUEST_FROM={}, CHUNK_FROM={}, CHUNK_TO={}",
             chunk.data.len(),
             &exchange_name,
@@ -61,12 +69,31 @@ impl SyncMarket {
             chunk.begin,
             chunk.end,
         );
-        drop(exchange_lock);
         let tree = self.store.interval(interval).await?;
         tree.extend(chunk.data)?;
+        self.listeners
+            .broadcast(ReactorEvent::MarketSynced {
+                market: self.store.id.clone(),
+                interval,
+                begin: chunk.begin,
+                end: chunk.end,
+            })
+            .await;
         Ok(chunk.begin..chunk.end)
     }
 
+    pub async fn order_book(&self, depth: usize) -> Result<OrderBook> {
+        self.exchange.get_order_book(&self.store.id, depth).await
+    }
+
+    /// Fetch the trades newer than the last stored one, returns how many were stored
+    pub async fn sync_trades(&self) -> Result<usize> {
+        let trades = self.store.trades()?;
+        let since = trades.last()?.map(|e| e.time_ms);
+        let fetched = self.exchange.get_trades(&self.store.id, since).await?;
+        trades.extend(fetched)
+    }
+
     async fn check_periode_availability(
         &self,
         from: Timestamp,
@@ -95,4 +122,5 @@ impl SyncMarket {
     }
 }
 
-pub type SyncExchange = Arc<Mutex<Box<dyn Exchange + Sync + Send>>>;
+/// Exchanges synchronize internally, a handle can be used from any number of tasks at once
+pub type SyncExchange = Arc<dyn Exchange + Sync + Send>;
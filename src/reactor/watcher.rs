@@ -0,0 +1,46 @@
+use super::*;
+use crate::config::WatchedMarket;
+use crate::store::now;
+
+impl Reactor {
+    /// Keep the watched markets of the configuration synchronized in the background
+    pub fn watch_markets(&self, watched: Vec<WatchedMarket>) {
+        for watched in watched {
+            log::info!(
+                "Watching market: EXCHANGE={}, BASE={}, QUOTE={}, INTERVAL={}, REFRESH={:?}",
+                &watched.market.exchange_name,
+                &watched.market.base,
+                &watched.market.quote,
+                watched.interval,
+                watched.refresh
+            );
+            tokio::spawn(Self::market_watcher(self.clone(), watched));
+        }
+    }
+
+    async fn market_watcher(reactor: Reactor, watched: WatchedMarket) {
+        let mut from = now() - (watched.backfill.as_secs() as Timestamp).max(watched.interval.as_secs());
+        loop {
+            let to = now();
+            let synced = async {
+                reactor
+                    .get_or_register_market(&watched.market)
+                    .await?
+                    .sync_periode(from, to, watched.interval)
+                    .await
+            }
+            .await;
+            match synced {
+                Ok(_) => from = to - watched.interval.as_secs(),
+                Err(e) => log::error!(
+                    "Failed to sync watched market: EXCHANGE={}, BASE={}, QUOTE={}, ERROR={}",
+                    &watched.market.exchange_name,
+                    &watched.market.base,
+                    &watched.market.quote,
+                    e
+                ),
+            }
+            tokio::time::sleep(watched.refresh).await;
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use super::*;
+use crate::store::WATCHLIST_PREFIX;
+use std::collections::HashSet;
+
+/// `*` matches any run of characters and `?` a single one
+fn wildcard_match(pattern: &[char], text: &[char]) -> bool {
+    match (pattern.first(), text.first()) {
+        (None, None) => true,
+        (Some('*'), _) => {
+            wildcard_match(&pattern[1..], text)
+                || (!text.is_empty() && wildcard_match(pattern, &text[1..]))
+        }
+        (Some('?'), Some(_)) => wildcard_match(&pattern[1..], &text[1..]),
+        (Some(p), Some(t)) if p == t => wildcard_match(&pattern[1..], &text[1..]),
+        _ => false,
+    }
+}
+
+pub fn is_glob(raw: &str) -> bool {
+    raw.contains('*') || raw.contains('?')
+}
+
+/// Match a market against an `exchange/base/quote` glob, missing parts match anything
+pub fn market_matches(pattern: &str, market: &MarketIdentifier) -> bool {
+    let mut parts = pattern.split('/');
+    let part_matches = |part: Option<&str>, value: &str| match part {
+        Some(part) if !part.is_empty() => wildcard_match(
+            &part.chars().collect::<Vec<_>>(),
+            &value.chars().collect::<Vec<_>>(),
+        ),
+        _ => true,
+    };
+    part_matches(parts.next(), &market.exchange_name)
+        && part_matches(parts.next().map(|e| e.to_uppercase()).as_deref(), &market.base)
+        && part_matches(parts.next().map(|e| e.to_uppercase()).as_deref(), &market.quote)
+}
+
+impl Reactor {
+    /// Replace `@name` arguments by the members of the watchlist, a watchlist referenced
+    /// several times (or by itself) is only expanded once
+    pub fn expand_watchlists<S: AsRef<str>>(&self, args: &[S]) -> Result<Vec<String>> {
+        let mut seen = HashSet::new();
+        let mut ret = Vec::new();
+        for arg in args {
+            self.expand_watchlist_into(arg.as_ref(), &mut seen, &mut ret)?;
+        }
+        Ok(ret)
+    }
+
+    fn expand_watchlist_into(
+        &self,
+        raw: &str,
+        seen: &mut HashSet<String>,
+        ret: &mut Vec<String>,
+    ) -> Result<()> {
+        match raw.strip_prefix(WATCHLIST_PREFIX) {
+            Some(name) => {
+                if !seen.insert(name.to_string()) {
+                    return Ok(());
+                }
+                let watchlist = self
+                    .store
+                    .watchlist(name)?
+                    .ok_or_else(|| Error::WatchlistNotFound(name.to_string()))?;
+                for member in watchlist.members.iter() {
+                    self.expand_watchlist_into(member, seen, ret)?;
+                }
+            }
+            None => ret.push(raw.to_string()),
+        }
+        Ok(())
+    }
+
+    /// Resolve market arguments (markets, `@name` watchlists and globs such as `kraken/*/EUR`
+    /// matched against the markets of the exchanges) in order and without duplicates
+    pub async fn resolve_markets<S: AsRef<str>>(&self, args: &[S]) -> Result<Vec<MarketIdentifier>> {
+        let mut available: HashMap<String, Vec<MarketIdentifier>> = HashMap::new();
+        let mut ret: Vec<MarketIdentifier> = Vec::new();
+        for raw in self.expand_watchlists(args)? {
+            let matched = if is_glob(&raw) {
+                let exchange_pattern: Vec<char> =
+                    raw.split('/').next().unwrap_or("").chars().collect();
+                let exchanges: Vec<(String, SyncExchange)> = self
+                    .exchanges
+                    .read()
+                    .await
+                    .iter()
+                    .filter(|(name, _)| {
+                        exchange_pattern.is_empty()
+                            || wildcard_match(&exchange_pattern, &name.chars().collect::<Vec<_>>())
+                    })
+                    .map(|(name, exchange)| (name.clone(), exchange.clone()))
+                    .collect();
+                let mut matched = Vec::new();
+                for (name, exchange) in exchanges {
+                    // Parts without wildcard may use any alias of the asset
+                    let pattern = raw
+                        .split('/')
+                        .enumerate()
+                        .map(|(i, part)| match i {
+                            0 => part.to_string(),
+                            _ if is_glob(part) => part.to_string(),
+                            _ => self.assets.canonical(&name, part),
+                        })
+                        .join("/");
+                    if !available.contains_key(&name) {
+                        let mut markets = exchange.get_markets().await?;
+                        markets.sort_by(|a, b| (&a.base, &a.quote).cmp(&(&b.base, &b.quote)));
+                        available.insert(name.clone(), markets);
+                    }
+                    matched.extend(
+                        available[&name]
+                            .iter()
+                            .filter(|e| market_matches(&pattern, &self.assets.canonical_market(e)))
+                            .cloned(),
+                    );
+                }
+                if matched.is_empty() {
+                    return Err(Error::Parsing(format!("No market matches `{}`", raw), 0..0));
+                }
+                matched
+            } else {
+                vec![MarketIdentifier::from(&raw)]
+            };
+            for market in matched {
+                if !ret.contains(&market) {
+                    ret.push(market);
+                }
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Resolve an argument naming a single market, a glob or a watchlist must match exactly one
+    pub async fn resolve_market(&self, arg: &str) -> Result<MarketIdentifier> {
+        let mut markets = self.resolve_markets(&[arg]).await?;
+        match markets.len() {
+            1 => Ok(markets.remove(0)),
+            count => Err(Error::Parsing(
+                format!("`{}` matches {} markets, expected a single one", arg, count),
+                0..0,
+            )),
+        }
+    }
+}
@@ -1,17 +1,61 @@
 use crate::{exchange::MarketIdentifier, prelude::*};
 use sled::{Db, IVec};
 
+macro_rules! try_result_opt {
+    ($ivec:expr) => {
+        Ok(if let Some(raw) = $ivec? {
+            Some($crate::store::schema::decode(raw.as_ref())?)
+        } else {
+            None
+        })
+    };
+}
+
+mod alert;
+mod backup;
+mod fill;
+mod history;
+mod inspect;
 mod market;
+mod retention;
+mod risk;
+mod schedule;
+pub mod schema;
+mod strategy;
+mod trade;
+mod transfer;
+mod watchlist;
+pub use alert::*;
+pub use backup::*;
+pub use fill::*;
+pub use history::*;
+pub use inspect::*;
 pub use market::*;
+pub use retention::*;
+pub use risk::*;
+pub use schedule::*;
+pub use strategy::*;
+pub use trade::*;
+pub use transfer::*;
+pub use watchlist::*;
 
 pub struct Store {
     db: Db,
+    write_gate: WriteGate,
+    history_retention: HistoryRetention,
 }
 
 #[derive(Clone)]
 pub struct StoreHandle {
     db: Db,
     settings_tree: sled::Tree,
+    write_gate: WriteGate,
+    pub history: StoreHistoryHandle,
+    pub schedules: StoreScheduleHandle,
+    pub alerts: StoreAlertHandle,
+    pub fills: StoreFillsHandle,
+    pub risk: StoreRiskHandle,
+    pub strategies: StoreStrategyHandle,
     pub trees: Arc<std::sync::Mutex<HashMap<String, StoreMarketHandle>>>,
 }
 
@@ -27,9 +71,53 @@ impl StoredTimestamp for IVec {
 }
 
 impl Store {
+    /// Open a store which must already exist, without migrating it
+    pub fn open_existing(path: PathBuf) -> Result<Self> {
+        if !path.exists() {
+            return Err(Error::IO(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("no store at {}", path.display()),
+            )));
+        }
+        Self::open(path)
+    }
+
+    /// Open the store and bring it to the current schema version
     pub fn new(path: PathBuf) -> Result<Self> {
+        let store = Self::open(path)?;
+        let report = store.migrate(false)?;
+        for step in report.steps.iter() {
+            log::info!(
+                "Store migrated: VERSION={}, TREES={}, DESCRIPTION={}",
+                step.version,
+                step.trees.len(),
+                step.description
+            );
+        }
+        Ok(store)
+    }
+
+    /// Open the store without migrating it
+    pub fn open(path: PathBuf) -> Result<Self> {
         let db = sled::open(path)?;
-        Ok(Self { db })
+        Ok(Self {
+            db,
+            write_gate: WriteGate::default(),
+            history_retention: HistoryRetention::default(),
+        })
+    }
+
+    pub fn migrate(&self, dry_run: bool) -> Result<schema::MigrationReport> {
+        schema::migrate(&self.db, dry_run)
+    }
+
+    pub fn schema_version(&self) -> Result<Option<u32>> {
+        schema::stored_version(&self.db)
+    }
+
+    pub fn with_history_retention(mut self, retention: HistoryRetention) -> Self {
+        self.history_retention = retention;
+        self
     }
 
     pub fn handle(&self) -> StoreHandle {
@@ -38,6 +126,23 @@ impl Store {
                 .db
                 .open_tree("settings")
                 .expect("Failed to create settings store"),
+            history: StoreHistoryHandle::new(
+                self.db.clone(),
+                self.history_retention.clone(),
+                self.write_gate.clone(),
+            )
+                .expect("Failed to create history store"),
+            schedules: StoreScheduleHandle::new(&self.db, self.write_gate.clone())
+                .expect("Failed to create schedules store"),
+            alerts: StoreAlertHandle::new(&self.db, self.write_gate.clone())
+                .expect("Failed to create alerts store"),
+            fills: StoreFillsHandle::new(&self.db, self.write_gate.clone())
+                .expect("Failed to create fills store"),
+            risk: StoreRiskHandle::new(&self.db, self.write_gate.clone())
+                .expect("Failed to create risk store"),
+            strategies: StoreStrategyHandle::new(&self.db, self.write_gate.clone())
+                .expect("Failed to create strategies store"),
+            write_gate: self.write_gate.clone(),
             db: self.db.clone(),
             trees: Arc::new(std::sync::Mutex::new(HashMap::new())),
         }
@@ -45,6 +150,18 @@ impl Store {
 }
 
 impl StoreHandle {
+    /// Every market and interval with stored candles
+    pub fn market_trees(&self) -> Result<Vec<(MarketIdentifier, Interval)>> {
+        let mut ret = Vec::new();
+        for name in self.db.tree_names() {
+            let name = String::from_utf8_lossy(&name);
+            if let Some(parsed) = MarketIdentifier::from_data_tree_uid(&name) {
+                ret.push(parsed);
+            }
+        }
+        Ok(ret)
+    }
+
     pub fn market(&self, market: MarketIdentifier) -> Result<StoreMarketHandle> {
         let uid = market.tree_uid();
         if let Some(exchange) = self.trees.lock().unwrap().get(&uid) {
@@ -52,7 +169,12 @@ impl StoreHandle {
         }
         self.trees.lock().unwrap().insert(
             uid.clone(),
-            StoreMarketHandle::new(self.db.clone(), self.settings_tree.clone(), market),
+            StoreMarketHandle::new(
+                self.db.clone(),
+                self.settings_tree.clone(),
+                self.write_gate.clone(),
+                market,
+            ),
         );
         Ok(self.trees.lock().unwrap()[&uid].clone())
     }
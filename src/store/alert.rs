@@ -0,0 +1,198 @@
+use super::*;
+
+#[derive(Debug, Clone, Encode, Decode, Serialize, Deserialize)]
+pub enum AlertCondition {
+    /// Close crosses the level upward
+    PriceAbove { level: Decimal },
+    /// Close crosses the level downward
+    PriceBelow { level: Decimal },
+    RsiAbove { period: usize, threshold: Decimal },
+    RsiBelow { period: usize, threshold: Decimal },
+    /// Absolute close change over the last `window` candles reach `percent`
+    PercentMove { window: usize, percent: Decimal },
+}
+
+#[derive(Debug, Clone, Encode, Decode, Serialize, Deserialize)]
+pub enum AlertSink {
+    EventBus,
+    File { path: String },
+    Command { command: String },
+    Webhook { url: String },
+}
+
+#[derive(Debug, Clone, Encode, Decode, Serialize, Deserialize)]
+pub struct Alert {
+    pub name: String,
+    pub market: String,
+    pub interval: Interval,
+    pub condition: AlertCondition,
+    pub sinks: Vec<AlertSink>,
+    /// Condition state at the last evaluation, alerts only fire when it becomes true
+    #[serde(default)]
+    pub active: bool,
+    #[serde(default)]
+    pub last_triggered: Option<Timestamp>,
+}
+
+#[derive(Clone)]
+pub struct StoreAlertHandle {
+    tree: sled::Tree,
+    write_gate: WriteGate,
+}
+
+impl std::fmt::Display for AlertCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AlertCondition::PriceAbove { level } => write!(f, "price above {}", level),
+            AlertCondition::PriceBelow { level } => write!(f, "price below {}", level),
+            AlertCondition::RsiAbove { period, threshold } => {
+                write!(f, "rsi({}) above {}", period, threshold)
+            }
+            AlertCondition::RsiBelow { period, threshold } => {
+                write!(f, "rsi({}) below {}", period, threshold)
+            }
+            AlertCondition::PercentMove { window, percent } => {
+                write!(f, "move of {}% over {} candles", percent, window)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for AlertSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AlertSink::EventBus => write!(f, "bus"),
+            AlertSink::File { path } => write!(f, "file:{}", path),
+            AlertSink::Command { command } => write!(f, "cmd:{}", command),
+            AlertSink::Webhook { url } => write!(f, "{}", url),
+        }
+    }
+}
+
+impl AlertCondition {
+    /// Reject conditions that can never be evaluated or would always hold
+    pub fn check(&self) -> Result<()> {
+        let invalid = |message: &str| Err(Error::Parsing(format!("{}: {}", self, message), 0..0));
+        match self {
+            AlertCondition::RsiAbove { period, threshold }
+            | AlertCondition::RsiBelow { period, threshold } => {
+                if *period == 0 {
+                    return invalid("the period must be at least 1");
+                }
+                if *threshold < Decimal::ZERO || *threshold > Decimal::from(100i64) {
+                    return invalid("the threshold must be between 0 and 100");
+                }
+            }
+            AlertCondition::PercentMove { window, percent } => {
+                if *window == 0 {
+                    return invalid("the window must be at least 1");
+                }
+                if *percent <= Decimal::ZERO {
+                    return invalid("the percent must be positive");
+                }
+            }
+            AlertCondition::PriceAbove { .. } | AlertCondition::PriceBelow { .. } => {}
+        }
+        Ok(())
+    }
+}
+
+impl AlertSink {
+    /// Whether delivering writes files or runs commands on the daemon host
+    pub fn is_local(&self) -> bool {
+        matches!(self, AlertSink::File { .. } | AlertSink::Command { .. })
+    }
+}
+
+impl std::str::FromStr for AlertSink {
+    type Err = Error;
+
+    fn from_str(raw: &str) -> Result<Self> {
+        if raw == "bus" || raw == "stdout" {
+            Ok(AlertSink::EventBus)
+        } else if let Some(path) = raw.strip_prefix("file:") {
+            Ok(AlertSink::File {
+                path: path.to_string(),
+            })
+        } else if let Some(command) = raw.strip_prefix("cmd:") {
+            Ok(AlertSink::Command {
+                command: command.to_string(),
+            })
+        } else if raw.starts_with("http://") || raw.starts_with("https://") {
+            Ok(AlertSink::Webhook {
+                url: raw.to_string(),
+            })
+        } else {
+            Err(Error::Parsing(
+                format!(
+                    "Wrong alert sink `{}`, expected one of: bus, file:<path>, cmd:<command>, http(s)://<url>",
+                    raw
+                ),
+                0..0,
+            ))
+        }
+    }
+}
+
+impl StoreAlertHandle {
+    pub fn new(db: &Db, write_gate: WriteGate) -> Result<Self> {
+        Ok(Self {
+            tree: db.open_tree("alerts")?,
+            write_gate,
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Result<Option<Alert>> {
+        try_result_opt!(self.tree.get(name.as_bytes()))
+    }
+
+    pub fn set(&self, alert: &Alert) -> Result<()> {
+        let encoded = schema::encode(alert)?;
+        let _gate = self.write_gate.enter();
+        self.tree.insert(alert.name.as_bytes(), encoded)?;
+        Ok(())
+    }
+
+    /// Save the evaluation state of an alert without touching the rest of it. Returns false
+    /// when the alert was removed in the meantime, it is not brought back
+    pub fn set_state(
+        &self,
+        name: &str,
+        active: bool,
+        last_triggered: Option<Timestamp>,
+    ) -> Result<bool> {
+        let _gate = self.write_gate.enter();
+        loop {
+            let current = match self.tree.get(name.as_bytes())? {
+                Some(current) => current,
+                None => return Ok(false),
+            };
+            let mut alert: Alert = schema::decode(current.as_ref())?;
+            alert.active = active;
+            alert.last_triggered = last_triggered;
+            let encoded = schema::encode(&alert)?;
+            if self
+                .tree
+                .compare_and_swap(name.as_bytes(), Some(current), Some(encoded))?
+                .is_ok()
+            {
+                return Ok(true);
+            }
+        }
+    }
+
+    pub fn remove(&self, name: &str) -> Result<bool> {
+        let _gate = self.write_gate.enter();
+        Ok(self.tree.remove(name.as_bytes())?.is_some())
+    }
+
+    pub fn list(&self) -> Result<Vec<Alert>> {
+        let mut ret = Vec::new();
+        for item in self.tree.iter() {
+            let (_, raw) = item?;
+            let decoded = schema::decode(raw.as_ref())?;
+            ret.push(decoded);
+        }
+        Ok(ret)
+    }
+}
@@ -0,0 +1,261 @@
+use super::*;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+
+const BACKUP_MAGIC: &[u8; 4] = b"PKBK";
+const BACKUP_VERSION: u32 = 1;
+/// Number of entries applied at once when restoring a tree
+const RESTORE_BATCH_SIZE: usize = 4096;
+
+/// Writers hold the gate shared while a backup holds it exclusively, so a backup sees every
+/// tree at the same point in time
+#[derive(Clone, Default, Debug)]
+pub struct WriteGate(Arc<std::sync::RwLock<()>>);
+
+impl WriteGate {
+    pub fn enter(&self) -> std::sync::RwLockReadGuard<'_, ()> {
+        self.0.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn freeze(&self) -> std::sync::RwLockWriteGuard<'_, ()> {
+        self.0.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupReport {
+    pub path: PathBuf,
+    pub trees: usize,
+    pub entries: usize,
+    /// Hex encoded SHA-256 of the uncompressed snapshot
+    pub checksum: String,
+}
+
+/// Feed everything written through it to a SHA-256 digest
+struct HashingWriter<W: Write> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+struct HashingReader<R: Read> {
+    inner: R,
+    hasher: Sha256,
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        Ok(read)
+    }
+}
+
+pub(super) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|e| format!("{:02x}", e)).collect()
+}
+
+fn corrupted(path: &Path, reason: &str) -> Error {
+    Error::Backup(format!("{}: {}", path.display(), reason))
+}
+
+fn write_chunk<W: Write>(out: &mut W, chunk: &[u8]) -> Result<()> {
+    out.write_all(&(chunk.len() as u32).to_be_bytes())?;
+    out.write_all(chunk)?;
+    Ok(())
+}
+
+fn read_u32<R: Read>(input: &mut R) -> Result<u32> {
+    let mut raw = [0u8; 4];
+    input.read_exact(&mut raw)?;
+    Ok(u32::from_be_bytes(raw))
+}
+
+fn read_flag<R: Read>(input: &mut R) -> Result<bool> {
+    let mut raw = [0u8; 1];
+    input.read_exact(&mut raw)?;
+    Ok(raw[0] == 1)
+}
+
+fn read_chunk<R: Read>(input: &mut R) -> Result<Vec<u8>> {
+    let mut ret = vec![0u8; read_u32(input)? as usize];
+    input.read_exact(&mut ret)?;
+    Ok(ret)
+}
+
+/// Walk the snapshot of a backup, `apply` is called once per tree with all its entries
+///
+/// Layout (gzip compressed): magic, version, then for each tree `1, name, entries..., 0` where an
+/// entry is `1, key, value`, a final `0` and the SHA-256 of everything before it
+fn read_backup<F>(path: &Path, mut apply: F) -> Result<String>
+where
+    F: FnMut(&[u8], Vec<(Vec<u8>, Vec<u8>)>) -> Result<()>,
+{
+    let mut input = HashingReader {
+        inner: GzDecoder::new(BufReader::new(File::open(path)?)),
+        hasher: Sha256::new(),
+    };
+    let mut magic = [0u8; 4];
+    input.read_exact(&mut magic)?;
+    if &magic != BACKUP_MAGIC {
+        return Err(corrupted(path, "not a pkbot backup"));
+    }
+    let version = read_u32(&mut input)?;
+    if version != BACKUP_VERSION {
+        return Err(corrupted(path, &format!("unsupported backup version {}", version)));
+    }
+    while read_flag(&mut input)? {
+        let name = read_chunk(&mut input)?;
+        let mut entries = Vec::new();
+        while read_flag(&mut input)? {
+            let key = read_chunk(&mut input)?;
+            entries.push((key, read_chunk(&mut input)?));
+        }
+        apply(&name, entries)?;
+    }
+    let computed = hex(&input.hasher.clone().finalize());
+    let mut expected = [0u8; 32];
+    input.inner.read_exact(&mut expected)?;
+    if hex(&expected) != computed {
+        return Err(corrupted(path, "checksum mismatch"));
+    }
+    Ok(computed)
+}
+
+impl StoreHandle {
+    /// Write a compressed snapshot of every tree to `path`. Writers are only paused while the
+    /// trees are streamed uncompressed to a temporary file, not while it is compressed
+    pub fn backup(&self, path: &Path) -> Result<BackupReport> {
+        let raw = path.with_extension("raw");
+        let copied = self.snapshot(&raw).and_then(|(trees, entries)| {
+            let partial = path.with_extension("partial");
+            let mut out = HashingWriter {
+                inner: GzEncoder::new(
+                    BufWriter::new(File::create(&partial)?),
+                    Compression::default(),
+                ),
+                hasher: Sha256::new(),
+            };
+            std::io::copy(&mut BufReader::new(File::open(&raw)?), &mut out)?;
+            let digest = out.hasher.clone().finalize();
+            let mut inner = out.inner;
+            inner.write_all(&digest)?;
+            inner.finish()?.flush()?;
+            std::fs::rename(&partial, path)?;
+            Ok((trees, entries, hex(&digest)))
+        });
+        let _ = std::fs::remove_file(&raw);
+        let (trees, entries, checksum) = copied?;
+        log::info!(
+            "Store backup DONE: PATH={}, TREES={}, ENTRIES={}, SHA256={}",
+            path.display(),
+            trees,
+            entries,
+            checksum
+        );
+        Ok(BackupReport {
+            path: path.to_path_buf(),
+            trees,
+            entries,
+            checksum,
+        })
+    }
+
+    /// Stream every tree at the same point in time to `raw` in the uncompressed layout of
+    /// `read_backup`, entry by entry so the store is never held in memory. Returns the number of
+    /// trees and entries
+    fn snapshot(&self, raw: &Path) -> Result<(usize, usize)> {
+        let mut out = BufWriter::new(File::create(raw)?);
+        let mut trees = 0;
+        let mut entries = 0;
+        out.write_all(BACKUP_MAGIC)?;
+        out.write_all(&BACKUP_VERSION.to_be_bytes())?;
+        let frozen = self.write_gate.freeze();
+        for name in self.db.tree_names() {
+            if &name[..] == b"__sled__default" {
+                continue;
+            }
+            out.write_all(&[1])?;
+            write_chunk(&mut out, &name)?;
+            for item in self.db.open_tree(&name)?.iter() {
+                let (key, value) = item?;
+                out.write_all(&[1])?;
+                write_chunk(&mut out, &key)?;
+                write_chunk(&mut out, &value)?;
+                entries += 1;
+            }
+            out.write_all(&[0])?;
+            trees += 1;
+        }
+        drop(frozen);
+        out.write_all(&[0])?;
+        out.flush()?;
+        Ok((trees, entries))
+    }
+}
+
+/// Check the integrity of a backup without restoring it
+pub fn verify_backup(path: &Path) -> Result<String> {
+    read_backup(path, |_, _| Ok(()))
+}
+
+/// Restore a backup into a new store at `target`, an existing store is only overwritten with `force`
+pub fn restore_backup(path: &Path, target: &Path, force: bool) -> Result<BackupReport> {
+    let checksum = verify_backup(path)?;
+    let db = sled::open(target)?;
+    if !force && db.tree_names().iter().any(|e| db.open_tree(e).map(|e| !e.is_empty()).unwrap_or(true)) {
+        return Err(Error::Backup(format!(
+            "{} is not empty, use --force to overwrite it",
+            target.display()
+        )));
+    }
+    let mut restored = Vec::new();
+    let mut entries = 0;
+    read_backup(path, |name, content| {
+        restored.push(name.to_vec());
+        let tree = db.open_tree(name)?;
+        tree.clear()?;
+        entries += content.len();
+        for chunk in content.chunks(RESTORE_BATCH_SIZE) {
+            let mut batch = sled::Batch::default();
+            for (key, value) in chunk {
+                batch.insert(key.as_slice(), value.as_slice());
+            }
+            tree.apply_batch(batch)?;
+        }
+        Ok(())
+    })?;
+    for name in db.tree_names() {
+        if &name[..] != b"__sled__default" && !restored.iter().any(|e| e[..] == name[..]) {
+            db.drop_tree(&name)?;
+        }
+    }
+    db.flush()?;
+    let trees = restored.len();
+    log::info!(
+        "Store restore DONE: PATH={}, TARGET={}, TREES={}, ENTRIES={}",
+        path.display(),
+        target.display(),
+        trees,
+        entries
+    );
+    Ok(BackupReport {
+        path: target.to_path_buf(),
+        trees,
+        entries,
+        checksum,
+    })
+}
@@ -0,0 +1,77 @@
+use super::*;
+use crate::exchange::Fill;
+
+/// Trades of the account, keyed by time then id so they iterate in order
+#[derive(Clone)]
+pub struct StoreFillsHandle {
+    db: Db,
+    tree: sled::Tree,
+    write_gate: WriteGate,
+}
+
+impl StoreFillsHandle {
+    pub fn new(db: &Db, write_gate: WriteGate) -> Result<Self> {
+        Ok(Self {
+            db: db.clone(),
+            tree: db.open_tree("fills")?,
+            write_gate,
+        })
+    }
+
+    fn key(fill: &Fill) -> Vec<u8> {
+        let mut key = fill.time.to_be_bytes().to_vec();
+        key.extend_from_slice(fill.id.as_bytes());
+        key
+    }
+
+    /// Identifier of a fill entered by hand
+    pub fn manual_id(&self) -> Result<String> {
+        Ok(format!("manual-{}", self.db.generate_id()?))
+    }
+
+    /// Insert fills, a fill already stored is replaced
+    pub fn extend(&self, fills: &[Fill]) -> Result<usize> {
+        let mut batch = sled::Batch::default();
+        for fill in fills.iter() {
+            batch.insert(Self::key(fill), schema::encode(fill)?);
+        }
+        let _gate = self.write_gate.enter();
+        self.tree.apply_batch(batch)?;
+        Ok(fills.len())
+    }
+
+    pub fn remove(&self, id: &str) -> Result<bool> {
+        let _gate = self.write_gate.enter();
+        for item in self.tree.iter() {
+            let (key, raw) = item?;
+            let fill: Fill = schema::decode(raw.as_ref())?;
+            if fill.id == id {
+                self.tree.remove(key)?;
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Fills oldest first
+    pub fn list(&self) -> Result<Vec<Fill>> {
+        let mut ret = Vec::new();
+        for item in self.tree.iter() {
+            let (_, raw) = item?;
+            ret.push(schema::decode(raw.as_ref())?);
+        }
+        Ok(ret)
+    }
+
+    /// Time of the last fill imported from `exchange`
+    pub fn last_time(&self, exchange: &str) -> Result<Option<Timestamp>> {
+        for item in self.tree.iter().rev() {
+            let (_, raw) = item?;
+            let fill: Fill = schema::decode(raw.as_ref())?;
+            if fill.market_id().exchange_name == exchange && !fill.id.starts_with("manual-") {
+                return Ok(Some(fill.time));
+            }
+        }
+        Ok(None)
+    }
+}
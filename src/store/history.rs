@@ -0,0 +1,260 @@
+use super::*;
+use crate::interpretor::{ProgramIdentifier, ProgramOutput, ProgramStatus};
+
+pub type RunIdentifier = u64;
+
+#[derive(Debug, Clone, Encode, Decode, Serialize, Deserialize)]
+pub struct ProgramRun {
+    pub run_id: RunIdentifier,
+    pub program_id: ProgramIdentifier,
+    pub source: String,
+    pub started_at: Timestamp,
+    pub ended_at: Option<Timestamp>,
+    pub status: ProgramStatus,
+}
+
+#[derive(Debug, Clone, Copy, Encode, Decode, Serialize, Deserialize)]
+pub enum RecordedOutputKind {
+    Text,
+    Json,
+    Exit,
+}
+
+#[derive(Debug, Clone, Encode, Decode, Serialize, Deserialize)]
+pub struct RecordedOutput {
+    pub time: Timestamp,
+    pub kind: RecordedOutputKind,
+    pub status: Option<ProgramStatus>,
+    pub content: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct HistoryRetention {
+    pub max_runs: usize,
+    pub max_age: Option<Duration>,
+}
+
+impl std::default::Default for HistoryRetention {
+    fn default() -> Self {
+        Self {
+            max_runs: 1_000,
+            max_age: Some(Duration::from_secs(60 * 60 * 24 * 30)),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct StoreHistoryHandle {
+    db: Db,
+    runs: sled::Tree,
+    outputs: sled::Tree,
+    retention: HistoryRetention,
+    write_gate: WriteGate,
+}
+
+pub fn now() -> Timestamp {
+    SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|e| e.as_secs() as Timestamp)
+        .unwrap_or(0)
+}
+
+impl From<&ProgramOutput> for RecordedOutput {
+    fn from(output: &ProgramOutput) -> Self {
+        let (kind, status, content) = match output {
+            ProgramOutput::Text { message } => (RecordedOutputKind::Text, None, message.clone()),
+            ProgramOutput::Json { content } => (
+                RecordedOutputKind::Json,
+                None,
+                content.to_json().to_string(),
+            ),
+            ProgramOutput::Exit { message, status } => (
+                RecordedOutputKind::Exit,
+                Some(*status),
+                message.clone().unwrap_or_default(),
+            ),
+        };
+        Self {
+            time: now(),
+            kind,
+            status,
+            content,
+        }
+    }
+}
+
+impl StoreHistoryHandle {
+    pub fn new(db: Db, retention: HistoryRetention, write_gate: WriteGate) -> Result<Self> {
+        Ok(Self {
+            runs: db.open_tree("history_runs")?,
+            outputs: db.open_tree("history_outputs")?,
+            db,
+            retention,
+            write_gate,
+        })
+    }
+
+    pub fn begin(&self, program_id: ProgramIdentifier, source: &str) -> Result<RunIdentifier> {
+        let run = ProgramRun {
+            run_id: self.db.generate_id()?,
+            program_id,
+            source: source.to_string(),
+            started_at: now(),
+            ended_at: None,
+            status: ProgramStatus::Running,
+        };
+        self.set_run(&run)?;
+        log::trace!("Begin program run record: RUN={}, ID={}", run.run_id, program_id);
+        Ok(run.run_id)
+    }
+
+    pub fn record(&self, run_id: RunIdentifier, output: &ProgramOutput) -> Result<()> {
+        let encoded = schema::encode(&RecordedOutput::from(output))?;
+        let mut key = run_id.to_be_bytes().to_vec();
+        key.extend_from_slice(&self.db.generate_id()?.to_be_bytes());
+        let _gate = self.write_gate.enter();
+        self.outputs.insert(key, encoded)?;
+        Ok(())
+    }
+
+    pub fn finish(&self, run_id: RunIdentifier, status: ProgramStatus) -> Result<()> {
+        if let Some(mut run) = self.run(run_id)? {
+            run.ended_at = Some(now());
+            run.status = status;
+            self.set_run(&run)?;
+        }
+        self.apply_retention()
+    }
+
+    /// Mark the runs left running by a previous process as failed, so retention can expire
+    /// them. Returns how many were found
+    pub fn abort_interrupted(&self) -> Result<usize> {
+        let mut interrupted = Vec::new();
+        for item in self.runs.iter() {
+            let (_, raw) = item?;
+            let run: ProgramRun = schema::decode(raw.as_ref())?;
+            if run.ended_at.is_none() {
+                interrupted.push(run);
+            }
+        }
+        for mut run in interrupted.iter().cloned() {
+            log::warn!("Marking interrupted program run as failed: RUN={}, ID={}", run.run_id, run.program_id);
+            self.record(
+                run.run_id,
+                &ProgramOutput::Exit {
+                    message: Some(String::from("interrupted by a restart of the daemon")),
+                    status: ProgramStatus::Error,
+                },
+            )?;
+            run.ended_at = Some(now());
+            run.status = ProgramStatus::Error;
+            self.set_run(&run)?;
+        }
+        Ok(interrupted.len())
+    }
+
+    pub fn run(&self, run_id: RunIdentifier) -> Result<Option<ProgramRun>> {
+        try_result_opt!(self.runs.get(run_id.to_be_bytes()))
+    }
+
+    /// Most recent runs first
+    pub fn runs(&self, limit: usize) -> Result<Vec<ProgramRun>> {
+        let mut ret = Vec::new();
+        for item in self.runs.iter().rev().take(limit) {
+            let (_, raw) = item?;
+            let decoded = schema::decode(raw.as_ref())?;
+            ret.push(decoded);
+        }
+        Ok(ret)
+    }
+
+    pub fn logs(&self, run_id: RunIdentifier) -> Result<Vec<RecordedOutput>> {
+        let mut ret = Vec::new();
+        for item in self.outputs.scan_prefix(run_id.to_be_bytes()) {
+            let (_, raw) = item?;
+            let decoded = schema::decode(raw.as_ref())?;
+            ret.push(decoded);
+        }
+        Ok(ret)
+    }
+
+    pub fn remove(&self, run_id: RunIdentifier) -> Result<()> {
+        let _gate = self.write_gate.enter();
+        self.runs.remove(run_id.to_be_bytes())?;
+        for item in self.outputs.scan_prefix(run_id.to_be_bytes()).keys() {
+            self.outputs.remove(item?)?;
+        }
+        Ok(())
+    }
+
+    fn set_run(&self, run: &ProgramRun) -> Result<()> {
+        let encoded = schema::encode(run)?;
+        let _gate = self.write_gate.enter();
+        self.runs.insert(run.run_id.to_be_bytes(), encoded)?;
+        Ok(())
+    }
+
+    fn apply_retention(&self) -> Result<()> {
+        let min_started_at = self
+            .retention
+            .max_age
+            .map(|e| now() - e.as_secs() as Timestamp);
+        let mut excess = self.runs.len().saturating_sub(self.retention.max_runs);
+        for item in self.runs.iter() {
+            let (_, raw) = item?;
+            let run: ProgramRun = schema::decode(raw.as_ref())?;
+            let expired = min_started_at
+                .map(|e| run.ended_at.is_some() && run.started_at < e)
+                .unwrap_or(false);
+            if excess == 0 && !expired {
+                break;
+            }
+            // Runs of programs still running are kept whatever the count, their outputs too
+            if run.ended_at.is_none() {
+                continue;
+            }
+            log::trace!("Pruning program run record: RUN={}", run.run_id);
+            self.remove(run.run_id)?;
+            excess = excess.saturating_sub(1);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retention_keeps_running_programs() {
+        let temp_path = std::env::temp_dir().join(format!("pkbot-history-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&temp_path);
+        let history = Store::new(temp_path.clone())
+            .unwrap()
+            .with_history_retention(HistoryRetention {
+                max_runs: 2,
+                max_age: None,
+            })
+            .handle()
+            .history;
+
+        let running = history.begin(1, "sleep 1h").unwrap();
+        let output = ProgramOutput::Text {
+            message: String::from("still there"),
+        };
+        history.record(running, &output).unwrap();
+        let mut finished = Vec::new();
+        for id in 2..5 {
+            let run_id = history.begin(id, "echo").unwrap();
+            history.finish(run_id, ProgramStatus::Success).unwrap();
+            finished.push(run_id);
+        }
+
+        // The oldest runs are pruned but never the one still running
+        let kept: Vec<_> = history.runs(10).unwrap().iter().map(|e| e.run_id).collect();
+        assert_eq!(kept, vec![finished[2], running]);
+        assert_eq!(history.logs(running).unwrap().len(), 1);
+        assert!(history.run(finished[0]).unwrap().is_none());
+        let _ = std::fs::remove_dir_all(&temp_path);
+    }
+}
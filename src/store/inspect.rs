@@ -0,0 +1,335 @@
+use super::backup::hex;
+use super::*;
+use serde_json::Value;
+
+/// What a tree holds, guessed from its name
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TreeContent {
+    MarketData {
+        market: MarketIdentifier,
+        interval: Interval,
+    },
+    Trades {
+        market: MarketIdentifier,
+    },
+    Settings,
+    Alerts,
+    Schedules,
+    Fills,
+    Risk,
+    Strategies,
+    StrategyStates,
+    HistoryRuns,
+    HistoryOutputs,
+    Meta,
+    Unknown,
+}
+
+fn decode_json<T: schema::Stored + Serialize>(raw: &[u8]) -> Result<Value> {
+    Ok(serde_json::to_value(schema::decode::<T>(raw)?)?)
+}
+
+impl TreeContent {
+    pub fn of(name: &str) -> Self {
+        match name {
+            "settings" => TreeContent::Settings,
+            "alerts" => TreeContent::Alerts,
+            "schedules" => TreeContent::Schedules,
+            "fills" => TreeContent::Fills,
+            "risk" => TreeContent::Risk,
+            "strategies" => TreeContent::Strategies,
+            "strategy_states" => TreeContent::StrategyStates,
+            "history_runs" => TreeContent::HistoryRuns,
+            "history_outputs" => TreeContent::HistoryOutputs,
+            "meta" => TreeContent::Meta,
+            _ => match MarketIdentifier::from_data_tree_uid(name) {
+                Some((market, interval)) => TreeContent::MarketData { market, interval },
+                None => match MarketIdentifier::from_trades_tree_uid(name) {
+                    Some(market) => TreeContent::Trades { market },
+                    None => TreeContent::Unknown,
+                },
+            },
+        }
+    }
+
+    /// Key of an entry as typed on the command line
+    fn encode_key(&self, raw: &str) -> Result<Vec<u8>> {
+        let invalid = |expected: &str| {
+            Error::Parsing(format!("Wrong key `{}`, expected {}", raw, expected), 0..0)
+        };
+        Ok(match self {
+            TreeContent::MarketData { .. } => raw
+                .parse::<Timestamp>()
+                .map_err(|_| invalid("a timestamp"))?
+                .to_be_bytes()
+                .to_vec(),
+            TreeContent::Trades { .. } => raw
+                .parse::<i64>()
+                .map_err(|_| invalid("a time in milliseconds"))?
+                .to_be_bytes()
+                .to_vec(),
+            TreeContent::Fills => {
+                let (time, id) = raw.split_once('/').unwrap_or((raw, ""));
+                let mut key = time
+                    .parse::<Timestamp>()
+                    .map_err(|_| invalid("a time followed by a fill id"))?
+                    .to_be_bytes()
+                    .to_vec();
+                key.extend_from_slice(id.as_bytes());
+                key
+            }
+            TreeContent::HistoryRuns | TreeContent::HistoryOutputs => raw
+                .parse::<RunIdentifier>()
+                .map_err(|_| invalid("a run id"))?
+                .to_be_bytes()
+                .to_vec(),
+            // Settings are keyed by the display name of the market, `exchange_BASE/QUOTE`
+            TreeContent::Settings if !raw.contains('_') && !raw.starts_with(WATCHLIST_PREFIX) => {
+                MarketIdentifier::from(raw).to_string().into_bytes()
+            }
+            _ => raw.as_bytes().to_vec(),
+        })
+    }
+
+    fn decode_key(&self, key: &[u8]) -> String {
+        let be_u64 = |bytes: &[u8]| {
+            let mut raw = [0u8; 8];
+            raw.copy_from_slice(&bytes[..8]);
+            u64::from_be_bytes(raw)
+        };
+        match self {
+            TreeContent::MarketData { .. } if key.len() == 8 => {
+                (be_u64(key) as Timestamp).to_string()
+            }
+            TreeContent::HistoryRuns if key.len() == 8 => be_u64(key).to_string(),
+            TreeContent::Fills if key.len() > 8 => format!(
+                "{}/{}",
+                be_u64(&key[..8]) as Timestamp,
+                String::from_utf8_lossy(&key[8..])
+            ),
+            TreeContent::Trades { .. } if key.len() == 16 => {
+                format!("{}/{}", be_u64(&key[..8]) as i64, be_u64(&key[8..]))
+            }
+            TreeContent::HistoryOutputs if key.len() == 16 => {
+                format!("{}/{}", be_u64(&key[..8]), be_u64(&key[8..]))
+            }
+            TreeContent::Unknown => hex(key),
+            _ => String::from_utf8_lossy(key).to_string(),
+        }
+    }
+
+    fn decode_value(&self, key: &[u8], raw: &[u8]) -> Result<Value> {
+        Ok(match self {
+            TreeContent::Settings if key.starts_with(&[WATCHLIST_PREFIX as u8]) => {
+                decode_json::<Watchlist>(raw)?
+            }
+            TreeContent::MarketData { .. } => decode_json::<OHLC>(raw)?,
+            TreeContent::Trades { .. } => decode_json::<crate::exchange::Trade>(raw)?,
+            TreeContent::Settings => decode_json::<MarketSettings>(raw)?,
+            TreeContent::Alerts => decode_json::<Alert>(raw)?,
+            TreeContent::Schedules => decode_json::<Schedule>(raw)?,
+            TreeContent::Fills => decode_json::<crate::exchange::Fill>(raw)?,
+            TreeContent::Risk => decode_json::<KillSwitch>(raw)?,
+            TreeContent::Strategies => decode_json::<StrategyDefinition>(raw)?,
+            TreeContent::StrategyStates => decode_json::<StrategyState>(raw)?,
+            TreeContent::HistoryRuns => decode_json::<ProgramRun>(raw)?,
+            TreeContent::HistoryOutputs => decode_json::<RecordedOutput>(raw)?,
+            TreeContent::Meta if raw.len() == 4 => {
+                Value::from(u32::from_be_bytes([raw[0], raw[1], raw[2], raw[3]]))
+            }
+            TreeContent::Meta | TreeContent::Unknown => Value::from(hex(raw)),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TreeInfo {
+    pub name: String,
+    pub content: TreeContent,
+    pub entries: usize,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarketDataStats {
+    pub market: MarketIdentifier,
+    pub interval: Interval,
+    pub candles: usize,
+    pub first: Option<Timestamp>,
+    pub last: Option<Timestamp>,
+    /// Number of holes between the first and the last candle
+    pub gaps: usize,
+    /// Number of candles missing in those holes
+    pub missing: usize,
+    pub bytes: u64,
+    pub settings: MarketSettings,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TreeEntry {
+    pub key: String,
+    pub value: Value,
+}
+
+/// Read only view of the trees of a store, it never creates a tree nor writes
+#[derive(Clone)]
+pub struct StoreInspector {
+    db: Db,
+}
+
+impl Store {
+    pub fn inspector(&self) -> StoreInspector {
+        StoreInspector {
+            db: self.db.clone(),
+        }
+    }
+}
+
+impl StoreHandle {
+    pub fn inspector(&self) -> StoreInspector {
+        StoreInspector {
+            db: self.db.clone(),
+        }
+    }
+}
+
+impl StoreInspector {
+    fn existing_tree(&self, name: &str) -> Result<sled::Tree> {
+        if !self.db.tree_names().iter().any(|e| &e[..] == name.as_bytes()) {
+            return Err(Error::TreeNotFound(name.to_string()));
+        }
+        Ok(self.db.open_tree(name)?)
+    }
+
+    /// Every tree of the store sorted by name
+    pub fn trees(&self) -> Result<Vec<TreeInfo>> {
+        let mut ret: Vec<_> = tree_usage(&self.db)?
+            .into_iter()
+            .filter(|e| e.name != "__sled__default")
+            .map(|e| TreeInfo {
+                content: TreeContent::of(&e.name),
+                name: e.name,
+                entries: e.entries,
+                bytes: e.bytes,
+            })
+            .collect();
+        ret.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(ret)
+    }
+
+    /// Settings of a market, the defaults when none were saved
+    fn settings(&self, market: &MarketIdentifier) -> Result<MarketSettings> {
+        let raw = match self.existing_tree("settings") {
+            Ok(tree) => tree.get(market.to_string().as_bytes())?,
+            Err(Error::TreeNotFound(_)) => None,
+            Err(e) => return Err(e),
+        };
+        match raw {
+            Some(raw) => schema::decode(raw.as_ref()),
+            None => Ok(MarketSettings::default()),
+        }
+    }
+
+    /// Stats of the market data trees, of a single market or interval when given
+    pub fn stats(
+        &self,
+        market: Option<&MarketIdentifier>,
+        interval: Option<Interval>,
+    ) -> Result<Vec<MarketDataStats>> {
+        let mut ret = Vec::new();
+        for name in self.db.tree_names() {
+            let name = String::from_utf8_lossy(&name);
+            let (id, i) = match MarketIdentifier::from_data_tree_uid(&name) {
+                Some(parsed) => parsed,
+                None => continue,
+            };
+            if market.map(|e| *e == id).unwrap_or(true)
+                && interval.map(|e| e == i).unwrap_or(true)
+            {
+                ret.push(self.stat(&id, i)?);
+            }
+        }
+        Ok(ret)
+    }
+
+    pub fn stat(&self, market: &MarketIdentifier, interval: Interval) -> Result<MarketDataStats> {
+        let tree = self.existing_tree(&market.data_tree_uid(interval))?;
+        let mut stats = MarketDataStats {
+            market: market.clone(),
+            interval,
+            candles: 0,
+            first: None,
+            last: None,
+            gaps: 0,
+            missing: 0,
+            bytes: 0,
+            settings: self.settings(market)?,
+        };
+        for item in tree.iter() {
+            let (key, value) = item?;
+            let time = key.clone().from_store();
+            if let Some(last) = stats.last {
+                let missing = ((time - last) / interval.as_secs() - 1).max(0) as usize;
+                if missing > 0 {
+                    stats.gaps += 1;
+                    stats.missing += missing;
+                }
+            }
+            stats.first = stats.first.or(Some(time));
+            stats.last = Some(time);
+            stats.candles += 1;
+            stats.bytes += (key.len() + value.len()) as u64;
+        }
+        Ok(stats)
+    }
+
+    /// Decoded entries of a tree, all the entries of `key` or the first (or last) `limit` ones
+    pub fn get(
+        &self,
+        tree: &str,
+        key: Option<&str>,
+        limit: usize,
+        last: bool,
+    ) -> Result<Vec<TreeEntry>> {
+        let content = TreeContent::of(tree);
+        let tree = self.existing_tree(tree)?;
+        let items: Vec<(IVec, IVec)> = match key {
+            // Outputs and trades have a composite key, the first part is enough to look them up
+            Some(key)
+                if matches!(
+                    content,
+                    TreeContent::HistoryOutputs | TreeContent::Trades { .. }
+                ) =>
+            {
+                tree
+                    .scan_prefix(content.encode_key(key)?)
+                    .collect::<std::result::Result<_, _>>()?
+            }
+            Some(key) => {
+                let key = content.encode_key(key)?;
+                tree.get(&key)?.map(|e| (IVec::from(key), e)).into_iter().collect()
+            }
+            None if last => {
+                let mut items = tree
+                    .iter()
+                    .rev()
+                    .take(limit)
+                    .collect::<std::result::Result<Vec<_>, _>>()?;
+                items.reverse();
+                items
+            }
+            None => tree.iter().take(limit).collect::<std::result::Result<_, _>>()?,
+        };
+        Ok(items
+            .into_iter()
+            .map(|(key, value)| TreeEntry {
+                key: content.decode_key(&key),
+                // A value that can't be decoded is what one is usually looking for
+                value: content.decode_value(&key, &value).unwrap_or_else(|e| {
+                    serde_json::json!({ "error": e.to_string(), "raw": hex(&value) })
+                }),
+            })
+            .collect())
+    }
+}
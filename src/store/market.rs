@@ -1,16 +1,5 @@
 use super::*;
 
-macro_rules! try_result_opt {
-    ($ivec:expr) => {
-        Ok(if let Some(raw) = $ivec? {
-            let (decoded, _) = bincode::decode_from_slice(raw.as_ref(), Configuration::standard())?;
-            Some(decoded)
-        } else {
-            None
-        })
-    };
-}
-
 #[repr(i32)]
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Encode, Decode, Serialize, Deserialize)]
 pub enum Interval {
@@ -40,7 +29,6 @@ impl Interval {
         }) * 60
     }
 
-    #[allow(unused)]
     pub fn from_minuts(mins: i64) -> Result<Self> {
         match mins {
             1 => Ok(Interval::Min1),
@@ -83,33 +71,91 @@ pub struct StoreMarketHandle {
     trees_cache: Arc<RwLock<HashMap<Interval, StoreMarketDataHandle>>>,
     #[allow(unused)]
     settings_tree: sled::Tree,
+    write_gate: WriteGate,
     pub id: MarketIdentifier,
 }
 
 #[derive(Debug, Clone, bincode::Encode, bincode::Decode, serde::Serialize, serde::Deserialize)]
 pub struct MarketSettings {
     pub ohlc_refresh_rate: Option<Interval>,
+    /// At most one rule per interval, intervals without rule are kept forever
+    #[serde(default)]
+    pub retention: Vec<RetentionRule>,
 }
 
 impl std::default::Default for MarketSettings {
     fn default() -> Self {
         Self {
             ohlc_refresh_rate: None,
+            retention: Vec::new(),
         }
     }
 }
 
+impl MarketSettings {
+    pub fn retention(&self, interval: Interval) -> Option<&RetentionRule> {
+        self.retention.iter().find(|e| e.interval == interval)
+    }
+
+    /// Replace the rule of the same interval if any
+    pub fn set_retention(&mut self, rule: RetentionRule) {
+        self.retention.retain(|e| e.interval != rule.interval);
+        self.retention.push(rule);
+        self.retention.sort_by_key(|e| e.interval.as_secs());
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode, serde::Serialize, serde::Deserialize)]
+pub struct RetentionRule {
+    pub interval: Interval,
+    /// Candles older than this many seconds are removed, `None` keeps them forever
+    pub max_age: Option<u64>,
+    /// Aggregate candles into this coarser interval before removing them
+    pub downsample: Option<Interval>,
+}
+
+impl RetentionRule {
+    pub fn validate(&self) -> Result<()> {
+        if let Some(target) = self.downsample {
+            if target.as_secs() <= self.interval.as_secs()
+                || target.as_secs() % self.interval.as_secs() != 0
+            {
+                return Err(Error::InvalidRetention(format!(
+                    "can't downsample {} candles to {}, expected a multiple of the interval",
+                    self.interval, target
+                )));
+            }
+            if self.max_age.is_none() {
+                return Err(Error::InvalidRetention(String::from(
+                    "downsampling requires a maximum age",
+                )));
+            }
+        }
+        if self.max_age == Some(0) {
+            return Err(Error::InvalidRetention(String::from(
+                "maximum age must not be zero",
+            )));
+        }
+        Ok(())
+    }
+}
+
 impl StoreMarketHandle {
-    pub fn new(db: sled::Db, settings_tree: sled::Tree, id: MarketIdentifier) -> Self {
+    pub fn new(
+        db: sled::Db,
+        settings_tree: sled::Tree,
+        write_gate: WriteGate,
+        id: MarketIdentifier,
+    ) -> Self {
         Self {
             db,
             trees_cache: Arc::new(RwLock::new(HashMap::new())),
             settings_tree,
+            write_gate,
             id,
         }
     }
 
-    #[allow(unused)]
     pub fn settings(&self) -> Result<MarketSettings> {
         if let Some(raw) = self
             .settings_tree
@@ -123,7 +169,7 @@ impl StoreMarketHandle {
             .ok()
             .flatten()
         {
-            let (decoded, _) = bincode::decode_from_slice(raw.as_ref(), Configuration::standard())?;
+            let decoded = schema::decode(raw.as_ref())?;
             Ok(decoded)
         } else {
             let settings = MarketSettings::default();
@@ -133,12 +179,18 @@ impl StoreMarketHandle {
     }
 
     pub fn set_settings(&self, settings: &MarketSettings) -> Result<()> {
-        let encoded = bincode::encode_to_vec(settings, Configuration::standard())?;
+        let encoded = schema::encode(settings)?;
+        let _gate = self.write_gate.enter();
         self.settings_tree
             .insert(format!("{}", &self.id).as_bytes(), encoded)?;
         Ok(())
     }
 
+    pub fn trades(&self) -> Result<StoreTradesHandle> {
+        let tree = self.db.open_tree(self.id.trades_tree_uid())?;
+        Ok(StoreTradesHandle::new(tree, self.id.clone(), self.write_gate.clone()))
+    }
+
     pub async fn interval(&self, interval: Interval) -> Result<StoreMarketDataHandle> {
         if let Some(handle) = self.trees_cache.read().await.get(&interval).cloned() {
             return Ok(handle);
@@ -151,7 +203,7 @@ impl StoreMarketHandle {
             interval
         );
         let tree = self.db.open_tree(self.id.data_tree_uid(interval))?;
-        let handle = StoreMarketDataHandle::new(tree, self.id.clone(), interval);
+        let handle = StoreMarketDataHandle::new(tree, self.id.clone(), interval, self.write_gate.clone());
         self.trees_cache
             .write()
             .await
@@ -166,11 +218,17 @@ pub struct StoreMarketDataHandle {
     id: MarketIdentifier,
     tree: sled::Tree,
     interval: Interval,
+    write_gate: WriteGate,
 }
 
 impl StoreMarketDataHandle {
-    pub fn new(tree: sled::Tree, id: MarketIdentifier, interval: Interval) -> Self {
-        Self { id, tree, interval }
+    pub fn new(tree: sled::Tree, id: MarketIdentifier, interval: Interval, write_gate: WriteGate) -> Self {
+        Self {
+            id,
+            tree,
+            interval,
+            write_gate,
+        }
     }
 
     pub fn prev_close_to(&self, target_time: Timestamp) -> Result<Option<i64>> {
@@ -227,12 +285,45 @@ impl StoreMarketDataHandle {
     }
 
     pub fn insert(&self, ohlc: OHLC) -> Result<()> {
-        let encoded =
-            bincode::encode_to_vec(&ohlc, Configuration::standard()).unwrap_or_else(|_| Vec::new());
+        let encoded = schema::encode(&ohlc)?;
+        let _gate = self.write_gate.enter();
         self.tree.insert(&ohlc.time.to_be_bytes(), encoded)?;
         Ok(())
     }
 
+    /// Insert a candle unless one already exists at the same time, returns whether it was inserted
+    pub fn insert_missing(&self, ohlc: OHLC) -> Result<bool> {
+        let encoded = schema::encode(&ohlc)?;
+        let _gate = self.write_gate.enter();
+        Ok(self
+            .tree
+            .compare_and_swap(&ohlc.time.to_be_bytes(), None as Option<&[u8]>, Some(encoded))?
+            .is_ok())
+    }
+
+    /// Candles strictly older than `time`, oldest first
+    pub fn before(&self, time: Timestamp) -> Result<Vec<OHLC>> {
+        let mut ret = Vec::new();
+        for item in self.tree.range(..time.to_be_bytes()) {
+            let (_, raw) = item?;
+            ret.push(schema::decode(raw.as_ref())?);
+        }
+        Ok(ret)
+    }
+
+    /// Remove the candles strictly older than `time`, returns how many were removed
+    pub fn remove_before(&self, time: Timestamp) -> Result<usize> {
+        let _gate = self.write_gate.enter();
+        let mut batch = sled::Batch::default();
+        let mut count = 0;
+        for key in self.tree.range(..time.to_be_bytes()).keys() {
+            batch.remove(key?);
+            count += 1;
+        }
+        self.tree.apply_batch(batch)?;
+        Ok(count)
+    }
+
     pub fn ohlc(&self, time: Timestamp) -> Result<Option<OHLC>> {
         try_result_opt!(self.tree.get(&time.to_be_bytes()))
     }
@@ -256,6 +347,26 @@ impl StoreMarketDataHandle {
         try_result_opt!(self.tree.first().map(|e| e.map(|(_, e)| e)))
     }
 
+    /// Every stored candle, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = Result<OHLC>> {
+        self.tree
+            .iter()
+            .values()
+            .map(|raw| -> Result<OHLC> { schema::decode(raw?.as_ref()) })
+    }
+
+    /// The `count` most recent candles, oldest first
+    pub fn last_n(&self, count: usize) -> Result<Vec<OHLC>> {
+        let mut ret = Vec::with_capacity(count);
+        for item in self.tree.iter().rev().take(count) {
+            let (_, raw) = item?;
+            let decoded = schema::decode(raw.as_ref())?;
+            ret.push(decoded);
+        }
+        ret.reverse();
+        Ok(ret)
+    }
+
     #[allow(unused)]
     pub fn last_ohlc(&self) -> Result<Option<OHLC>> {
         try_result_opt!(self.tree.last().map(|e| e.map(|(_, e)| e)))
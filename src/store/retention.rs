@@ -0,0 +1,142 @@
+use super::*;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CompactionReport {
+    pub market: MarketIdentifier,
+    pub interval: Interval,
+    /// Candles written to the coarser interval
+    pub downsampled: usize,
+    pub removed: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TreeUsage {
+    pub name: String,
+    pub entries: usize,
+    /// Size of the keys and values, sled overhead excluded
+    pub bytes: u64,
+}
+
+/// Size of every tree, largest first
+pub(super) fn tree_usage(db: &Db) -> Result<Vec<TreeUsage>> {
+    let mut ret = Vec::new();
+    for name in db.tree_names() {
+        let mut usage = TreeUsage {
+            name: String::from_utf8_lossy(&name).to_string(),
+            entries: 0,
+            bytes: 0,
+        };
+        for item in db.open_tree(&name)?.iter() {
+            let (key, value) = item?;
+            usage.entries += 1;
+            usage.bytes += (key.len() + value.len()) as u64;
+        }
+        ret.push(usage);
+    }
+    ret.sort_by(|a, b| b.bytes.cmp(&a.bytes));
+    Ok(ret)
+}
+
+/// Merge the candles of one bucket of a coarser interval, candles must be sorted by time
+fn downsample(time: Timestamp, candles: &[OHLC]) -> OHLC {
+    let volume: Decimal = candles.iter().map(|e| e.volume).sum();
+    let close = candles.last().unwrap().close;
+    let vwap = if volume.is_zero() {
+        close
+    } else {
+        let scale = candles.iter().map(|e| e.vwap.scale()).max().unwrap_or(0);
+        (candles.iter().map(|e| e.vwap * e.volume).sum::<Decimal>() / volume).round_dp(scale)
+    };
+    OHLC {
+        first_available: candles.iter().any(|e| e.first_available),
+        time,
+        open: candles[0].open,
+        high: candles.iter().map(|e| e.high).max().unwrap(),
+        low: candles.iter().map(|e| e.low).min().unwrap(),
+        close,
+        vwap,
+        volume,
+        count: candles.iter().map(|e| e.count).sum(),
+    }
+}
+
+impl StoreMarketHandle {
+    /// Apply the retention rules of the market, candles are downsampled before being removed
+    pub async fn compact(&self, now: Timestamp) -> Result<Vec<CompactionReport>> {
+        let mut ret = Vec::new();
+        for rule in self.settings()?.retention {
+            let max_age = match rule.max_age {
+                Some(max_age) => max_age as Timestamp,
+                None => continue,
+            };
+            let data = self.interval(rule.interval).await?;
+            let mut cutoff = now - max_age;
+            let mut downsampled = 0;
+            if let Some(target) = rule.downsample {
+                // Only remove candles of complete buckets
+                cutoff -= cutoff.rem_euclid(target.as_secs());
+                let mut buckets: BTreeMap<Timestamp, Vec<OHLC>> = BTreeMap::new();
+                for ohlc in data.before(cutoff)? {
+                    buckets
+                        .entry(ohlc.time - ohlc.time.rem_euclid(target.as_secs()))
+                        .or_default()
+                        .push(ohlc);
+                }
+                let target = self.interval(target).await?;
+                for (time, candles) in buckets {
+                    if target.insert_missing(downsample(time, &candles))? {
+                        downsampled += 1;
+                    }
+                }
+            }
+            let removed = data.remove_before(cutoff)?;
+            if removed > 0 {
+                log::info!(
+                    "Market data compacted: EXCHANGE={}, BASE={}, QUOTE={}, INTERVAL={}, DOWNSAMPLED={}, REMOVED={}",
+                    &self.id.exchange_name,
+                    &self.id.base,
+                    &self.id.quote,
+                    rule.interval,
+                    downsampled,
+                    removed
+                );
+            }
+            ret.push(CompactionReport {
+                market: self.id.clone(),
+                interval: rule.interval,
+                downsampled,
+                removed,
+            });
+        }
+        Ok(ret)
+    }
+}
+
+impl StoreHandle {
+    /// Apply the retention rules of every stored market
+    pub async fn compact(&self) -> Result<Vec<CompactionReport>> {
+        let mut markets: Vec<MarketIdentifier> = Vec::new();
+        for (market, _) in self.market_trees()? {
+            if !markets.contains(&market) {
+                markets.push(market);
+            }
+        }
+        let now = now();
+        let mut ret = Vec::new();
+        for market in markets {
+            ret.extend(self.market(market)?.compact(now).await?);
+        }
+        Ok(ret)
+    }
+
+    /// Size of every tree, largest first
+    pub fn usage(&self) -> Result<Vec<TreeUsage>> {
+        tree_usage(&self.db)
+    }
+
+    /// Size of the store on disk, sled overhead included
+    pub fn size_on_disk(&self) -> Result<u64> {
+        Ok(self.db.size_on_disk()?)
+    }
+}
@@ -0,0 +1,45 @@
+use super::*;
+
+const KILL_SWITCH_KEY: &str = "kill_switch";
+
+/// Stops every order until released, it survives restarts
+#[derive(Debug, Clone, Encode, Decode, Serialize, Deserialize)]
+pub struct KillSwitch {
+    pub reason: String,
+    pub engaged_at: Timestamp,
+}
+
+#[derive(Clone)]
+pub struct StoreRiskHandle {
+    tree: sled::Tree,
+    write_gate: WriteGate,
+}
+
+impl StoreRiskHandle {
+    pub fn new(db: &Db, write_gate: WriteGate) -> Result<Self> {
+        Ok(Self {
+            tree: db.open_tree("risk")?,
+            write_gate,
+        })
+    }
+
+    pub fn kill_switch(&self) -> Result<Option<KillSwitch>> {
+        try_result_opt!(self.tree.get(KILL_SWITCH_KEY))
+    }
+
+    pub fn engage_kill_switch(&self, kill_switch: &KillSwitch) -> Result<()> {
+        let encoded = schema::encode(kill_switch)?;
+        let _gate = self.write_gate.enter();
+        self.tree.insert(KILL_SWITCH_KEY, encoded)?;
+        self.tree.flush()?;
+        Ok(())
+    }
+
+    /// Whether the kill switch was engaged
+    pub fn release_kill_switch(&self) -> Result<bool> {
+        let _gate = self.write_gate.enter();
+        let released = self.tree.remove(KILL_SWITCH_KEY)?.is_some();
+        self.tree.flush()?;
+        Ok(released)
+    }
+}
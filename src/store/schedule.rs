@@ -0,0 +1,134 @@
+use super::*;
+use chrono::{DateTime, Utc};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Encode, Decode, Serialize, Deserialize)]
+pub enum ScheduleTrigger {
+    /// Cron expression with seconds (`sec min hour day month weekday [year]`)
+    Cron { expression: String },
+    /// Fixed delay between two runs
+    Every { secs: i64 },
+    /// Right after each candle of the interval closed
+    Candle { interval: Interval },
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Encode, Decode, Serialize, Deserialize)]
+pub enum OverlapPolicy {
+    Skip,
+    Queue,
+    CancelPrevious,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Encode, Decode, Serialize, Deserialize)]
+pub enum MissedRunPolicy {
+    Skip,
+    RunOnce,
+}
+
+#[derive(Debug, Clone, Encode, Decode, Serialize, Deserialize)]
+pub struct Schedule {
+    pub name: String,
+    pub source: String,
+    pub trigger: ScheduleTrigger,
+    pub overlap: OverlapPolicy,
+    pub missed: MissedRunPolicy,
+    pub timeout: Option<u64>,
+    pub created_at: Timestamp,
+    pub last_run: Option<Timestamp>,
+}
+
+#[derive(Clone)]
+pub struct StoreScheduleHandle {
+    tree: sled::Tree,
+    write_gate: WriteGate,
+}
+
+impl ScheduleTrigger {
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            ScheduleTrigger::Cron { expression } => {
+                cron::Schedule::from_str(expression)
+                    .map_err(|e| Error::InvalidSchedule(format!("{}: {}", expression, e)))?;
+                Ok(())
+            }
+            ScheduleTrigger::Every { secs } if *secs <= 0 => Err(Error::InvalidSchedule(
+                format!("interval must be positive, found {}s", secs),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// First fire time strictly after `after`
+    pub fn next_after(&self, after: Timestamp) -> Result<Timestamp> {
+        match self {
+            ScheduleTrigger::Cron { expression } => {
+                let schedule = cron::Schedule::from_str(expression)
+                    .map_err(|e| Error::InvalidSchedule(format!("{}: {}", expression, e)))?;
+                let after =
+                    DateTime::<Utc>::from_utc(NaiveDateTime::from_timestamp(after, 0), Utc);
+                schedule
+                    .after(&after)
+                    .next()
+                    .map(|e| e.timestamp())
+                    .ok_or_else(|| Error::InvalidSchedule(format!("{} never fires", expression)))
+            }
+            ScheduleTrigger::Every { secs } => Ok(after + secs),
+            ScheduleTrigger::Candle { interval } => {
+                let secs = interval.as_secs();
+                Ok((after / secs + 1) * secs)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ScheduleTrigger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScheduleTrigger::Cron { expression } => write!(f, "cron `{}`", expression),
+            ScheduleTrigger::Every { secs } => write!(f, "every {}s", secs),
+            ScheduleTrigger::Candle { interval } => write!(f, "every closed {} candle", interval),
+        }
+    }
+}
+
+impl Schedule {
+    pub fn next_run(&self) -> Result<Timestamp> {
+        self.trigger
+            .next_after(self.last_run.unwrap_or(self.created_at))
+    }
+}
+
+impl StoreScheduleHandle {
+    pub fn new(db: &Db, write_gate: WriteGate) -> Result<Self> {
+        Ok(Self {
+            tree: db.open_tree("schedules")?,
+            write_gate,
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Result<Option<Schedule>> {
+        try_result_opt!(self.tree.get(name.as_bytes()))
+    }
+
+    pub fn set(&self, schedule: &Schedule) -> Result<()> {
+        let encoded = schema::encode(schedule)?;
+        let _gate = self.write_gate.enter();
+        self.tree.insert(schedule.name.as_bytes(), encoded)?;
+        Ok(())
+    }
+
+    pub fn remove(&self, name: &str) -> Result<bool> {
+        let _gate = self.write_gate.enter();
+        Ok(self.tree.remove(name.as_bytes())?.is_some())
+    }
+
+    pub fn list(&self) -> Result<Vec<Schedule>> {
+        let mut ret = Vec::new();
+        for item in self.tree.iter() {
+            let (_, raw) = item?;
+            let decoded = schema::decode(raw.as_ref())?;
+            ret.push(decoded);
+        }
+        Ok(ret)
+    }
+}
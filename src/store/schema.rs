@@ -0,0 +1,741 @@
+use super::*;
+use crate::exchange::{AssetRegistry, Fill, MarketIdentifier, Trade};
+use crate::store::{
+    Alert, AlertCondition, AlertSink, KillSwitch, MarketSettings, ProgramRun, RecordedOutput,
+    Schedule, StrategyDefinition, StrategyState, Watchlist,
+};
+
+/// Current layout of the store, bump it and append a `Migration` whenever a stored type changes,
+/// the `LAYOUT_VERSION` of the type is raised to the new version
+pub const SCHEMA_VERSION: u32 = 4;
+
+const META_TREE: &str = "meta";
+const SCHEMA_VERSION_KEY: &str = "schema_version";
+/// `0xFF` is never the first byte of a bincode value so envelopes can't be mistaken for legacy values
+const ENVELOPE_MAGIC: [u8; 2] = [0xFF, b'P'];
+const ENVELOPE_HEADER_LEN: usize = 6;
+/// Number of values rewritten at once by a migration
+const MIGRATION_BATCH_SIZE: usize = 4096;
+
+/// Encode a value in a versioned envelope: magic, schema version (u32 BE), bincode payload
+pub fn encode<T: Encode>(value: &T) -> Result<Vec<u8>> {
+    encode_version(value, SCHEMA_VERSION)
+}
+
+/// Migrations write the version they bring values to, not necessarily the current one
+fn encode_version<T: Encode>(value: &T, version: u32) -> Result<Vec<u8>> {
+    Ok(wrap_raw(
+        &bincode::encode_to_vec(value, Configuration::standard())?,
+        version,
+    ))
+}
+
+/// A type written in the store, values written since its `LAYOUT_VERSION` share its layout
+/// and are decoded as they are, migrations only rewrite the older ones
+pub trait Stored: Decode {
+    const LAYOUT_VERSION: u32 = 1;
+}
+
+impl Stored for OHLC {}
+impl Stored for Trade {}
+impl Stored for Fill {
+    const LAYOUT_VERSION: u32 = 3;
+}
+impl Stored for MarketSettings {
+    const LAYOUT_VERSION: u32 = 2;
+}
+impl Stored for Watchlist {}
+impl Stored for Alert {}
+impl Stored for Schedule {}
+impl Stored for KillSwitch {}
+impl Stored for StrategyDefinition {}
+impl Stored for StrategyState {
+    const LAYOUT_VERSION: u32 = 3;
+}
+impl Stored for ProgramRun {}
+impl Stored for RecordedOutput {}
+
+pub fn decode<T: Stored>(raw: &[u8]) -> Result<T> {
+    let version = envelope_version(raw).ok_or(Error::SchemaMismatch {
+        expected: SCHEMA_VERSION,
+        found: 0,
+    })?;
+    if version < T::LAYOUT_VERSION || version > SCHEMA_VERSION {
+        return Err(Error::SchemaMismatch {
+            expected: SCHEMA_VERSION,
+            found: version,
+        });
+    }
+    let (decoded, _) =
+        bincode::decode_from_slice(&raw[ENVELOPE_HEADER_LEN..], Configuration::standard())?;
+    Ok(decoded)
+}
+
+fn envelope_version(raw: &[u8]) -> Option<u32> {
+    if raw.len() < ENVELOPE_HEADER_LEN || raw[..2] != ENVELOPE_MAGIC {
+        return None;
+    }
+    Some(u32::from_be_bytes([raw[2], raw[3], raw[4], raw[5]]))
+}
+
+fn wrap_raw(payload: &[u8], version: u32) -> Vec<u8> {
+    let mut ret = Vec::with_capacity(payload.len() + ENVELOPE_HEADER_LEN);
+    ret.extend_from_slice(&ENVELOPE_MAGIC);
+    ret.extend_from_slice(&version.to_be_bytes());
+    ret.extend_from_slice(payload);
+    ret
+}
+
+pub fn stored_version(db: &Db) -> Result<Option<u32>> {
+    Ok(match existing_tree(db, META_TREE)? {
+        Some(meta) => meta
+            .get(SCHEMA_VERSION_KEY)?
+            .map(|e| u32::from_be_bytes([e[0], e[1], e[2], e[3]])),
+        None => None,
+    })
+}
+
+/// Open a tree only if it exists, opening a missing tree creates it and dry runs never write
+fn existing_tree(db: &Db, name: &str) -> Result<Option<sled::Tree>> {
+    if !db.tree_names().iter().any(|e| &e[..] == name.as_bytes()) {
+        return Ok(None);
+    }
+    Ok(Some(db.open_tree(name)?))
+}
+
+fn set_stored_version(db: &Db, version: u32) -> Result<()> {
+    db.open_tree(META_TREE)?
+        .insert(SCHEMA_VERSION_KEY, &version.to_be_bytes())?;
+    db.flush()?;
+    Ok(())
+}
+
+/// Trees holding only sled internals or store metadata
+fn is_internal_tree(name: &[u8]) -> bool {
+    name == b"__sled__default" || name == META_TREE.as_bytes()
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum TreeKind {
+    MarketData,
+    Alerts,
+    Opaque,
+}
+
+fn tree_kind(name: &str) -> TreeKind {
+    match name {
+        "alerts" => TreeKind::Alerts,
+        "settings" | "schedules" | "fills" | "risk" | "strategies" | "strategy_states"
+        | "history_runs" | "history_outputs" => TreeKind::Opaque,
+        // Market data trees are named `{exchange}_{base}/{quote}_{interval secs}`
+        _ if name.contains('/')
+            && name
+                .rsplit('_')
+                .next()
+                .map(|e| e.parse::<i64>().is_ok())
+                .unwrap_or(false) =>
+        {
+            TreeKind::MarketData
+        }
+        _ => TreeKind::Opaque,
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MigrationReport {
+    pub from: u32,
+    pub to: u32,
+    pub dry_run: bool,
+    pub steps: Vec<MigrationStepReport>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MigrationStepReport {
+    pub version: u32,
+    pub description: &'static str,
+    /// Tree name and number of values rewritten
+    pub trees: Vec<(String, usize)>,
+}
+
+pub struct Migration {
+    /// Version of the store once the migration is applied
+    pub version: u32,
+    pub description: &'static str,
+    apply: fn(&Db, bool, &mut MigrationStepReport) -> Result<()>,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "wrap values in versioned envelopes, store OHLC and alert levels as decimals",
+        apply: migrate_v1,
+    },
+    Migration {
+        version: 2,
+        description: "add retention rules to market settings",
+        apply: migrate_v2,
+    },
+    Migration {
+        version: 3,
+        description: "keep the order id of fills and the live orders of strategies",
+        apply: migrate_v3,
+    },
+    Migration {
+        version: 4,
+        description: "key market data, trades and settings by canonical asset symbols",
+        apply: migrate_v4,
+    },
+];
+
+/// Bring the store to `SCHEMA_VERSION`, nothing is written when `dry_run` is set
+pub fn migrate(db: &Db, dry_run: bool) -> Result<MigrationReport> {
+    let from = match stored_version(db)? {
+        Some(version) => version,
+        // A store without data doesn't need to be migrated
+        None if db.tree_names().iter().all(|e| is_internal_tree(e)) => SCHEMA_VERSION,
+        None => 0,
+    };
+    if from > SCHEMA_VERSION {
+        return Err(Error::SchemaMismatch {
+            expected: SCHEMA_VERSION,
+            found: from,
+        });
+    }
+    let mut report = MigrationReport {
+        from,
+        to: SCHEMA_VERSION,
+        dry_run,
+        steps: Vec::new(),
+    };
+    for migration in MIGRATIONS.iter().filter(|e| e.version > from) {
+        log::info!(
+            "Apply store migration: VERSION={}, DRY_RUN={}, DESCRIPTION={}",
+            migration.version,
+            dry_run,
+            migration.description
+        );
+        let mut step = MigrationStepReport {
+            version: migration.version,
+            description: migration.description,
+            trees: Vec::new(),
+        };
+        (migration.apply)(db, dry_run, &mut step)?;
+        if !dry_run {
+            set_stored_version(db, migration.version)?;
+        }
+        report.steps.push(step);
+    }
+    if !dry_run && stored_version(db)?.is_none() {
+        set_stored_version(db, SCHEMA_VERSION)?;
+    }
+    Ok(report)
+}
+
+/// Rewrite the values of a tree by batches of `MIGRATION_BATCH_SIZE`, values already at `version`
+/// are left untouched so an interrupted migration can be resumed. `convert` receives the payload
+/// of the value, without its envelope if it has one
+fn rewrite_tree<F: Fn(&[u8]) -> Result<Vec<u8>>>(
+    tree: &sled::Tree,
+    dry_run: bool,
+    version: u32,
+    convert: F,
+) -> Result<usize> {
+    let mut batch = sled::Batch::default();
+    let mut pending = 0;
+    let mut count = 0;
+    for item in tree.iter() {
+        let (key, raw) = item?;
+        let payload = match envelope_version(&raw) {
+            Some(found) if found >= version => continue,
+            Some(_) => &raw[ENVELOPE_HEADER_LEN..],
+            None => &raw[..],
+        };
+        let converted = convert(payload)?;
+        count += 1;
+        if dry_run {
+            continue;
+        }
+        batch.insert(key, converted);
+        pending += 1;
+        if pending == MIGRATION_BATCH_SIZE {
+            tree.apply_batch(std::mem::take(&mut batch))?;
+            pending = 0;
+        }
+    }
+    if pending > 0 {
+        tree.apply_batch(batch)?;
+    }
+    Ok(count)
+}
+
+#[derive(Decode)]
+struct LegacyOHLC {
+    first_available: bool,
+    time: Timestamp,
+    open: String,
+    high: String,
+    low: String,
+    close: String,
+    _open_normalized: f64,
+    _high_normalized: f64,
+    _low_normalized: f64,
+    _close_normalized: f64,
+    vwap: String,
+    volume: String,
+    count: u64,
+}
+
+#[derive(Decode)]
+enum LegacyAlertCondition {
+    PriceAbove { level: f64 },
+    PriceBelow { level: f64 },
+    RsiAbove { period: usize, threshold: f64 },
+    RsiBelow { period: usize, threshold: f64 },
+    PercentMove { window: usize, percent: f64 },
+}
+
+#[derive(Decode)]
+struct LegacyAlert {
+    name: String,
+    market: String,
+    interval: Interval,
+    condition: LegacyAlertCondition,
+    sinks: Vec<AlertSink>,
+    active: bool,
+    last_triggered: Option<Timestamp>,
+}
+
+fn legacy_decode<T: Decode>(raw: &[u8]) -> Result<T> {
+    let (decoded, _) = bincode::decode_from_slice(raw, Configuration::standard())?;
+    Ok(decoded)
+}
+
+fn migrate_v1(db: &Db, dry_run: bool, report: &mut MigrationStepReport) -> Result<()> {
+    let decimal = |value: f64| -> Result<Decimal> { std::convert::TryFrom::try_from(value) };
+    for name in db.tree_names() {
+        if is_internal_tree(&name) {
+            continue;
+        }
+        let name = String::from_utf8_lossy(&name).to_string();
+        let tree = db.open_tree(&name)?;
+        let count = match tree_kind(&name) {
+            TreeKind::MarketData => rewrite_tree(&tree, dry_run, 1, |raw| {
+                let legacy: LegacyOHLC = legacy_decode(raw)?;
+                encode_version(
+                    &OHLC::new(
+                        legacy.first_available,
+                        legacy.time,
+                        &legacy.open,
+                        &legacy.high,
+                        &legacy.low,
+                        &legacy.close,
+                        &legacy.vwap,
+                        &legacy.volume,
+                        legacy.count,
+                    )?,
+                    1,
+                )
+            })?,
+            TreeKind::Alerts => rewrite_tree(&tree, dry_run, 1, |raw| {
+                let legacy: LegacyAlert = legacy_decode(raw)?;
+                let condition = match legacy.condition {
+                    LegacyAlertCondition::PriceAbove { level } => AlertCondition::PriceAbove {
+                        level: decimal(level)?,
+                    },
+                    LegacyAlertCondition::PriceBelow { level } => AlertCondition::PriceBelow {
+                        level: decimal(level)?,
+                    },
+                    LegacyAlertCondition::RsiAbove { period, threshold } => {
+                        AlertCondition::RsiAbove {
+                            period,
+                            threshold: decimal(threshold)?,
+                        }
+                    }
+                    LegacyAlertCondition::RsiBelow { period, threshold } => {
+                        AlertCondition::RsiBelow {
+                            period,
+                            threshold: decimal(threshold)?,
+                        }
+                    }
+                    LegacyAlertCondition::PercentMove { window, percent } => {
+                        AlertCondition::PercentMove {
+                            window,
+                            percent: decimal(percent)?,
+                        }
+                    }
+                };
+                encode_version(
+                    &Alert {
+                        name: legacy.name,
+                        market: legacy.market,
+                        interval: legacy.interval,
+                        condition,
+                        sinks: legacy.sinks,
+                        active: legacy.active,
+                        last_triggered: legacy.last_triggered,
+                    },
+                    1,
+                )
+            })?,
+            TreeKind::Opaque => rewrite_tree(&tree, dry_run, 1, |raw| Ok(wrap_raw(raw, 1)))?,
+        };
+        if count > 0 {
+            report.trees.push((name, count));
+        }
+    }
+    Ok(())
+}
+
+#[derive(Decode)]
+struct LegacyMarketSettings {
+    ohlc_refresh_rate: Option<Interval>,
+}
+
+fn migrate_v2(db: &Db, dry_run: bool, report: &mut MigrationStepReport) -> Result<()> {
+    // Other layouts are unchanged, their values keep the version they were written with
+    let tree = match existing_tree(db, "settings")? {
+        Some(tree) => tree,
+        None => return Ok(()),
+    };
+    let count = rewrite_tree(&tree, dry_run, 2, |raw| {
+        let legacy: LegacyMarketSettings = legacy_decode(raw)?;
+        encode_version(
+            &MarketSettings {
+                ohlc_refresh_rate: legacy.ohlc_refresh_rate,
+                retention: Vec::new(),
+            },
+            2,
+        )
+    })?;
+    if count > 0 {
+        report.trees.push((String::from("settings"), count));
+    }
+    Ok(())
+}
+
+#[derive(Decode)]
+struct LegacyFill {
+    id: String,
+    market: String,
+    time: Timestamp,
+    side: crate::exchange::TradeSide,
+    price: Decimal,
+    volume: Decimal,
+    fee: Decimal,
+}
+
+impl From<LegacyFill> for Fill {
+    fn from(legacy: LegacyFill) -> Self {
+        Fill {
+            id: legacy.id,
+            market: legacy.market,
+            time: legacy.time,
+            side: legacy.side,
+            price: legacy.price,
+            volume: legacy.volume,
+            fee: legacy.fee,
+            order: None,
+        }
+    }
+}
+
+#[derive(Decode)]
+struct LegacyStrategyState {
+    values: Vec<crate::store::StrategyParam>,
+    last_candle: Option<Timestamp>,
+    position: Decimal,
+    cash: Decimal,
+    orders: u64,
+    fills: Vec<LegacyFill>,
+    updated_at: Timestamp,
+}
+
+fn migrate_v3(db: &Db, dry_run: bool, report: &mut MigrationStepReport) -> Result<()> {
+    if let Some(tree) = existing_tree(db, "fills")? {
+        let fills = rewrite_tree(&tree, dry_run, 3, |raw| {
+            encode_version(&Fill::from(legacy_decode::<LegacyFill>(raw)?), 3)
+        })?;
+        if fills > 0 {
+            report.trees.push((String::from("fills"), fills));
+        }
+    }
+    let tree = match existing_tree(db, "strategy_states")? {
+        Some(tree) => tree,
+        None => return Ok(()),
+    };
+    let states = rewrite_tree(&tree, dry_run, 3, |raw| {
+        let legacy: LegacyStrategyState = legacy_decode(raw)?;
+        encode_version(
+            &StrategyState {
+                values: legacy.values,
+                last_candle: legacy.last_candle,
+                position: legacy.position,
+                cash: legacy.cash,
+                orders: legacy.orders,
+                order_ids: Vec::new(),
+                fills: legacy.fills.into_iter().map(Fill::from).collect(),
+                updated_at: legacy.updated_at,
+            },
+            3,
+        )
+    })?;
+    if states > 0 {
+        report.trees.push((String::from("strategy_states"), states));
+    }
+    Ok(())
+}
+
+/// Copy the entries of `from` missing from `to` then drop `from`, resumable since entries
+/// already copied are skipped
+fn merge_tree(db: &Db, from: &str, to: &str, dry_run: bool) -> Result<usize> {
+    let source = db.open_tree(from)?;
+    if dry_run {
+        return Ok(source.len());
+    }
+    let target = db.open_tree(to)?;
+    let mut batch = sled::Batch::default();
+    let mut pending = 0;
+    let mut count = 0;
+    for item in source.iter() {
+        let (key, value) = item?;
+        count += 1;
+        if target.contains_key(&key)? {
+            continue;
+        }
+        batch.insert(key, value);
+        pending += 1;
+        if pending == MIGRATION_BATCH_SIZE {
+            target.apply_batch(std::mem::take(&mut batch))?;
+            pending = 0;
+        }
+    }
+    if pending > 0 {
+        target.apply_batch(batch)?;
+    }
+    db.drop_tree(from)?;
+    Ok(count)
+}
+
+/// Only the built-in aliases are known here, pairs named with aliases of the configuration
+/// keep their trees
+fn migrate_v4(db: &Db, dry_run: bool, report: &mut MigrationStepReport) -> Result<()> {
+    let assets = AssetRegistry::new();
+    for name in db.tree_names() {
+        let name = String::from_utf8_lossy(&name).to_string();
+        let target = if let Some((id, interval)) = MarketIdentifier::from_data_tree_uid(&name) {
+            assets.canonical_market(&id).data_tree_uid(interval)
+        } else if let Some(id) = MarketIdentifier::from_trades_tree_uid(&name) {
+            assets.canonical_market(&id).trades_tree_uid()
+        } else {
+            continue;
+        };
+        if target != name {
+            let count = merge_tree(db, &name, &target, dry_run)?;
+            report.trees.push((name, count));
+        }
+    }
+    // Settings are keyed by `{exchange}_{base}/{quote}`, the canonical entry wins when both exist
+    let settings = match existing_tree(db, "settings")? {
+        Some(settings) => settings,
+        None => return Ok(()),
+    };
+    let mut count = 0;
+    for item in settings.iter() {
+        let (key, value) = item?;
+        let raw = String::from_utf8_lossy(&key).to_string();
+        if raw.starts_with(crate::store::WATCHLIST_PREFIX) {
+            continue;
+        }
+        let id = match MarketIdentifier::from_trades_tree_uid(&format!("{}_trades", raw)) {
+            Some(id) => id,
+            None => continue,
+        };
+        let canonical = assets.canonical_market(&id).tree_uid();
+        if canonical == raw {
+            continue;
+        }
+        count += 1;
+        if !dry_run {
+            if !settings.contains_key(canonical.as_bytes())? {
+                settings.insert(canonical.as_bytes(), value)?;
+            }
+            settings.remove(key)?;
+        }
+    }
+    if count > 0 {
+        report.trees.push((String::from("settings"), count));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchange::{TradeOrderType, TradeSide};
+
+    /// Layouts written before the envelopes, mirror of the `Legacy*` decoders
+    #[derive(Encode)]
+    struct V0OHLC {
+        first_available: bool,
+        time: Timestamp,
+        open: String,
+        high: String,
+        low: String,
+        close: String,
+        open_normalized: f64,
+        high_normalized: f64,
+        low_normalized: f64,
+        close_normalized: f64,
+        vwap: String,
+        volume: String,
+        count: u64,
+    }
+
+    #[derive(Encode)]
+    enum V0AlertCondition {
+        PriceAbove { level: f64 },
+    }
+
+    #[derive(Encode)]
+    struct V0Alert {
+        name: String,
+        market: String,
+        interval: Interval,
+        condition: V0AlertCondition,
+        sinks: Vec<AlertSink>,
+        active: bool,
+        last_triggered: Option<Timestamp>,
+    }
+
+    #[derive(Encode)]
+    struct V0MarketSettings {
+        ohlc_refresh_rate: Option<Interval>,
+    }
+
+    fn dec(raw: &str) -> Decimal {
+        raw.parse().unwrap()
+    }
+
+    fn raw<T: Encode>(value: &T) -> Vec<u8> {
+        bincode::encode_to_vec(value, Configuration::standard()).unwrap()
+    }
+
+    fn dump(db: &Db) -> Vec<(Vec<u8>, Vec<(IVec, IVec)>)> {
+        db.tree_names()
+            .into_iter()
+            .map(|name| {
+                let content = db
+                    .open_tree(&name)
+                    .unwrap()
+                    .iter()
+                    .collect::<std::result::Result<Vec<_>, _>>()
+                    .unwrap();
+                (name.to_vec(), content)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn migrate_legacy_store() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let candles = db.open_tree("kraken_XBT/EUR_3600").unwrap();
+        let candle = V0OHLC {
+            first_available: true,
+            time: 1_640_995_200,
+            open: String::from("41000.1"),
+            high: String::from("41500"),
+            low: String::from("40900.25"),
+            close: String::from("41200.5"),
+            open_normalized: 41000.1,
+            high_normalized: 41500.0,
+            low_normalized: 40900.25,
+            close_normalized: 41200.5,
+            vwap: String::from("41180.12345"),
+            volume: String::from("12.5"),
+            count: 320,
+        };
+        candles
+            .insert(candle.time.to_be_bytes(), raw(&candle))
+            .unwrap();
+        let trades = db.open_tree("kraken_XBT/EUR_trades").unwrap();
+        let trade = Trade {
+            id: 7,
+            time_ms: 1_640_995_200_123,
+            price: dec("41000.1"),
+            volume: dec("0.01"),
+            side: TradeSide::Buy,
+            order_type: TradeOrderType::Market,
+        };
+        trades.insert(7u64.to_be_bytes(), raw(&trade)).unwrap();
+        let alert = V0Alert {
+            name: String::from("btc-high"),
+            market: String::from("kraken/XBT/EUR"),
+            interval: Interval::Hour1,
+            condition: V0AlertCondition::PriceAbove { level: 50000.5 },
+            sinks: vec![AlertSink::EventBus],
+            active: false,
+            last_triggered: None,
+        };
+        db.open_tree("alerts")
+            .unwrap()
+            .insert(alert.name.as_bytes(), raw(&alert))
+            .unwrap();
+        let settings = V0MarketSettings {
+            ohlc_refresh_rate: Some(Interval::Min5),
+        };
+        db.open_tree("settings")
+            .unwrap()
+            .insert("kraken_XBT/EUR", raw(&settings))
+            .unwrap();
+
+        // A dry run reports every step but neither rewrites values nor creates trees
+        let before = dump(&db);
+        let report = migrate(&db, true).unwrap();
+        assert_eq!(report.from, 0);
+        assert_eq!(report.steps.len(), MIGRATIONS.len());
+        assert_eq!(dump(&db), before);
+        assert_eq!(stored_version(&db).unwrap(), None);
+
+        let report = migrate(&db, false).unwrap();
+        assert_eq!(report.to, SCHEMA_VERSION);
+        assert_eq!(stored_version(&db).unwrap(), Some(SCHEMA_VERSION));
+        let names: Vec<String> = db
+            .tree_names()
+            .iter()
+            .map(|e| String::from_utf8_lossy(e).to_string())
+            .collect();
+        assert!(!names.iter().any(|e| e.contains("XBT")));
+
+        let candles = db.open_tree("kraken_BTC/EUR_3600").unwrap();
+        let (_, value) = candles.first().unwrap().unwrap();
+        let migrated: OHLC = decode(&value).unwrap();
+        assert!(migrated.first_available);
+        assert_eq!(migrated.time, 1_640_995_200);
+        assert_eq!(migrated.low, dec("40900.25"));
+        assert_eq!(migrated.vwap, dec("41180.12345"));
+        assert_eq!(migrated.volume, dec("12.5"));
+        assert_eq!(migrated.count, 320);
+
+        let trades = db.open_tree("kraken_BTC/EUR_trades").unwrap();
+        let migrated: Trade = decode(&trades.get(7u64.to_be_bytes()).unwrap().unwrap()).unwrap();
+        assert_eq!(migrated.time_ms, trade.time_ms);
+        assert_eq!(migrated.price, trade.price);
+
+        let alerts = db.open_tree("alerts").unwrap();
+        let migrated: Alert = decode(&alerts.get("btc-high").unwrap().unwrap()).unwrap();
+        assert!(matches!(
+            migrated.condition,
+            AlertCondition::PriceAbove { level } if level == dec("50000.5")
+        ));
+
+        let settings = db.open_tree("settings").unwrap();
+        assert!(settings.get("kraken_XBT/EUR").unwrap().is_none());
+        let migrated: MarketSettings =
+            decode(&settings.get("kraken_BTC/EUR").unwrap().unwrap()).unwrap();
+        assert_eq!(migrated.ohlc_refresh_rate, Some(Interval::Min5));
+        assert!(migrated.retention.is_empty());
+
+        // Nothing left to do once migrated
+        assert!(migrate(&db, false).unwrap().steps.is_empty());
+    }
+}
@@ -0,0 +1,161 @@
+use super::*;
+use crate::exchange::Fill;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StrategyMode {
+    /// Orders are sent to the exchange, after the risk checks
+    Live,
+    /// Live candles, orders are filled at the candle close without reaching the exchange
+    Paper,
+    /// Stored candles of a past period, orders are filled like in paper mode
+    Backtest,
+}
+
+/// Script run by each hook of a script strategy, hooks left empty do nothing
+#[derive(Debug, Clone, Default, Encode, Decode, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScriptHooks {
+    pub init: Option<String>,
+    pub on_candle: Option<String>,
+    pub on_fill: Option<String>,
+    pub on_timer: Option<String>,
+    pub shutdown: Option<String>,
+}
+
+#[derive(Debug, Clone, Encode, Decode, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StrategySource {
+    /// Strategy implemented in Rust, see `crate::reactor::native_strategy`
+    Native {
+        name: String,
+    },
+    Script {
+        hooks: ScriptHooks,
+    },
+}
+
+#[derive(Debug, Clone, Encode, Decode, Serialize, Deserialize)]
+pub struct StrategyParam {
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Encode, Decode, Serialize, Deserialize)]
+pub struct StrategyDefinition {
+    pub name: String,
+    pub source: StrategySource,
+    /// `exchange/BASE/QUOTE` with canonical symbols
+    pub market: String,
+    pub interval: Interval,
+    pub mode: StrategyMode,
+    /// Delay between two `on_timer` calls, in seconds
+    pub timer: Option<i64>,
+    pub params: Vec<StrategyParam>,
+    /// Period replayed by a backtest
+    pub from: Option<Timestamp>,
+    pub to: Option<Timestamp>,
+    pub created_at: Timestamp,
+    /// Whether the daemon resumes the strategy when it starts
+    pub running: bool,
+}
+
+/// What a strategy keeps between two runs
+#[derive(Debug, Clone, Default, Encode, Decode, Serialize, Deserialize)]
+pub struct StrategyState {
+    /// Values set by the strategy itself
+    pub values: Vec<StrategyParam>,
+    /// Time of the last candle given to the strategy
+    pub last_candle: Option<Timestamp>,
+    /// Base asset bought minus sold by the strategy
+    pub position: Decimal,
+    /// Quote asset received minus spent by the strategy, fees included
+    pub cash: Decimal,
+    pub orders: u64,
+    /// Transaction ids of the live orders of the strategy, only their fills are booked
+    pub order_ids: Vec<String>,
+    /// Fills of the orders of the strategy, simulated ones in paper and backtest mode
+    pub fills: Vec<Fill>,
+    pub updated_at: Timestamp,
+}
+
+#[derive(Clone)]
+pub struct StoreStrategyHandle {
+    tree: sled::Tree,
+    states: sled::Tree,
+    write_gate: WriteGate,
+}
+
+impl std::fmt::Display for StrategyMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StrategyMode::Live => write!(f, "live"),
+            StrategyMode::Paper => write!(f, "paper"),
+            StrategyMode::Backtest => write!(f, "backtest"),
+        }
+    }
+}
+
+impl std::str::FromStr for StrategyMode {
+    type Err = Error;
+
+    fn from_str(raw: &str) -> Result<Self> {
+        match raw {
+            "live" => Ok(StrategyMode::Live),
+            "paper" => Ok(StrategyMode::Paper),
+            "backtest" => Ok(StrategyMode::Backtest),
+            _ => Err(Error::InvalidStrategy(format!(
+                "wrong mode `{}`, expected one of: live, paper, backtest",
+                raw
+            ))),
+        }
+    }
+}
+
+impl StoreStrategyHandle {
+    pub fn new(db: &Db, write_gate: WriteGate) -> Result<Self> {
+        Ok(Self {
+            tree: db.open_tree("strategies")?,
+            states: db.open_tree("strategy_states")?,
+            write_gate,
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Result<Option<StrategyDefinition>> {
+        try_result_opt!(self.tree.get(name.as_bytes()))
+    }
+
+    pub fn set(&self, definition: &StrategyDefinition) -> Result<()> {
+        let encoded = schema::encode(definition)?;
+        let _gate = self.write_gate.enter();
+        self.tree.insert(definition.name.as_bytes(), encoded)?;
+        Ok(())
+    }
+
+    /// Remove the definition and the state of a strategy
+    pub fn remove(&self, name: &str) -> Result<bool> {
+        let _gate = self.write_gate.enter();
+        self.states.remove(name.as_bytes())?;
+        Ok(self.tree.remove(name.as_bytes())?.is_some())
+    }
+
+    pub fn list(&self) -> Result<Vec<StrategyDefinition>> {
+        let mut ret = Vec::new();
+        for item in self.tree.iter() {
+            let (_, raw) = item?;
+            ret.push(schema::decode(raw.as_ref())?);
+        }
+        Ok(ret)
+    }
+
+    pub fn state(&self, name: &str) -> Result<Option<StrategyState>> {
+        try_result_opt!(self.states.get(name.as_bytes()))
+    }
+
+    pub fn set_state(&self, name: &str, state: &StrategyState) -> Result<()> {
+        let encoded = schema::encode(state)?;
+        let _gate = self.write_gate.enter();
+        self.states.insert(name.as_bytes(), encoded)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+use super::*;
+use crate::exchange::Trade;
+
+/// Public trades of a market keyed by time (ms) and trade id, both big endian
+#[derive(Clone, Debug)]
+pub struct StoreTradesHandle {
+    id: MarketIdentifier,
+    tree: sled::Tree,
+    write_gate: WriteGate,
+}
+
+fn trade_key(time_ms: i64, id: u64) -> [u8; 16] {
+    let mut key = [0u8; 16];
+    key[..8].copy_from_slice(&time_ms.to_be_bytes());
+    key[8..].copy_from_slice(&id.to_be_bytes());
+    key
+}
+
+impl StoreTradesHandle {
+    pub fn new(tree: sled::Tree, id: MarketIdentifier, write_gate: WriteGate) -> Self {
+        Self {
+            id,
+            tree,
+            write_gate,
+        }
+    }
+
+    /// Store trades, trades already stored are replaced. Streamed trades carry no exchange id,
+    /// so a trade matching one stored under another id (same millisecond, price, volume and
+    /// side) is the same trade received from the other source and is skipped
+    pub fn extend<T: IntoIterator<Item = Trade>>(&self, trades: T) -> Result<usize> {
+        let mut batch = sled::Batch::default();
+        let mut matched = std::collections::HashSet::new();
+        let mut count = 0;
+        for trade in trades {
+            let key = trade_key(trade.time_ms, trade.id);
+            if !self.tree.contains_key(&key)? {
+                if let Some(duplicate) = self.duplicate(&trade, &matched)? {
+                    matched.insert(duplicate);
+                    continue;
+                }
+            }
+            batch.insert(&key, schema::encode(&trade)?);
+            count += 1;
+        }
+        let _gate = self.write_gate.enter();
+        self.tree.apply_batch(batch)?;
+        log::trace!(
+            "Appending trades into store: EXCHANGE={}, BASE={}, QUOTE={}, NBR_TRADES={}",
+            &self.id.exchange_name,
+            &self.id.base,
+            &self.id.quote,
+            count
+        );
+        Ok(count)
+    }
+
+    /// Key of a stored trade of the same millisecond, price, volume and side as `trade`, each
+    /// stored trade matches a single received one
+    fn duplicate(&self, trade: &Trade, matched: &std::collections::HashSet<sled::IVec>) -> Result<Option<sled::IVec>> {
+        for item in self.tree.scan_prefix(trade.time_ms.to_be_bytes()) {
+            let (key, raw) = item?;
+            if matched.contains(&key) {
+                continue;
+            }
+            let stored: Trade = schema::decode(raw.as_ref())?;
+            if stored.price == trade.price && stored.volume == trade.volume && stored.side == trade.side {
+                return Ok(Some(key));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn last(&self) -> Result<Option<Trade>> {
+        try_result_opt!(self.tree.last().map(|e| e.map(|(_, v)| v)))
+    }
+
+    /// The `count` most recent trades, oldest first
+    pub fn last_n(&self, count: usize) -> Result<Vec<Trade>> {
+        let mut ret = Vec::with_capacity(count);
+        for item in self.tree.iter().rev().take(count) {
+            let (_, raw) = item?;
+            ret.push(schema::decode(raw.as_ref())?);
+        }
+        ret.reverse();
+        Ok(ret)
+    }
+
+    /// Trades at or after `time_ms`, oldest first
+    pub fn since(&self, time_ms: i64) -> Result<Vec<Trade>> {
+        let mut ret = Vec::new();
+        for item in self.tree.range(trade_key(time_ms, 0)..) {
+            let (_, raw) = item?;
+            ret.push(schema::decode(raw.as_ref())?);
+        }
+        Ok(ret)
+    }
+}
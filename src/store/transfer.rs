@@ -0,0 +1,509 @@
+use super::*;
+use parquet::{
+    column::writer::ColumnWriter,
+    data_type::ByteArray,
+    file::{
+        properties::WriterProperties,
+        reader::{FileReader, SerializedFileReader},
+        writer::{FileWriter, RowGroupWriter, SerializedFileWriter},
+    },
+    record::RowAccessor,
+    schema::parser::parse_message_type,
+};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::str::FromStr;
+
+/// Column layout shared by every format, only ever append new columns at the end
+pub const COLUMNS: [&str; 11] = [
+    "market",
+    "interval",
+    "time",
+    "open",
+    "high",
+    "low",
+    "close",
+    "vwap",
+    "volume",
+    "count",
+    "first_available",
+];
+
+/// Prices and volumes are exact decimals at the finest scale of `Decimal`, stored as big endian
+/// two's complement mantissas
+const PARQUET_SCHEMA: &str = "
+message candle {
+    REQUIRED BYTE_ARRAY market (UTF8);
+    REQUIRED INT64 interval;
+    REQUIRED INT64 time;
+    REQUIRED BYTE_ARRAY open (DECIMAL(38, 18));
+    REQUIRED BYTE_ARRAY high (DECIMAL(38, 18));
+    REQUIRED BYTE_ARRAY low (DECIMAL(38, 18));
+    REQUIRED BYTE_ARRAY close (DECIMAL(38, 18));
+    REQUIRED BYTE_ARRAY vwap (DECIMAL(38, 18));
+    REQUIRED BYTE_ARRAY volume (DECIMAL(38, 18));
+    REQUIRED INT64 count;
+    REQUIRED BOOLEAN first_available;
+}
+";
+const PARQUET_PRECISION: u32 = 38;
+const PARQUET_SCALE: u32 = crate::decimal::MAX_SCALE;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TransferFormat {
+    Csv,
+    Jsonl,
+    Parquet,
+}
+
+impl FromStr for TransferFormat {
+    type Err = Error;
+
+    fn from_str(raw: &str) -> Result<Self> {
+        match raw.to_lowercase().as_str() {
+            "csv" => Ok(TransferFormat::Csv),
+            "jsonl" | "ndjson" => Ok(TransferFormat::Jsonl),
+            "parquet" => Ok(TransferFormat::Parquet),
+            _ => Err(Error::Transfer(format!(
+                "unknown format `{}`, expected one of: csv, jsonl, parquet",
+                raw
+            ))),
+        }
+    }
+}
+
+impl TransferFormat {
+    /// Guess the format from the extension of a file
+    pub fn from_path(path: &Path) -> Result<Self> {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .ok_or_else(|| Error::Transfer(format!("can't guess format of {}", path.display())))?
+            .parse()
+    }
+
+    /// Use the explicit format if any, otherwise guess it from the path
+    pub fn resolve(explicit: Option<&str>, path: &Path) -> Result<Self> {
+        match explicit {
+            Some(format) => format.parse(),
+            None => Self::from_path(path),
+        }
+    }
+}
+
+/// One candle of a market and interval, see `COLUMNS`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CandleRecord {
+    pub market: String,
+    /// Interval in minutes
+    pub interval: i64,
+    pub time: Timestamp,
+    pub open: Decimal,
+    pub high: Decimal,
+    pub low: Decimal,
+    pub close: Decimal,
+    pub vwap: Decimal,
+    pub volume: Decimal,
+    pub count: u64,
+    pub first_available: bool,
+}
+
+impl CandleRecord {
+    pub fn new(market: &MarketIdentifier, interval: Interval, ohlc: &OHLC) -> Self {
+        Self {
+            market: format!("{}/{}/{}", market.exchange_name, market.base, market.quote),
+            interval: interval.as_secs() / 60,
+            time: ohlc.time,
+            open: ohlc.open,
+            high: ohlc.high,
+            low: ohlc.low,
+            close: ohlc.close,
+            vwap: ohlc.vwap,
+            volume: ohlc.volume,
+            count: ohlc.count,
+            first_available: ohlc.first_available,
+        }
+    }
+
+    pub fn into_parts(self) -> Result<(MarketIdentifier, Interval, OHLC)> {
+        let market = MarketIdentifier::from(&self.market);
+        if market.exchange_name.is_empty() || market.base.is_empty() || market.quote.is_empty() {
+            return Err(Error::Transfer(format!("invalid market `{}`", self.market)));
+        }
+        let ohlc = OHLC {
+            first_available: self.first_available,
+            time: self.time,
+            open: self.open,
+            high: self.high,
+            low: self.low,
+            close: self.close,
+            vwap: self.vwap,
+            volume: self.volume,
+            count: self.count,
+        };
+        Ok((market, Interval::from_minuts(self.interval)?, ohlc))
+    }
+
+    fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{},{},{},{}",
+            self.market,
+            self.interval,
+            self.time,
+            self.open,
+            self.high,
+            self.low,
+            self.close,
+            self.vwap,
+            self.volume,
+            self.count,
+            self.first_available
+        )
+    }
+
+    fn from_csv(line: &str) -> Result<Self> {
+        let invalid = |field: &str| Error::Transfer(format!("invalid {} in csv line `{}`", field, line));
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        if fields.len() != COLUMNS.len() {
+            return Err(Error::Transfer(format!(
+                "expected {} columns, found {} in csv line `{}`",
+                COLUMNS.len(),
+                fields.len(),
+                line
+            )));
+        }
+        Ok(Self {
+            market: fields[0].to_string(),
+            interval: fields[1].parse().map_err(|_| invalid("interval"))?,
+            time: fields[2].parse().map_err(|_| invalid("time"))?,
+            open: fields[3].parse()?,
+            high: fields[4].parse()?,
+            low: fields[5].parse()?,
+            close: fields[6].parse()?,
+            vwap: fields[7].parse()?,
+            volume: fields[8].parse()?,
+            count: fields[9].parse().map_err(|_| invalid("count"))?,
+            first_available: fields[10].parse().map_err(|_| invalid("first_available"))?,
+        })
+    }
+}
+
+/// Mantissa of `value` at `PARQUET_SCALE`, it must fit the precision of the column
+fn parquet_decimal(value: Decimal) -> Result<ByteArray> {
+    let mantissa = 10i128
+        .checked_pow(PARQUET_SCALE - value.scale().min(PARQUET_SCALE))
+        .and_then(|e| value.mantissa().checked_mul(e))
+        .filter(|e| e.unsigned_abs() < 10u128.pow(PARQUET_PRECISION))
+        .ok_or_else(|| {
+            Error::Transfer(format!(
+                "{} doesn't fit a DECIMAL({}, {}) column",
+                value, PARQUET_PRECISION, PARQUET_SCALE
+            ))
+        })?;
+    Ok(ByteArray::from(mantissa.to_be_bytes().to_vec()))
+}
+
+/// Decimal columns, or strings as written by earlier versions
+fn read_parquet_decimal(row: &parquet::record::Row, i: usize) -> Result<Decimal> {
+    let decimal = match row.get_decimal(i) {
+        Ok(decimal) => decimal,
+        Err(_) => return row.get_string(i)?.parse(),
+    };
+    let bytes = decimal.data();
+    if bytes.is_empty() || bytes.len() > 16 || !(0..=PARQUET_SCALE as i32).contains(&decimal.scale()) {
+        return Err(Error::Transfer(format!(
+            "unsupported decimal of {} bytes at scale {}",
+            bytes.len(),
+            decimal.scale()
+        )));
+    }
+    // Sign extend the big endian two's complement mantissa
+    let mut mantissa = if bytes[0] & 0x80 != 0 { [0xff; 16] } else { [0; 16] };
+    mantissa[16 - bytes.len()..].copy_from_slice(bytes);
+    Ok(Decimal::new(i128::from_be_bytes(mantissa), decimal.scale() as u32).normalize())
+}
+
+fn write_parquet_row_group(
+    writer: &mut SerializedFileWriter<File>,
+    records: &[CandleRecord],
+) -> Result<()> {
+    let decimals = |f: fn(&CandleRecord) -> Decimal| -> Result<Vec<ByteArray>> {
+        records.iter().map(|e| parquet_decimal(f(e))).collect()
+    };
+    let mut row_group = writer.next_row_group()?;
+    let mut index = 0;
+    while let Some(mut column) = row_group.next_column()? {
+        match (&mut column, COLUMNS[index]) {
+            (ColumnWriter::ByteArrayColumnWriter(w), name) => {
+                let values = match name {
+                    "market" => records.iter().map(|e| ByteArray::from(e.market.as_str())).collect(),
+                    "open" => decimals(|e| e.open)?,
+                    "high" => decimals(|e| e.high)?,
+                    "low" => decimals(|e| e.low)?,
+                    "close" => decimals(|e| e.close)?,
+                    "vwap" => decimals(|e| e.vwap)?,
+                    _ => decimals(|e| e.volume)?,
+                };
+                w.write_batch(&values, None, None)?;
+            }
+            (ColumnWriter::Int64ColumnWriter(w), name) => {
+                let values: Vec<i64> = records
+                    .iter()
+                    .map(|e| match name {
+                        "interval" => e.interval,
+                        "time" => e.time,
+                        _ => e.count as i64,
+                    })
+                    .collect();
+                w.write_batch(&values, None, None)?;
+            }
+            (ColumnWriter::BoolColumnWriter(w), _) => {
+                let values: Vec<bool> = records.iter().map(|e| e.first_available).collect();
+                w.write_batch(&values, None, None)?;
+            }
+            _ => unreachable!("Column types are defined by PARQUET_SCHEMA"),
+        }
+        row_group.close_column(column)?;
+        index += 1;
+    }
+    writer.close_row_group(row_group)?;
+    Ok(())
+}
+
+fn read_parquet(path: &Path) -> Result<Vec<CandleRecord>> {
+    let reader = SerializedFileReader::new(File::open(path)?)?;
+    let mut ret = Vec::new();
+    for row in reader.get_row_iter(None)? {
+        ret.push(CandleRecord {
+            market: row.get_string(0)?.clone(),
+            interval: row.get_long(1)?,
+            time: row.get_long(2)?,
+            open: read_parquet_decimal(&row, 3)?,
+            high: read_parquet_decimal(&row, 4)?,
+            low: read_parquet_decimal(&row, 5)?,
+            close: read_parquet_decimal(&row, 6)?,
+            vwap: read_parquet_decimal(&row, 7)?,
+            volume: read_parquet_decimal(&row, 8)?,
+            count: row.get_long(9)? as u64,
+            first_available: row.get_bool(10)?,
+        });
+    }
+    Ok(ret)
+}
+
+impl StoreHandle {
+    /// Export the candles of a market and interval, or of every market when `market` is `None`
+    pub async fn export(
+        &self,
+        market: Option<&MarketIdentifier>,
+        interval: Option<Interval>,
+        format: TransferFormat,
+        path: &Path,
+    ) -> Result<usize> {
+        let trees: Vec<_> = self
+            .market_trees()?
+            .into_iter()
+            .filter(|(id, i)| {
+                market.map(|e| e == id).unwrap_or(true) && interval.map(|e| e == *i).unwrap_or(true)
+            })
+            .collect();
+        let mut count = 0;
+        match format {
+            TransferFormat::Csv | TransferFormat::Jsonl => {
+                let mut out = BufWriter::new(File::create(path)?);
+                if format == TransferFormat::Csv {
+                    writeln!(out, "{}", COLUMNS.join(","))?;
+                }
+                for (id, interval) in trees {
+                    let data = self.market(id.clone())?.interval(interval).await?;
+                    for ohlc in data.iter() {
+                        let record = CandleRecord::new(&id, interval, &ohlc?);
+                        match format {
+                            TransferFormat::Csv => writeln!(out, "{}", record.to_csv())?,
+                            _ => writeln!(out, "{}", serde_json::to_string(&record)?)?,
+                        }
+                        count += 1;
+                    }
+                }
+                out.flush()?;
+            }
+            TransferFormat::Parquet => {
+                let schema = Arc::new(parse_message_type(PARQUET_SCHEMA)?);
+                let properties = Arc::new(WriterProperties::builder().build());
+                let mut writer = SerializedFileWriter::new(File::create(path)?, schema, properties)?;
+                for (id, interval) in trees {
+                    let data = self.market(id.clone())?.interval(interval).await?;
+                    let records = data
+                        .iter()
+                        .map(|e| e.map(|e| CandleRecord::new(&id, interval, &e)))
+                        .collect::<Result<Vec<_>>>()?;
+                    if !records.is_empty() {
+                        count += records.len();
+                        write_parquet_row_group(&mut writer, &records)?;
+                    }
+                }
+                writer.close()?;
+            }
+        }
+        log::info!("Store export DONE: PATH={}, CANDLES={}", path.display(), count);
+        Ok(count)
+    }
+
+    /// Load candles into the store, existing candles with the same time are replaced, markets
+    /// are stored under their canonical name
+    pub async fn import(
+        &self,
+        format: TransferFormat,
+        path: &Path,
+        assets: &crate::exchange::AssetRegistry,
+    ) -> Result<usize> {
+        let records: Box<dyn Iterator<Item = Result<CandleRecord>>> = match format {
+            TransferFormat::Csv => {
+                let mut lines = BufReader::new(File::open(path)?).lines();
+                let header = lines.next().transpose()?.unwrap_or_default();
+                if header.trim() != COLUMNS.join(",") {
+                    return Err(Error::Transfer(format!(
+                        "unexpected csv header `{}`, expected `{}`",
+                        header.trim(),
+                        COLUMNS.join(",")
+                    )));
+                }
+                Box::new(
+                    lines
+                        .filter(|e| e.as_ref().map(|e| !e.trim().is_empty()).unwrap_or(true))
+                        .map(|e| CandleRecord::from_csv(&e?)),
+                )
+            }
+            TransferFormat::Jsonl => Box::new(
+                BufReader::new(File::open(path)?)
+                    .lines()
+                    .filter(|e| e.as_ref().map(|e| !e.trim().is_empty()).unwrap_or(true))
+                    .map(|e| Ok(serde_json::from_str(&e?)?)),
+            ),
+            TransferFormat::Parquet => Box::new(read_parquet(path)?.into_iter().map(Ok)),
+        };
+        let mut handles: HashMap<(MarketIdentifier, Interval), StoreMarketDataHandle> = HashMap::new();
+        let mut count = 0;
+        for record in records {
+            let (market, interval, ohlc) = record?.into_parts()?;
+            let key = (assets.canonical_market(&market), interval);
+            if !handles.contains_key(&key) {
+                let handle = self.market(key.0.clone())?.interval(interval).await?;
+                handles.insert(key.clone(), handle);
+            }
+            handles[&key].insert(ohlc)?;
+            count += 1;
+        }
+        log::info!("Store import DONE: PATH={}, CANDLES={}", path.display(), count);
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(raw: &str) -> Decimal {
+        raw.parse().unwrap()
+    }
+
+    fn candles() -> Vec<OHLC> {
+        vec![
+            OHLC {
+                first_available: true,
+                time: 1_640_995_200,
+                open: dec("41000.1"),
+                high: dec("41500"),
+                low: dec("0.000000000000000001"),
+                close: dec("99999999999999999999.999999999999999999"),
+                vwap: dec("41180.123456789012345678"),
+                volume: dec("-12.5"),
+                count: 320,
+            },
+            OHLC {
+                first_available: false,
+                time: 1_640_998_800,
+                open: dec("41200.5"),
+                high: dec("41210"),
+                low: dec("41100.25"),
+                close: dec("41150"),
+                vwap: dec("41160.000000000000000001"),
+                volume: dec("0.1"),
+                count: 12,
+            },
+        ]
+    }
+
+    /// Export every candle of a store and import them back into a fresh one
+    async fn round_trip(format: TransferFormat, extension: &str) {
+        let root = std::env::temp_dir().join(format!(
+            "pkbot-transfer-{}-{}",
+            extension,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let market = MarketIdentifier::from("kraken/BTC/EUR");
+        let file = root.join(format!("candles.{}", extension));
+
+        let source = Store::new(root.join("source")).unwrap().handle();
+        let data = source
+            .market(market.clone())
+            .unwrap()
+            .interval(Interval::Hour1)
+            .await
+            .unwrap();
+        for ohlc in candles() {
+            data.insert(ohlc).unwrap();
+        }
+        assert_eq!(source.export(None, None, format, &file).await.unwrap(), 2);
+        assert_eq!(TransferFormat::from_path(&file).unwrap(), format);
+
+        let target = Store::new(root.join("target")).unwrap().handle();
+        let assets = crate::exchange::AssetRegistry::new();
+        assert_eq!(target.import(format, &file, &assets).await.unwrap(), 2);
+        let data = target
+            .market(market)
+            .unwrap()
+            .interval(Interval::Hour1)
+            .await
+            .unwrap();
+        let imported = data.iter().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(imported.len(), 2);
+        for (expected, imported) in candles().iter().zip(imported.iter()) {
+            assert_eq!(imported.first_available, expected.first_available);
+            assert_eq!(imported.time, expected.time);
+            assert_eq!(imported.open, expected.open);
+            assert_eq!(imported.high, expected.high);
+            assert_eq!(imported.low, expected.low);
+            assert_eq!(imported.close, expected.close);
+            assert_eq!(imported.vwap, expected.vwap);
+            assert_eq!(imported.volume, expected.volume);
+            assert_eq!(imported.vwap.to_string(), expected.vwap.to_string());
+            assert_eq!(imported.count, expected.count);
+        }
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn csv_round_trip() {
+        round_trip(TransferFormat::Csv, "csv").await;
+    }
+
+    #[tokio::test]
+    async fn jsonl_round_trip() {
+        round_trip(TransferFormat::Jsonl, "jsonl").await;
+    }
+
+    #[tokio::test]
+    async fn parquet_round_trip() {
+        round_trip(TransferFormat::Parquet, "parquet").await;
+    }
+
+    #[test]
+    fn parquet_decimal_bounds() {
+        let max = dec("99999999999999999999.999999999999999999");
+        let bytes = parquet_decimal(max).unwrap();
+        assert_eq!(bytes.data().len(), 16);
+        assert!(parquet_decimal(dec("100000000000000000000")).is_err());
+    }
+}
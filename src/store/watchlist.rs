@@ -0,0 +1,64 @@
+use super::*;
+
+/// Watchlists share the settings tree with the market settings, their keys start with `@`
+pub const WATCHLIST_PREFIX: char = '@';
+
+#[derive(Debug, Clone, Encode, Decode, Serialize, Deserialize)]
+pub struct Watchlist {
+    pub name: String,
+    /// Markets (`kraken/XBT/EUR`), globs (`kraken/*/EUR`) or other watchlists (`@name`)
+    pub members: Vec<String>,
+}
+
+impl Watchlist {
+    pub fn validate_name(name: &str) -> Result<()> {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(Error::InvalidWatchlist(format!(
+                "`{}` must only contain letters, digits, `-` and `_`",
+                name
+            )));
+        }
+        Ok(())
+    }
+
+    fn key(name: &str) -> String {
+        format!("{}{}", WATCHLIST_PREFIX, name)
+    }
+}
+
+impl StoreHandle {
+    pub fn watchlist(&self, name: &str) -> Result<Option<Watchlist>> {
+        try_result_opt!(self.settings_tree.get(Watchlist::key(name).as_bytes()))
+    }
+
+    pub fn set_watchlist(&self, watchlist: &Watchlist) -> Result<()> {
+        Watchlist::validate_name(&watchlist.name)?;
+        let encoded = schema::encode(watchlist)?;
+        let _gate = self.write_gate.enter();
+        self.settings_tree
+            .insert(Watchlist::key(&watchlist.name).as_bytes(), encoded)?;
+        Ok(())
+    }
+
+    pub fn remove_watchlist(&self, name: &str) -> Result<bool> {
+        let _gate = self.write_gate.enter();
+        Ok(self
+            .settings_tree
+            .remove(Watchlist::key(name).as_bytes())?
+            .is_some())
+    }
+
+    pub fn watchlists(&self) -> Result<Vec<Watchlist>> {
+        let mut ret = Vec::new();
+        let prefix = WATCHLIST_PREFIX.to_string();
+        for item in self.settings_tree.scan_prefix(prefix.as_bytes()) {
+            let (_, raw) = item?;
+            ret.push(schema::decode(raw.as_ref())?);
+        }
+        Ok(ret)
+    }
+}
@@ -0,0 +1,524 @@
+use crate::exchange::MarketIdentifier;
+use crate::interpretor::{Program, ProgramIdentifier, ProgramOutput, ProgramStatus};
+use crate::prelude::*;
+use crate::reactor::{ReactorEvent, ReactorEventListenerHandle};
+use crossterm::{
+    cursor,
+    event::{self, KeyCode, KeyEvent, KeyModifiers},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use std::collections::BTreeMap;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tui::{
+    backend::{Backend, CrosstermBackend},
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    Frame, Terminal,
+};
+
+pub(crate) mod chart;
+mod events;
+mod logs;
+
+use chart::CandleChart;
+use events::UseKeyInput;
+pub use logs::TuiLogger;
+
+const TICK_RATE: Duration = Duration::from_millis(250);
+const OUTPUT_CAPACITY: usize = 200;
+const CHART_CANDLES: usize = 512;
+const INTERVALS: [Interval; 7] = [
+    Interval::Min1,
+    Interval::Min5,
+    Interval::Min15,
+    Interval::Min30,
+    Interval::Hour1,
+    Interval::Hour4,
+    Interval::Day1,
+];
+
+#[derive(Debug)]
+pub enum AppEvent {
+    Key(KeyEvent),
+    Tick,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Focus {
+    Markets,
+    Filter,
+    Command,
+}
+
+struct ProgramEntry {
+    source: String,
+    status: ProgramStatus,
+}
+
+struct App {
+    reactor: Reactor,
+    focus: Focus,
+    markets: Vec<MarketIdentifier>,
+    market_state: ListState,
+    filter: String,
+    interval: usize,
+    chart_market: Option<MarketIdentifier>,
+    candles: Vec<OHLC>,
+    programs: BTreeMap<ProgramIdentifier, ProgramEntry>,
+    output: VecDeque<String>,
+    logs: VecDeque<String>,
+    command: String,
+    status: String,
+    exit: bool,
+}
+
+fn market_name(market: &MarketIdentifier) -> String {
+    format!("{}/{}/{}", market.exchange_name, market.base, market.quote)
+}
+
+impl App {
+    async fn new(reactor: Reactor) -> Self {
+        let markets =
+            crate::reactor::runtime::ls::get_markets(reactor.clone(), None, None, None).await;
+        let mut market_state = ListState::default();
+        if !markets.is_empty() {
+            market_state.select(Some(0));
+        }
+        Self {
+            reactor,
+            focus: Focus::Markets,
+            status: format!("{} markets loaded", markets.len()),
+            markets,
+            market_state,
+            filter: String::new(),
+            interval: 0,
+            chart_market: None,
+            candles: Vec::new(),
+            programs: BTreeMap::new(),
+            output: VecDeque::with_capacity(OUTPUT_CAPACITY),
+            logs: VecDeque::with_capacity(OUTPUT_CAPACITY),
+            command: String::new(),
+            exit: false,
+        }
+    }
+
+    fn interval(&self) -> Interval {
+        INTERVALS[self.interval]
+    }
+
+    fn visible_markets(&self) -> Vec<&MarketIdentifier> {
+        let filter = self.filter.to_uppercase();
+        self.markets
+            .iter()
+            .filter(|e| filter.is_empty() || market_name(e).to_uppercase().contains(&filter))
+            .collect()
+    }
+
+    fn selected_market(&self) -> Option<MarketIdentifier> {
+        let visible = self.visible_markets();
+        self.market_state
+            .selected()
+            .and_then(|i| visible.get(i).map(|e| (*e).clone()))
+    }
+
+    fn move_selection(&mut self, offset: isize) {
+        let len = self.visible_markets().len();
+        if len == 0 {
+            self.market_state.select(None);
+            return;
+        }
+        let current = self.market_state.selected().unwrap_or(0) as isize;
+        let next = (current + offset).max(0).min(len as isize - 1);
+        self.market_state.select(Some(next as usize));
+    }
+
+    fn push_output(&mut self, line: String) {
+        if self.output.len() == OUTPUT_CAPACITY {
+            self.output.pop_front();
+        }
+        self.output.push_back(line);
+    }
+
+    fn push_log(&mut self, line: String) {
+        if self.logs.len() == OUTPUT_CAPACITY {
+            self.logs.pop_front();
+        }
+        self.logs.push_back(line);
+    }
+
+    async fn load_candles(&mut self) {
+        let market = match self.chart_market.clone() {
+            Some(market) => market,
+            None => return,
+        };
+        let interval = self.interval();
+        let loaded = async {
+            self.reactor
+                .market_store(&market)?
+                .interval(interval)
+                .await?
+                .last_n(CHART_CANDLES)
+        }
+        .await;
+        match loaded {
+            Ok(candles) => {
+                self.status = format!(
+                    "{} {}: {} candles",
+                    market_name(&market),
+                    interval,
+                    candles.len()
+                );
+                self.candles = candles;
+            }
+            Err(e) => self.status = format!("Failed to load candles: {}", e),
+        }
+    }
+
+    fn sync_chart_market(&self) {
+        let market = match self.chart_market.clone() {
+            Some(market) => market,
+            None => return,
+        };
+        let reactor = self.reactor.clone();
+        let interval = self.interval();
+        tokio::spawn((async move || {
+            let to = chrono::Utc::now().timestamp();
+            let from = to - interval.as_secs() * CHART_CANDLES as i64;
+            let synced = async {
+                reactor
+                    .get_or_register_market(&market)
+                    .await?
+                    .sync_periode(from, to, interval)
+                    .await
+            }
+            .await;
+            if let Err(e) = synced {
+                error!(
+                    "Failed to sync chart market: MARKET={}, ERROR={}",
+                    market_name(&market),
+                    e
+                );
+            }
+        })());
+    }
+
+    async fn open_selected(&mut self) {
+        self.chart_market = self.selected_market();
+        self.candles.clear();
+        self.load_candles().await;
+        self.sync_chart_market();
+    }
+
+    async fn submit_command(&mut self) {
+        let command = std::mem::take(&mut self.command);
+        if command.trim().is_empty() {
+            return;
+        }
+        self.push_output(format!("> {}", command));
+        match Program::new(&command) {
+            Ok(program) => {
+                let id = self.reactor.spawn_program(program).await;
+                self.programs.insert(
+                    id,
+                    ProgramEntry {
+                        source: command,
+                        status: ProgramStatus::Running,
+                    },
+                );
+            }
+            Err(e) => self.push_output(format!("Failed to parse command: {}", e)),
+        }
+    }
+
+    async fn on_key(&mut self, key: KeyEvent) {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            self.exit = true;
+            return;
+        }
+        match (self.focus, key.code) {
+            (_, KeyCode::Tab) => {
+                self.focus = match self.focus {
+                    Focus::Markets => Focus::Command,
+                    _ => Focus::Markets,
+                }
+            }
+            (Focus::Filter | Focus::Command, KeyCode::Esc) => self.focus = Focus::Markets,
+            (Focus::Filter, KeyCode::Enter) => self.focus = Focus::Markets,
+            (Focus::Filter, KeyCode::Backspace) => {
+                self.filter.pop();
+                self.move_selection(0);
+            }
+            (Focus::Filter, KeyCode::Char(c)) => {
+                self.filter.push(c);
+                self.market_state.select(Some(0));
+                self.move_selection(0);
+            }
+            (Focus::Command, KeyCode::Enter) => self.submit_command().await,
+            (Focus::Command, KeyCode::Backspace) => {
+                self.command.pop();
+            }
+            (Focus::Command, KeyCode::Char(c)) => self.command.push(c),
+            (Focus::Markets, KeyCode::Char('q')) => self.exit = true,
+            (Focus::Markets, KeyCode::Char('/')) => self.focus = Focus::Filter,
+            (Focus::Markets, KeyCode::Char(':')) => self.focus = Focus::Command,
+            (Focus::Markets, KeyCode::Up | KeyCode::Char('k')) => self.move_selection(-1),
+            (Focus::Markets, KeyCode::Down | KeyCode::Char('j')) => self.move_selection(1),
+            (Focus::Markets, KeyCode::Enter) => self.open_selected().await,
+            (Focus::Markets, KeyCode::Char('i')) => {
+                self.interval = (self.interval + 1) % INTERVALS.len();
+                self.load_candles().await;
+                self.sync_chart_market();
+            }
+            _ => {}
+        }
+    }
+
+    async fn on_reactor_event(&mut self, event: ReactorEvent) {
+        match event {
+            ReactorEvent::RuntimeCreated { id } => {
+                let source = self
+                    .reactor
+                    .programs
+                    .read()
+                    .await
+                    .get(&id)
+                    .map(|e| e.source.clone())
+                    .unwrap_or_default();
+                self.programs.entry(id).or_insert(ProgramEntry {
+                    source,
+                    status: ProgramStatus::Running,
+                });
+            }
+            ReactorEvent::ProgramStatus { id, status } => {
+                if let Some(entry) = self.programs.get_mut(&id) {
+                    entry.status = status;
+                }
+            }
+            ReactorEvent::ProgramOutput { id, content } => match content {
+                ProgramOutput::Text { message } => self.push_output(format!("[{}] {}", id, message)),
+                ProgramOutput::Json { content } => {
+                    self.push_output(format!("[{}] {}", id, content.to_json()))
+                }
+                ProgramOutput::Exit {
+                    message: Some(message),
+                    ..
+                } => self.push_output(format!("[{}] {}", id, message)),
+                ProgramOutput::Exit { .. } => {}
+            },
+            ReactorEvent::MarketSynced {
+                market, interval, ..
+            } => {
+                if self.chart_market.as_ref() == Some(&market) && interval == self.interval() {
+                    self.load_candles().await;
+                }
+            }
+            ReactorEvent::AlertTriggered { notification } => {
+                self.push_output(format!("[alert] {}", notification.message))
+            }
+            ReactorEvent::RiskViolation { order, violation } => {
+                self.push_output(format!("[risk] {} rejected: {}", order, violation))
+            }
+            ReactorEvent::KillSwitch { engaged, reason } => self.push_output(match engaged {
+                true => format!("[risk] kill switch engaged ({})", reason.unwrap_or_default()),
+                false => String::from("[risk] kill switch released"),
+            }),
+            ReactorEvent::StrategyStatus {
+                name,
+                mode,
+                running,
+                error,
+            } => self.push_output(match (running, error) {
+                (true, _) => format!("[strategy] {} started ({})", name, mode),
+                (false, Some(error)) => format!("[strategy] {} stopped: {}", name, error),
+                (false, None) => format!("[strategy] {} stopped", name),
+            }),
+            ReactorEvent::Lagged { skipped } => self.status = format!("Missed {} events", skipped),
+            _ => {}
+        }
+    }
+
+    fn draw<B: Backend>(&mut self, f: &mut Frame<B>) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(8), Constraint::Length(3), Constraint::Length(1)])
+            .split(f.size());
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Length(32), Constraint::Min(20)])
+            .split(rows[0]);
+        let left = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+            .split(columns[0]);
+        let right = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(70), Constraint::Percentage(30)])
+            .split(columns[1]);
+        let bottom = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(right[1]);
+
+        let current = self.focus;
+        let focused = move |focus: Focus| {
+            if current == focus {
+                Style::default().fg(Color::Yellow)
+            } else {
+                Style::default()
+            }
+        };
+
+        let title = if self.filter.is_empty() && current != Focus::Filter {
+            "Markets".to_string()
+        } else {
+            format!("Markets /{}", self.filter)
+        };
+        let markets: Vec<ListItem> = self
+            .visible_markets()
+            .into_iter()
+            .map(|e| ListItem::new(market_name(e)))
+            .collect();
+        let markets = List::new(markets)
+            .block(
+                Block::default()
+                    .title(title)
+                    .borders(Borders::ALL)
+                    .border_style(focused(Focus::Markets).patch(focused(Focus::Filter))),
+            )
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        f.render_stateful_widget(markets, left[0], &mut self.market_state);
+
+        let programs: Vec<ListItem> = self
+            .programs
+            .iter()
+            .rev()
+            .map(|(id, entry)| {
+                let color = match entry.status {
+                    ProgramStatus::Running => Color::Cyan,
+                    ProgramStatus::Success => Color::Green,
+                    ProgramStatus::None => Color::Gray,
+                    _ => Color::Red,
+                };
+                ListItem::new(Spans::from(vec![
+                    Span::styled(format!("{:<4}", id), Style::default().fg(color)),
+                    Span::raw(entry.source.clone()),
+                ]))
+            })
+            .collect();
+        f.render_widget(
+            List::new(programs).block(Block::default().title("Programs").borders(Borders::ALL)),
+            left[1],
+        );
+
+        let chart_title = match &self.chart_market {
+            Some(market) => format!("{} {}", market_name(market), self.interval()),
+            None => "Chart".to_string(),
+        };
+        f.render_widget(
+            CandleChart::new(&self.candles)
+                .block(Block::default().title(chart_title).borders(Borders::ALL)),
+            right[0],
+        );
+
+        let height = right[1].height.saturating_sub(2) as usize;
+        let output: Vec<ListItem> = self
+            .output
+            .iter()
+            .skip(self.output.len().saturating_sub(height))
+            .map(|e| ListItem::new(e.as_str()))
+            .collect();
+        f.render_widget(
+            List::new(output).block(Block::default().title("Output").borders(Borders::ALL)),
+            bottom[0],
+        );
+        let logs: Vec<ListItem> = self
+            .logs
+            .iter()
+            .skip(self.logs.len().saturating_sub(height))
+            .map(|e| ListItem::new(e.as_str()))
+            .collect();
+        f.render_widget(
+            List::new(logs).block(Block::default().title("Logs").borders(Borders::ALL)),
+            bottom[1],
+        );
+
+        f.render_widget(
+            Paragraph::new(format!(":{}", self.command)).block(
+                Block::default()
+                    .title("Command")
+                    .borders(Borders::ALL)
+                    .border_style(focused(Focus::Command)),
+            ),
+            rows[1],
+        );
+        if current == Focus::Command {
+            f.set_cursor(rows[1].x + 2 + self.command.len() as u16, rows[1].y + 1);
+        }
+
+        f.render_widget(
+            Paragraph::new(format!(
+                "{} | q quit, / filter, : command, enter open, i interval",
+                self.status
+            ))
+            .style(Style::default().fg(Color::Gray)),
+            rows[2],
+        );
+    }
+}
+
+async fn event_loop<B: Backend>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+    mut listener: ReactorEventListenerHandle,
+    mut logs: UnboundedReceiver<String>,
+) -> Result<()> {
+    let (sender, mut receiver) = unbounded_channel();
+    UseKeyInput::new(TICK_RATE, sender);
+    while !app.exit {
+        terminal.draw(|f| app.draw(f))?;
+        tokio::select! {
+            event = receiver.recv() => match event {
+                Some(AppEvent::Key(key)) => app.on_key(key).await,
+                Some(AppEvent::Tick) => {}
+                None => break,
+            },
+            event = listener.recv() => match event {
+                Some(event) => app.on_reactor_event(event).await,
+                None => break,
+            },
+            Some(line) = logs.recv() => app.push_log(line),
+        }
+    }
+    Ok(())
+}
+
+fn restore_terminal() -> Result<()> {
+    disable_raw_mode()?;
+    execute!(std::io::stdout(), LeaveAlternateScreen, cursor::Show)?;
+    Ok(())
+}
+
+/// `logs` receives the records of the `TuiLogger`, they are shown in their own pane
+pub async fn run(reactor: Reactor, logs: UnboundedReceiver<String>) -> Result<()> {
+    let listener = reactor.event_listener().await;
+    let mut app = App::new(reactor).await;
+    // Leave the alternate screen before the panic message is printed, otherwise it is lost
+    // and the shell is left in raw mode
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = restore_terminal();
+        hook(info);
+    }));
+    enable_raw_mode()?;
+    let mut stdout = std::io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+    let res = event_loop(&mut terminal, &mut app, listener, logs).await;
+    // Dropping our hook brings back the default one
+    let _ = std::panic::take_hook();
+    restore_terminal()?;
+    res
+}
@@ -0,0 +1,140 @@
+use crate::prelude::*;
+use tui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Style},
+    widgets::{Block, Widget},
+};
+
+/// Width reserved on the right of the chart for the price axis
+pub const AXIS_WIDTH: u16 = 12;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum CandlePart {
+    Body,
+    Wick,
+}
+
+/// Maps prices to terminal rows, row 0 being the top of the chart
+#[derive(Debug, Clone, Copy)]
+pub struct PriceScale {
+    pub min: f64,
+    pub max: f64,
+    pub height: u16,
+}
+
+impl PriceScale {
+    pub fn new(min: f64, max: f64, height: u16) -> Option<Self> {
+        if !min.is_finite() || !max.is_finite() || height < 2 {
+            return None;
+        }
+        let (min, max) = if max > min {
+            (min, max)
+        } else {
+            let pad = (min.abs() * 0.01).max(f64::EPSILON);
+            (min - pad, max + pad)
+        };
+        Some(Self { min, max, height })
+    }
+
+    pub fn from_candles(candles: &[OHLC], height: u16) -> Option<Self> {
+        let min = candles
+            .iter()
+            .map(|e| e.low.to_f64())
+            .fold(f64::INFINITY, f64::min);
+        let max = candles
+            .iter()
+            .map(|e| e.high.to_f64())
+            .fold(f64::NEG_INFINITY, f64::max);
+        Self::new(min, max, height)
+    }
+
+    pub fn row(&self, price: f64) -> u16 {
+        let ratio = ((self.max - price) / (self.max - self.min)).max(0.0).min(1.0);
+        (ratio * (self.height - 1) as f64).round() as u16
+    }
+
+    pub fn price_at(&self, row: u16) -> f64 {
+        self.max - (self.max - self.min) * row as f64 / (self.height - 1) as f64
+    }
+
+    pub fn candle(&self, candle: &OHLC) -> Vec<(u16, CandlePart)> {
+        let body_top = self.row(candle.open.max(candle.close).to_f64());
+        let body_bottom = self.row(candle.open.min(candle.close).to_f64());
+        (self.row(candle.high.to_f64())..=self.row(candle.low.to_f64()))
+            .map(|row| {
+                if row >= body_top && row <= body_bottom {
+                    (row, CandlePart::Body)
+                } else {
+                    (row, CandlePart::Wick)
+                }
+            })
+            .collect()
+    }
+}
+
+pub struct CandleChart<'a> {
+    candles: &'a [OHLC],
+    block: Option<Block<'a>>,
+}
+
+impl<'a> CandleChart<'a> {
+    pub fn new(candles: &'a [OHLC]) -> Self {
+        Self {
+            candles,
+            block: None,
+        }
+    }
+
+    pub fn block(mut self, block: Block<'a>) -> Self {
+        self.block = Some(block);
+        self
+    }
+}
+
+impl<'a> Widget for CandleChart<'a> {
+    fn render(mut self, area: Rect, buf: &mut Buffer) {
+        let area = match self.block.take() {
+            Some(block) => {
+                let inner = block.inner(area);
+                block.render(area, buf);
+                inner
+            }
+            None => area,
+        };
+        if area.width <= AXIS_WIDTH || self.candles.is_empty() {
+            return;
+        }
+        let plot_width = (area.width - AXIS_WIDTH) as usize;
+        let visible = &self.candles[self.candles.len().saturating_sub(plot_width)..];
+        let scale = match PriceScale::from_candles(visible, area.height) {
+            Some(scale) => scale,
+            None => return,
+        };
+        for (x, candle) in visible.iter().enumerate() {
+            let color = if candle.is_bullish() {
+                Color::Green
+            } else {
+                Color::Red
+            };
+            for (row, part) in scale.candle(candle) {
+                let symbol = match part {
+                    CandlePart::Body => '┃',
+                    CandlePart::Wick => '│',
+                };
+                buf.get_mut(area.x + x as u16, area.y + row)
+                    .set_char(symbol)
+                    .set_fg(color);
+            }
+        }
+        let axis_x = area.x + plot_width as u16 + 1;
+        for row in [0, area.height / 2, area.height - 1] {
+            buf.set_string(
+                axis_x,
+                area.y + row,
+                format!("{:.*}", 2, scale.price_at(row)),
+                Style::default().fg(Color::Gray),
+            );
+        }
+    }
+}
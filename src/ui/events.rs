@@ -3,15 +3,24 @@ use super::*;
 pub struct UseKeyInput;
 
 impl UseKeyInput {
-    pub fn new(tick_rate: Duration, sender: Sender<AppEvent>) {
-        std::thread::spawn(move || {
-            loop {
-                if crossterm::event::poll(tick_rate).unwrap() {
-                    if let event::Event::Key(key) = event::read().unwrap() {
-                        sender.send(AppEvent::Key(key)).unwrap();
+    pub fn new(tick_rate: Duration, sender: UnboundedSender<AppEvent>) {
+        std::thread::spawn(move || loop {
+            match event::poll(tick_rate) {
+                Ok(true) => match event::read() {
+                    Ok(event::Event::Key(key)) => {
+                        if sender.send(AppEvent::Key(key)).is_err() {
+                            break;
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => error!("Failed to read terminal event: ERROR={}", e),
+                },
+                Ok(false) => {
+                    if sender.send(AppEvent::Tick).is_err() {
+                        break;
                     }
                 }
-                // sender.send(AppEvent::Tick).unwrap();
+                Err(e) => error!("Failed to poll terminal events: ERROR={}", e),
             }
         });
     }
@@ -0,0 +1,42 @@
+use super::*;
+use log::{LevelFilter, Log, Metadata, Record};
+use tokio::sync::mpsc::UnboundedReceiver;
+
+/// Logger of the terminal dashboard, writing on stderr would corrupt the screen so records
+/// are sent to the logs pane instead
+pub struct TuiLogger {
+    level: LevelFilter,
+    sender: UnboundedSender<String>,
+}
+
+impl TuiLogger {
+    /// Install the logger, records emitted before the dashboard starts are kept in the channel
+    pub fn init(level: LevelFilter) -> Result<UnboundedReceiver<String>> {
+        let (sender, receiver) = unbounded_channel();
+        log::set_boxed_logger(Box::new(TuiLogger { level, sender }))
+            .map_err(|e| Error::Config(e.to_string()))?;
+        log::set_max_level(level);
+        Ok(receiver)
+    }
+}
+
+impl Log for TuiLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let _ = self.sender.send(format!(
+            "{} {:<5} {} > {}",
+            chrono::Local::now().format("%H:%M:%S"),
+            record.level(),
+            record.target(),
+            record.args()
+        ));
+    }
+
+    fn flush(&self) {}
+}